@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+/// Accounts for the time a count down spends counting and paused.
+///
+/// Everything is measured in readings of the timer's clock. Pauses push the
+/// deadline back by however long they last, so the time left is the same
+/// after a pause as before it.
+///
+#[derive(Debug, Default)]
+pub struct Countdown {
+    // Clock readings the count down started at and was due at before any
+    // pauses, if counting down.
+    span: Option<(Duration, Duration)>,
+    // Time spent paused this count down, not counting a pause in progress.
+    paused: Duration,
+    // Clock reading the pause in progress began at, if paused.
+    paused_at: Option<Duration>,
+    // Time spent counting down, excluding pauses, over every finished
+    // count down.
+    active: Duration,
+}
+
+impl Countdown {
+    /// Start counting down at `now` to `deadline`. A paused count down
+    /// starts paused.
+    ///
+    pub fn begin(&mut self, now: Duration, deadline: Duration) {
+        self.span = Some((now, deadline));
+        self.paused = Duration::from_secs(0);
+        if self.paused_at.is_some() {
+            self.paused_at = Some(now);
+        }
+    }
+    /// Move the deadline of the count down in progress to `deadline`, e.g.,
+    /// after the wall clock it was computed against jumped.
+    ///
+    pub fn rearm(&mut self, deadline: Duration) {
+        if let Some((_, ref mut due)) = self.span {
+            *due = deadline;
+        }
+    }
+    /// Finish the count down at `now`, whether it expired or was cut short.
+    ///
+    pub fn finish(&mut self, now: Duration) {
+        self.active = self.active.saturating_add(self.elapsed(now));
+        self.span = None;
+        self.paused = Duration::from_secs(0);
+    }
+    /// Stop counting down, forgetting any pause.
+    ///
+    pub fn end(&mut self, now: Duration) {
+        self.finish(now);
+        self.paused_at = None;
+    }
+    /// Pause at `now`, returning false if already paused.
+    ///
+    pub fn pause(&mut self, now: Duration) -> bool {
+        if self.paused_at.is_some() {
+            return false;
+        }
+        self.paused_at = Some(now);
+        true
+    }
+    /// Resume at `now`, returning false if not paused.
+    ///
+    pub fn resume(&mut self, now: Duration) -> bool {
+        match self.paused_at.take() {
+            Some(at) => {
+                self.paused = self.paused.saturating_add(now.saturating_sub(at));
+                true
+            },
+            None => false,
+        }
+    }
+    /// True if paused.
+    ///
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+    /// Time spent paused this count down as of `now`.
+    ///
+    fn paused_for(&self, now: Duration) -> Duration {
+        let current = self.paused_at.map_or(Duration::from_secs(0), |at| now.saturating_sub(at));
+        self.paused.saturating_add(current)
+    }
+    /// The clock reading the count down is due at, pushed back by pauses,
+    /// or `None` if paused or not counting down.
+    ///
+    pub fn due(&self) -> Option<Duration> {
+        if self.is_paused() {
+            return None;
+        }
+        self.span.map(|(_, deadline)| deadline.saturating_add(self.paused))
+    }
+    /// How far pauses have pushed the deadline back.
+    ///
+    pub fn delay(&self) -> Duration {
+        self.paused
+    }
+    /// Time spent counting down as of `now`, excluding pauses.
+    ///
+    pub fn elapsed(&self, now: Duration) -> Duration {
+        self.span.map_or(Duration::from_secs(0), |(started, _)| {
+            now.saturating_sub(started).saturating_sub(self.paused_for(now))
+        })
+    }
+    /// Time spent counting down over every count down as of `now`,
+    /// excluding pauses.
+    ///
+    pub fn active(&self, now: Duration) -> Duration {
+        self.active.saturating_add(self.elapsed(now))
+    }
+    /// Time left to count down as of `now`.
+    ///
+    pub fn remaining(&self, now: Duration) -> Duration {
+        self.span.map_or(Duration::from_secs(0), |(started, deadline)| {
+            deadline.saturating_sub(started).saturating_sub(self.elapsed(now))
+        })
+    }
+    /// Fraction of the count down elapsed as of `now`, from 0.0 to 1.0.
+    ///
+    pub fn fraction_elapsed(&self, now: Duration) -> f64 {
+        let (started, deadline) = match self.span {
+            Some(span) => span,
+            None => return 0.0,
+        };
+        let total = deadline.saturating_sub(started);
+        if total == Duration::from_secs(0) {
+            return 1.0;
+        }
+        (self.elapsed(now).as_secs_f64() / total.as_secs_f64()).min(1.0)
+    }
+}
+
+#[test]
+fn countdown_excludes_pauses() {
+    let s = Duration::from_secs;
+    let mut countdown = Countdown::default();
+    countdown.begin(s(10), s(20));
+    assert_eq!(countdown.due(), Some(s(20)));
+    assert!(countdown.pause(s(14)));
+    assert!(!countdown.pause(s(15)));
+    assert_eq!(countdown.due(), None);
+    assert_eq!(countdown.elapsed(s(17)), s(4));
+    assert!(countdown.resume(s(17)));
+    assert_eq!(countdown.due(), Some(s(23)));
+    assert_eq!(countdown.remaining(s(18)), s(5));
+    assert_eq!(countdown.fraction_elapsed(s(18)), 0.5);
+    countdown.pause(s(19));
+    countdown.finish(s(20));
+    assert_eq!(countdown.active(s(25)), s(6));
+    countdown.begin(s(30), s(40));
+    assert!(countdown.is_paused());
+    assert_eq!(countdown.remaining(s(35)), s(10));
+}
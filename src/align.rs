@@ -0,0 +1,108 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use Timer;
+
+/// Wall clock boundaries to expire on.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    /// The start of every second.
+    Second,
+    /// The start of every minute, at :00.
+    Minute,
+    /// The start of every hour.
+    Hour,
+    /// Midnight UTC.
+    Day,
+    /// Every whole multiple of a period since the Unix epoch.
+    Every(Duration),
+}
+
+impl Alignment {
+    /// The time between boundaries.
+    ///
+    pub fn period(&self) -> Duration {
+        match *self {
+            Alignment::Second => Duration::from_secs(1),
+            Alignment::Minute => Duration::from_secs(60),
+            Alignment::Hour => Duration::from_secs(3600),
+            Alignment::Day => Duration::from_secs(86400),
+            Alignment::Every(period) => period,
+        }
+    }
+}
+
+/// The first time strictly after `now` that falls `offset` past a whole
+/// multiple of `period` since the Unix epoch.
+///
+/// Returns `None` if `now` is before the epoch.
+///
+fn next_boundary(now: SystemTime, period: Duration, offset: Duration) -> Option<SystemTime> {
+    let period = period.as_nanos();
+    let offset = offset.as_nanos() % period;
+    let since = now.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    let boundaries = (since + period - offset) / period;
+    let at = boundaries * period + offset;
+    UNIX_EPOCH.checked_add(Duration::new((at / 1_000_000_000) as u64, (at % 1_000_000_000) as u32))
+}
+
+impl Timer {
+    /// Expire on every `alignment` boundary of the wall clock, instead of
+    /// counting down `step`.
+    ///
+    /// E.g., `Alignment::Minute` expires at :00 of every minute, however
+    /// long after a boundary the timer was started.
+    ///
+    pub fn align_to(&mut self, alignment: Alignment) {
+        self.align_to_epoch(alignment.period(), Duration::from_secs(0));
+    }
+    /// Expire `offset` past every whole multiple of `period` since the Unix
+    /// epoch, instead of counting down `step`.
+    ///
+    /// E.g., a five minute `period` and five second `offset` expires at
+    /// 00:05, 05:05, 10:05 and so on, as metric emission and cron-like jobs
+    /// need. Boundaries are computed afresh before each count down, so the
+    /// timer stays aligned however long its callbacks take.
+    ///
+    /// # Panics
+    ///
+    /// If `period` is zero.
+    ///
+    pub fn align_to_epoch(&mut self, period: Duration, offset: Duration) {
+        assert!(period > Duration::from_secs(0), "Alignment period must be non-zero!");
+        self.set_wall_schedule(move |now| next_boundary(now, period, offset));
+    }
+}
+
+#[test]
+fn align_next_boundary() {
+    let s = Duration::from_secs;
+    let at = |secs| UNIX_EPOCH + s(secs);
+    assert_eq!(next_boundary(at(119), s(60), s(0)), Some(at(120)));
+    // Strictly after, so a timer on a boundary waits for the next...
+    assert_eq!(next_boundary(at(120), s(60), s(0)), Some(at(180)));
+    // ...and offsets larger than the period wrap around.
+    assert_eq!(next_boundary(at(301), s(300), s(5)), Some(at(305)));
+    assert_eq!(next_boundary(at(305), s(300), s(305)), Some(at(605)));
+    assert_eq!(next_boundary(at(0) + Duration::from_millis(1), Alignment::Second.period(), s(0)),
+               Some(at(1)));
+}
+
+#[test]
+fn timer_align_to_epoch() {
+    use std::sync::{Arc, Condvar, Mutex};
+    let ms = Duration::from_millis;
+    let fired = Arc::new(Mutex::new(Vec::new()));
+    let mut t = Timer::new(ms(1000), ms(0), Arc::new(Condvar::new()));
+    t.align_to(Alignment::Every(ms(50)));
+    let f = fired.clone();
+    t.on_expiry(move || f.lock().unwrap().push(SystemTime::now()));
+    t.start();
+    std::thread::sleep(ms(180));
+    t.stop();
+    let fired = fired.lock().unwrap();
+    assert!(fired.len() >= 2, "{:?}", fired);
+    for at in fired.iter() {
+        let past = at.duration_since(UNIX_EPOCH).unwrap().as_millis() % 50;
+        assert!(past < 20, "{:?} is {}ms past a boundary", at, past);
+    }
+}
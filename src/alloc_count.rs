@@ -0,0 +1,93 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Counts the allocations made on threads that ask it to.
+///
+struct CountingAlloc;
+
+thread_local! {
+    // Allocations made on this thread since counting began, if counting.
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Count an allocation on this thread, if counting.
+///
+fn count() {
+    let _ = ALLOCATIONS.try_with(|allocations| {
+        if let Some(n) = allocations.get() {
+            allocations.set(Some(n + 1));
+        }
+    });
+}
+
+/// Start counting the allocations made on this thread, if not already.
+///
+pub fn start_counting() {
+    ALLOCATIONS.with(|allocations| {
+        if allocations.get().is_none() {
+            allocations.set(Some(0));
+        }
+    });
+}
+
+/// Allocations made on this thread since it started counting.
+///
+pub fn allocations() -> usize {
+    ALLOCATIONS.with(|allocations| allocations.get().unwrap_or(0))
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc_zeroed(layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+#[test]
+fn expiry_path_does_not_allocate() {
+    use channel::OnFull;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Condvar};
+    use std::time::Duration;
+    use Timer;
+    let ms = Duration::from_millis;
+    // Allocations made on the timer thread as of the 10th expiry, and as of
+    // the last.
+    let warm = Arc::new(AtomicUsize::new(0));
+    let last = Arc::new(AtomicUsize::new(0));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut t = Timer::new(ms(1), ms(0), Arc::new(Condvar::new()));
+    t.set_max_expiries(50);
+    let events = t.subscribe_bounded(4, OnFull::DropOldest);
+    let (w, l, c) = (warm.clone(), last.clone(), calls.clone());
+    t.on_expiry(move || {
+        start_counting();
+        match c.fetch_add(1, Ordering::SeqCst) + 1 {
+            10 => w.store(allocations(), Ordering::SeqCst),
+            50 => l.store(allocations(), Ordering::SeqCst),
+            _ => {},
+        }
+    });
+    t.start();
+    while calls.load(Ordering::SeqCst) < 50 {
+        std::thread::sleep(ms(10));
+    }
+    t.stop();
+    // Every expiry but the last four, and then `Completed` pushed out one more.
+    assert_eq!(events.dropped(), 47);
+    assert_eq!(last.load(Ordering::SeqCst), warm.load(Ordering::SeqCst));
+}
@@ -0,0 +1,29 @@
+use async_io::Timer as AsyncIoTimer;
+use deadline::Deadline;
+
+/// Convert a `Deadline` into an `async_io::Timer` that fires at it.
+///
+/// `async_io::Timer` doesn't expose when it fires, so there's no converting
+/// back; keep the `Deadline` around instead. A `future::Sleep` converts by
+/// way of `Sleep::instant`.
+///
+impl From<Deadline> for AsyncIoTimer {
+    fn from(deadline: Deadline) -> AsyncIoTimer {
+        AsyncIoTimer::at(deadline.instant())
+    }
+}
+
+#[test]
+fn async_io_timer_from_deadline() {
+    use future::{block_on, Sleep};
+    use std::time::{Duration, Instant};
+    let ms = Duration::from_millis;
+    let deadline = Deadline::after(ms(20));
+    let started = Instant::now();
+    block_on(AsyncIoTimer::from(deadline));
+    assert!(deadline.has_passed());
+    assert!(started.elapsed() < Duration::from_secs(1));
+    let sleep = Sleep::new(ms(20));
+    let at = sleep.instant().unwrap();
+    assert!(block_on(AsyncIoTimer::from(Deadline::at(at))) >= at);
+}
@@ -0,0 +1,257 @@
+use config::JitterPolicy;
+use deadline::{Deadline, WithDeadline};
+use std::time::Duration;
+use Timer;
+
+/// A strategy for spacing out repeated attempts.
+///
+pub trait Backoff {
+    /// The delay before the next attempt, or `None` to give up.
+    ///
+    fn next_backoff(&mut self) -> Option<Duration>;
+    /// Start over from the first delay.
+    ///
+    fn reset(&mut self);
+    /// Give up at `deadline`, truncating the last delay to the time left.
+    ///
+    fn with_deadline(self, deadline: Deadline) -> WithDeadline<Self>
+        where Self: Sized
+    {
+        WithDeadline::new(self, deadline)
+    }
+}
+
+/// Apply jitter to a delay, never randomizing by more than the delay itself.
+///
+fn jittered(delay: Duration, jitter: Duration, policy: JitterPolicy) -> Duration {
+    Timer::calculate_wait_duration(delay, jitter.min(delay), policy)
+}
+
+/// Wait the same delay before every attempt.
+///
+#[derive(Clone, Debug)]
+pub struct ConstantBackoff {
+    delay: Duration,
+    jitter: Duration,
+}
+
+impl ConstantBackoff {
+    /// Create a new constant backoff.
+    ///
+    pub fn new(delay: Duration) -> ConstantBackoff {
+        ConstantBackoff { delay, jitter: Duration::from_secs(0) }
+    }
+    /// Randomize each delay by up to `jitter` less.
+    ///
+    #[cfg(feature = "jitter")]
+    pub fn with_jitter(mut self, jitter: Duration) -> ConstantBackoff {
+        self.jitter = jitter;
+        self
+    }
+}
+
+impl Backoff for ConstantBackoff {
+    fn next_backoff(&mut self) -> Option<Duration> {
+        Some(jittered(self.delay, self.jitter, JitterPolicy::Subtractive))
+    }
+    fn reset(&mut self) {}
+}
+
+/// Multiply the delay by a constant factor after every attempt, up to a
+/// maximum.
+///
+#[derive(Clone, Debug)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    factor: f64,
+    max: Duration,
+    jitter: Duration,
+    // The delay to hand out next, before jitter.
+    current: Duration,
+}
+
+impl ExponentialBackoff {
+    /// Create a new exponential backoff that doubles from `initial` up to
+    /// `max`.
+    ///
+    pub fn new(initial: Duration, max: Duration) -> ExponentialBackoff {
+        ExponentialBackoff {
+            initial,
+            factor: 2.0,
+            max,
+            jitter: Duration::from_secs(0),
+            current: initial,
+        }
+    }
+    /// Multiply the delay by `factor` instead of two.
+    ///
+    pub fn with_factor(mut self, factor: f64) -> ExponentialBackoff {
+        self.factor = factor;
+        self
+    }
+    /// Randomize each delay by up to `jitter` less.
+    ///
+    #[cfg(feature = "jitter")]
+    pub fn with_jitter(mut self, jitter: Duration) -> ExponentialBackoff {
+        self.jitter = jitter;
+        self
+    }
+}
+
+impl Backoff for ExponentialBackoff {
+    fn next_backoff(&mut self) -> Option<Duration> {
+        let delay = self.current;
+        self.current = Duration::try_from_secs_f64(delay.as_secs_f64() * self.factor)
+            .unwrap_or(self.max)
+            .min(self.max);
+        Some(jittered(delay, self.jitter, JitterPolicy::Subtractive))
+    }
+    fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+/// Grow the delay along the Fibonacci sequence, 1, 1, 2, 3, 5, 8... times
+/// a base delay, up to a maximum.
+///
+/// Grows more gently than doubling early on, which some retry guidelines
+/// prefer.
+///
+#[derive(Clone, Debug)]
+pub struct FibonacciBackoff {
+    base: Duration,
+    max: Duration,
+    jitter: Duration,
+    // The delay to hand out next, and the one after it, before jitter.
+    current: Duration,
+    next: Duration,
+}
+
+impl FibonacciBackoff {
+    /// Create a new Fibonacci backoff in multiples of `base`, up to `max`.
+    ///
+    pub fn new(base: Duration, max: Duration) -> FibonacciBackoff {
+        FibonacciBackoff {
+            base,
+            max,
+            jitter: Duration::from_secs(0),
+            current: base,
+            next: base,
+        }
+    }
+    /// Randomize each delay by up to `jitter` less.
+    ///
+    #[cfg(feature = "jitter")]
+    pub fn with_jitter(mut self, jitter: Duration) -> FibonacciBackoff {
+        self.jitter = jitter;
+        self
+    }
+}
+
+impl Backoff for FibonacciBackoff {
+    fn next_backoff(&mut self) -> Option<Duration> {
+        let delay = self.current.min(self.max);
+        let after = self.current.saturating_add(self.next);
+        self.current = self.next;
+        self.next = after;
+        Some(jittered(delay, self.jitter, JitterPolicy::Subtractive))
+    }
+    fn reset(&mut self) {
+        self.current = self.base;
+        self.next = self.base;
+    }
+}
+
+/// Add a constant increment to the delay after every attempt, up to a
+/// maximum.
+///
+/// For systems where exponential growth overshoots acceptable retry latency
+/// too quickly.
+///
+#[derive(Clone, Debug)]
+pub struct LinearBackoff {
+    base: Duration,
+    increment: Duration,
+    max: Duration,
+    jitter: Duration,
+    // The delay to hand out next, before jitter.
+    current: Duration,
+}
+
+impl LinearBackoff {
+    /// Create a new linear backoff that starts at `base` and grows by
+    /// `increment` per attempt, up to `max`.
+    ///
+    pub fn new(base: Duration, increment: Duration, max: Duration) -> LinearBackoff {
+        LinearBackoff {
+            base,
+            increment,
+            max,
+            jitter: Duration::from_secs(0),
+            current: base,
+        }
+    }
+    /// Randomize each delay by up to `jitter` less.
+    ///
+    #[cfg(feature = "jitter")]
+    pub fn with_jitter(mut self, jitter: Duration) -> LinearBackoff {
+        self.jitter = jitter;
+        self
+    }
+}
+
+impl Backoff for LinearBackoff {
+    fn next_backoff(&mut self) -> Option<Duration> {
+        let delay = self.current.min(self.max);
+        self.current = delay.saturating_add(self.increment);
+        Some(jittered(delay, self.jitter, JitterPolicy::Subtractive))
+    }
+    fn reset(&mut self) {
+        self.current = self.base;
+    }
+}
+
+#[test]
+fn exponential_backoff() {
+    let ms = Duration::from_millis;
+    let mut backoff = ExponentialBackoff::new(ms(10), ms(50));
+    let delays: Vec<_> = (0..5).map(|_| backoff.next_backoff().unwrap()).collect();
+    assert_eq!(delays, vec![ms(10), ms(20), ms(40), ms(50), ms(50)]);
+    backoff.reset();
+    assert_eq!(backoff.next_backoff(), Some(ms(10)));
+}
+
+#[test]
+#[cfg(feature = "jitter")]
+fn constant_backoff_jitter() {
+    let ms = Duration::from_millis;
+    let mut backoff = ConstantBackoff::new(ms(10)).with_jitter(ms(100));
+    for _ in 0..100 {
+        assert!(backoff.next_backoff().unwrap() <= ms(10));
+    }
+}
+
+#[test]
+fn fibonacci_backoff() {
+    let ms = Duration::from_millis;
+    let mut backoff = FibonacciBackoff::new(ms(10), ms(60));
+    let delays: Vec<_> = (0..7).map(|_| backoff.next_backoff().unwrap()).collect();
+    assert_eq!(delays, vec![ms(10), ms(10), ms(20), ms(30), ms(50), ms(60), ms(60)]);
+    backoff.reset();
+    assert_eq!(backoff.next_backoff(), Some(ms(10)));
+    let huge = Duration::from_secs(u64::MAX);
+    let mut backoff = FibonacciBackoff::new(huge, huge);
+    for _ in 0..5 {
+        assert_eq!(backoff.next_backoff(), Some(huge));
+    }
+}
+
+#[test]
+fn linear_backoff() {
+    let ms = Duration::from_millis;
+    let mut backoff = LinearBackoff::new(ms(10), ms(15), ms(50));
+    let delays: Vec<_> = (0..5).map(|_| backoff.next_backoff().unwrap()).collect();
+    assert_eq!(delays, vec![ms(10), ms(25), ms(40), ms(50), ms(50)]);
+    backoff.reset();
+    assert_eq!(backoff.next_backoff(), Some(ms(10)));
+}
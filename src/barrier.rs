@@ -0,0 +1,117 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use Timer;
+
+/// How far each member of a barrier has got.
+struct State {
+    // Expiries seen from each member since the barrier was created.
+    counts: Vec<usize>,
+    // Expiries each member needs for the barrier to complete.
+    required: usize,
+}
+
+impl State {
+    fn complete(&self) -> bool {
+        self.counts.iter().all(|&count| count >= self.required)
+    }
+}
+
+/// Completes once every one of a group of timers has expired.
+///
+/// Useful for staged startup, such as waiting for every subsystem's warmup
+/// timer, without counting expiries by hand around a shared condition
+/// variable. Only expiries after the barrier is created count.
+///
+pub struct TimerBarrier {
+    timers: Vec<Timer>,
+    shared: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl TimerBarrier {
+    /// Create a barrier that completes once every timer has expired at
+    /// least once.
+    ///
+    pub fn all(timers: Vec<Timer>) -> TimerBarrier {
+        TimerBarrier::all_n(timers, 1)
+    }
+    /// Create a barrier that completes once every timer has expired at
+    /// least `n` times.
+    ///
+    /// Timers that aren't running yet are started.
+    ///
+    pub fn all_n(mut timers: Vec<Timer>, n: usize) -> TimerBarrier {
+        let shared = Arc::new((Mutex::new(State {
+            counts: vec![0; timers.len()],
+            required: n,
+        }), Condvar::new()));
+        for (i, timer) in timers.iter_mut().enumerate() {
+            let s = shared.clone();
+            timer.on_expiry(move || {
+                let (ref m, ref cv) = *s;
+                let mut state = m.lock().unwrap();
+                state.counts[i] += 1;
+                if state.complete() {
+                    cv.notify_all();
+                }
+            });
+            if timer.handle.is_none() {
+                timer.start();
+            }
+        }
+        TimerBarrier { timers, shared }
+    }
+    /// True once every timer has expired enough times.
+    ///
+    pub fn is_complete(&self) -> bool {
+        self.shared.0.lock().unwrap().complete()
+    }
+    /// Block until every timer has expired enough times.
+    ///
+    pub fn wait(&self) {
+        let (ref m, ref cv) = *self.shared;
+        let mut state = m.lock().unwrap();
+        while !state.complete() {
+            state = cv.wait(state).unwrap();
+        }
+    }
+    /// Block until every timer has expired enough times, or `timeout`
+    /// passes. Returns true if the barrier completed.
+    ///
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now().checked_add(timeout);
+        let (ref m, ref cv) = *self.shared;
+        let mut state = m.lock().unwrap();
+        while !state.complete() {
+            let now = Instant::now();
+            let remaining = match deadline {
+                Some(deadline) if deadline > now => deadline - now,
+                Some(_) => return false,
+                None => Duration::MAX,
+            };
+            state = cv.wait_timeout(state, remaining).unwrap().0;
+        }
+        true
+    }
+    /// Give back the timers, still running.
+    ///
+    pub fn into_timers(self) -> Vec<Timer> {
+        self.timers
+    }
+}
+
+#[test]
+fn barrier_waits_for_every_timer() {
+    let ms = Duration::from_millis;
+    let timer = |step| Timer::new(ms(step), ms(0), Arc::new(Condvar::new()));
+    let barrier = TimerBarrier::all_n(vec![timer(10), timer(30), timer(60)], 2);
+    assert!(!barrier.is_complete());
+    assert!(!barrier.wait_timeout(ms(60)));
+    let started = Instant::now();
+    barrier.wait();
+    assert!(barrier.is_complete());
+    assert!(started.elapsed() < ms(200));
+    for mut timer in barrier.into_timers() {
+        timer.stop();
+        assert!(timer.expiries.load(std::sync::atomic::Ordering::SeqCst) >= 2);
+    }
+}
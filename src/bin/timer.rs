@@ -0,0 +1,121 @@
+//! A command line front end to the timer library.
+//!
+//! ```text
+//! timer countdown 5m [--jitter 10s] [--jitter-mode additive] [--notify-cmd 'notify-send done']
+//! timer every 30s [--jitter 5s] [--jitter-mode additive] [--count 10] -- cmd args...
+//! ```
+//!
+//! Commands run with `sh -c` for `--notify-cmd`, or directly for `every`.
+
+extern crate humantime;
+extern crate timer;
+
+use std::process::{self, Command};
+use std::sync::{Arc, Condvar};
+use std::time::{Duration, Instant};
+use timer::{Event, Timer, TimerConfig};
+
+const USAGE: &str = "usage:
+    timer countdown <duration> [--jitter <duration>] [--jitter-mode <mode>] [--notify-cmd <cmd>]
+    timer every <duration> [--jitter <duration>] [--jitter-mode <mode>] [--count <n>] -- <cmd> [args...]";
+
+/// Everything given on the command line.
+struct Args {
+    periodic: bool,
+    config: TimerConfig,
+    count: Option<usize>,
+    notify: Option<String>,
+    command: Vec<String>,
+}
+
+fn duration(s: &str) -> Result<Duration, String> {
+    humantime::parse_duration(s).map_err(|e| format!("invalid duration {:?}: {}", s, e))
+}
+
+fn parse(mut args: std::vec::IntoIter<String>) -> Result<Args, String> {
+    let periodic = match args.next().as_deref() {
+        Some("countdown") => false,
+        Some("every") => true,
+        Some(other) => return Err(format!("unknown command {:?}", other)),
+        None => return Err("missing command".to_string()),
+    };
+    let step = duration(&args.next().ok_or("missing duration")?)?;
+    let mut parsed = Args {
+        periodic,
+        config: TimerConfig::new(step),
+        count: None,
+        notify: None,
+        command: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--jitter" => parsed.config.jitter = duration(&value()?)?,
+            "--jitter-mode" => parsed.config.jitter_policy = value()?.parse().map_err(|e| format!("{}", e))?,
+            "--count" if periodic => {
+                let count = value()?;
+                parsed.count = Some(count.parse().map_err(|_| format!("invalid count {:?}", count))?);
+            },
+            "--notify-cmd" if !periodic => parsed.notify = Some(value()?),
+            "--" if periodic => {
+                parsed.command = args.by_ref().collect();
+                break;
+            },
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+    if periodic && parsed.command.is_empty() {
+        return Err("missing command to run".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Run `command`, reporting but otherwise ignoring failures.
+fn run(command: &mut Command) {
+    match command.status() {
+        Ok(status) if !status.success() => eprintln!("timer: command exited with {}", status),
+        Ok(_) => {},
+        Err(e) => eprintln!("timer: couldn't run command: {}", e),
+    }
+}
+
+fn main() {
+    let args = match parse(std::env::args().skip(1).collect::<Vec<_>>().into_iter()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("timer: {}\n{}", e, USAGE);
+            process::exit(2);
+        },
+    };
+    let mut t = match Timer::from_config(args.config, Arc::new(Condvar::new())) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("timer: {}", e);
+            process::exit(2);
+        },
+    };
+    let limit = if args.periodic { args.count } else { Some(1) };
+    if let Some(limit) = limit {
+        t.set_max_expiries(limit);
+    }
+    let events = t.subscribe();
+    let started = Instant::now();
+    t.start();
+    let expiries = events.iter().filter_map(|event| match event {
+        Event::Expired(expiry) => Some(expiry),
+        _ => None,
+    });
+    for expiry in expiries.take(limit.unwrap_or(usize::MAX)) {
+        let elapsed = Duration::from_millis(started.elapsed().as_millis() as u64);
+        if args.periodic {
+            println!("timer: tick {} after {}", expiry.count, humantime::format_duration(elapsed));
+            run(Command::new(&args.command[0]).args(&args.command[1..]));
+        } else {
+            println!("timer: expired after {}", humantime::format_duration(elapsed));
+            if let Some(ref notify) = args.notify {
+                run(Command::new("sh").arg("-c").arg(notify));
+            }
+        }
+    }
+    t.stop();
+}
@@ -0,0 +1,239 @@
+use cancel::CancellationToken;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use Timer;
+
+/// Whether a `CircuitBreaker` is letting calls through.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through, and failures are counted.
+    Closed,
+    /// Calls are rejected until the open period passes.
+    Open,
+    /// A single probe call is let through to decide whether to close again.
+    HalfOpen,
+}
+
+/// Returned by a `CircuitBreaker` call that failed or was never made.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BreakerError<E> {
+    /// The breaker rejected the call without making it.
+    Open,
+    /// The call was made and failed.
+    Inner(E),
+}
+
+impl<E: fmt::Display> fmt::Display for BreakerError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BreakerError::Open => f.write_str("circuit breaker is open"),
+            BreakerError::Inner(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> Error for BreakerError<E> {}
+
+/// State shared between a breaker and its timers.
+struct Shared {
+    state: CircuitState,
+    // Failures counted in the current window.
+    failures: usize,
+    // True while a half-open probe call is running.
+    probing: bool,
+}
+
+/// Held while a half-open probe call runs, so that a probe that panics
+/// doesn't leave the breaker waiting on it forever.
+struct Probe<'a>(&'a Mutex<Shared>);
+
+impl<'a> Drop for Probe<'a> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            if let Ok(mut shared) = self.0.lock() {
+                shared.probing = false;
+            }
+        }
+    }
+}
+
+/// Stops calling a failing operation for a while, then probes it to see
+/// whether it has recovered.
+///
+/// The breaker opens once `threshold` calls fail within one failure window.
+/// A one-shot timer moves it to half-open after `open_for`, where the next
+/// call is a probe: success closes the breaker and failure opens it again.
+/// The failure count is cleared by a periodic timer at the end of every
+/// window.
+///
+pub struct CircuitBreaker {
+    shared: Arc<Mutex<Shared>>,
+    // Failures within one window that open the breaker.
+    threshold: usize,
+    // Timer clearing the failure count at the end of every window.
+    window: Timer,
+    // One-shot timer half-opening the breaker, armed again each trip.
+    opener: Mutex<Timer>,
+    // Cancelled on drop to stop the breaker's timers.
+    token: CancellationToken,
+}
+
+impl CircuitBreaker {
+    /// Create a new, closed circuit breaker.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Failures within one window that open the breaker.
+    /// * `window` - How long failures are counted for before being cleared.
+    /// * `open_for` - How long the breaker stays open before probing.
+    ///
+    pub fn new(threshold: usize, window: Duration, open_for: Duration) -> CircuitBreaker {
+        let shared = Arc::new(Mutex::new(Shared {
+            state: CircuitState::Closed,
+            failures: 0,
+            probing: false,
+        }));
+        let token = CancellationToken::new();
+        let mut timer = Timer::new(window, Duration::from_secs(0), Arc::new(Condvar::new()))
+            .with_cancel(token.child_token());
+        let s = shared.clone();
+        timer.on_expiry(move || s.lock().unwrap().failures = 0);
+        timer.start();
+        let mut opener = Timer::new(open_for, Duration::from_secs(0), Arc::new(Condvar::new()))
+            .with_cancel(token.child_token());
+        opener.set_max_expiries(1);
+        let s = shared.clone();
+        opener.on_expiry(move || {
+            let mut shared = s.lock().unwrap();
+            if shared.state == CircuitState::Open {
+                shared.state = CircuitState::HalfOpen;
+            }
+        });
+        CircuitBreaker {
+            shared,
+            threshold,
+            window: timer,
+            opener: Mutex::new(opener),
+            token,
+        }
+    }
+    /// The breaker's current state.
+    ///
+    pub fn state(&self) -> CircuitState {
+        self.shared.lock().unwrap().state
+    }
+    /// Call `f` unless the breaker is open, recording whether it failed.
+    ///
+    pub fn call<T, E, F>(&self, f: F) -> Result<T, BreakerError<E>>
+        where F: FnOnce() -> Result<T, E>
+    {
+        let _probe = {
+            let mut shared = self.shared.lock().unwrap();
+            match shared.state {
+                CircuitState::Closed => None,
+                CircuitState::Open => return Err(BreakerError::Open),
+                CircuitState::HalfOpen => {
+                    if shared.probing {
+                        return Err(BreakerError::Open);
+                    }
+                    shared.probing = true;
+                    Some(Probe(&self.shared))
+                },
+            }
+        };
+        let result = f();
+        let mut shared = self.shared.lock().unwrap();
+        match result {
+            Ok(value) => {
+                if shared.state == CircuitState::HalfOpen {
+                    shared.state = CircuitState::Closed;
+                    shared.failures = 0;
+                    shared.probing = false;
+                }
+                Ok(value)
+            },
+            Err(e) => {
+                shared.failures += 1;
+                if shared.state == CircuitState::HalfOpen || shared.failures >= self.threshold {
+                    shared.state = CircuitState::Open;
+                    shared.failures = 0;
+                    shared.probing = false;
+                    // The opener's callback takes the shared lock.
+                    drop(shared);
+                    self.arm_opener();
+                }
+                Err(BreakerError::Inner(e))
+            },
+        }
+    }
+    /// Arm the one-shot timer to half-open the breaker after `open_for`.
+    ///
+    fn arm_opener(&self) {
+        let mut opener = self.opener.lock().unwrap();
+        if opener.lifecycle.is_running() {
+            opener.reset();
+        } else {
+            // Already expired for the last trip, so start it again.
+            opener.stop();
+            opener.start();
+        }
+    }
+}
+
+impl Drop for CircuitBreaker {
+    fn drop(&mut self) {
+        self.token.cancel();
+        self.window.stop();
+        self.opener.lock().unwrap().stop();
+    }
+}
+
+#[test]
+fn breaker_opens_and_recovers() {
+    let ms = Duration::from_millis;
+    let breaker = CircuitBreaker::new(2, Duration::from_secs(60), ms(30));
+    assert_eq!(breaker.call(|| Err::<(), _>("down")), Err(BreakerError::Inner("down")));
+    assert_eq!(breaker.state(), CircuitState::Closed);
+    assert_eq!(breaker.call(|| Err::<(), _>("down")), Err(BreakerError::Inner("down")));
+    assert_eq!(breaker.state(), CircuitState::Open);
+    assert_eq!(breaker.call(|| Ok::<_, ()>(1)), Err(BreakerError::Open));
+    std::thread::sleep(ms(60));
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    // A failed probe opens the breaker again...
+    assert_eq!(breaker.call(|| Err::<(), _>("down")), Err(BreakerError::Inner("down")));
+    assert_eq!(breaker.state(), CircuitState::Open);
+    std::thread::sleep(ms(60));
+    // ...and a successful one closes it.
+    assert_eq!(breaker.call(|| Ok::<_, ()>(1)), Ok(1));
+    assert_eq!(breaker.state(), CircuitState::Closed);
+}
+
+#[test]
+fn breaker_window_clears_failures() {
+    let ms = Duration::from_millis;
+    let breaker = CircuitBreaker::new(2, ms(30), Duration::from_secs(60));
+    assert!(breaker.call(|| Err::<(), _>("down")).is_err());
+    std::thread::sleep(ms(50));
+    assert!(breaker.call(|| Err::<(), _>("down")).is_err());
+    assert_eq!(breaker.state(), CircuitState::Closed);
+}
+
+#[test]
+fn breaker_survives_a_panicking_probe() {
+    let ms = Duration::from_millis;
+    let breaker = CircuitBreaker::new(1, Duration::from_secs(60), ms(20));
+    assert!(breaker.call(|| Err::<(), _>("down")).is_err());
+    std::thread::sleep(ms(50));
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    let probe = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        breaker.call::<(), (), _>(|| panic!("probe failed"))
+    }));
+    assert!(probe.is_err());
+    // The next call probes again instead of being rejected.
+    assert_eq!(breaker.call(|| Ok::<_, ()>(1)), Ok(1));
+    assert_eq!(breaker.state(), CircuitState::Closed);
+}
@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Counters describing how a retry budget has been used.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudgetMetrics {
+    /// Retries currently allowed before the budget runs dry.
+    pub available: usize,
+    /// Retries the budget has allowed.
+    pub withdrawn: usize,
+    /// Retries the budget has refused.
+    pub rejected: usize,
+}
+
+/// The state of a budget's bucket.
+struct Bucket {
+    // Retries allowed right now, including fractions refilled so far.
+    tokens: f64,
+    // When `tokens` was last refilled.
+    refilled: Instant,
+    withdrawn: usize,
+    rejected: usize,
+}
+
+/// Bounds the total number of retries across many operations.
+///
+/// A token bucket holding up to `max` retries, refilled at `max` retries
+/// per `per`. Every retry takes a token, and once the bucket is empty
+/// retries are refused until it refills. Sharing one budget between every
+/// caller of a service stops a slow or failing service from being buried
+/// under a retry storm, which per-call backoff alone can't do. Clones share
+/// the same bucket.
+///
+#[derive(Clone)]
+pub struct RetryBudget {
+    max: usize,
+    per: Duration,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+impl RetryBudget {
+    /// Create a new, full budget allowing `max` retries per `per`.
+    ///
+    pub fn new(max: usize, per: Duration) -> RetryBudget {
+        RetryBudget {
+            max,
+            per,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: max as f64,
+                refilled: Instant::now(),
+                withdrawn: 0,
+                rejected: 0,
+            })),
+        }
+    }
+    fn refill(&self, bucket: &mut Bucket) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        let rate = self.max as f64 / self.per.as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(self.max as f64);
+        bucket.refilled = now;
+    }
+    /// Take a token for one retry, returning false if the budget is empty.
+    ///
+    pub fn try_withdraw(&self) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+        self.refill(&mut bucket);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.withdrawn += 1;
+            true
+        } else {
+            bucket.rejected += 1;
+            false
+        }
+    }
+    /// How the budget has been used so far.
+    ///
+    pub fn metrics(&self) -> BudgetMetrics {
+        let mut bucket = self.bucket.lock().unwrap();
+        self.refill(&mut bucket);
+        BudgetMetrics {
+            available: bucket.tokens as usize,
+            withdrawn: bucket.withdrawn,
+            rejected: bucket.rejected,
+        }
+    }
+}
+
+#[test]
+fn retry_budget_refills() {
+    let budget = RetryBudget::new(2, Duration::from_millis(50));
+    let shared = budget.clone();
+    assert!(budget.try_withdraw());
+    assert!(shared.try_withdraw());
+    assert!(!budget.try_withdraw());
+    assert_eq!(budget.metrics(), BudgetMetrics { available: 0, withdrawn: 2, rejected: 1 });
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(shared.metrics().available, 2);
+    assert!(budget.try_withdraw());
+}
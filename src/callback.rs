@@ -0,0 +1,298 @@
+use pool::ThreadPool;
+use std::any::Any;
+use std::ops::ControlFlow;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A callback run each time a timer expires.
+type Callback = Box<dyn Fn() -> ControlFlow<()> + Send + Sync>;
+
+/// A hook run with the payload of a panicking callback.
+type PanicHook = Arc<dyn Fn(Box<dyn Any + Send>) + Send + Sync>;
+
+/// A hook run when a callback asks the timer to stop.
+type BreakHook = Arc<dyn Fn() + Send + Sync>;
+
+/// What an expiry callback returns: nothing, to keep the timer going, or a
+/// `ControlFlow` saying whether to.
+///
+pub trait TickOutcome {
+    /// Whether the timer should keep going.
+    ///
+    fn into_control_flow(self) -> ControlFlow<()>;
+}
+
+impl TickOutcome for () {
+    fn into_control_flow(self) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+impl TickOutcome for ControlFlow<()> {
+    fn into_control_flow(self) -> ControlFlow<()> {
+        self
+    }
+}
+
+/// Where expiry callbacks are run.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dispatch {
+    /// Run callbacks on the timer thread itself, one after another.
+    Inline,
+    /// Run callbacks on an internal pool with the given number of threads.
+    Pool(usize),
+}
+
+/// What to do when a timer expires while a callback from an earlier expiry
+/// is still running.
+///
+/// Only matters when callbacks are dispatched to a pool, since inline
+/// callbacks always finish before the next count down begins.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Drop the new invocation.
+    Skip,
+    /// Run the new invocation once the running one finishes.
+    Queue,
+    /// Run the new invocation alongside the running one.
+    #[default]
+    Concurrent,
+}
+
+/// A registered callback and its overlap bookkeeping.
+struct Slot {
+    f: Callback,
+    // Whether an invocation is running, and how many are queued behind it.
+    state: Mutex<(bool, usize)>,
+    // Total nanoseconds spent running the callback.
+    busy: AtomicU64,
+}
+
+impl Slot {
+    /// Run the callback once, handing any panic to `hooks.0`, and running
+    /// `hooks.1` if it breaks.
+    ///
+    fn call(&self, hooks: &(Option<PanicHook>, Option<BreakHook>)) {
+        let started = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| (self.f)()));
+        self.busy.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        match result {
+            Ok(ControlFlow::Continue(())) => {},
+            Ok(ControlFlow::Break(())) => {
+                if let Some(ref hook) = hooks.1 {
+                    hook();
+                }
+            },
+            Err(payload) => {
+                if let Some(ref hook) = hooks.0 {
+                    hook(payload);
+                }
+            },
+        }
+    }
+    /// Run the callback, then any invocations queued while it ran.
+    ///
+    fn drain(&self, hooks: &(Option<PanicHook>, Option<BreakHook>)) {
+        loop {
+            self.call(hooks);
+            let mut state = self.state.lock().unwrap();
+            if state.1 == 0 {
+                state.0 = false;
+                return;
+            }
+            state.1 -= 1;
+        }
+    }
+}
+
+/// The set of callbacks registered on a timer.
+///
+#[derive(Default)]
+pub struct Callbacks {
+    // Replaced rather than changed in place, so running the callbacks only
+    // has to clone an `Arc`.
+    slots: Mutex<Arc<Vec<Arc<Slot>>>>,
+    // Hook to run when a callback panics.
+    panic_hook: Mutex<Option<PanicHook>>,
+    // Hook to run when a callback breaks.
+    break_hook: Mutex<Option<BreakHook>>,
+}
+
+impl Callbacks {
+    /// Register a callback.
+    ///
+    pub fn push<F, R>(&self, f: F)
+        where F: Fn() -> R + Send + Sync + 'static,
+              R: TickOutcome
+    {
+        Arc::make_mut(&mut *self.slots.lock().unwrap()).push(Arc::new(Slot {
+            f: Box::new(move || f().into_control_flow()),
+            state: Mutex::new((false, 0)),
+            busy: AtomicU64::new(0),
+        }));
+    }
+    /// Move every callback of `other` over to these, to run after them
+    /// under these hooks.
+    ///
+    pub fn absorb(&self, other: &Callbacks) {
+        let moved = std::mem::take(&mut *other.slots.lock().unwrap());
+        Arc::make_mut(&mut *self.slots.lock().unwrap()).extend(moved.iter().cloned());
+    }
+    /// Number of callbacks registered.
+    ///
+    pub fn count(&self) -> usize {
+        self.slots.lock().unwrap().len()
+    }
+    /// Total time spent running callbacks.
+    ///
+    pub fn busy(&self) -> Duration {
+        let slots = self.slots.lock().unwrap();
+        Duration::from_nanos(slots.iter().map(|slot| slot.busy.load(Ordering::Relaxed)).sum())
+    }
+    /// Set the hook to run when a callback panics.
+    ///
+    pub fn set_panic_hook<F>(&self, f: F)
+        where F: Fn(Box<dyn Any + Send>) + Send + Sync + 'static
+    {
+        *self.panic_hook.lock().unwrap() = Some(Arc::new(f));
+    }
+    /// Set the hook to run when a callback returns `ControlFlow::Break`.
+    ///
+    pub fn set_break_hook<F>(&self, f: F)
+        where F: Fn() + Send + Sync + 'static
+    {
+        *self.break_hook.lock().unwrap() = Some(Arc::new(f));
+    }
+    /// Run `f` once inline, handing any panic to the panic hook like a
+    /// registered callback.
+    ///
+    pub fn run_borrowed(&self, f: &mut (dyn FnMut() + Send)) {
+        let hook = self.panic_hook.lock().unwrap().clone();
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+            if let Some(hook) = hook {
+                hook(payload);
+            }
+        }
+    }
+    /// Run every callback once, either inline or on `pool`.
+    ///
+    /// A panicking callback is caught and handed to the panic hook, if any,
+    /// so that it can't take down the timer thread or a pool worker. One
+    /// returning `ControlFlow::Break` runs the break hook, if any. Running
+    /// inline doesn't allocate.
+    ///
+    /// Returns how many callbacks were dropped because the pool's queue was
+    /// full.
+    ///
+    pub fn run(&self, pool: Option<&ThreadPool>, policy: OverlapPolicy) -> usize {
+        let mut dropped = 0;
+        let slots = self.slots.lock().unwrap().clone();
+        let hooks = (self.panic_hook.lock().unwrap().clone(), self.break_hook.lock().unwrap().clone());
+        for slot in slots.iter() {
+            let pool = match pool {
+                Some(pool) => pool,
+                None => {
+                    slot.call(&hooks);
+                    continue;
+                }
+            };
+            if policy != OverlapPolicy::Concurrent {
+                let mut state = slot.state.lock().unwrap();
+                if state.0 {
+                    if policy == OverlapPolicy::Queue {
+                        state.1 += 1;
+                    }
+                    continue;
+                }
+                state.0 = true;
+            }
+            let (queued, hooks) = (slot.clone(), hooks.clone());
+            let accepted = match policy {
+                OverlapPolicy::Concurrent => pool.execute(move || queued.call(&hooks)),
+                _ => pool.execute(move || queued.drain(&hooks)),
+            };
+            if !accepted {
+                if policy != OverlapPolicy::Concurrent {
+                    slot.state.lock().unwrap().0 = false;
+                }
+                dropped += 1;
+            }
+        }
+        dropped
+    }
+}
+
+#[test]
+fn callbacks_skip_while_running() {
+    use thread::ThreadConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    let calls = Arc::new(AtomicUsize::new(0));
+    let callbacks = Callbacks::default();
+    let c = calls.clone();
+    callbacks.push(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+    {
+        let pool = ThreadPool::new(4, &ThreadConfig::default(), "pool").unwrap();
+        for _ in 0..3 {
+            callbacks.run(Some(&pool), OverlapPolicy::Skip);
+        }
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn callbacks_queue_while_running() {
+    use thread::ThreadConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    let running = Arc::new(AtomicUsize::new(0));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let callbacks = Callbacks::default();
+    let (r, c) = (running.clone(), calls.clone());
+    callbacks.push(move || {
+        // Queued invocations must never overlap...
+        assert_eq!(r.fetch_add(1, Ordering::SeqCst), 0);
+        std::thread::sleep(Duration::from_millis(20));
+        r.fetch_sub(1, Ordering::SeqCst);
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+    {
+        let pool = ThreadPool::new(4, &ThreadConfig::default(), "pool").unwrap();
+        for _ in 0..3 {
+            callbacks.run(Some(&pool), OverlapPolicy::Queue);
+        }
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn callbacks_catch_panics() {
+    use thread::ThreadConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let panics = Arc::new(AtomicUsize::new(0));
+    let callbacks = Callbacks::default();
+    callbacks.push::<_, ()>(|| panic!("bad tick"));
+    let p = panics.clone();
+    callbacks.set_panic_hook(move |payload| {
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"bad tick"));
+        p.fetch_add(1, Ordering::SeqCst);
+    });
+    callbacks.run(None, OverlapPolicy::Skip);
+    {
+        let pool = ThreadPool::new(1, &ThreadConfig::default(), "pool").unwrap();
+        callbacks.run(Some(&pool), OverlapPolicy::Skip);
+    }
+    // Skip must not think the panicked invocation is still running...
+    {
+        let pool = ThreadPool::new(1, &ThreadConfig::default(), "pool").unwrap();
+        callbacks.run(Some(&pool), OverlapPolicy::Skip);
+    }
+    assert_eq!(panics.load(Ordering::SeqCst), 3);
+}
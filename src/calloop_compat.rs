@@ -0,0 +1,99 @@
+use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
+use event::{Event, ExpiryEvent};
+use std::io;
+use std::sync::mpsc::Receiver;
+use {Timer, TimerFd};
+
+/// A calloop event source delivering a timer's expiries.
+///
+/// The loop polls the timer's eventfd directly, so each `ExpiryEvent`
+/// reaches the callback on the loop's own thread without passing through
+/// another one.
+///
+pub struct TimerSource {
+    timer: Timer,
+    // The timer's eventfd, polled by the loop.
+    fd: TimerFd,
+    // Expiries waiting to be handed to the loop.
+    events: Receiver<Event>,
+    // Token the eventfd is registered under, once registered.
+    token: Option<Token>,
+}
+
+impl TimerSource {
+    /// Wrap `timer` in an event source, enabling its eventfd.
+    ///
+    pub fn new(mut timer: Timer) -> io::Result<TimerSource> {
+        let fd = timer.enable_eventfd()?;
+        let events = timer.subscribe();
+        Ok(TimerSource { timer, fd, events, token: None })
+    }
+    /// The wrapped timer.
+    ///
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
+    /// The wrapped timer, for starting, stopping and resetting it.
+    ///
+    pub fn timer_mut(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+}
+
+impl EventSource for TimerSource {
+    type Event = ExpiryEvent;
+    type Metadata = Timer;
+    type Ret = ();
+    type Error = io::Error;
+    fn process_events<F>(&mut self, _: Readiness, token: Token, mut callback: F)
+                         -> Result<PostAction, io::Error>
+        where F: FnMut(ExpiryEvent, &mut Timer)
+    {
+        if self.token != Some(token) {
+            return Ok(PostAction::Continue);
+        }
+        self.fd.drain()?;
+        while let Ok(event) = self.events.try_recv() {
+            if let Event::Expired(expiry) = event {
+                callback(expiry, &mut self.timer);
+            }
+        }
+        Ok(PostAction::Continue)
+    }
+    fn register(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
+        let token = factory.token();
+        self.token = Some(token);
+        // The loop unregisters the source before dropping it, so the
+        // descriptor outlives its registration.
+        unsafe { poll.register(&self.fd, Interest::READ, Mode::Level, token) }
+    }
+    fn reregister(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
+        let token = factory.token();
+        self.token = Some(token);
+        poll.reregister(&self.fd, Interest::READ, Mode::Level, token)
+    }
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.token = None;
+        poll.unregister(&self.fd)
+    }
+}
+
+#[test]
+fn timer_calloop_source() {
+    use calloop::EventLoop;
+    use std::sync::{Arc, Condvar};
+    use std::time::{Duration, Instant};
+    let mut event_loop: EventLoop<Vec<usize>> = EventLoop::try_new().unwrap();
+    let timer = Timer::new(Duration::from_millis(10), Duration::from_secs(0), Arc::new(Condvar::new()));
+    let mut source = TimerSource::new(timer).unwrap();
+    source.timer_mut().start();
+    event_loop.handle()
+        .insert_source(source, |expiry, _, counts: &mut Vec<usize>| counts.push(expiry.count))
+        .unwrap();
+    let mut counts = Vec::new();
+    let started = Instant::now();
+    while counts.len() < 3 && started.elapsed() < Duration::from_secs(1) {
+        event_loop.dispatch(Duration::from_millis(100), &mut counts).unwrap();
+    }
+    assert_eq!(&counts[..3], &[1, 2, 3]);
+}
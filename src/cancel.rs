@@ -0,0 +1,300 @@
+use global::global;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// A hook run when a token is cancelled.
+type OnCancel = Box<dyn FnOnce() + Send>;
+
+/// Whether a token is cancelled, and the hooks waiting for it to be.
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    // Keyed by registration id, so they run in the order registered.
+    hooks: BTreeMap<u64, OnCancel>,
+    // Id to give the next hook.
+    next_id: u64,
+}
+
+/// The shared state behind a token and its clones.
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+    // Signalled when the token is cancelled.
+    cv: Condvar,
+    // This token's hook on its parent, if it's a child, dropped along with
+    // it so that the parent doesn't keep a hook per child forever.
+    parent: Mutex<Option<CancelRegistration>>,
+}
+
+/// A hook registered with `CancellationToken::on_cancel`, removed from the
+/// token when dropped unless it has already run.
+///
+/// Use `detach` to keep the hook for as long as the token lives.
+///
+#[must_use = "dropping the registration removes the hook straight away"]
+pub struct CancelRegistration {
+    inner: Weak<Inner>,
+    id: u64,
+}
+
+impl CancelRegistration {
+    /// Keep the hook registered for as long as the token lives.
+    ///
+    pub fn detach(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for CancelRegistration {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.upgrade() {
+            let hook = inner.state.lock().unwrap().hooks.remove(&self.id);
+            // Dropped outside the lock, in case the hook owns a token too.
+            drop(hook);
+        }
+    }
+}
+
+/// A flag that wakes blocked sleepers, stops timers, and cancels child
+/// tokens when set.
+///
+/// Clones share the same flag, so one can be handed to each thread that
+/// needs to observe a shutdown. Child tokens are cancelled along with their
+/// parent, but can also be cancelled on their own without affecting it.
+///
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+/// The original name of `CancellationToken`.
+///
+pub type CancelToken = CancellationToken;
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl CancellationToken {
+    /// Create a new, uncancelled token.
+    ///
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+    /// Create a token that is cancelled along with this one.
+    ///
+    /// The child's hook on this token is removed once every clone of the
+    /// child is dropped, or the child is cancelled.
+    ///
+    pub fn child_token(&self) -> CancellationToken {
+        let child = CancellationToken::new();
+        let weak = Arc::downgrade(&child.inner);
+        let registration = self.on_cancel(move || {
+            if let Some(inner) = weak.upgrade() {
+                CancellationToken { inner }.cancel();
+            }
+        });
+        *child.inner.parent.lock().unwrap() = Some(registration);
+        child
+    }
+    /// Cancel the token, waking everything sleeping on it and running its
+    /// cancellation hooks.
+    ///
+    pub fn cancel(&self) {
+        let hooks = {
+            let mut state = self.inner.state.lock().unwrap();
+            if state.cancelled {
+                return;
+            }
+            state.cancelled = true;
+            std::mem::take(&mut state.hooks)
+        };
+        self.inner.cv.notify_all();
+        // Cancelled, so there's no need to hear from the parent any more.
+        let parent = self.inner.parent.lock().unwrap().take();
+        drop(parent);
+        for (_, hook) in hooks {
+            hook();
+        }
+    }
+    /// Cancel the token once `d` has passed, unless it is cancelled first.
+    ///
+    /// Runs on the global timer pool rather than a thread of its own, and
+    /// doesn't keep the token alive meanwhile.
+    ///
+    pub fn cancel_after(&self, d: Duration) {
+        let weak = Arc::downgrade(&self.inner);
+        global().schedule(d, move || {
+            if let Some(inner) = weak.upgrade() {
+                CancellationToken { inner }.cancel();
+            }
+        });
+    }
+    /// True if the token has been cancelled.
+    ///
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.state.lock().unwrap().cancelled
+    }
+    /// Run `f` when the token is cancelled, or right away if it already is.
+    ///
+    /// The hook stays registered until the returned registration is
+    /// dropped, so keep it for as long as `f` should run, or `detach` it.
+    ///
+    pub fn on_cancel<F>(&self, f: F) -> CancelRegistration
+        where F: FnOnce() + Send + 'static
+    {
+        let id = {
+            let mut state = self.inner.state.lock().unwrap();
+            let id = state.next_id;
+            state.next_id += 1;
+            if !state.cancelled {
+                state.hooks.insert(id, Box::new(f));
+                return CancelRegistration { inner: Arc::downgrade(&self.inner), id };
+            }
+            id
+        };
+        f();
+        CancelRegistration { inner: Arc::downgrade(&self.inner), id }
+    }
+    /// Number of hooks waiting for the token to be cancelled.
+    ///
+    #[cfg(test)]
+    fn hooks(&self) -> usize {
+        self.inner.state.lock().unwrap().hooks.len()
+    }
+}
+
+/// Returned by a sleep that was cut short by its token.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("sleep was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+/// Block for `d`, or until `token` is cancelled.
+///
+/// A `d` too long to represent as an `Instant` blocks until the token is
+/// cancelled.
+///
+pub fn sleep(d: Duration, token: &CancellationToken) -> Result<(), Cancelled> {
+    wait(Instant::now().checked_add(d), token)
+}
+
+/// Block until `deadline`, or until `token` is cancelled.
+///
+/// Returns immediately with `Cancelled` if the token was already cancelled.
+///
+pub fn sleep_until(deadline: Instant, token: &CancellationToken) -> Result<(), Cancelled> {
+    wait(Some(deadline), token)
+}
+
+/// Block until `deadline`, if any, or until `token` is cancelled.
+///
+fn wait(deadline: Option<Instant>, token: &CancellationToken) -> Result<(), Cancelled> {
+    let mut state = token.inner.state.lock().unwrap();
+    loop {
+        if state.cancelled {
+            return Err(Cancelled);
+        }
+        state = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Ok(());
+                }
+                token.inner.cv.wait_timeout(state, deadline - now).unwrap().0
+            },
+            None => token.inner.cv.wait(state).unwrap(),
+        };
+    }
+}
+
+#[test]
+fn sleep_runs_to_completion() {
+    let token = CancellationToken::new();
+    let started = Instant::now();
+    assert_eq!(sleep(Duration::from_millis(20), &token), Ok(()));
+    assert!(started.elapsed() >= Duration::from_millis(20));
+}
+
+#[test]
+fn sleep_is_cancelled() {
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    let started = Instant::now();
+    let t = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        canceller.cancel();
+    });
+    assert_eq!(sleep(Duration::from_secs(10), &token), Err(Cancelled));
+    assert!(started.elapsed() < Duration::from_secs(10));
+    t.join().unwrap();
+    // Already cancelled tokens don't sleep at all...
+    assert_eq!(sleep_until(Instant::now() + Duration::from_secs(10), &token), Err(Cancelled));
+}
+
+#[test]
+fn child_tokens() {
+    let parent = CancellationToken::new();
+    let child = parent.child_token();
+    let grandchild = child.child_token();
+    let sibling = parent.child_token();
+    child.cancel();
+    assert!(grandchild.is_cancelled());
+    assert!(!parent.is_cancelled());
+    assert!(!sibling.is_cancelled());
+    parent.cancel();
+    assert!(sibling.is_cancelled());
+}
+
+#[test]
+fn hooks_are_removed_with_their_registrations() {
+    let parent = CancellationToken::new();
+    for _ in 0..10 {
+        let child = parent.child_token();
+        let _clone = child.clone();
+    }
+    assert_eq!(parent.hooks(), 0);
+    let child = parent.child_token();
+    let registration = parent.on_cancel(|| {});
+    assert_eq!(parent.hooks(), 2);
+    drop(registration);
+    child.cancel();
+    assert_eq!(parent.hooks(), 0);
+    let (tx, rx) = std::sync::mpsc::channel();
+    parent.on_cancel(move || tx.send(()).unwrap()).detach();
+    parent.cancel();
+    assert_eq!(rx.try_recv(), Ok(()));
+}
+
+#[test]
+fn cancel_after() {
+    let token = CancellationToken::new();
+    token.cancel_after(Duration::from_millis(20));
+    assert!(!token.is_cancelled());
+    assert_eq!(sleep(Duration::from_secs(10), &token), Err(Cancelled));
+    // Doesn't keep the token alive while waiting.
+    let token = CancellationToken::new();
+    token.cancel_after(Duration::from_secs(10));
+    assert_eq!(Arc::strong_count(&token.inner), 1);
+}
+
+#[test]
+fn sleep_forever_until_cancelled() {
+    let token = CancellationToken::new();
+    token.cancel_after(Duration::from_millis(20));
+    assert_eq!(sleep(Duration::MAX, &token), Err(Cancelled));
+}
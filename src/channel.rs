@@ -0,0 +1,308 @@
+use event::{Event, ExpiryEvent};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// What a bounded subscription does with an event that arrives while it's
+/// full.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnFull {
+    /// Wait for the consumer to make room, stalling the timer meanwhile.
+    /// Stopping the timer drops the event instead of waiting any longer.
+    Block,
+    /// Drop the oldest queued event to make room.
+    DropOldest,
+    /// Drop the new event.
+    DropNewest,
+    /// Merge a new expiry into the newest queued one, adding up their
+    /// `coalesced` counts. Any other event is dropped.
+    Coalesce,
+}
+
+/// The queue and who's still connected to it.
+struct Queue {
+    events: VecDeque<Event>,
+    // False once the receiver is dropped.
+    receiver: bool,
+    // False once the sender is dropped.
+    sender: bool,
+    // True while sends mustn't block, e.g., while the timer stops.
+    interrupted: bool,
+}
+
+/// State shared by both ends of a bounded subscription.
+struct Shared {
+    queue: Mutex<Queue>,
+    capacity: usize,
+    on_full: OnFull,
+    // Events dropped because the queue was full.
+    dropped: AtomicUsize,
+    // Signalled when an event is queued, or the sender is dropped.
+    readable: Condvar,
+    // Signalled when an event is taken, or the receiver is dropped.
+    writable: Condvar,
+}
+
+/// Create a new bounded subscription holding up to `capacity` events.
+///
+pub fn bounded(capacity: usize, on_full: OnFull) -> (BoundedSender, BoundedReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue {
+            events: VecDeque::with_capacity(capacity.max(1)),
+            receiver: true,
+            sender: true,
+            interrupted: false,
+        }),
+        capacity: capacity.max(1),
+        on_full,
+        dropped: AtomicUsize::new(0),
+        readable: Condvar::new(),
+        writable: Condvar::new(),
+    });
+    (BoundedSender { shared: shared.clone() }, BoundedReceiver { shared })
+}
+
+/// The sending end of a bounded subscription.
+///
+pub struct BoundedSender {
+    shared: Arc<Shared>,
+}
+
+impl BoundedSender {
+    /// Queue `event` per the subscription's `OnFull` policy, returning false
+    /// if the receiver has hung up.
+    ///
+    pub fn send(&self, event: Event) -> bool {
+        let shared = &*self.shared;
+        let mut queue = shared.queue.lock().unwrap();
+        if queue.events.len() >= shared.capacity && queue.receiver {
+            match shared.on_full {
+                OnFull::Block => {
+                    while queue.events.len() >= shared.capacity && queue.receiver && !queue.interrupted {
+                        queue = shared.writable.wait(queue).unwrap();
+                    }
+                    if queue.events.len() >= shared.capacity && queue.receiver {
+                        // Given up on, since the timer is stopping.
+                        shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        return true;
+                    }
+                },
+                OnFull::DropOldest => {
+                    queue.events.pop_front();
+                    shared.dropped.fetch_add(1, Ordering::Relaxed);
+                },
+                OnFull::DropNewest => {
+                    shared.dropped.fetch_add(1, Ordering::Relaxed);
+                    return true;
+                },
+                OnFull::Coalesce => {
+                    match (queue.events.back_mut(), event) {
+                        (Some(&mut Event::Expired(ref mut last)), Event::Expired(next)) => {
+                            *last = ExpiryEvent { coalesced: last.coalesced + next.coalesced, ..next };
+                        },
+                        _ => {
+                            shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        },
+                    }
+                    return true;
+                },
+            }
+        }
+        if !queue.receiver {
+            return false;
+        }
+        queue.events.push_back(event);
+        shared.readable.notify_one();
+        true
+    }
+    /// A handle to interrupt sends with, which doesn't keep the
+    /// subscription alive.
+    ///
+    pub fn interrupter(&self) -> Interrupter {
+        Interrupter { shared: Arc::downgrade(&self.shared) }
+    }
+}
+
+/// Wakes sends blocked on a full subscription, from `BoundedSender`.
+///
+pub struct Interrupter {
+    shared: Weak<Shared>,
+}
+
+impl Interrupter {
+    /// Make sends that find the subscription full drop their event rather
+    /// than block, waking any blocked now, or block again once `false`.
+    /// Returns false if the subscription is gone.
+    ///
+    pub fn interrupt(&self, interrupted: bool) -> bool {
+        let shared = match self.shared.upgrade() {
+            Some(shared) => shared,
+            None => return false,
+        };
+        shared.queue.lock().unwrap().interrupted = interrupted;
+        shared.writable.notify_all();
+        true
+    }
+}
+
+impl Drop for BoundedSender {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().sender = false;
+        self.shared.readable.notify_all();
+    }
+}
+
+/// The receiving end of a bounded subscription, from
+/// `Timer::subscribe_bounded`.
+///
+/// Works like a `Receiver`, but holds at most a fixed number of events,
+/// and counts those it had to drop.
+///
+pub struct BoundedReceiver {
+    shared: Arc<Shared>,
+}
+
+impl BoundedReceiver {
+    /// Take the next event, waiting for one if need be.
+    ///
+    /// Fails once the timer is gone and every queued event has been taken.
+    ///
+    pub fn recv(&self) -> Result<Event, RecvError> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        loop {
+            if let Some(event) = queue.events.pop_front() {
+                self.shared.writable.notify_one();
+                return Ok(event);
+            }
+            if !queue.sender {
+                return Err(RecvError);
+            }
+            queue = self.shared.readable.wait(queue).unwrap();
+        }
+    }
+    /// Take the next event, if one is queued.
+    ///
+    pub fn try_recv(&self) -> Result<Event, TryRecvError> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        match queue.events.pop_front() {
+            Some(event) => {
+                self.shared.writable.notify_one();
+                Ok(event)
+            },
+            None if queue.sender => Err(TryRecvError::Empty),
+            None => Err(TryRecvError::Disconnected),
+        }
+    }
+    /// Take the next event, waiting up to `timeout` for one.
+    ///
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Event, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.shared.queue.lock().unwrap();
+        loop {
+            if let Some(event) = queue.events.pop_front() {
+                self.shared.writable.notify_one();
+                return Ok(event);
+            }
+            if !queue.sender {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            queue = self.shared.readable.wait_timeout(queue, deadline - now).unwrap().0;
+        }
+    }
+    /// Number of events dropped because the subscription was full.
+    ///
+    pub fn dropped(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for BoundedReceiver {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().receiver = false;
+        self.shared.writable.notify_all();
+    }
+}
+
+#[test]
+fn bounded_on_full_policies() {
+    use clock::Timestamp;
+    let expiry = |count| {
+        let stamp = Timestamp::Custom(Duration::from_millis(count as u64));
+        Event::Expired(ExpiryEvent { count, deadline: stamp, fired: stamp, coalesced: 1 })
+    };
+    let count = |event| match event {
+        Event::Expired(e) => (e.count, e.coalesced),
+        _ => unreachable!(),
+    };
+    for &(on_full, kept, dropped) in &[(OnFull::DropOldest, [(2, 1), (3, 1)], 1),
+                                       (OnFull::DropNewest, [(1, 1), (2, 1)], 1),
+                                       (OnFull::Coalesce, [(1, 1), (3, 2)], 0)] {
+        let (tx, rx) = bounded(2, on_full);
+        for n in 1..4 {
+            assert!(tx.send(expiry(n)));
+        }
+        drop(tx);
+        assert_eq!(count(rx.recv().unwrap()), kept[0]);
+        assert_eq!(count(rx.recv().unwrap()), kept[1]);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(rx.dropped(), dropped);
+    }
+    let (tx, rx) = bounded(1, OnFull::Block);
+    assert!(tx.send(expiry(1)));
+    let blocked = std::thread::spawn(move || tx.send(expiry(2)));
+    assert_eq!(count(rx.recv().unwrap()), (1, 1));
+    assert_eq!(count(rx.recv().unwrap()), (2, 1));
+    assert!(blocked.join().unwrap());
+}
+
+#[test]
+fn timer_subscribe_bounded() {
+    use std::sync::Condvar;
+    use Timer;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(5), ms(0), Arc::new(Condvar::new()));
+    t.set_max_expiries(10);
+    let events = t.subscribe_bounded(2, OnFull::DropOldest);
+    t.start();
+    std::thread::sleep(ms(100));
+    t.stop();
+    let mut counts = Vec::new();
+    while let Ok(Event::Expired(expiry)) = events.try_recv() {
+        counts.push(expiry.count);
+    }
+    // The last expiry, then `Completed`, which pushed out the one before.
+    assert_eq!(counts, vec![10]);
+    assert_eq!(events.dropped(), 9);
+}
+
+#[test]
+fn timer_stops_with_a_stalled_bounded_subscriber() {
+    use std::sync::Condvar;
+    use Timer;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(1), ms(0), Arc::new(Condvar::new()));
+    let events = t.subscribe_bounded(1, OnFull::Block);
+    t.start();
+    // Nobody reads, so the timer blocks on its second expiry.
+    std::thread::sleep(ms(20));
+    let stopping = Instant::now();
+    t.stop();
+    assert!(stopping.elapsed() < Duration::from_secs(1));
+    assert!(events.dropped() >= 1);
+    assert!(matches!(events.try_recv(), Ok(Event::Expired(_))));
+    // Restarted, it blocks for the receiver again.
+    t.start();
+    std::thread::sleep(ms(20));
+    assert!(matches!(events.recv_timeout(ms(100)), Ok(Event::Expired(_))));
+    let dropped = events.dropped();
+    std::thread::sleep(ms(20));
+    assert_eq!(events.dropped(), dropped);
+    t.stop();
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Durable storage for a timer's next deadline.
+///
+/// A timer with a checkpoint saves the wall clock time of every deadline it
+/// counts down to, and resumes from the saved one when next started. A
+/// count down of days or weeks therefore survives restarts of the process,
+/// firing immediately on start if its deadline passed while it was down.
+///
+pub trait Checkpoint: Send {
+    /// Record `due` as the deadline currently being counted down to.
+    ///
+    fn save(&mut self, due: SystemTime) -> io::Result<()>;
+    /// The deadline last recorded, if any.
+    ///
+    fn load(&mut self) -> io::Result<Option<SystemTime>>;
+}
+
+/// A checkpoint kept in a small text file.
+///
+/// Each save writes a sibling temporary file and renames it over the
+/// original, so a crash mid-save leaves the previous deadline intact.
+///
+#[derive(Clone, Debug)]
+pub struct FileCheckpoint {
+    path: PathBuf,
+}
+
+impl FileCheckpoint {
+    /// Create a new checkpoint stored at `path`.
+    ///
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileCheckpoint {
+        FileCheckpoint { path: path.into() }
+    }
+}
+
+impl Checkpoint for FileCheckpoint {
+    fn save(&mut self, due: SystemTime) -> io::Result<()> {
+        let since = due.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, format!("{}.{:09}\n", since.as_secs(), since.subsec_nanos()))?;
+        fs::rename(&tmp, &self.path)
+    }
+    fn load(&mut self) -> io::Result<Option<SystemTime>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed checkpoint");
+        let mut parts = contents.trim().splitn(2, '.');
+        let secs = parts.next().and_then(|s| s.parse().ok()).ok_or_else(invalid)?;
+        let nanos = parts.next().and_then(|s| s.parse().ok()).ok_or_else(invalid)?;
+        Ok(Some(UNIX_EPOCH + Duration::new(secs, nanos)))
+    }
+}
+
+#[test]
+fn file_checkpoint_round_trips() {
+    let path = std::env::temp_dir().join(format!("timer-checkpoint-{}", std::process::id()));
+    let mut checkpoint = FileCheckpoint::new(&path);
+    assert!(checkpoint.load().unwrap().is_none());
+    let due = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+    checkpoint.save(due).unwrap();
+    assert_eq!(checkpoint.load().unwrap(), Some(due));
+    fs::write(&path, "garbage").unwrap();
+    assert!(checkpoint.load().is_err());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,81 @@
+use chrono::{DateTime, NaiveTime, OutOfRangeError, TimeZone, Utc};
+use std::sync::{Arc, Condvar};
+use std::time::SystemTime;
+use Timer;
+
+/// Find the first time after `now` that reads `time` on the wall clock of
+/// `now`'s time zone.
+///
+/// Times skipped by a daylight saving change fall an hour later, and times
+/// repeated by one use their earliest occurrence.
+///
+fn next_daily<Tz: TimeZone>(now: &DateTime<Tz>, time: NaiveTime) -> DateTime<Tz> {
+    let tz = now.timezone();
+    let mut date = now.date_naive();
+    loop {
+        let local = date.and_time(time);
+        let candidate = tz.from_local_datetime(&local).earliest()
+            .or_else(|| tz.from_local_datetime(&(local + chrono::Duration::hours(1))).earliest());
+        if let Some(candidate) = candidate {
+            if candidate > *now {
+                return candidate;
+            }
+        }
+        date = date.succ_opt().expect("Ran out of dates!");
+    }
+}
+
+impl Timer {
+    /// Create a new timer from `chrono` durations.
+    ///
+    /// Fails if either duration is negative.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The duration of time to wait for each count down.
+    /// * `jitter` - The duration of time to randomize each count down.
+    /// * `timed_out` - Condition to signal if the timer expires.
+    ///
+    pub fn from_chrono(step: chrono::Duration,
+                       jitter: chrono::Duration,
+                       timed_out: Arc<Condvar>) -> Result<Timer, OutOfRangeError> {
+        Ok(Timer::new(step.to_std()?, jitter.to_std()?, timed_out))
+    }
+    /// Expire every day when the wall clock in `tz` reads `time`.
+    ///
+    /// Replaces counting down `step`, and follows daylight saving changes
+    /// in `tz`.
+    ///
+    pub fn daily_at<Tz>(&mut self, time: NaiveTime, tz: Tz)
+        where Tz: TimeZone + Send + 'static,
+              Tz::Offset: Send
+    {
+        self.set_wall_schedule(move |now: SystemTime| {
+            let now = DateTime::<Utc>::from(now).with_timezone(&tz);
+            Some(SystemTime::from(next_daily(&now, time)))
+        });
+    }
+}
+
+#[test]
+fn chrono_next_daily() {
+    use chrono::FixedOffset;
+    let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+    let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+    let before = tz.with_ymd_and_hms(2024, 3, 1, 8, 0, 0).unwrap();
+    assert_eq!(next_daily(&before, nine), tz.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap());
+    let after = tz.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+    assert_eq!(next_daily(&after, nine), tz.with_ymd_and_hms(2024, 3, 2, 9, 0, 0).unwrap());
+}
+
+#[test]
+fn chrono_constructor() {
+    let cv = Arc::new(Condvar::new());
+    let t = Timer::from_chrono(chrono::Duration::seconds(5),
+                               chrono::Duration::milliseconds(10),
+                               cv.clone()).unwrap();
+    assert_eq!(t.step(), std::time::Duration::from_secs(5));
+    assert!(Timer::from_chrono(chrono::Duration::seconds(-5),
+                               chrono::Duration::zero(),
+                               cv).is_err());
+}
@@ -0,0 +1,361 @@
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use suspend;
+
+/// A source of time for a timer.
+///
+/// Readings are offsets from the clock's own epoch and must never be
+/// compared across clocks.
+///
+pub trait Clock: Send + Sync {
+    /// The current reading of the clock.
+    ///
+    fn now(&self) -> Duration;
+    /// How long to block, in real time, when waiting `d` on this clock.
+    ///
+    /// Timers re-read the clock after every wait, so clocks that don't
+    /// advance with real time can return a short poll interval here.
+    ///
+    fn real_wait(&self, d: Duration) -> Duration {
+        d
+    }
+    /// Total time the machine has spent suspended that this clock didn't
+    /// count, offset by an arbitrary constant.
+    ///
+    fn suspended(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+    /// The wall clock time alongside the current reading, for clocks that
+    /// stand in for the wall clock too, or `None` to use the system's.
+    ///
+    fn wall(&self) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// The clock a timer computes its deadlines and timestamps against.
+///
+#[derive(Clone, Default)]
+pub enum ClockSource {
+    /// A monotonic clock, unaffected by changes to the system time.
+    #[default]
+    Monotonic,
+    /// The system's wall clock, measured from the UNIX epoch.
+    Wall,
+    /// A user provided clock.
+    Custom(Arc<dyn Clock>),
+}
+
+impl fmt::Debug for ClockSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClockSource::Monotonic => f.write_str("Monotonic"),
+            ClockSource::Wall => f.write_str("Wall"),
+            ClockSource::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// The instant all monotonic readings are measured from.
+///
+fn anchor() -> Instant {
+    static ANCHOR: OnceLock<Instant> = OnceLock::new();
+    *ANCHOR.get_or_init(Instant::now)
+}
+
+impl ClockSource {
+    /// The current reading of the clock.
+    ///
+    pub fn reading(&self) -> Duration {
+        match *self {
+            ClockSource::Monotonic => anchor().elapsed(),
+            ClockSource::Wall => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            ClockSource::Custom(ref clock) => clock.now(),
+        }
+    }
+    /// The current time on the clock.
+    ///
+    pub fn now(&self) -> Timestamp {
+        self.stamp(self.reading())
+    }
+    /// Convert a reading of this clock into a timestamp.
+    ///
+    pub fn stamp(&self, reading: Duration) -> Timestamp {
+        match *self {
+            ClockSource::Monotonic => Timestamp::Monotonic(anchor() + reading),
+            ClockSource::Wall => Timestamp::Wall(UNIX_EPOCH + reading),
+            ClockSource::Custom(_) => Timestamp::Custom(reading),
+        }
+    }
+    /// The wall clock time, as told by a custom clock if it has one.
+    ///
+    pub fn wall(&self) -> SystemTime {
+        match *self {
+            ClockSource::Custom(ref clock) => clock.wall().unwrap_or_else(SystemTime::now),
+            _ => SystemTime::now(),
+        }
+    }
+    /// True if the clock's readings relate to the wall clock at all.
+    ///
+    /// Custom clocks only do if they tell the wall clock time themselves.
+    ///
+    pub fn has_wall(&self) -> bool {
+        match *self {
+            ClockSource::Custom(ref clock) => clock.wall().is_some(),
+            _ => true,
+        }
+    }
+    /// The reading this clock is expected to show at wall clock time `at`.
+    ///
+    /// Times in the past map to the current reading.
+    ///
+    pub fn reading_at(&self, at: SystemTime) -> Duration {
+        match *self {
+            ClockSource::Wall => at.duration_since(UNIX_EPOCH).unwrap_or_default(),
+            _ => self.reading().saturating_add(at.duration_since(self.wall()).unwrap_or_default()),
+        }
+    }
+    /// The wall clock time this clock is expected to show `reading` at, or
+    /// `None` for custom clocks, which have no relation to the wall clock.
+    ///
+    pub fn wall_time_at(&self, reading: Duration) -> Option<SystemTime> {
+        match *self {
+            ClockSource::Monotonic => {
+                let now = self.reading();
+                match reading.checked_sub(now) {
+                    Some(ahead) => SystemTime::now().checked_add(ahead),
+                    None => SystemTime::now().checked_sub(now - reading),
+                }
+            },
+            ClockSource::Wall => UNIX_EPOCH.checked_add(reading),
+            ClockSource::Custom(_) => None,
+        }
+    }
+    /// How long to block, in real time, when waiting `d` on this clock.
+    ///
+    pub fn real_wait(&self, d: Duration) -> Duration {
+        match *self {
+            ClockSource::Custom(ref clock) => clock.real_wait(d),
+            _ => d,
+        }
+    }
+    /// Total time the machine has spent suspended that this clock didn't
+    /// count, offset by an arbitrary constant.
+    ///
+    /// The wall clock keeps counting while suspended, so this is always
+    /// zero for it.
+    ///
+    pub fn suspended(&self) -> Duration {
+        match *self {
+            ClockSource::Monotonic => suspend::suspended(),
+            ClockSource::Wall => Duration::from_secs(0),
+            ClockSource::Custom(ref clock) => clock.suspended(),
+        }
+    }
+}
+
+/// A point in time, as read from a timer's clock.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Timestamp {
+    /// A reading of the monotonic clock.
+    Monotonic(Instant),
+    /// A reading of the wall clock.
+    Wall(SystemTime),
+    /// A reading of a user provided clock.
+    Custom(Duration),
+}
+
+impl Timestamp {
+    /// The wall clock time this timestamp corresponds to.
+    ///
+    /// Monotonic timestamps are mapped through the current offset between
+    /// the two clocks, and timestamps of user provided clocks can't be
+    /// mapped at all.
+    ///
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        match *self {
+            Timestamp::Monotonic(t) => {
+                let now = Instant::now();
+                if t <= now {
+                    SystemTime::now().checked_sub(now - t)
+                } else {
+                    SystemTime::now().checked_add(t - now)
+                }
+            },
+            Timestamp::Wall(t) => Some(t),
+            Timestamp::Custom(_) => None,
+        }
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Useful for testing timer driven logic without sleeping.
+///
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: Mutex<Duration>,
+    suspended: Mutex<Duration>,
+    // Wall clock time at a reading of zero, once the wall clock is mocked.
+    wall: Mutex<Option<SystemTime>>,
+}
+
+impl MockClock {
+    /// Create a new mock clock reading zero.
+    ///
+    pub fn new() -> MockClock {
+        MockClock::default()
+    }
+    /// Move the clock forward by `d`.
+    ///
+    pub fn advance(&self, d: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now = now.saturating_add(d);
+    }
+    /// Set the clock to read `now`.
+    ///
+    pub fn set(&self, now: Duration) {
+        *self.now.lock().unwrap() = now;
+    }
+    /// Pretend the machine was suspended for `d`, without moving the clock.
+    ///
+    pub fn suspend(&self, d: Duration) {
+        let mut suspended = self.suspended.lock().unwrap();
+        *suspended = suspended.saturating_add(d);
+    }
+    /// Set the wall clock to read `at`, as if the system time were changed,
+    /// without moving the clock.
+    ///
+    /// Until this is called the mock follows the system's wall clock. After,
+    /// the wall clock moves along with `advance` and `set`.
+    ///
+    pub fn set_wall(&self, at: SystemTime) {
+        let now = *self.now.lock().unwrap();
+        *self.wall.lock().unwrap() = Some(at.checked_sub(now).unwrap_or(UNIX_EPOCH));
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+    fn real_wait(&self, d: Duration) -> Duration {
+        d.min(Duration::from_millis(1))
+    }
+    fn suspended(&self) -> Duration {
+        *self.suspended.lock().unwrap()
+    }
+    fn wall(&self) -> Option<SystemTime> {
+        let origin = (*self.wall.lock().unwrap())?;
+        origin.checked_add(self.now())
+    }
+}
+
+/// A sudden step of a clock, e.g., an NTP correction or a manual change.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockJump {
+    /// True if the clock jumped forward, false if it jumped back.
+    pub forward: bool,
+    /// How far the clock jumped.
+    pub by: Duration,
+}
+
+/// Detects jumps of a clock by comparing it against a reference clock that
+/// never jumps.
+///
+pub struct JumpDetector {
+    clock: Duration,
+    reference: Duration,
+    threshold: Duration,
+}
+
+impl JumpDetector {
+    /// Create a new detector from a pair of readings.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - A reading of the clock to watch.
+    /// * `reference` - A reading of the reference clock taken at the same time.
+    /// * `threshold` - Smallest difference in progress that counts as a jump.
+    ///
+    pub fn new(clock: Duration, reference: Duration, threshold: Duration) -> JumpDetector {
+        JumpDetector { clock, reference, threshold }
+    }
+    /// Compare a new pair of readings against the previous pair.
+    ///
+    /// Returns the jump if the clock moved further from the reference than
+    /// the threshold since the previous readings.
+    ///
+    pub fn observe(&mut self, clock: Duration, reference: Duration) -> Option<ClockJump> {
+        let expected = self.clock.saturating_add(reference.checked_sub(self.reference).unwrap_or_default());
+        self.clock = clock;
+        self.reference = reference;
+        let jump = if clock >= expected {
+            ClockJump { forward: true, by: clock - expected }
+        } else {
+            ClockJump { forward: false, by: expected - clock }
+        };
+        if jump.by > self.threshold {
+            Some(jump)
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn clock_stamps() {
+    let mock = Arc::new(MockClock::new());
+    let clock = ClockSource::Custom(mock.clone());
+    mock.advance(Duration::from_secs(3));
+    assert_eq!(clock.now(), Timestamp::Custom(Duration::from_secs(3)));
+    let wall = ClockSource::Wall;
+    match wall.now() {
+        Timestamp::Wall(t) => assert!(t > UNIX_EPOCH),
+        other => panic!("unexpected {:?}", other),
+    }
+    let monotonic = ClockSource::Monotonic;
+    assert!(monotonic.reading() <= monotonic.reading());
+}
+
+#[test]
+fn clock_jump_detection() {
+    let s = Duration::from_secs;
+    let mut detector = JumpDetector::new(s(1000), s(10), s(1));
+    assert_eq!(detector.observe(s(1005), s(15)), None);
+    assert_eq!(detector.observe(s(4605), s(16)),
+               Some(ClockJump { forward: true, by: s(3599) }));
+    assert_eq!(detector.observe(s(4600), s(17)),
+               Some(ClockJump { forward: false, by: s(6) }));
+    assert_eq!(detector.observe(s(4601), s(18)), None);
+}
+
+#[test]
+fn timestamp_to_system_time() {
+    let wall = SystemTime::now();
+    assert_eq!(Timestamp::Wall(wall).to_system_time(), Some(wall));
+    let mapped = Timestamp::Monotonic(Instant::now()).to_system_time().unwrap();
+    assert!(mapped.duration_since(wall).unwrap() < Duration::from_secs(1));
+    assert_eq!(Timestamp::Custom(Duration::from_secs(1)).to_system_time(), None);
+}
+
+#[test]
+fn mock_clock_wall() {
+    let s = Duration::from_secs;
+    let mock = Arc::new(MockClock::new());
+    let clock = ClockSource::Custom(mock.clone());
+    assert!(!clock.has_wall());
+    mock.advance(s(10));
+    mock.set_wall(UNIX_EPOCH + s(1000));
+    assert!(clock.has_wall());
+    mock.advance(s(5));
+    assert_eq!(clock.wall(), UNIX_EPOCH + s(1005));
+    assert_eq!(clock.reading_at(UNIX_EPOCH + s(1065)), s(75));
+    mock.set_wall(UNIX_EPOCH + s(1060));
+    assert_eq!(clock.reading_at(UNIX_EPOCH + s(1065)), s(20));
+}
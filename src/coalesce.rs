@@ -0,0 +1,117 @@
+use event::ExpiryEvent;
+use std::time::Duration;
+use Timer;
+
+/// Batches expiries that follow closely on one another into a single
+/// notification.
+///
+/// The first expiry is delivered straight away and opens a window. Any
+/// more before the window closes are held back, then delivered together as
+/// it closes.
+///
+pub struct Coalescer {
+    window: Duration,
+    // Clock reading the open window closes at, if one is open.
+    closes: Option<Duration>,
+    // Expiries held back in the open window, merged into one.
+    held: Option<ExpiryEvent>,
+}
+
+impl Coalescer {
+    /// Create a new coalescer with the given window.
+    ///
+    pub fn new(window: Duration) -> Coalescer {
+        Coalescer { window, closes: None, held: None }
+    }
+    /// Take in an expiry at clock reading `now`, returning what to deliver
+    /// right away, if anything.
+    ///
+    pub fn hold(&mut self, now: Duration, event: ExpiryEvent) -> Option<ExpiryEvent> {
+        match self.closes {
+            Some(closes) if now < closes => {
+                self.held = Some(match self.held.take() {
+                    Some(held) => ExpiryEvent { coalesced: held.coalesced + event.coalesced, ..event },
+                    None => event,
+                });
+                None
+            },
+            _ => {
+                // A window that closed unnoticed is flushed along with this.
+                let event = match self.held.take() {
+                    Some(held) => ExpiryEvent { coalesced: held.coalesced + event.coalesced, ..event },
+                    None => event,
+                };
+                self.closes = Some(now.saturating_add(self.window));
+                Some(event)
+            },
+        }
+    }
+    /// When the held expiries are due to be delivered, if there are any.
+    ///
+    pub fn due(&self) -> Option<Duration> {
+        self.held.and(self.closes)
+    }
+    /// Close the window, returning the expiries held back in it, if any.
+    ///
+    pub fn flush(&mut self) -> Option<ExpiryEvent> {
+        self.closes = None;
+        self.held.take()
+    }
+}
+
+impl Timer {
+    /// Deliver expiries that come within `window` of a delivered expiry as
+    /// one, carrying the number of expiries in `ExpiryEvent::coalesced`.
+    ///
+    /// Protects consumers from wakeup storms, such as a burst of catch up
+    /// expiries after a suspend. The first expiry in a burst is delivered
+    /// straight away, and the rest once the window closes. Coalesced
+    /// expiries signal `timed_out` and run callbacks once. A zero `window`
+    /// turns coalescing off. Takes effect the next time the timer is
+    /// started.
+    ///
+    pub fn coalesce_within(&mut self, window: Duration) {
+        self.coalesce = if window > Duration::from_secs(0) { Some(window) } else { None };
+    }
+}
+
+#[test]
+fn coalescer_batches_bursts() {
+    use clock::Timestamp;
+    let ms = Duration::from_millis;
+    let event = |count| {
+        let stamp = Timestamp::Custom(ms(count as u64));
+        ExpiryEvent { count, deadline: stamp, fired: stamp, coalesced: 1 }
+    };
+    let mut coalescer = Coalescer::new(ms(10));
+    assert_eq!(coalescer.hold(ms(0), event(1)).map(|e| e.count), Some(1));
+    assert_eq!(coalescer.due(), None);
+    assert_eq!(coalescer.hold(ms(1), event(2)), None);
+    assert_eq!(coalescer.hold(ms(2), event(3)), None);
+    assert_eq!(coalescer.due(), Some(ms(10)));
+    let batch = coalescer.flush().unwrap();
+    assert_eq!((batch.count, batch.coalesced), (3, 2));
+    assert_eq!(coalescer.hold(ms(11), event(4)).map(|e| e.coalesced), Some(1));
+}
+
+#[test]
+fn timer_coalesce_within() {
+    use event::Event;
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(5), ms(0), Arc::new(Condvar::new()));
+    t.coalesce_within(ms(30));
+    t.set_max_expiries(10);
+    let events = t.subscribe();
+    t.start();
+    std::thread::sleep(ms(100));
+    t.stop();
+    let coalesced: Vec<usize> = events.try_iter()
+        .filter_map(|event| match event {
+            Event::Expired(expiry) => Some(expiry.coalesced),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(coalesced.iter().sum::<usize>(), 10);
+    assert!(coalesced.len() < 10, "{:?}", coalesced);
+}
@@ -0,0 +1,73 @@
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use Timer;
+
+/// Whether a timer has used up its expiry budget.
+///
+#[derive(Default)]
+pub struct Completion {
+    done: Mutex<bool>,
+    cv: Condvar,
+}
+
+impl Completion {
+    /// Mark the budget used up and wake every waiter.
+    ///
+    pub fn complete(&self) {
+        *self.done.lock().unwrap() = true;
+        self.cv.notify_all();
+    }
+    /// Forget a budget used up before, as the timer starts again.
+    ///
+    pub fn clear(&self) {
+        *self.done.lock().unwrap() = false;
+    }
+    /// Wait up to `timeout` for the budget to be used up, returning true if
+    /// it has been.
+    ///
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut done = self.done.lock().unwrap();
+        while !*done {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            done = self.cv.wait_timeout(done, deadline - now).unwrap().0;
+        }
+        true
+    }
+}
+
+impl Timer {
+    /// Wait up to `timeout` for the timer to expire the number of times
+    /// given to `set_max_expiries` and stop, returning true if it has.
+    ///
+    /// Completing also emits `Event::Completed`, after the last expiry. A
+    /// timer stopped any other way never completes. Starting the timer
+    /// again starts a fresh budget.
+    ///
+    pub fn wait_for_completion(&self, timeout: Duration) -> bool {
+        self.completion.wait(timeout)
+    }
+}
+
+#[test]
+fn timer_completion() {
+    use event::Event;
+    use std::sync::Arc;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(10), ms(0), Arc::new(Condvar::new()));
+    t.set_max_expiries(3);
+    let events = t.subscribe();
+    assert!(!t.wait_for_completion(ms(0)));
+    t.start();
+    assert!(t.wait_for_completion(Duration::from_secs(1)));
+    assert!(!t.is_running());
+    let events: Vec<Event> = events.try_iter().collect();
+    assert_eq!(events.len(), 4, "{:?}", events);
+    assert_eq!(events.last(), Some(&Event::Completed));
+    t.start();
+    assert!(!t.wait_for_completion(ms(0)));
+    t.stop();
+}
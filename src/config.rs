@@ -0,0 +1,243 @@
+use clock::ClockSource;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The largest step or jitter a timer accepts, about a hundred years.
+///
+pub const MAX_DURATION: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// How jitter is applied to each count down.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JitterPolicy {
+    /// Count down from somewhere between `step - jitter` and `step`.
+    #[default]
+    Subtractive,
+    /// Count down from somewhere between `step` and `step + jitter`.
+    Additive,
+}
+
+impl FromStr for JitterPolicy {
+    type Err = ConfigError;
+    /// Parse `"subtractive"` or `"additive"`, ignoring case.
+    ///
+    fn from_str(s: &str) -> Result<JitterPolicy, ConfigError> {
+        match s.to_ascii_lowercase().as_str() {
+            "subtractive" => Ok(JitterPolicy::Subtractive),
+            "additive" => Ok(JitterPolicy::Additive),
+            _ => Err(ConfigError::UnknownJitterPolicy(s.to_string())),
+        }
+    }
+}
+
+/// Everything needed to build a timer.
+///
+/// Use `validate` or `Timer::from_config` to catch misconfiguration before
+/// the timer starts.
+///
+#[derive(Clone, Debug)]
+pub struct TimerConfig {
+    /// The duration of time to wait for each count down.
+    pub step: Duration,
+    /// The duration of time to randomize each count down.
+    pub jitter: Duration,
+    /// How `jitter` is applied to `step`.
+    pub jitter_policy: JitterPolicy,
+    /// Clock to compute deadlines and event timestamps against.
+    pub clock: ClockSource,
+}
+
+/// Why a `TimerConfig` is invalid.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Jitter was given for a zero step, leaving nothing to randomize.
+    ZeroStepWithJitter {
+        jitter: Duration,
+    },
+    /// Subtractive jitter larger than the step would count down from a
+    /// negative duration.
+    JitterExceedsStep {
+        step: Duration,
+        jitter: Duration,
+    },
+    /// Jitter was given, but the crate was built without the `jitter`
+    /// feature.
+    JitterUnsupported {
+        jitter: Duration,
+    },
+    /// A duration larger than `MAX_DURATION`.
+    TooLarge {
+        field: &'static str,
+        value: Duration,
+    },
+    /// A jitter policy other than `"subtractive"` or `"additive"`.
+    UnknownJitterPolicy(String),
+    /// A required environment variable isn't set.
+    MissingEnv {
+        var: String,
+    },
+    /// An environment variable holds a value that can't be parsed.
+    InvalidEnv {
+        var: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::ZeroStepWithJitter { jitter } => {
+                write!(f, "jitter of {:?} given for a zero step", jitter)
+            },
+            ConfigError::JitterExceedsStep { step, jitter } => {
+                write!(f, "subtractive jitter of {:?} exceeds step of {:?}", jitter, step)
+            },
+            ConfigError::JitterUnsupported { jitter } => {
+                write!(f, "jitter of {:?} given without the jitter feature", jitter)
+            },
+            ConfigError::TooLarge { field, value } => {
+                write!(f, "{} of {:?} exceeds the maximum of {:?}", field, value, MAX_DURATION)
+            },
+            ConfigError::UnknownJitterPolicy(ref policy) => {
+                write!(f, "unknown jitter policy {:?}", policy)
+            },
+            ConfigError::MissingEnv { ref var } => write!(f, "{} is not set", var),
+            ConfigError::InvalidEnv { ref var, ref value } => {
+                write!(f, "{} has invalid value {:?}", var, value)
+            },
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl TimerConfig {
+    /// Create a new config with no jitter.
+    ///
+    pub fn new(step: Duration) -> TimerConfig {
+        TimerConfig {
+            step,
+            jitter: Duration::from_secs(0),
+            jitter_policy: JitterPolicy::default(),
+            clock: ClockSource::default(),
+        }
+    }
+    /// Read a config from the environment, so deployments can tune a timer
+    /// without code changes.
+    ///
+    /// `PREFIX_STEP` is required, while `PREFIX_JITTER` defaults to none
+    /// and `PREFIX_MODE`, the jitter policy, to subtractive. Durations are
+    /// written like `"1m 30s"` or `"250ms"`. The config is validated
+    /// before it's returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Prepended to each variable name, with an underscore.
+    ///
+    #[cfg(feature = "humantime")]
+    pub fn from_env(prefix: &str) -> Result<TimerConfig, ConfigError> {
+        use std::env::{self, VarError};
+        let var = |name: &str| {
+            let var = format!("{}_{}", prefix, name);
+            match env::var(&var) {
+                Ok(value) => Ok(Some((var, value))),
+                Err(VarError::NotPresent) => Ok(None),
+                Err(VarError::NotUnicode(value)) => {
+                    Err(ConfigError::InvalidEnv { var, value: value.to_string_lossy().into_owned() })
+                },
+            }
+        };
+        let duration = |(var, value): (String, String)| {
+            match humantime::parse_duration(&value) {
+                Ok(d) => Ok(d),
+                Err(_) => Err(ConfigError::InvalidEnv { var, value }),
+            }
+        };
+        let step = match var("STEP")? {
+            Some(step) => duration(step)?,
+            None => return Err(ConfigError::MissingEnv { var: format!("{}_STEP", prefix) }),
+        };
+        let mut config = TimerConfig::new(step);
+        if let Some(jitter) = var("JITTER")? {
+            config.jitter = duration(jitter)?;
+        }
+        if let Some((var, value)) = var("MODE")? {
+            config.jitter_policy = match value.parse() {
+                Ok(policy) => policy,
+                Err(_) => return Err(ConfigError::InvalidEnv { var, value }),
+            };
+        }
+        config.validate()?;
+        Ok(config)
+    }
+    /// Check the config for values that would misbehave at runtime.
+    ///
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.step > MAX_DURATION {
+            return Err(ConfigError::TooLarge { field: "step", value: self.step });
+        }
+        if self.jitter > MAX_DURATION {
+            return Err(ConfigError::TooLarge { field: "jitter", value: self.jitter });
+        }
+        let zero = Duration::from_secs(0);
+        if !cfg!(feature = "jitter") && self.jitter > zero {
+            return Err(ConfigError::JitterUnsupported { jitter: self.jitter });
+        }
+        if self.step == zero && self.jitter > zero {
+            return Err(ConfigError::ZeroStepWithJitter { jitter: self.jitter });
+        }
+        if self.jitter_policy == JitterPolicy::Subtractive && self.jitter > self.step {
+            return Err(ConfigError::JitterExceedsStep { step: self.step, jitter: self.jitter });
+        }
+        Ok(())
+    }
+}
+
+#[test]
+#[cfg(feature = "jitter")]
+fn config_validate() {
+    let ms = Duration::from_millis;
+    let mut config = TimerConfig::new(ms(100));
+    assert_eq!(config.validate(), Ok(()));
+    config.jitter = ms(200);
+    assert_eq!(config.validate(),
+               Err(ConfigError::JitterExceedsStep { step: ms(100), jitter: ms(200) }));
+    config.jitter_policy = JitterPolicy::Additive;
+    assert_eq!(config.validate(), Ok(()));
+    config.step = ms(0);
+    assert_eq!(config.validate(), Err(ConfigError::ZeroStepWithJitter { jitter: ms(200) }));
+    config.step = Duration::from_secs(u64::MAX);
+    assert!(config.validate().unwrap_err().to_string().starts_with("step of"));
+}
+
+#[test]
+#[cfg(not(feature = "jitter"))]
+fn config_validate_without_jitter() {
+    let ms = Duration::from_millis;
+    let mut config = TimerConfig::new(ms(100));
+    assert_eq!(config.validate(), Ok(()));
+    config.jitter = ms(10);
+    assert_eq!(config.validate(), Err(ConfigError::JitterUnsupported { jitter: ms(10) }));
+}
+
+#[cfg(all(feature = "humantime", feature = "jitter"))]
+#[test]
+fn config_from_env() {
+    use std::env;
+    let ms = Duration::from_millis;
+    assert_eq!(TimerConfig::from_env("TIMER_TEST_ENV").unwrap_err(),
+               ConfigError::MissingEnv { var: "TIMER_TEST_ENV_STEP".to_string() });
+    env::set_var("TIMER_TEST_ENV_STEP", "1m 30s");
+    env::set_var("TIMER_TEST_ENV_JITTER", "250ms");
+    env::set_var("TIMER_TEST_ENV_MODE", "Additive");
+    let config = TimerConfig::from_env("TIMER_TEST_ENV").unwrap();
+    assert_eq!((config.step, config.jitter), (ms(90_000), ms(250)));
+    assert_eq!(config.jitter_policy, JitterPolicy::Additive);
+    env::set_var("TIMER_TEST_ENV_JITTER", "soon");
+    assert_eq!(TimerConfig::from_env("TIMER_TEST_ENV").unwrap_err(),
+               ConfigError::InvalidEnv { var: "TIMER_TEST_ENV_JITTER".to_string(),
+                                         value: "soon".to_string() });
+}
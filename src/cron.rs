@@ -0,0 +1,439 @@
+use schedule::{Schedule, TickContext};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Most steps to take looking for a match before giving up, which
+/// comfortably covers the 28 year cycle of weekdays and leap days.
+///
+const SEARCH_LIMIT: usize = 100_000;
+
+/// Quartz names for months and days of the week, and their numbers.
+///
+const MONTH_NAMES: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// Why a cron expression couldn't be parsed.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CronError {
+    /// The expression didn't have exactly five fields.
+    FieldCount(usize),
+    /// The Quartz expression didn't have six or seven fields.
+    QuartzFieldCount(usize),
+    /// A field had a malformed or out of range value.
+    Invalid {
+        field: &'static str,
+        value: String,
+    },
+}
+
+impl fmt::Display for CronError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CronError::FieldCount(n) => write!(f, "expected 5 cron fields, found {}", n),
+            CronError::QuartzFieldCount(n) => write!(f, "expected 6 or 7 Quartz cron fields, found {}", n),
+            CronError::Invalid { field, ref value } => write!(f, "invalid cron {} {:?}", field, value),
+        }
+    }
+}
+
+impl Error for CronError {}
+
+/// A Quartz day of month or day of week modifier.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DayRule {
+    /// `L` or `L-n`, the last day of the month or `n` days before it.
+    Last(u32),
+    /// `LW`, the last weekday of the month.
+    LastWeekday,
+    /// `nW`, the weekday nearest the `n`th, within the same month.
+    NearestWeekday(u32),
+    /// `d#n`, the `n`th day `d` of the week in the month.
+    Nth(u32, u32),
+    /// `dL`, the last day `d` of the week in the month.
+    LastOf(u32),
+}
+
+impl DayRule {
+    /// True if the rule matches `day` of a month with `length` days, the
+    /// first of which falls on `first` day of the week, Sunday being 0.
+    ///
+    fn matches(self, day: u32, length: u32, first: u32) -> bool {
+        let weekday = |day: u32| (first + day - 1) % 7;
+        let is_weekday = |day: u32| (1..=5).contains(&weekday(day));
+        match self {
+            DayRule::Last(offset) => offset < length && day == length - offset,
+            DayRule::LastWeekday => is_weekday(day) && (day + 1..=length).all(|later| !is_weekday(later)),
+            DayRule::NearestWeekday(n) if n > length => false,
+            DayRule::NearestWeekday(n) => day == match weekday(n) {
+                6 if n == 1 => 3,
+                6 => n - 1,
+                0 if n == length => n - 2,
+                0 => n + 1,
+                _ => n,
+            },
+            DayRule::Nth(d, n) => weekday(day) == d && (day - 1) / 7 + 1 == n,
+            DayRule::LastOf(d) => weekday(day) == d && day + 7 > length,
+        }
+    }
+}
+
+/// A schedule given by a cron expression, evaluated in UTC.
+///
+/// The five fields are minute, hour, day of month, month and day of week (0
+/// or 7 for Sunday), each `*`, a value, a range `a-b`, a step `*/n` or
+/// `a-b/n`, or a comma separated list of those. As in cron, a time matches
+/// if either day field matches when both are restricted.
+///
+/// Quartz expressions, from `Cron::parse_quartz`, add seconds before the
+/// minutes and an optional year after the day of week.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cron {
+    // One bit per allowed value of each field.
+    seconds: u64,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Quartz modifiers, matched alongside `days` and `weekdays`.
+    day_rules: Vec<DayRule>,
+    weekday_rules: Vec<DayRule>,
+    // Allowed years, in order, or `None` for any.
+    years: Option<Vec<i64>>,
+    // True if the day fields are `*`, or `?` in Quartz.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Parse one cron field into the values it allows.
+///
+fn parse_values(field: &'static str, value: &str, min: u32, max: u32) -> Result<Vec<u32>, CronError> {
+    let invalid = || CronError::Invalid { field, value: value.to_string() };
+    let number = |s: &str| s.parse::<u32>().ok().filter(|n| (min..=max).contains(n)).ok_or_else(invalid);
+    let mut values = Vec::new();
+    for part in value.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(i) => (&part[..i], part[i + 1..].parse::<u32>().ok().filter(|&n| n > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (first, last) = if range == "*" {
+            (min, max)
+        } else if let Some(i) = range.find('-') {
+            (number(&range[..i])?, number(&range[i + 1..])?)
+        } else {
+            let n = number(range)?;
+            (n, if step > 1 { max } else { n })
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        values.extend((first..=last).step_by(step as usize));
+    }
+    Ok(values)
+}
+
+/// Parse one cron field into a bit set of the values it allows.
+///
+fn parse_field(field: &'static str, value: &str, min: u32, max: u32) -> Result<u64, CronError> {
+    Ok(parse_values(field, value, min, max)?.into_iter().fold(0, |bits, n| bits | 1 << n))
+}
+
+/// Replace Quartz names, e.g., `MON-FRI`, with their numbers, counting
+/// from one.
+///
+fn replace_names(value: &str, names: &[&str]) -> String {
+    let mut value = value.to_ascii_uppercase();
+    for (i, name) in names.iter().enumerate() {
+        value = value.replace(name, &(i + 1).to_string());
+    }
+    value
+}
+
+/// Parse a Quartz day of month or day of week field into a bit set of the
+/// plain values it allows, and its modifiers.
+///
+fn parse_day_field(field: &'static str,
+                   value: &str,
+                   max: u32,
+                   rule: fn(&str) -> Option<DayRule>)
+                   -> Result<(u64, Vec<DayRule>), CronError> {
+    let (mut plain, mut rules) = (Vec::new(), Vec::new());
+    for part in value.split(',') {
+        if part.contains(['L', 'W', '#']) {
+            rules.push(rule(part).ok_or_else(|| CronError::Invalid { field, value: value.to_string() })?);
+        } else {
+            plain.push(part);
+        }
+    }
+    let bits = if plain.is_empty() { 0 } else { parse_field(field, &plain.join(","), 1, max)? };
+    Ok((bits, rules))
+}
+
+/// Parse a Quartz day of month modifier.
+///
+fn day_rule(part: &str) -> Option<DayRule> {
+    let day = |s: &str| s.parse::<u32>().ok().filter(|n| (1..=31).contains(n));
+    match part {
+        "L" => Some(DayRule::Last(0)),
+        "LW" => Some(DayRule::LastWeekday),
+        _ if part.starts_with("L-") => part[2..].parse().ok().filter(|&n| n < 31).map(DayRule::Last),
+        _ if part.ends_with('W') => day(&part[..part.len() - 1]).map(DayRule::NearestWeekday),
+        _ => None,
+    }
+}
+
+/// Parse a Quartz day of week modifier, numbering days from 1 for Sunday.
+///
+fn weekday_rule(part: &str) -> Option<DayRule> {
+    let weekday = |s: &str| s.parse::<u32>().ok().filter(|n| (1..=7).contains(n)).map(|n| n - 1);
+    if let Some(i) = part.find('#') {
+        let n = part[i + 1..].parse::<u32>().ok().filter(|n| (1..=5).contains(n))?;
+        return Some(DayRule::Nth(weekday(&part[..i])?, n));
+    }
+    match part {
+        "L" => Some(DayRule::LastOf(6)),
+        _ if part.ends_with('L') => weekday(&part[..part.len() - 1]).map(DayRule::LastOf),
+        _ => None,
+    }
+}
+
+/// Days since the Unix epoch of a proleptic Gregorian date.
+///
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The proleptic Gregorian date of a number of days since the Unix epoch.
+///
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl Cron {
+    /// Parse a five field cron expression, e.g., `*/15 9-17 * * 1-5`.
+    ///
+    pub fn parse(expr: &str) -> Result<Cron, CronError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(CronError::FieldCount(fields.len()));
+        }
+        let mut weekdays = parse_field("day of week", fields[4], 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Cron {
+            seconds: 1,
+            minutes: parse_field("minute", fields[0], 0, 59)?,
+            hours: parse_field("hour", fields[1], 0, 23)?,
+            days: parse_field("day of month", fields[2], 1, 31)?,
+            months: parse_field("month", fields[3], 1, 12)?,
+            weekdays,
+            day_rules: Vec::new(),
+            weekday_rules: Vec::new(),
+            years: None,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+    /// Parse a six or seven field Quartz cron expression, e.g.,
+    /// `0 15 10 ? * 6#3 2025`.
+    ///
+    /// The fields are second, minute, hour, day of month, month, day of week
+    /// (1 for Sunday to 7 for Saturday), and optionally year, from 1970 to
+    /// 2099. Months and days of the week may be given by name, e.g., `JAN`
+    /// or `MON-FRI`, and either day field may be `?` for no restriction.
+    ///
+    /// The day of month may also be `L` for the last day of the month, `L-n`
+    /// for `n` days before it, `LW` for the last weekday, or `nW` for the
+    /// weekday nearest the `n`th. The day of week may also be `dL` for the
+    /// last day `d` of the month, or `d#n` for the `n`th.
+    ///
+    pub fn parse_quartz(expr: &str) -> Result<Cron, CronError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 6 && fields.len() != 7 {
+            return Err(CronError::QuartzFieldCount(fields.len()));
+        }
+        let any = |value: &str| value == "*" || value == "?";
+        let (days, day_rules) = if any(fields[3]) {
+            (0, Vec::new())
+        } else {
+            parse_day_field("day of month", fields[3], 31, day_rule)?
+        };
+        let (weekdays, weekday_rules) = if any(fields[5]) {
+            (0, Vec::new())
+        } else {
+            let value = replace_names(fields[5], &WEEKDAY_NAMES);
+            let (weekdays, rules) = parse_day_field("day of week", &value, 7, weekday_rule)?;
+            (weekdays >> 1, rules)
+        };
+        let years = match fields.get(6) {
+            Some(&value) if !any(value) => {
+                let mut years: Vec<i64> = parse_values("year", value, 1970, 2099)?.into_iter().map(i64::from).collect();
+                years.sort_unstable();
+                years.dedup();
+                Some(years)
+            },
+            _ => None,
+        };
+        Ok(Cron {
+            seconds: parse_field("second", fields[0], 0, 59)?,
+            minutes: parse_field("minute", fields[1], 0, 59)?,
+            hours: parse_field("hour", fields[2], 0, 23)?,
+            days,
+            months: if any(fields[4]) {
+                parse_field("month", "*", 1, 12)?
+            } else {
+                parse_field("month", &replace_names(fields[4], &MONTH_NAMES), 1, 12)?
+            },
+            weekdays,
+            day_rules,
+            weekday_rules,
+            years,
+            any_day: any(fields[3]),
+            any_weekday: any(fields[5]),
+        })
+    }
+    fn day_matches(&self, days: i64, year: i64, month: u32, day: u32) -> bool {
+        let first = days_from_civil(year, month, 1);
+        let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+        let length = (days_from_civil(next_year, next_month, 1) - first) as u32;
+        let first = (first + 4).rem_euclid(7) as u32;
+        let by_day = self.days & (1 << day) != 0 || self.day_rules.iter().any(|rule| rule.matches(day, length, first));
+        let by_weekday = self.weekdays & (1 << (days + 4).rem_euclid(7)) != 0 ||
+                         self.weekday_rules.iter().any(|rule| rule.matches(day, length, first));
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => by_weekday,
+            (false, true) => by_day,
+            (false, false) => by_day || by_weekday,
+        }
+    }
+    /// The first matching second strictly after `t`, which for a five field
+    /// expression is always on the minute.
+    ///
+    /// Returns `None` if nothing matches, e.g., for February 30th, or if `t`
+    /// is before the Unix epoch.
+    ///
+    pub fn next_after(&self, t: SystemTime) -> Option<SystemTime> {
+        let mut second = t.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64 + 1;
+        for _ in 0..SEARCH_LIMIT {
+            let days = second / 86_400;
+            let (year, month, day) = civil_from_days(days);
+            if let Some(ref years) = self.years {
+                if !years.contains(&year) {
+                    let year = years.iter().find(|&&y| y > year)?;
+                    second = days_from_civil(*year, 1, 1) * 86_400;
+                    continue;
+                }
+            }
+            if self.months & (1 << month) == 0 {
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                second = days_from_civil(year, month, 1) * 86_400;
+            } else if !self.day_matches(days, year, month, day) {
+                second = (days + 1) * 86_400;
+            } else if self.hours & (1 << (second % 86_400 / 3600)) == 0 {
+                second = (second / 3600 + 1) * 3600;
+            } else if self.minutes & (1 << (second % 3600 / 60)) == 0 {
+                second = (second / 60 + 1) * 60;
+            } else if self.seconds & (1 << (second % 60)) == 0 {
+                second += 1;
+            } else {
+                return UNIX_EPOCH.checked_add(Duration::from_secs(second as u64));
+            }
+        }
+        None
+    }
+}
+
+impl Schedule for Cron {
+    fn next_interval(&mut self, ctx: &TickContext) -> Option<Duration> {
+        let at = self.next_after(ctx.now)?;
+        Some(at.duration_since(ctx.now).unwrap_or_default())
+    }
+}
+
+#[test]
+fn cron_parse() {
+    assert_eq!(Cron::parse("* * *"), Err(CronError::FieldCount(3)));
+    assert!(Cron::parse("60 * * * *").is_err());
+    assert!(Cron::parse("*/0 * * * *").is_err());
+    assert!(Cron::parse("5-1 * * * *").is_err());
+    let cron = Cron::parse("*/15 9-17 * * 1-5,7").unwrap();
+    assert_eq!(cron.minutes, 1 | 1 << 15 | 1 << 30 | 1 << 45);
+    assert_eq!(cron.hours, 0b11_1111_1110_0000_0000);
+    assert_eq!(cron.weekdays, 0b11_1111);
+}
+
+#[test]
+fn cron_next_after() {
+    let at = |days: i64, hour: u64, minute: u64| {
+        UNIX_EPOCH + Duration::from_secs(days as u64 * 86_400 + hour * 3600 + minute * 60)
+    };
+    // 2024-02-29 was a Thursday.
+    let leap_day = days_from_civil(2024, 2, 29);
+    assert_eq!(civil_from_days(leap_day), (2024, 2, 29));
+    let cron = Cron::parse("30 12 * * *").unwrap();
+    assert_eq!(cron.next_after(at(leap_day, 12, 0)), Some(at(leap_day, 12, 30)));
+    assert_eq!(cron.next_after(at(leap_day, 12, 30)), Some(at(leap_day + 1, 12, 30)));
+    let cron = Cron::parse("0 0 29 2 *").unwrap();
+    assert_eq!(cron.next_after(at(leap_day, 0, 0)),
+               Some(at(days_from_civil(2028, 2, 29), 0, 0)));
+    let cron = Cron::parse("0 9 * * 1").unwrap();
+    assert_eq!(cron.next_after(at(leap_day, 0, 0)), Some(at(leap_day + 4, 9, 0)));
+    assert_eq!(Cron::parse("0 0 30 2 *").unwrap().next_after(at(leap_day, 0, 0)), None);
+}
+
+#[test]
+fn cron_parse_quartz() {
+    assert_eq!(Cron::parse_quartz("* * * * *"), Err(CronError::QuartzFieldCount(5)));
+    assert!(Cron::parse_quartz("0 0 0 32W * ?").is_err());
+    assert!(Cron::parse_quartz("0 0 0 ? * 8#1").is_err());
+    assert!(Cron::parse_quartz("0 0 0 ? * 2#6").is_err());
+    assert!(Cron::parse_quartz("0 0 0 * * ? 1969").is_err());
+    let cron = Cron::parse_quartz("*/20 0 9 ? JAN,MAR MON-FRI").unwrap();
+    assert_eq!(cron.seconds, 1 | 1 << 20 | 1 << 40);
+    assert_eq!(cron.months, 1 << 1 | 1 << 3);
+    assert_eq!(cron.weekdays, 0b011_1110);
+    let cron = Cron::parse_quartz("0 0 0 L-2,15W ? * 2020-2022").unwrap();
+    assert_eq!(cron.day_rules, vec![DayRule::Last(2), DayRule::NearestWeekday(15)]);
+    assert_eq!(cron.years, Some(vec![2020, 2021, 2022]));
+}
+
+#[test]
+fn cron_quartz_next_after() {
+    let at = |(year, month, day): (i64, u32, u32), hour: u64, minute: u64, second: u64| {
+        let days = days_from_civil(year, month, day) as u64;
+        UNIX_EPOCH + Duration::from_secs(days * 86_400 + hour * 3600 + minute * 60 + second)
+    };
+    let next = |expr, after| Cron::parse_quartz(expr).unwrap().next_after(after);
+    // January 2024 began on a Monday.
+    let new_year = at((2024, 1, 1), 0, 0, 0);
+    assert_eq!(next("30 0 0 * * ?", new_year), Some(at((2024, 1, 1), 0, 0, 30)));
+    assert_eq!(next("0 15 10 ? * 6#3", new_year), Some(at((2024, 1, 19), 10, 15, 0)));
+    assert_eq!(next("0 0 12 ? * 2L", new_year), Some(at((2024, 1, 29), 12, 0, 0)));
+    assert_eq!(next("0 0 0 L * ?", at((2024, 2, 1), 0, 0, 0)), Some(at((2024, 2, 29), 0, 0, 0)));
+    // August 31st 2024 was a Saturday, as was June 1st.
+    assert_eq!(next("0 0 0 LW * ?", at((2024, 8, 1), 0, 0, 0)), Some(at((2024, 8, 30), 0, 0, 0)));
+    assert_eq!(next("0 0 0 1W * ?", at((2024, 5, 31), 0, 0, 0)), Some(at((2024, 6, 3), 0, 0, 0)));
+    assert_eq!(next("0 0 0 1 1 ? 2030", new_year), Some(at((2030, 1, 1), 0, 0, 0)));
+    assert_eq!(next("0 0 0 1 1 ? 2020", new_year), None);
+}
@@ -0,0 +1,107 @@
+use backoff::Backoff;
+use schedule::{Schedule, TickContext};
+use std::time::{Duration, Instant};
+
+/// A point in time by which something must be done.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline {
+    at: Instant,
+}
+
+impl Deadline {
+    /// Create a new deadline at `at`.
+    ///
+    pub fn at(at: Instant) -> Deadline {
+        Deadline { at }
+    }
+    /// Create a new deadline `d` from now.
+    ///
+    /// Durations too long to represent never pass in practice, so they're
+    /// capped at a hundred years.
+    ///
+    pub fn after(d: Duration) -> Deadline {
+        let now = Instant::now();
+        let at = now.checked_add(d)
+            .or_else(|| now.checked_add(Duration::from_secs(100 * 365 * 24 * 3600)))
+            .unwrap_or(now);
+        Deadline { at }
+    }
+    /// When the deadline is.
+    ///
+    pub fn instant(&self) -> Instant {
+        self.at
+    }
+    /// Time left until the deadline, or zero if it has passed.
+    ///
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+    /// True if the deadline has passed.
+    ///
+    pub fn has_passed(&self) -> bool {
+        Instant::now() >= self.at
+    }
+    /// Truncate `interval` to the time left, or `None` if there's none.
+    ///
+    fn truncate(&self, interval: Duration) -> Option<Duration> {
+        let remaining = self.remaining();
+        if remaining == Duration::from_secs(0) {
+            None
+        } else {
+            Some(interval.min(remaining))
+        }
+    }
+}
+
+/// A backoff or schedule that gives up at an overall deadline.
+///
+/// Each interval is truncated to the time left before the deadline, and
+/// none are handed out once it passes. Built with `Backoff::with_deadline`
+/// or `WithDeadline::new`.
+///
+#[derive(Clone, Debug)]
+pub struct WithDeadline<B> {
+    inner: B,
+    deadline: Deadline,
+}
+
+impl<B> WithDeadline<B> {
+    /// Wrap a backoff or schedule to give up at `deadline`.
+    ///
+    pub fn new(inner: B, deadline: Deadline) -> WithDeadline<B> {
+        WithDeadline { inner, deadline }
+    }
+}
+
+impl<B: Backoff> Backoff for WithDeadline<B> {
+    fn next_backoff(&mut self) -> Option<Duration> {
+        self.deadline.truncate(self.inner.next_backoff()?)
+    }
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+impl<S: Schedule> Schedule for WithDeadline<S> {
+    fn next_interval(&mut self, ctx: &TickContext) -> Option<Duration> {
+        self.deadline.truncate(self.inner.next_interval(ctx)?)
+    }
+    fn restart(&mut self) {
+        self.inner.restart();
+    }
+}
+
+#[test]
+fn backoff_with_deadline() {
+    use backoff::ConstantBackoff;
+    let ms = Duration::from_millis;
+    let mut backoff = ConstantBackoff::new(ms(30)).with_deadline(Deadline::after(ms(50)));
+    assert_eq!(backoff.next_backoff(), Some(ms(30)));
+    std::thread::sleep(ms(30));
+    let last = backoff.next_backoff().unwrap();
+    assert!(last <= ms(20));
+    std::thread::sleep(last);
+    assert_eq!(backoff.next_backoff(), None);
+    assert!(Deadline::after(Duration::MAX).remaining() > ms(1_000_000));
+}
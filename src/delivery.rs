@@ -0,0 +1,138 @@
+use event::ExpiryEvent;
+use std::sync::{Condvar, Mutex};
+use std::task::Poll;
+use Timer;
+
+/// How a timer tells its consumer it expired.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// Signal the `timed_out` condition and nothing more. A consumer that
+    /// isn't waiting when the timer expires misses the expiry entirely.
+    #[default]
+    Notify,
+    /// Also count each expiry until it's consumed with `wait_for_expiry` or
+    /// `try_wait`, so a busy consumer still sees every expiry, if perhaps
+    /// several at once.
+    AtLeastOnce,
+}
+
+/// Expiries not yet consumed.
+///
+#[derive(Default)]
+pub struct Pending {
+    // Every expiry not yet consumed, merged into the last of them.
+    expiry: Mutex<Option<ExpiryEvent>>,
+    cv: Condvar,
+}
+
+impl Pending {
+    /// Count `expiry` and wake any waiter.
+    ///
+    pub fn add(&self, expiry: ExpiryEvent) {
+        let mut pending = self.expiry.lock().unwrap();
+        *pending = Some(match pending.take() {
+            Some(last) => ExpiryEvent { coalesced: last.coalesced + expiry.coalesced, ..expiry },
+            None => expiry,
+        });
+        self.cv.notify_all();
+    }
+    /// Wait for at least one expiry, then take them all.
+    ///
+    pub fn wait(&self) -> usize {
+        let mut pending = self.expiry.lock().unwrap();
+        loop {
+            if let Some(expiry) = pending.take() {
+                return expiry.coalesced;
+            }
+            pending = self.cv.wait(pending).unwrap();
+        }
+    }
+    /// Take every expiry, if there are any, merged into the last of them.
+    ///
+    pub fn take(&self) -> Option<ExpiryEvent> {
+        self.expiry.lock().unwrap().take()
+    }
+}
+
+impl Timer {
+    /// Choose how expiries are delivered. Takes effect the next time the
+    /// timer is started.
+    ///
+    pub fn set_delivery(&mut self, mode: DeliveryMode) {
+        self.delivery = mode;
+    }
+    /// Block until the timer has expired since the last wait, returning how
+    /// many times.
+    ///
+    /// # Panics
+    ///
+    /// Unless the delivery mode is `DeliveryMode::AtLeastOnce`, since no
+    /// expiries would ever be counted.
+    ///
+    pub fn wait_for_expiry(&self) -> usize {
+        assert_eq!(self.delivery, DeliveryMode::AtLeastOnce,
+                   "wait_for_expiry needs DeliveryMode::AtLeastOnce");
+        self.pending.wait()
+    }
+    /// How many times the timer has expired since the last wait, if any,
+    /// without blocking.
+    ///
+    pub fn try_wait(&self) -> Option<usize> {
+        self.pending.take().map(|expiry| expiry.coalesced)
+    }
+    /// Check once, without ever blocking, whether the timer has expired
+    /// since the last check.
+    ///
+    /// Meant for loops that poll once per frame. Every expiry since the last
+    /// check is drained and returned as one event carrying their number in
+    /// `coalesced`, and the `deadline` and `fired` of the last. Expiries are
+    /// only counted in `DeliveryMode::AtLeastOnce`; otherwise this is always
+    /// `Pending`.
+    ///
+    pub fn poll_expiry(&self) -> Poll<ExpiryEvent> {
+        match self.pending.take() {
+            Some(expiry) => Poll::Ready(expiry),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[test]
+fn at_least_once_delivery() {
+    use std::sync::{Arc, Condvar};
+    use std::time::Duration;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(10), ms(0), Arc::new(Condvar::new()));
+    t.set_delivery(DeliveryMode::AtLeastOnce);
+    t.set_max_expiries(5);
+    assert_eq!(t.try_wait(), None);
+    t.start();
+    let mut seen = t.wait_for_expiry();
+    // Busy elsewhere while the rest fire, unobserved...
+    std::thread::sleep(ms(100));
+    seen += t.try_wait().unwrap_or(0);
+    assert_eq!(seen, 5);
+    assert_eq!(t.try_wait(), None);
+    t.stop();
+}
+
+#[test]
+fn timer_poll_expiry() {
+    use std::sync::{Arc, Condvar};
+    use std::time::Duration;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(10), ms(0), Arc::new(Condvar::new()));
+    t.set_delivery(DeliveryMode::AtLeastOnce);
+    t.set_max_expiries(3);
+    assert_eq!(t.poll_expiry(), Poll::Pending);
+    t.start();
+    // A slow frame, long enough for every expiry.
+    std::thread::sleep(ms(60));
+    match t.poll_expiry() {
+        Poll::Ready(expiry) => assert_eq!((expiry.count, expiry.coalesced), (3, 3)),
+        Poll::Pending => panic!("expected the timer to have expired"),
+    }
+    assert_eq!(t.poll_expiry(), Poll::Pending);
+    t.stop();
+}
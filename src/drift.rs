@@ -0,0 +1,295 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use Timer;
+
+/// Upper bounds of every bucket but the last, in milliseconds.
+const BOUNDS_MS: [u64; 5] = [1, 5, 20, 100, 1000];
+
+/// Per-expiry lateness counts, updated from the timer thread.
+///
+#[derive(Default)]
+pub struct Drift {
+    counts: [AtomicUsize; 6],
+    // Total and greatest lateness, in nanoseconds.
+    total: AtomicU64,
+    worst: AtomicU64,
+}
+
+impl Drift {
+    /// Count an expiry that fired `lateness` after its deadline.
+    ///
+    pub fn record(&self, lateness: Duration) {
+        let i = BOUNDS_MS.iter()
+            .position(|&bound| lateness < Duration::from_millis(bound))
+            .unwrap_or(BOUNDS_MS.len());
+        self.counts[i].fetch_add(1, Ordering::Relaxed);
+        let nanos = lateness.as_nanos().min(u64::MAX as u128) as u64;
+        self.total.fetch_add(nanos, Ordering::Relaxed);
+        self.worst.fetch_max(nanos, Ordering::Relaxed);
+    }
+    /// Mean lateness of every expiry counted.
+    ///
+    pub fn mean(&self) -> Duration {
+        let count = self.counts.iter().map(|count| count.load(Ordering::Relaxed) as u64).sum::<u64>();
+        match count {
+            0 => Duration::from_secs(0),
+            n => Duration::from_nanos(self.total.load(Ordering::Relaxed) / n),
+        }
+    }
+    /// Greatest lateness of any expiry counted.
+    ///
+    pub fn worst(&self) -> Duration {
+        Duration::from_nanos(self.worst.load(Ordering::Relaxed))
+    }
+    /// The counts so far.
+    ///
+    pub fn snapshot(&self) -> DriftHistogram {
+        let mut counts = [0; 6];
+        for (count, recorded) in counts.iter_mut().zip(self.counts.iter()) {
+            *count = recorded.load(Ordering::Relaxed);
+        }
+        DriftHistogram { counts }
+    }
+}
+
+/// Sliding window of wait overshoots, for closed-loop correction.
+///
+pub struct Correction {
+    window: VecDeque<Duration>,
+    size: usize,
+    // Sum of every overshoot in `window`.
+    total: Duration,
+}
+
+impl Correction {
+    /// Create a new correction averaging over the last `size` expiries.
+    ///
+    pub fn new(size: usize) -> Correction {
+        let size = size.max(1);
+        Correction {
+            window: VecDeque::with_capacity(size),
+            size,
+            total: Duration::from_secs(0),
+        }
+    }
+    /// Record how far past its requested wake up time a wait overshot,
+    /// returning the average overshoot over the window.
+    ///
+    pub fn observe(&mut self, overshoot: Duration) -> Duration {
+        if self.window.len() == self.size {
+            self.total -= self.window.pop_front().unwrap();
+        }
+        self.window.push_back(overshoot);
+        self.total += overshoot;
+        self.total / self.window.len() as u32
+    }
+}
+
+/// Calls back when an expiry fires later than a threshold.
+///
+#[derive(Clone)]
+pub struct DriftAlert {
+    threshold: Duration,
+    f: Arc<dyn Fn(Duration) + Send + Sync>,
+}
+
+impl DriftAlert {
+    /// Create a new alert calling `f` with the lateness of any expiry later
+    /// than `threshold`.
+    ///
+    pub fn new<F>(threshold: Duration, f: F) -> DriftAlert
+        where F: Fn(Duration) + Send + Sync + 'static
+    {
+        DriftAlert { threshold, f: Arc::new(f) }
+    }
+    /// Call back if an expiry that fired `lateness` after its deadline is
+    /// over the threshold.
+    ///
+    pub fn check(&self, lateness: Duration) {
+        if lateness > self.threshold {
+            (self.f)(lateness);
+        }
+    }
+}
+
+/// One bucket of a `DriftHistogram`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DriftBucket {
+    /// Least lateness counted in this bucket.
+    pub min: Duration,
+    /// Lateness this bucket counts up to but not including, or `None` for
+    /// the last bucket.
+    pub max: Option<Duration>,
+    /// Number of expiries this late.
+    pub count: usize,
+}
+
+/// How late a timer's expiries have fired relative to their deadlines.
+///
+/// Buckets are 0-1ms, 1-5ms, 5-20ms, 20-100ms, 100ms-1s and over 1s.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DriftHistogram {
+    counts: [usize; 6],
+}
+
+impl DriftHistogram {
+    /// Every bucket, from least to most late.
+    ///
+    pub fn buckets(&self) -> impl Iterator<Item = DriftBucket> + '_ {
+        self.counts.iter().enumerate().map(|(i, &count)| DriftBucket {
+            min: Duration::from_millis(if i == 0 { 0 } else { BOUNDS_MS[i - 1] }),
+            max: BOUNDS_MS.get(i).map(|&ms| Duration::from_millis(ms)),
+            count,
+        })
+    }
+    /// Number of expiries counted across every bucket.
+    ///
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+impl Timer {
+    /// How late the timer's expiries have fired relative to their
+    /// deadlines, since it was created.
+    ///
+    pub fn drift_histogram(&self) -> DriftHistogram {
+        self.drift.snapshot()
+    }
+    /// Call `f` with the lateness of every expiry that fires more than
+    /// `threshold` after its deadline.
+    ///
+    /// Meant for watchdogs, to alert on an overloaded host or a wedged timer
+    /// thread rather than finding out from what it delays downstream. `f`
+    /// runs on the timer thread before the expiry is delivered, so it
+    /// should be quick. Takes effect the next time the timer is started.
+    ///
+    pub fn on_drift_exceeding<F>(&mut self, threshold: Duration, f: F)
+        where F: Fn(Duration) + Send + Sync + 'static
+    {
+        self.drift_alert = Some(DriftAlert::new(threshold, f));
+    }
+    /// Skip delivering any expiry that fires more than `bound` after its
+    /// deadline, counting it in `suppressed` instead.
+    ///
+    /// For expiries that are pointless once stale, such as a video frame
+    /// tick 200ms late. A suppressed expiry still counts towards
+    /// `set_max_expiries` and the drift histogram, but doesn't signal
+    /// `timed_out`, run callbacks or reach subscribers. Takes effect the
+    /// next time the timer is started.
+    ///
+    pub fn suppress_later_than(&mut self, bound: Duration) {
+        self.stale_after = Some(bound);
+    }
+    /// Number of expiries suppressed for firing too late.
+    ///
+    pub fn suppressed(&self) -> usize {
+        self.suppressed.load(Ordering::SeqCst)
+    }
+}
+
+#[test]
+fn drift_buckets() {
+    let drift = Drift::default();
+    for &ms in &[0, 0, 3, 19, 20, 5000] {
+        drift.record(Duration::from_millis(ms));
+    }
+    let histogram = drift.snapshot();
+    assert_eq!(histogram.counts, [2, 1, 1, 1, 0, 1]);
+    let buckets: Vec<DriftBucket> = histogram.buckets().collect();
+    assert_eq!(buckets[0].min, Duration::from_millis(0));
+    assert_eq!(buckets[2].min, Duration::from_millis(5));
+    assert_eq!(buckets[2].max, Some(Duration::from_millis(20)));
+    assert_eq!(buckets[5].max, None);
+    assert_eq!(histogram.total(), 6);
+    assert_eq!(drift.mean(), Duration::from_millis(5042) / 6);
+    assert_eq!(drift.worst(), Duration::from_millis(5000));
+}
+
+#[test]
+fn correction_averages_window() {
+    let ms = Duration::from_millis;
+    let mut correction = Correction::new(3);
+    assert_eq!(correction.observe(ms(3)), ms(3));
+    assert_eq!(correction.observe(ms(6)), ms(4) + Duration::from_micros(500));
+    assert_eq!(correction.observe(ms(9)), ms(6));
+    // The first overshoot slides out of the window...
+    assert_eq!(correction.observe(ms(0)), ms(5));
+}
+
+#[test]
+fn timer_drift_histogram() {
+    use std::sync::{Arc, Condvar};
+    let mut t = Timer::new(Duration::from_millis(10), Duration::from_secs(0), Arc::new(Condvar::new()));
+    t.start();
+    std::thread::sleep(Duration::from_millis(55));
+    t.stop();
+    let histogram = t.drift_histogram();
+    assert_eq!(histogram.total(), t.expiries.load(std::sync::atomic::Ordering::SeqCst));
+    assert!(histogram.total() >= 3);
+}
+
+#[test]
+fn timer_on_drift_exceeding() {
+    use clock::{ClockSource, MockClock, Timestamp};
+    use std::sync::mpsc::channel;
+    use std::sync::{Condvar, Mutex};
+    let s = Duration::from_secs;
+    let mock = Arc::new(MockClock::new());
+    let mut t = Timer::with_clock(s(60), s(0), Arc::new(Condvar::new()), ClockSource::Custom(mock.clone()));
+    let (tx, alerts) = channel();
+    let tx = Mutex::new(tx);
+    t.on_drift_exceeding(s(10), move |lateness| tx.lock().unwrap().send(lateness).unwrap());
+    let counting_to = |t: &Timer, deadline| {
+        while t.introspect().deadline != Some(Timestamp::Custom(deadline)) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    };
+    let events = t.subscribe();
+    t.start();
+    counting_to(&t, s(60));
+    mock.advance(s(65));
+    events.recv_timeout(s(1)).unwrap();
+    counting_to(&t, s(125));
+    // Wedged well past the second deadline...
+    mock.advance(s(85));
+    events.recv_timeout(s(1)).unwrap();
+    t.stop();
+    assert_eq!(alerts.try_iter().collect::<Vec<_>>(), vec![s(25)]);
+}
+
+#[test]
+fn timer_suppress_later_than() {
+    use clock::{ClockSource, MockClock, Timestamp};
+    use event::Event;
+    use std::sync::Condvar;
+    let s = Duration::from_secs;
+    let mock = Arc::new(MockClock::new());
+    let mut t = Timer::with_clock(s(60), s(0), Arc::new(Condvar::new()), ClockSource::Custom(mock.clone()));
+    t.suppress_later_than(s(10));
+    let counting_to = |t: &Timer, deadline| {
+        while t.introspect().deadline != Some(Timestamp::Custom(deadline)) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    };
+    let events = t.subscribe();
+    t.start();
+    counting_to(&t, s(60));
+    mock.advance(s(65));
+    events.recv_timeout(s(1)).unwrap();
+    counting_to(&t, s(125));
+    mock.advance(s(85));
+    let started = std::time::Instant::now();
+    while t.suppressed() == 0 && started.elapsed() < s(1) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    t.stop();
+    assert_eq!(t.suppressed(), 1);
+    let expiries = events.try_iter().filter(|event| matches!(event, Event::Expired(_))).count();
+    assert_eq!(expiries, 0);
+    assert_eq!(t.drift_histogram().total(), 2);
+}
@@ -0,0 +1,153 @@
+use channel::{self, BoundedReceiver, BoundedSender, Interrupter, OnFull};
+use clock::{ClockJump, Timestamp};
+use config::JitterPolicy;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use trace::{Recording, TraceEvent};
+
+/// Something that happened to a timer.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The timer expired.
+    Expired(ExpiryEvent),
+    /// The wall clock a timer counts down against jumped, and the pending
+    /// count down was re-armed against the new time.
+    ClockJumped(ClockJump),
+    /// The timer's step, jitter or schedule was changed at runtime from a
+    /// watched configuration file.
+    Reconfigured(Reconfiguration),
+    /// The timer stopped itself at the time given to `stop_at` or
+    /// `stop_after`.
+    Stopped,
+    /// The timer expired the number of times given to `set_max_expiries`
+    /// and stopped itself.
+    Completed,
+}
+
+/// Details of a single expiry.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpiryEvent {
+    /// Number of times the timer has expired, including this one.
+    pub count: usize,
+    /// When the timer was due to expire.
+    pub deadline: Timestamp,
+    /// When the timer actually expired.
+    pub fired: Timestamp,
+    /// Number of expiries this event stands for, more than one if several
+    /// were coalesced into it, in which case `deadline` and `fired` are
+    /// those of the last.
+    pub coalesced: usize,
+}
+
+/// The settings a timer was reconfigured with.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reconfiguration {
+    /// The step now counted down from.
+    pub step: Duration,
+    /// The jitter now applied.
+    pub jitter: Duration,
+    /// How the jitter is now applied.
+    pub jitter_policy: JitterPolicy,
+    /// The cron expression now followed, if it changed.
+    pub schedule: Option<String>,
+}
+
+/// Where a subscriber's events are sent.
+enum Sink {
+    Unbounded(Sender<Event>),
+    Bounded(BoundedSender),
+    // Gone once its `Recorder` is dropped.
+    Recorder(Weak<Recording>),
+}
+
+impl Sink {
+    /// Send `event`, returning false if the subscriber has hung up.
+    fn send(&self, event: Event) -> bool {
+        match *self {
+            Sink::Unbounded(ref tx) => tx.send(event).is_ok(),
+            Sink::Bounded(ref tx) => tx.send(event),
+            Sink::Recorder(ref recording) => recording.upgrade()
+                .map(|recording| recording.record(TraceEvent::Emitted(event)))
+                .is_some(),
+        }
+    }
+}
+
+/// The set of channels events are delivered to.
+///
+#[derive(Default)]
+pub struct Subscribers {
+    senders: Mutex<Vec<Sink>>,
+    // For waking bounded subscribers' blocked sends, which hold `senders`.
+    interrupters: Mutex<Vec<Interrupter>>,
+}
+
+impl Subscribers {
+    /// Add a subscriber, returning the receiving end of its channel.
+    ///
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = channel();
+        self.senders.lock().unwrap().push(Sink::Unbounded(tx));
+        rx
+    }
+    /// Add a subscriber holding at most `capacity` events, returning the
+    /// receiving end of its channel.
+    ///
+    pub fn subscribe_bounded(&self, capacity: usize, on_full: OnFull) -> BoundedReceiver {
+        let (tx, rx) = channel::bounded(capacity, on_full);
+        self.interrupters.lock().unwrap().push(tx.interrupter());
+        self.senders.lock().unwrap().push(Sink::Bounded(tx));
+        rx
+    }
+    /// Make sends to full bounded subscribers drop their event rather than
+    /// block, waking any blocked now, so that a stalled receiver can't keep
+    /// the timer from stopping, or block again once `false`.
+    ///
+    pub fn interrupt(&self, interrupted: bool) {
+        self.interrupters.lock().unwrap().retain(|interrupter| interrupter.interrupt(interrupted));
+    }
+    /// Move every subscriber and recorder of `other` over to these.
+    ///
+    pub fn absorb(&self, other: &Subscribers) {
+        let interrupters = std::mem::take(&mut *other.interrupters.lock().unwrap());
+        self.interrupters.lock().unwrap().extend(interrupters);
+        let senders = std::mem::take(&mut *other.senders.lock().unwrap());
+        self.senders.lock().unwrap().extend(senders);
+    }
+    /// Add a recorder, which is sent every event and lifecycle change.
+    ///
+    pub fn record(&self, recording: &Arc<Recording>) {
+        self.senders.lock().unwrap().push(Sink::Recorder(Arc::downgrade(recording)));
+    }
+    /// Tell every recorder about a lifecycle change.
+    ///
+    pub fn note(&self, event: TraceEvent) {
+        for sink in self.senders.lock().unwrap().iter() {
+            if let Sink::Recorder(ref recording) = *sink {
+                if let Some(recording) = recording.upgrade() {
+                    recording.record(event.clone());
+                }
+            }
+        }
+    }
+    /// Deliver `event` to every subscriber, forgetting hung up ones.
+    ///
+    pub fn emit(&self, event: Event) {
+        self.senders.lock().unwrap().retain(|sink| sink.send(event.clone()));
+    }
+}
+
+#[test]
+fn subscribers_forget_hung_up_receivers() {
+    let subscribers = Subscribers::default();
+    let kept = subscribers.subscribe();
+    drop(subscribers.subscribe());
+    let stamp = Timestamp::Custom(Duration::from_secs(1));
+    subscribers.emit(Event::Expired(ExpiryEvent { count: 1, deadline: stamp, fired: stamp, coalesced: 1 }));
+    assert_eq!(subscribers.senders.lock().unwrap().len(), 1);
+    assert!(kept.try_recv().is_ok());
+}
@@ -0,0 +1,175 @@
+use std::io;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::Arc;
+use Timer;
+
+/// Create a non-blocking eventfd.
+///
+fn create() -> io::Result<OwnedFd> {
+    let fd = unsafe { ::libc::eventfd(0, ::libc::EFD_NONBLOCK | ::libc::EFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Add `n` to an eventfd's counter.
+///
+pub fn signal(fd: &OwnedFd, n: u64) {
+    unsafe {
+        ::libc::write(fd.as_raw_fd(), &n as *const u64 as *const ::libc::c_void, 8);
+    }
+}
+
+/// Read and clear an eventfd's counter.
+///
+fn drain(fd: &OwnedFd) -> io::Result<u64> {
+    let mut count: u64 = 0;
+    let n = unsafe { ::libc::read(fd.as_raw_fd(), &mut count as *mut u64 as *mut ::libc::c_void, 8) };
+    if n < 0 {
+        let e = io::Error::last_os_error();
+        return match e.kind() {
+            io::ErrorKind::WouldBlock => Ok(0),
+            _ => Err(e),
+        };
+    }
+    Ok(count)
+}
+
+/// A timer's eventfd, readable once the timer has expired since it was
+/// last drained.
+///
+/// Returned by `Timer::enable_eventfd`, for registering directly with poll,
+/// epoll or io_uring. Shares the descriptor with the timer, which stays
+/// open as long as either does.
+///
+#[derive(Clone, Debug)]
+pub struct TimerFd(Arc<OwnedFd>);
+
+impl TimerFd {
+    /// Read and clear the number of expiries signalled since the
+    /// descriptor was last drained, without blocking.
+    ///
+    pub fn drain(&self) -> io::Result<u64> {
+        drain(&self.0)
+    }
+}
+
+impl AsFd for TimerFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl AsRawFd for TimerFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl Timer {
+    /// Signal an eventfd on every expiry, returning the descriptor.
+    ///
+    /// The descriptor becomes readable once the timer expires, so an epoll
+    /// or mio based event loop can add it to its poll set instead of
+    /// dedicating a thread to waiting on `timed_out`. Drain it with
+    /// `TimerFd::drain` or `drain_eventfd`. It's signalled by the timer
+    /// thread as each expiry is delivered, however callbacks are
+    /// dispatched. Calling this again returns the same descriptor.
+    ///
+    pub fn enable_eventfd(&mut self) -> io::Result<TimerFd> {
+        let mut eventfd = self.eventfd.lock().unwrap();
+        if let Some(fd) = eventfd.first() {
+            return Ok(TimerFd(fd.clone()));
+        }
+        let fd = Arc::new(create()?);
+        eventfd.push(fd.clone());
+        Ok(TimerFd(fd))
+    }
+    /// Read and clear the number of expiries signalled on the eventfd since
+    /// it was last drained, without blocking.
+    ///
+    pub fn drain_eventfd(&self) -> io::Result<u64> {
+        match self.eventfd.lock().unwrap().first() {
+            Some(fd) => drain(fd),
+            None => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn timer_eventfd() {
+    use std::sync::Condvar;
+    use std::time::Duration;
+    let mut t = Timer::new(Duration::from_millis(10), Duration::from_secs(0), Arc::new(Condvar::new()));
+    let timer_fd = t.enable_eventfd().unwrap();
+    let fd = timer_fd.as_raw_fd();
+    assert_eq!(t.enable_eventfd().unwrap().as_raw_fd(), fd);
+    assert_eq!(timer_fd.as_fd().as_raw_fd(), fd);
+    assert_eq!(timer_fd.drain().unwrap(), 0);
+    // The descriptor isn't a callback...
+    assert_eq!(t.callbacks.count(), 0);
+    t.start();
+    let mut poll = ::libc::pollfd { fd, events: ::libc::POLLIN, revents: 0 };
+    assert_eq!(unsafe { ::libc::poll(&mut poll, 1, 1000) }, 1);
+    std::thread::sleep(Duration::from_millis(25));
+    t.stop();
+    let count = timer_fd.drain().unwrap();
+    assert!(count >= 2);
+    assert_eq!(count as usize, t.expiries.load(std::sync::atomic::Ordering::SeqCst));
+    assert_eq!(t.drain_eventfd().unwrap(), 0);
+    // The descriptor stays open for as long as the handle does.
+    drop(t);
+    assert_eq!(timer_fd.drain().unwrap(), 0);
+}
+
+#[test]
+fn timer_eventfd_bypasses_the_callback_pool() {
+    use callback::Dispatch;
+    use std::sync::{Condvar, Mutex};
+    use std::time::Duration;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(1), ms(0), Arc::new(Condvar::new()));
+    let fd = t.enable_eventfd().unwrap();
+    let release = Arc::new((Mutex::new(false), Condvar::new()));
+    let r = release.clone();
+    // A stuck callback fills the pool's queue...
+    t.on_expiry(move || {
+        let mut released = r.0.lock().unwrap();
+        while !*released {
+            released = r.1.wait(released).unwrap();
+        }
+    });
+    t.set_dispatch(Dispatch::Pool(1));
+    t.start();
+    let started = std::time::Instant::now();
+    while t.dropped_callbacks() == 0 && started.elapsed() < Duration::from_secs(2) {
+        std::thread::sleep(ms(5));
+    }
+    *release.0.lock().unwrap() = true;
+    release.1.notify_all();
+    t.stop();
+    // ...but every expiry still reaches the descriptor.
+    assert!(t.dropped_callbacks() > 0);
+    assert_eq!(fd.drain().unwrap() as usize, t.expiries.load(std::sync::atomic::Ordering::SeqCst));
+}
+
+#[test]
+fn timer_race_keeps_eventfds() {
+    use std::sync::Condvar;
+    use std::time::Duration;
+    let ms = Duration::from_millis;
+    let cv = Arc::new(Condvar::new());
+    let mut a = Timer::new(ms(10), ms(0), cv.clone());
+    let a_fd = a.enable_eventfd().unwrap();
+    a.set_max_expiries(2);
+    let mut b = Timer::new(ms(60), ms(0), cv);
+    let b_fd = b.enable_eventfd().unwrap();
+    let mut t = Timer::race(a, b);
+    assert_eq!(t.enable_eventfd().unwrap().as_raw_fd(), a_fd.as_raw_fd());
+    t.start();
+    assert!(t.wait_for_completion(Duration::from_secs(1)));
+    t.stop();
+    assert_eq!(a_fd.drain().unwrap(), 2);
+    assert_eq!(b_fd.drain().unwrap(), 2);
+}
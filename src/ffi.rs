@@ -0,0 +1,145 @@
+//! A C interface to `Timer`, for embedding in C and C++ programs.
+//!
+//! Timers are handed out as opaque pointers by `timer_new` and released
+//! with `timer_free`. Build the shared library to link against with
+//! `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+use config::TimerConfig;
+use std::os::raw::{c_int, c_void};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Condvar};
+use std::time::Duration;
+use Timer;
+
+/// An expiry callback, called with the user data it was registered with.
+///
+pub type TimerCallback = extern "C" fn(user_data: *mut c_void);
+
+/// User data handed back to a callback.
+///
+/// The caller of `timer_on_expiry` promises it can be used from the timer
+/// thread.
+///
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+/// Create a new, stopped timer, or return null if the step and jitter are
+/// invalid.
+///
+#[no_mangle]
+pub extern "C" fn timer_new(step_ms: u64, jitter_ms: u64) -> *mut Timer {
+    let mut config = TimerConfig::new(Duration::from_millis(step_ms));
+    config.jitter = Duration::from_millis(jitter_ms);
+    match Timer::from_config(config, Arc::new(Condvar::new())) {
+        Ok(timer) => Box::into_raw(Box::new(timer)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Stop the timer if it's running, and release it.
+///
+/// # Safety
+///
+/// `timer` must have come from `timer_new`, and not already been freed.
+/// Null is ignored.
+///
+#[no_mangle]
+pub unsafe extern "C" fn timer_free(timer: *mut Timer) {
+    if timer.is_null() {
+        return;
+    }
+    let mut timer = Box::from_raw(timer);
+    if timer.handle.is_some() {
+        timer.stop();
+    }
+}
+
+/// Start counting down.
+///
+/// # Safety
+///
+/// `timer` must be a live timer from `timer_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn timer_start(timer: *mut Timer) {
+    (*timer).start();
+}
+
+/// Stop counting down, waiting for the timer thread to exit. Returns -1 if
+/// the timer wasn't running.
+///
+/// # Safety
+///
+/// `timer` must be a live timer from `timer_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn timer_stop(timer: *mut Timer) -> c_int {
+    let timer = &mut *timer;
+    if timer.handle.is_none() {
+        return -1;
+    }
+    timer.stop();
+    0
+}
+
+/// Start the count down over from a full step.
+///
+/// # Safety
+///
+/// `timer` must be a live timer from `timer_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn timer_reset(timer: *mut Timer) {
+    (*timer).reset();
+}
+
+/// Number of times the timer has expired.
+///
+/// # Safety
+///
+/// `timer` must be a live timer from `timer_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn timer_expiries(timer: *const Timer) -> u64 {
+    (*timer).expiries.load(Ordering::SeqCst) as u64
+}
+
+/// Call `callback` with `user_data` on the timer thread each time the timer
+/// expires.
+///
+/// # Safety
+///
+/// `timer` must be a live timer from `timer_new`, and `user_data` must stay
+/// valid, and be safe to use from another thread, for as long as the timer
+/// is running.
+///
+#[no_mangle]
+pub unsafe extern "C" fn timer_on_expiry(timer: *mut Timer, callback: TimerCallback, user_data: *mut c_void) {
+    let user_data = UserData(user_data);
+    (*timer).on_expiry(move || callback(user_data.0));
+}
+
+#[test]
+fn ffi_round_trip() {
+    use std::sync::atomic::AtomicUsize;
+    extern "C" fn count(user_data: *mut c_void) {
+        let counter = unsafe { &*(user_data as *const AtomicUsize) };
+        counter.fetch_add(1, Ordering::SeqCst);
+    }
+    assert!(timer_new(10, 20).is_null());
+    let counter = AtomicUsize::new(0);
+    unsafe {
+        let timer = timer_new(10, 0);
+        assert_eq!(timer_stop(timer), -1);
+        timer_on_expiry(timer, count, &counter as *const AtomicUsize as *mut c_void);
+        timer_start(timer);
+        std::thread::sleep(Duration::from_millis(35));
+        timer_reset(timer);
+        assert_eq!(timer_stop(timer), 0);
+        assert!(timer_expiries(timer) >= 2);
+        assert_eq!(timer_expiries(timer) as usize, counter.load(Ordering::SeqCst));
+        timer_free(timer);
+    }
+}
@@ -0,0 +1,174 @@
+use lifecycle::Phase;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use Timer;
+
+/// How many of the most recent internal events a timer remembers.
+///
+pub const FLIGHT_RECORDER_CAPACITY: usize = 64;
+
+/// Why the timer's thread stopped waiting on a count down.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WakeReason {
+    /// The count down was due.
+    Due,
+    /// The count down was reset.
+    Reset,
+    /// The timer was asked to stop, or reached the time set by `stop_at`.
+    Stop,
+    /// The count down ran out while the machine was suspended, and was
+    /// skipped per the timer's `SuspendPolicy`.
+    Skipped,
+    /// Waiting on the timer's condition variable failed.
+    Error,
+}
+
+/// Something that happened inside a timer, as kept by its flight recorder.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlightEvent {
+    /// The timer moved from one phase to another.
+    Transition {
+        /// The phase it left.
+        from: Phase,
+        /// The phase it entered.
+        to: Phase,
+    },
+    /// The timer's thread went to sleep for at most `wait`, or until woken.
+    Waiting {
+        /// Longest the thread would sleep for.
+        wait: Duration,
+        /// True if the count down was paused.
+        paused: bool,
+    },
+    /// The timer's thread stopped waiting on a count down.
+    Woke(WakeReason),
+    /// The timer expired for the `n`th time.
+    Expired(usize),
+}
+
+impl fmt::Display for FlightEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FlightEvent::Transition { from, to } => write!(f, "{:?} -> {:?}", from, to),
+            FlightEvent::Waiting { wait, paused: false } => write!(f, "waiting up to {:?}", wait),
+            FlightEvent::Waiting { wait, paused: true } => write!(f, "waiting up to {:?}, paused", wait),
+            FlightEvent::Woke(reason) => write!(f, "woke: {:?}", reason),
+            FlightEvent::Expired(n) => write!(f, "expired #{}", n),
+        }
+    }
+}
+
+/// An internal event and when it happened.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlightEntry {
+    /// Time since the timer was created.
+    pub at: Duration,
+    /// What happened.
+    pub event: FlightEvent,
+}
+
+impl fmt::Display for FlightEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {}", self.at, self.event)
+    }
+}
+
+/// A ring buffer of a timer's most recent internal events, always on.
+///
+/// Each event claims the next slot with an atomic increment, then takes
+/// only that slot's lock, which nothing else holds unless the buffer is
+/// being dumped, so recording never waits on the timer's own lock.
+///
+#[derive(Debug)]
+pub struct FlightRecorder {
+    origin: Instant,
+    // Number of events ever recorded.
+    next: AtomicUsize,
+    // Each entry with its sequence number, to put them back in order.
+    slots: Vec<Mutex<Option<(usize, FlightEntry)>>>,
+}
+
+impl Default for FlightRecorder {
+    fn default() -> FlightRecorder {
+        FlightRecorder {
+            origin: Instant::now(),
+            next: AtomicUsize::new(0),
+            slots: (0..FLIGHT_RECORDER_CAPACITY).map(|_| Mutex::new(None)).collect(),
+        }
+    }
+}
+
+impl FlightRecorder {
+    /// Record `event` as happening now, overwriting the oldest entry if
+    /// full.
+    ///
+    pub fn record(&self, event: FlightEvent) {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let entry = FlightEntry { at: self.origin.elapsed(), event };
+        let mut slot = self.slots[seq % self.slots.len()].lock().unwrap();
+        // A slower writer lapped by a faster one mustn't clobber it.
+        if slot.is_none_or(|(held, _)| held < seq) {
+            *slot = Some((seq, entry));
+        }
+    }
+    /// Every entry still held, oldest first.
+    ///
+    pub fn dump(&self) -> Vec<FlightEntry> {
+        let mut entries: Vec<(usize, FlightEntry)> = self.slots.iter()
+            .filter_map(|slot| *slot.lock().unwrap())
+            .collect();
+        entries.sort_by_key(|&(seq, _)| seq);
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+impl Timer {
+    /// The timer's most recent internal events, oldest first.
+    ///
+    /// Every timer keeps its last `FLIGHT_RECORDER_CAPACITY` phase changes,
+    /// waits, wake ups and expiries, at little cost, so that a timer that
+    /// seems to have stopped ticking can be diagnosed after the fact, e.g.,
+    /// by logging the dump, one entry per line, from a health check.
+    ///
+    pub fn dump_flight_recorder(&self) -> Vec<FlightEntry> {
+        self.lifecycle.flight().dump()
+    }
+}
+
+#[test]
+fn flight_recorder_keeps_the_latest() {
+    let recorder = FlightRecorder::default();
+    for n in 0..FLIGHT_RECORDER_CAPACITY + 10 {
+        recorder.record(FlightEvent::Expired(n));
+    }
+    let dump = recorder.dump();
+    assert_eq!(dump.len(), FLIGHT_RECORDER_CAPACITY);
+    assert_eq!(dump[0].event, FlightEvent::Expired(10));
+    assert_eq!(dump.last().unwrap().event, FlightEvent::Expired(FLIGHT_RECORDER_CAPACITY + 9));
+    assert!(dump.windows(2).all(|pair| pair[0].at <= pair[1].at));
+    assert_eq!(dump[0].to_string(), format!("{:?} expired #10", dump[0].at));
+}
+
+#[test]
+fn timer_dump_flight_recorder() {
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(10), ms(0), Arc::new(Condvar::new()));
+    assert!(t.dump_flight_recorder().is_empty());
+    t.set_max_expiries(2);
+    t.start();
+    assert!(t.wait_for_completion(Duration::from_secs(1)));
+    t.stop();
+    let events: Vec<FlightEvent> = t.dump_flight_recorder().into_iter().map(|entry| entry.event).collect();
+    let expired: Vec<&FlightEvent> = events.iter().filter(|event| matches!(event, FlightEvent::Expired(_))).collect();
+    assert_eq!(expired, vec![&FlightEvent::Expired(1), &FlightEvent::Expired(2)]);
+    assert_eq!(events[0], FlightEvent::Transition { from: Phase::Stopped, to: Phase::Starting });
+    assert!(events.contains(&FlightEvent::Woke(WakeReason::Due)));
+    assert!(events.iter().any(|event| matches!(event, FlightEvent::Waiting { paused: false, .. })));
+    assert_eq!(events.last(), Some(&FlightEvent::Transition { from: Phase::Stopping, to: Phase::Stopped }));
+}
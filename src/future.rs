@@ -0,0 +1,497 @@
+use clock::{ClockSource, Timestamp};
+use deadline::Deadline;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant, SystemTime};
+use thread::ThreadConfig;
+
+/// A sleeping future waiting to be woken by the driver.
+struct Entry {
+    id: usize,
+    clock: ClockSource,
+    deadline: Duration,
+    waker: Waker,
+}
+
+/// The shared driver, once its thread is running.
+static DRIVER: OnceLock<Arc<Driver>> = OnceLock::new();
+
+/// The background thread that wakes sleeping futures once their deadlines
+/// pass.
+///
+/// Every future shares one driver, which is spawned on first use unless
+/// `start_driver` spawned it already. It waits
+/// on each entry's own clock, so futures on a mock clock wake as soon as
+/// the clock is advanced.
+///
+#[derive(Default)]
+struct Driver {
+    entries: Mutex<Vec<Entry>>,
+    cv: Condvar,
+    // Source of unique entry ids.
+    ids: AtomicUsize,
+}
+
+impl Driver {
+    /// The shared driver, spawning its thread if needed.
+    ///
+    fn get() -> &'static Driver {
+        if let Some(driver) = DRIVER.get() {
+            return driver;
+        }
+        start_driver(&ThreadConfig::default()).expect("Couldn't spawn driver thread!");
+        DRIVER.get().unwrap()
+    }
+    /// Internal driver loop.
+    ///
+    fn run(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        loop {
+            let mut wait: Option<Duration> = None;
+            entries.retain(|e| {
+                let now = e.clock.reading();
+                if now >= e.deadline {
+                    e.waker.wake_by_ref();
+                    return false;
+                }
+                let real_wait = e.clock.real_wait(e.deadline - now);
+                wait = Some(wait.map_or(real_wait, |w| w.min(real_wait)));
+                true
+            });
+            entries = match wait {
+                Some(wait) => self.cv.wait_timeout(entries, wait).unwrap().0,
+                None => self.cv.wait(entries).unwrap(),
+            };
+        }
+    }
+    /// Wake `waker` once `clock` reads `deadline`, replacing any earlier
+    /// registration under `id`.
+    ///
+    fn register(&self, id: usize, clock: &ClockSource, deadline: Duration, waker: &Waker) {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.iter_mut().find(|e| e.id == id) {
+            Some(e) => {
+                e.deadline = deadline;
+                if !e.waker.will_wake(waker) {
+                    e.waker = waker.clone();
+                }
+            },
+            None => entries.push(Entry {
+                id,
+                clock: clock.clone(),
+                deadline,
+                waker: waker.clone(),
+            }),
+        }
+        self.cv.notify_all();
+    }
+    /// Move the registration under `id`, if any, to `deadline`.
+    ///
+    fn reschedule(&self, id: usize, deadline: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(e) = entries.iter_mut().find(|e| e.id == id) {
+            e.deadline = deadline;
+            self.cv.notify_all();
+        }
+    }
+    /// Forget the registration under `id`, if any.
+    ///
+    fn deregister(&self, id: usize) {
+        self.entries.lock().unwrap().retain(|e| e.id != id);
+    }
+}
+
+/// Spawn the thread waking sleeping futures per `threads`, named
+/// `"timer-driver"` after its prefix, unless it's running already.
+///
+/// The thread is otherwise spawned on first use with the default config,
+/// panicking if it can't be, so call this first to choose how it's spawned
+/// or to handle the failure.
+///
+pub fn start_driver(threads: &ThreadConfig) -> io::Result<()> {
+    static SPAWNING: Mutex<()> = Mutex::new(());
+    let _spawning = SPAWNING.lock().unwrap();
+    if DRIVER.get().is_some() {
+        return Ok(());
+    }
+    let driver = Arc::new(Driver::default());
+    let d = driver.clone();
+    threads.spawn("timer-driver", move || d.run())?;
+    let _ = DRIVER.set(driver);
+    Ok(())
+}
+
+/// A future that completes once its clock reaches a deadline.
+///
+/// Cancellation safe: dropping it before it completes loses nothing, and
+/// it can be polled by reference, e.g., as one branch of a `select!` loop,
+/// and `reset` between iterations rather than recreated.
+///
+pub struct Sleep {
+    clock: ClockSource,
+    deadline: Duration,
+    // Driver registration, once the future has been polled.
+    id: Option<usize>,
+}
+
+impl Sleep {
+    /// Sleep for `d` on the monotonic clock.
+    ///
+    pub fn new(d: Duration) -> Sleep {
+        Sleep::with_clock(d, ClockSource::Monotonic)
+    }
+    /// Sleep for `d` on the given clock.
+    ///
+    pub fn with_clock(d: Duration, clock: ClockSource) -> Sleep {
+        let deadline = clock.reading().saturating_add(d);
+        Sleep::until(deadline, clock)
+    }
+    /// Sleep until `clock` reads `deadline`.
+    ///
+    pub fn until(deadline: Duration, clock: ClockSource) -> Sleep {
+        Sleep { clock, deadline, id: None }
+    }
+    /// The clock reading this sleep completes at.
+    ///
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+    /// True if the deadline has passed.
+    ///
+    pub fn is_elapsed(&self) -> bool {
+        self.clock.reading() >= self.deadline
+    }
+    /// The instant this sleep completes at, or `None` on a custom clock,
+    /// which has no relation to real time.
+    ///
+    /// For handing the deadline to other timer libraries. A wall clock
+    /// deadline is converted as of now, so it won't follow later changes to
+    /// the system time.
+    ///
+    pub fn instant(&self) -> Option<Instant> {
+        match self.clock.stamp(self.deadline) {
+            Timestamp::Monotonic(at) => Some(at),
+            Timestamp::Wall(at) => {
+                let now = Instant::now();
+                match at.duration_since(SystemTime::now()) {
+                    Ok(ahead) => now.checked_add(ahead),
+                    Err(e) => now.checked_sub(e.duration()).or(Some(now)),
+                }
+            },
+            Timestamp::Custom(_) => None,
+        }
+    }
+    /// Complete once the clock reads `deadline` instead, whether or not
+    /// the sleep has already completed.
+    ///
+    /// A task waiting on the sleep is woken at the new deadline, even if
+    /// it's earlier than the old one.
+    ///
+    pub fn reset(&mut self, deadline: Duration) {
+        self.deadline = deadline;
+        if let Some(id) = self.id {
+            Driver::get().reschedule(id, deadline);
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.is_elapsed() {
+            if let Some(id) = self.id.take() {
+                Driver::get().deregister(id);
+            }
+            return Poll::Ready(());
+        }
+        let driver = Driver::get();
+        let id = *self.id.get_or_insert_with(|| driver.ids.fetch_add(1, Ordering::Relaxed));
+        driver.register(id, &self.clock, self.deadline, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl From<Deadline> for Sleep {
+    /// Sleep on the monotonic clock until `deadline`.
+    ///
+    fn from(deadline: Deadline) -> Sleep {
+        Sleep::new(deadline.remaining())
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            Driver::get().deregister(id);
+        }
+    }
+}
+
+/// Sleep for `d` on the monotonic clock.
+///
+pub fn sleep(d: Duration) -> Sleep {
+    Sleep::new(d)
+}
+
+/// An async periodic schedule, yielding the clock reading each tick was
+/// due at.
+///
+/// Like `Interval`, deadlines are aligned to the first one, and missed
+/// ticks are returned immediately, one per call, until caught up. A tick is
+/// only consumed when the `Tick` future returned by `tick` completes, so
+/// one dropped unfinished, e.g., by losing a `select!` race, is neither
+/// lost nor handed out twice.
+///
+pub struct Ticks {
+    // Sleeps until the next tick is due.
+    sleep: Sleep,
+    period: Duration,
+}
+
+impl Ticks {
+    /// Tick every `period` on the monotonic clock, starting immediately.
+    ///
+    pub fn new(period: Duration) -> Ticks {
+        Ticks::with_clock(period, ClockSource::Monotonic)
+    }
+    /// Tick every `period` on the given clock, starting immediately.
+    ///
+    /// # Panics
+    ///
+    /// If `period` is zero.
+    ///
+    pub fn with_clock(period: Duration, clock: ClockSource) -> Ticks {
+        assert!(period > Duration::from_secs(0), "Tick period must be non-zero!");
+        let now = clock.reading();
+        Ticks { sleep: Sleep::until(now, clock), period }
+    }
+    /// The clock reading the next tick is due at.
+    ///
+    pub fn deadline(&self) -> Duration {
+        self.sleep.deadline()
+    }
+    /// Wait for the next tick.
+    ///
+    pub fn tick<'a>(&'a mut self) -> Tick<'a> {
+        Tick { ticks: self }
+    }
+    /// Check whether the next tick is due, consuming it if so, and
+    /// arranging for the task to be woken when it is if not.
+    ///
+    pub fn poll_tick(&mut self, cx: &mut Context) -> Poll<Duration> {
+        match Pin::new(&mut self.sleep).poll(cx) {
+            Poll::Ready(()) => {
+                let due = self.sleep.deadline();
+                self.sleep.reset(due.saturating_add(self.period));
+                Poll::Ready(due)
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+    /// Restart the schedule so the next tick is due a period from now,
+    /// dropping any missed ticks.
+    ///
+    pub fn reset(&mut self) {
+        let next = self.sleep.clock.reading().saturating_add(self.period);
+        self.sleep.reset(next);
+    }
+}
+
+/// A future that completes with the next tick of a `Ticks`.
+///
+pub struct Tick<'a> {
+    ticks: &'a mut Ticks,
+}
+
+impl<'a> Future for Tick<'a> {
+    type Output = Duration;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Duration> {
+        self.ticks.poll_tick(cx)
+    }
+}
+
+/// Returned by a `Timeout` whose deadline passed first.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
+
+/// A future that resolves to its inner future's output, or to `Elapsed` if
+/// a deadline passes first.
+///
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F> Timeout<F> {
+    /// Consume the timeout, returning the inner future.
+    ///
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // Safe since `future` is never moved out of a pinned `Timeout`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Require `future` to complete within `d` on the monotonic clock.
+///
+pub fn timeout<F: Future>(d: Duration, future: F) -> Timeout<F> {
+    timeout_with_clock(d, ClockSource::Monotonic, future)
+}
+
+/// Require `future` to complete within `d` on the given clock.
+///
+pub fn timeout_with_clock<F: Future>(d: Duration, clock: ClockSource, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: Sleep::with_clock(d, clock),
+    }
+}
+
+/// Run a future to completion on the current thread.
+///
+#[cfg(test)]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    use std::task::Wake;
+    use std::thread::Thread;
+    struct Unparker(Thread);
+    impl Wake for Unparker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+    let waker = Waker::from(Arc::new(Unparker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+#[test]
+fn future_timeout() {
+    use std::time::Instant;
+    let started = Instant::now();
+    assert_eq!(block_on(timeout(Duration::from_millis(50), sleep(Duration::from_millis(10)))),
+               Ok(()));
+    assert_eq!(block_on(timeout(Duration::from_millis(10), sleep(Duration::from_secs(10)))),
+               Err(Elapsed));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn future_timeout_mock_clock() {
+    use clock::MockClock;
+    let mock = Arc::new(MockClock::new());
+    let clock = ClockSource::Custom(mock.clone());
+    let m = mock.clone();
+    let t = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        m.advance(Duration::from_secs(3600));
+    });
+    let forever = Sleep::with_clock(Duration::from_secs(7200), clock.clone());
+    assert_eq!(block_on(timeout_with_clock(Duration::from_secs(3600), clock, forever)),
+               Err(Elapsed));
+    t.join().unwrap();
+}
+
+#[test]
+fn sleep_reset() {
+    use clock::MockClock;
+    use std::sync::mpsc::{channel, Sender};
+    use std::task::Wake;
+    struct Notify(Mutex<Sender<()>>);
+    impl Wake for Notify {
+        fn wake(self: Arc<Self>) {
+            let _ = self.0.lock().unwrap().send(());
+        }
+    }
+    let s = Duration::from_secs;
+    let mock = Arc::new(MockClock::new());
+    let (tx, woken) = channel();
+    let waker = Waker::from(Arc::new(Notify(Mutex::new(tx))));
+    let mut cx = Context::from_waker(&waker);
+    let mut sleep = Sleep::with_clock(s(3600), ClockSource::Custom(mock.clone()));
+    assert_eq!(Pin::new(&mut sleep).poll(&mut cx), Poll::Pending);
+    // Moved earlier while a task waits on it, which is then woken on time.
+    sleep.reset(s(1));
+    mock.advance(s(1));
+    assert!(woken.recv_timeout(s(1)).is_ok());
+    assert_eq!(Pin::new(&mut sleep).poll(&mut cx), Poll::Ready(()));
+    // Completed sleeps can be reused.
+    sleep.reset(s(2));
+    assert_eq!(Pin::new(&mut sleep).poll(&mut cx), Poll::Pending);
+    mock.advance(s(1));
+    assert_eq!(Pin::new(&mut sleep).poll(&mut cx), Poll::Ready(()));
+}
+
+#[test]
+fn ticks_survive_cancellation() {
+    use std::time::Instant;
+    let ms = Duration::from_millis;
+    let mut ticks = Ticks::new(ms(20));
+    let first = block_on(ticks.tick());
+    let mut seen = vec![first];
+    let started = Instant::now();
+    while seen.len() < 4 {
+        // Race each tick against a shorter sleep, dropping it when it loses.
+        if let Ok(due) = block_on(timeout(ms(7), ticks.tick())) {
+            seen.push(due);
+        }
+    }
+    let gaps: Vec<Duration> = seen.windows(2).map(|w| w[1] - w[0]).collect();
+    assert_eq!(gaps, vec![ms(20); 3]);
+    assert!(started.elapsed() >= ms(55));
+}
+
+#[test]
+fn sleep_instant() {
+    use clock::MockClock;
+    let ms = Duration::from_millis;
+    let deadline = Deadline::after(ms(50));
+    let at = Sleep::from(deadline).instant().unwrap();
+    assert!(at >= deadline.instant() && at - deadline.instant() < ms(10));
+    let wall = Sleep::with_clock(ms(50), ClockSource::Wall).instant().unwrap();
+    assert!(wall > Instant::now() && wall - Instant::now() <= ms(50));
+    let mock = ClockSource::Custom(Arc::new(MockClock::new()));
+    assert_eq!(Sleep::with_clock(ms(50), mock).instant(), None);
+}
+
+#[test]
+fn future_start_driver() {
+    assert!(start_driver(&ThreadConfig::new().with_name_prefix("app-")).is_ok());
+    // Already running, so a config that couldn't spawn anything is fine.
+    assert!(start_driver(&ThreadConfig::new().with_stack_size(usize::MAX / 2)).is_ok());
+    block_on(sleep(Duration::from_millis(5)));
+}
@@ -0,0 +1,30 @@
+use deadline::Deadline;
+use futures_timer::Delay;
+
+/// Convert a `Deadline` into a `futures_timer::Delay` for the time left
+/// until it.
+///
+/// `Delay` doesn't expose when it fires, so there's no converting back;
+/// keep the `Deadline` around instead, and `reset` the delay to its
+/// `remaining` time to move it.
+///
+impl From<Deadline> for Delay {
+    fn from(deadline: Deadline) -> Delay {
+        Delay::new(deadline.remaining())
+    }
+}
+
+#[test]
+fn delay_from_deadline() {
+    use future::{block_on, timeout, Sleep};
+    use std::time::{Duration, Instant};
+    let ms = Duration::from_millis;
+    let deadline = Deadline::after(ms(20));
+    let started = Instant::now();
+    block_on(Delay::from(deadline));
+    assert!(deadline.has_passed());
+    // Timeouts on either side agree.
+    assert!(block_on(timeout(ms(10), Delay::from(Deadline::after(Duration::from_secs(10))))).is_err());
+    block_on(Delay::from(Deadline::at(Sleep::new(ms(10)).instant().unwrap())));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
@@ -0,0 +1,138 @@
+use glib::thread_guard::ThreadGuard;
+use glib::{ControlFlow, MainContext, Priority};
+use std::cell::RefCell;
+use std::sync::Arc;
+use Timer;
+
+/// A callback that mustn't leave the thread it was registered on.
+type Local<F> = Arc<ThreadGuard<RefCell<F>>>;
+
+/// Run `f` on `context`'s thread the next time it's idle, never on the
+/// calling thread, unlike `MainContext::invoke` on a context nobody owns.
+///
+fn defer<F>(context: &MainContext, f: F)
+    where F: FnOnce() + Send + 'static
+{
+    let mut f = Some(f);
+    let source = glib::source::idle_source_new(None, Priority::DEFAULT_IDLE, move || {
+        if let Some(f) = f.take() {
+            f();
+        }
+        ControlFlow::Break
+    });
+    source.attach(Some(context));
+}
+
+/// A local callback as held by the timer, which is dropped back on the
+/// callback's own thread, since its guard panics if dropped anywhere else.
+struct LocalCallback<F: 'static> {
+    context: MainContext,
+    // Only ever taken on drop.
+    f: Option<Local<F>>,
+}
+
+impl<F: 'static> Drop for LocalCallback<F> {
+    fn drop(&mut self) {
+        if let Some(f) = self.f.take() {
+            defer(&self.context, move || drop(f));
+        }
+    }
+}
+
+impl Timer {
+    /// Run the timer's callbacks on `context`'s thread rather than the timer
+    /// thread.
+    ///
+    /// Each expiry is handed to the context as an idle source, so with the
+    /// GTK main context the callbacks run on the GUI thread between other
+    /// events and can update widgets directly. Replaces the timer's
+    /// dispatch, and takes effect the next time the timer is started.
+    ///
+    pub fn attach_to_main_context(&mut self, context: &MainContext) {
+        self.main_context = Some(context.clone());
+    }
+    /// Register a callback to run on `context`'s thread each time the timer
+    /// expires, which needn't be `Send`, so it can capture `Rc`s, widgets
+    /// and the like.
+    ///
+    /// Must be called on the thread that runs `context`, e.g., the GTK main
+    /// thread. Each expiry is handed to the context as an idle source, and
+    /// the callback runs once the context gets to it, whatever the timer's
+    /// dispatch.
+    ///
+    pub fn on_expiry_local<F>(&mut self, context: &MainContext, f: F)
+        where F: FnMut() + 'static
+    {
+        let local = LocalCallback {
+            context: context.clone(),
+            f: Some(Arc::new(ThreadGuard::new(RefCell::new(f)))),
+        };
+        self.on_expiry(move || {
+            let f = local.f.clone();
+            defer(&local.context, move || {
+                // A callback that iterates the context itself mustn't be
+                // reentered.
+                if let Some(mut f) = f.as_ref().and_then(|f| f.get_ref().try_borrow_mut().ok()) {
+                    (*f)();
+                }
+            });
+        });
+    }
+}
+
+#[test]
+fn timer_main_context() {
+    use glib::MainLoop;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Condvar};
+    use std::time::Duration;
+    let context = MainContext::new();
+    let main_loop = MainLoop::new(Some(&context), false);
+    let mut t = Timer::new(Duration::from_millis(10), Duration::from_secs(0), Arc::new(Condvar::new()));
+    t.attach_to_main_context(&context);
+    let count = Arc::new(AtomicUsize::new(0));
+    let on_context = Arc::new(AtomicBool::new(true));
+    let (c, o, l, ctx) = (count.clone(), on_context.clone(), main_loop.clone(), context.clone());
+    t.on_expiry(move || {
+        if !ctx.is_owner() {
+            o.store(false, Ordering::SeqCst);
+        }
+        if c.fetch_add(1, Ordering::SeqCst) == 2 {
+            l.quit();
+        }
+    });
+    context.with_thread_default(|| {
+        t.start();
+        main_loop.run();
+    }).unwrap();
+    t.stop();
+    assert!(count.load(Ordering::SeqCst) >= 3);
+    assert!(on_context.load(Ordering::SeqCst));
+}
+
+#[test]
+fn timer_on_expiry_local() {
+    use glib::MainLoop;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::Condvar;
+    use std::time::Duration;
+    let context = MainContext::new();
+    let main_loop = MainLoop::new(Some(&context), false);
+    let mut t = Timer::new(Duration::from_millis(10), Duration::from_secs(0), Arc::new(Condvar::new()));
+    // Neither `Send` nor `Sync`.
+    let count = Rc::new(Cell::new(0));
+    let (c, l) = (count.clone(), main_loop.clone());
+    context.with_thread_default(|| {
+        t.on_expiry_local(&context, move || {
+            c.set(c.get() + 1);
+            if c.get() == 3 {
+                l.quit();
+            }
+        });
+        t.start();
+        main_loop.run();
+    }).unwrap();
+    t.stop();
+    assert!(count.get() >= 3);
+}
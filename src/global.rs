@@ -0,0 +1,57 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+use timer_pool::{PooledTimer, TimerPool};
+
+/// Most workers the global pool runs timers on.
+///
+const MAX_WORKERS: usize = 4;
+
+/// The process-wide timer pool, started the first time it's used.
+///
+/// For applications that want `setTimeout` style timers without passing a
+/// pool around. It runs on as many workers as there are cores, up to four,
+/// and lives as long as the process.
+///
+pub fn global() -> &'static TimerPool {
+    static GLOBAL: OnceLock<TimerPool> = OnceLock::new();
+    GLOBAL.get_or_init(|| {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        TimerPool::new(cores.min(MAX_WORKERS))
+    })
+}
+
+/// Run `f` once, `after` from now, on the global pool.
+///
+pub fn after<F>(after: Duration, f: F) -> PooledTimer
+    where F: FnOnce() + Send + 'static
+{
+    global().schedule(after, f)
+}
+
+/// Run `f` every `period` from now on the global pool, until cancelled.
+///
+/// # Panics
+///
+/// If `period` is zero.
+///
+pub fn every<F>(period: Duration, f: F) -> PooledTimer
+    where F: Fn() + Send + Sync + 'static
+{
+    global().schedule_every(period, f)
+}
+
+#[test]
+fn global_after_and_every() {
+    use std::sync::mpsc::channel;
+    use std::sync::Mutex;
+    let ms = Duration::from_millis;
+    let (tx, rx) = channel();
+    after(ms(5), move || tx.send(()).unwrap());
+    rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    let (tx, rx) = channel();
+    let tx = Mutex::new(tx);
+    let ticks = every(ms(5), move || tx.lock().unwrap().send(()).unwrap());
+    assert_eq!(rx.iter().take(3).count(), 3);
+    assert!(ticks.cancel());
+    assert!(std::ptr::eq(global(), global()));
+}
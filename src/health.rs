@@ -0,0 +1,104 @@
+use clock::ClockSource;
+#[cfg(any(test, feature = "testing"))]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use Timer;
+
+/// When the timer thread promised to check in again, for liveness checks.
+///
+#[derive(Debug, Default)]
+pub struct Heartbeat {
+    // Monotonic clock reading to check in by, in nanoseconds.
+    due: AtomicU64,
+}
+
+impl Heartbeat {
+    /// Check in, promising to check in again `within` from now.
+    ///
+    pub fn beat(&self, within: Duration) {
+        let due = ClockSource::Monotonic.reading().saturating_add(within);
+        self.due.store(due.as_nanos().min(u64::MAX as u128) as u64, Ordering::SeqCst);
+    }
+    /// True if the last promise to check in has been kept, give or take
+    /// `tolerance`.
+    ///
+    pub fn is_on_time(&self, tolerance: Duration) -> bool {
+        let due = Duration::from_nanos(self.due.load(Ordering::SeqCst));
+        ClockSource::Monotonic.reading() <= due.saturating_add(tolerance)
+    }
+}
+
+/// Where the timer's thread last went idle, waiting on its clock, for
+/// `testing::Harness`.
+///
+/// Only recorded while holding the timer's lock, which is also held to
+/// reset or pause it, so a snapshot taken under the lock is consistent.
+///
+#[cfg(any(test, feature = "testing"))]
+#[derive(Debug, Default)]
+pub struct Idle {
+    // Clock reading in nanoseconds, plus one, or zero since the timer's
+    // thread was last spawned without going idle.
+    at: AtomicU64,
+    // Resets and pauses the thread had seen.
+    resets: AtomicUsize,
+    pauses: AtomicUsize,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl Idle {
+    /// Record going idle at clock reading `now`, having seen `resets`
+    /// resets and `pauses` pauses.
+    ///
+    pub fn record(&self, now: Duration, resets: usize, pauses: usize) {
+        self.resets.store(resets, Ordering::SeqCst);
+        self.pauses.store(pauses, Ordering::SeqCst);
+        self.at.store(now.as_nanos() as u64 + 1, Ordering::SeqCst);
+    }
+    /// Forget the last time the thread went idle.
+    ///
+    pub fn clear(&self) {
+        self.at.store(0, Ordering::SeqCst);
+    }
+    /// True if the thread went idle at `now` having seen `resets` and
+    /// `pauses`.
+    ///
+    pub fn is(&self, now: Duration, resets: usize, pauses: usize) -> bool {
+        self.at.load(Ordering::SeqCst) == now.as_nanos() as u64 + 1
+            && self.resets.load(Ordering::SeqCst) == resets
+            && self.pauses.load(Ordering::SeqCst) == pauses
+    }
+}
+
+impl Timer {
+    /// True unless the timer thread is wedged or starved.
+    ///
+    /// Each time the timer thread goes to sleep it says when it'll wake up,
+    /// so a long step isn't mistaken for a wedged thread. The timer is
+    /// unhealthy once its thread is more than `tolerance` late waking up,
+    /// whether it's starved of CPU or stuck in an inline callback. A timer
+    /// that isn't running is always healthy.
+    ///
+    pub fn is_healthy(&self, tolerance: Duration) -> bool {
+        !self.lifecycle.is_running() || self.heartbeat.is_on_time(tolerance)
+    }
+}
+
+#[test]
+fn timer_is_healthy() {
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(Duration::from_secs(60), ms(0), Arc::new(Condvar::new()));
+    assert!(t.is_healthy(ms(0)));
+    t.start();
+    std::thread::sleep(ms(50));
+    assert!(t.is_healthy(ms(20)));
+    t.halt();
+    let mut t = Timer::new(ms(10), ms(0), Arc::new(Condvar::new()));
+    t.on_expiry(move || std::thread::sleep(ms(200)));
+    t.start();
+    std::thread::sleep(ms(100));
+    assert!(!t.is_healthy(ms(20)));
+    t.stop();
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+use std::time::Duration;
+use Timer;
+
+/// The time left in a count down, displayed like "4m 32s".
+///
+/// Rounded up to whole seconds, so it reads "1s" rather than "0s" until the
+/// timer actually expires, and formatted by the same rules durations are
+/// parsed by, so what's displayed can be read back in.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RemainingDisplay(Duration);
+
+impl RemainingDisplay {
+    /// The exact time left.
+    ///
+    pub fn duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl fmt::Display for RemainingDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.0.as_secs() + u64::from(self.0.subsec_nanos() > 0);
+        write!(f, "{}", humantime::format_duration(Duration::from_secs(secs)))
+    }
+}
+
+impl Timer {
+    /// The time left in the current count down, for display.
+    ///
+    pub fn remaining_display(&self) -> RemainingDisplay {
+        RemainingDisplay(self.remaining())
+    }
+    /// The time left in the current count down, like "4m 32s".
+    ///
+    pub fn remaining_human(&self) -> String {
+        self.remaining_display().to_string()
+    }
+}
+
+#[test]
+fn remaining_human() {
+    use std::sync::{Arc, Condvar};
+    assert_eq!(RemainingDisplay(Duration::from_millis(271_200)).to_string(), "4m 32s");
+    assert_eq!(RemainingDisplay(Duration::from_secs(3600)).to_string(), "1h");
+    let mut t = Timer::new(Duration::from_secs(90), Duration::from_secs(0), Arc::new(Condvar::new()));
+    assert_eq!(t.remaining_human(), "0s");
+    t.start();
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(t.remaining_human(), "1m 30s");
+    t.halt();
+}
@@ -0,0 +1,103 @@
+use cancel::CancellationToken;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use Timer;
+
+/// A timer that expires once a stretch of inactivity reaches `idle_for`.
+///
+/// Every `touch` records activity and restarts the count down, so the
+/// timer only expires after `idle_for` passes with no touches at all. It
+/// expires once per idle stretch, and the next touch arms it again. This is
+/// the pattern behind session timeouts and screensavers.
+///
+pub struct IdleTimer {
+    timer: Timer,
+    // When activity was last recorded.
+    last_active: Arc<Mutex<Instant>>,
+    // Cancelled on drop to stop the timer without waiting out the count down.
+    token: CancellationToken,
+}
+
+impl IdleTimer {
+    /// Create a new idle timer, counting down from now.
+    ///
+    /// # Arguments
+    ///
+    /// * `idle_for` - How long without activity before expiring.
+    /// * `timed_out` - Condition to signal when the timer expires.
+    ///
+    pub fn new(idle_for: Duration, timed_out: Arc<Condvar>) -> IdleTimer {
+        let token = CancellationToken::new();
+        let mut timer = Timer::new(idle_for, Duration::from_secs(0), timed_out)
+            .with_cancel(token.clone());
+        timer.set_max_expiries(1);
+        timer.start();
+        IdleTimer {
+            timer,
+            last_active: Arc::new(Mutex::new(Instant::now())),
+            token,
+        }
+    }
+    /// Record activity, restarting the count down.
+    ///
+    pub fn touch(&mut self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+        if self.timer.lifecycle.is_running() {
+            self.timer.reset();
+        } else {
+            // Already expired for the last idle stretch, so arm it again.
+            self.timer.stop();
+            self.timer.start();
+        }
+    }
+    /// How long since activity was last recorded.
+    ///
+    pub fn idle_duration(&self) -> Duration {
+        self.last_active.lock().unwrap().elapsed()
+    }
+    /// True if the timer has expired since the last touch.
+    ///
+    pub fn is_idle(&self) -> bool {
+        !self.timer.lifecycle.is_running()
+    }
+    /// Number of idle stretches that have reached `idle_for`.
+    ///
+    pub fn expiries(&self) -> usize {
+        self.timer.expiries.load(Ordering::SeqCst)
+    }
+    /// Register a callback to run each time the timer expires.
+    ///
+    pub fn on_idle<F>(&mut self, f: F)
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.timer.on_expiry(f);
+    }
+}
+
+impl Drop for IdleTimer {
+    fn drop(&mut self) {
+        self.token.cancel();
+        self.timer.stop();
+    }
+}
+
+#[test]
+fn idle_timer_resets_on_touch() {
+    let ms = Duration::from_millis;
+    let mut idle = IdleTimer::new(ms(50), Arc::new(Condvar::new()));
+    for _ in 0..4 {
+        std::thread::sleep(ms(20));
+        idle.touch();
+    }
+    assert!(idle.idle_duration() < ms(20));
+    assert!(!idle.is_idle());
+    std::thread::sleep(ms(100));
+    // Fires once per idle stretch, not once per `idle_for`...
+    assert!(idle.is_idle());
+    assert_eq!(idle.expiries(), 1);
+    assert!(idle.idle_duration() >= ms(100));
+    idle.touch();
+    std::thread::sleep(ms(100));
+    assert_eq!(idle.expiries(), 2);
+}
@@ -0,0 +1,125 @@
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+/// What an `Interval` does when `tick` is called after one or more deadlines
+/// have already passed.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// Return the missed ticks immediately, one per call, until caught up.
+    #[default]
+    Burst,
+    /// Start counting the period again from the late tick.
+    Delay,
+    /// Drop the missed ticks and wait for the next deadline on the original
+    /// schedule.
+    Skip,
+}
+
+/// A blocking, thread free periodic schedule.
+///
+/// Each call to `tick` parks the calling thread until the next deadline and
+/// returns it. Deadlines are aligned to the first one, so time spent between
+/// calls doesn't accumulate into drift.
+///
+#[derive(Debug)]
+pub struct Interval {
+    // The time between deadlines.
+    period: Duration,
+    // The deadline the next call to `tick` waits for.
+    next: Instant,
+    // What to do when deadlines are missed.
+    missed: MissedTickBehavior,
+}
+
+impl Interval {
+    /// Create a new interval whose first tick completes immediately.
+    ///
+    pub fn new(period: Duration) -> Interval {
+        Interval::new_at(Instant::now(), period)
+    }
+    /// Create a new interval whose first tick completes at `start`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    ///
+    pub fn new_at(start: Instant, period: Duration) -> Interval {
+        assert!(period > Duration::from_secs(0), "Interval period must be non-zero!");
+        Interval {
+            period,
+            next: start,
+            missed: MissedTickBehavior::default(),
+        }
+    }
+    /// The time between deadlines.
+    ///
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+    /// Choose what happens when deadlines are missed.
+    ///
+    pub fn set_missed_tick_behavior(&mut self, missed: MissedTickBehavior) {
+        self.missed = missed;
+    }
+    /// Restart the schedule so the next deadline is one period from now.
+    ///
+    pub fn reset(&mut self) {
+        self.next = Instant::now() + self.period;
+    }
+    /// Park until the next deadline, and return it.
+    ///
+    pub fn tick(&mut self) -> Instant {
+        let target = self.next;
+        let mut now = Instant::now();
+        while now < target {
+            std::thread::sleep(target - now);
+            now = Instant::now();
+        }
+        self.next = if now >= target + self.period {
+            match self.missed {
+                MissedTickBehavior::Burst => target + self.period,
+                MissedTickBehavior::Delay => now + self.period,
+                MissedTickBehavior::Skip => {
+                    let missed = (now - target).as_nanos() / self.period.as_nanos();
+                    u32::try_from(missed + 1).ok()
+                        .and_then(|n| self.period.checked_mul(n))
+                        .and_then(|ahead| target.checked_add(ahead))
+                        .unwrap_or(now + self.period)
+                },
+            }
+        } else {
+            target + self.period
+        };
+        target
+    }
+}
+
+#[test]
+fn interval_ticks_are_aligned() {
+    let period = Duration::from_millis(20);
+    let mut interval = Interval::new(period);
+    let first = interval.tick();
+    assert!(first.elapsed() < period);
+    let second = interval.tick();
+    assert_eq!(second - first, period);
+    std::thread::sleep(Duration::from_millis(5));
+    assert_eq!(interval.tick() - second, period);
+}
+
+#[test]
+fn interval_missed_ticks() {
+    let period = Duration::from_millis(20);
+    let mut burst = Interval::new(period);
+    let mut skip = Interval::new(period);
+    skip.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let start = burst.tick();
+    let skip_start = skip.tick();
+    std::thread::sleep(Duration::from_millis(70));
+    // Burst hands back every missed deadline...
+    assert_eq!(burst.tick(), start + period);
+    assert_eq!(burst.tick(), start + period * 2);
+    // ...while Skip jumps to the next one still ahead.
+    assert_eq!(skip.tick(), skip_start + period);
+    assert_eq!(skip.tick(), skip_start + period * 4);
+}
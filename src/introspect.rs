@@ -0,0 +1,147 @@
+use callback::{Dispatch, OverlapPolicy};
+use clock::{ClockSource, Timestamp};
+use config::JitterPolicy;
+use ledger::TimerId;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use suspend::SuspendPolicy;
+use Timer;
+
+/// Whether a timer is counting down.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerState {
+    /// Never started, or stopped with `stop`.
+    Stopped,
+    /// Counting down.
+    Running,
+    /// Running, but with the count down paused.
+    Paused,
+    /// Started, but stopped counting down of its own accord: its schedule
+    /// ran out, it reached its maximum expiries, or it was cancelled.
+    Finished,
+}
+
+/// What decides a timer's next deadline.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeadlineSource {
+    /// A one-off wall clock time set with `fire_at`.
+    FireAt,
+    /// A wall clock schedule set with `set_wall_schedule`.
+    WallSchedule,
+    /// A schedule set with `set_schedule`.
+    Schedule,
+    /// The timer's own step and jitter.
+    Step,
+}
+
+/// A snapshot of a timer's internal state, for debugging.
+///
+/// Print it with `{:#?}` to answer "why didn't my timer fire" without a
+/// debugger.
+///
+#[derive(Clone, Debug)]
+pub struct TimerIntrospection {
+    /// The timer's id.
+    pub id: TimerId,
+    /// Whether the timer is counting down.
+    pub state: TimerState,
+    /// The configured step.
+    pub step: Duration,
+    /// The configured jitter.
+    pub jitter: Duration,
+    /// How the jitter is applied.
+    pub jitter_policy: JitterPolicy,
+    /// What decides the next deadline.
+    pub source: DeadlineSource,
+    /// The deadline currently being counted down to, if running and not
+    /// paused.
+    pub deadline: Option<Timestamp>,
+    /// Number of times the count down has been restarted, by `reset` or
+    /// by changing the schedule.
+    pub generation: usize,
+    /// Number of times the timer has expired.
+    pub expiries: usize,
+    /// Number of expiries after which the timer stops, if any.
+    pub max_expiries: Option<usize>,
+    /// The clock deadlines are computed against.
+    pub clock: ClockSource,
+    /// Where callbacks run.
+    pub dispatch: Dispatch,
+    /// What happens when a callback overlaps the next expiry.
+    pub overlap: OverlapPolicy,
+    /// Number of callbacks registered.
+    pub callbacks: usize,
+    /// How time spent suspended is treated.
+    pub suspend_policy: SuspendPolicy,
+    /// Measured wait overshoot compensated for, if calibrated.
+    pub calibration: Option<Duration>,
+}
+
+impl Timer {
+    /// Take a snapshot of the timer's internal state.
+    ///
+    pub fn introspect(&self) -> TimerIntrospection {
+        let state = if self.lifecycle.is_running() {
+            if self.is_paused() { TimerState::Paused } else { TimerState::Running }
+        } else if self.handle.is_some() {
+            TimerState::Finished
+        } else {
+            TimerState::Stopped
+        };
+        let source = if self.fire_at.lock().unwrap().is_some() {
+            DeadlineSource::FireAt
+        } else if self.schedule.lock().unwrap().is_some() {
+            DeadlineSource::WallSchedule
+        } else if self.intervals.lock().unwrap().is_some() {
+            DeadlineSource::Schedule
+        } else {
+            DeadlineSource::Step
+        };
+        TimerIntrospection {
+            id: self.id,
+            state,
+            step: self.step(),
+            jitter: self.jitter(),
+            jitter_policy: self.jitter_policy(),
+            source,
+            deadline: self.countdown.lock().unwrap().due().map(|deadline| self.clock.stamp(deadline)),
+            generation: self.resets.load(Ordering::SeqCst),
+            expiries: self.expiries.load(Ordering::SeqCst),
+            max_expiries: self.max_expiries,
+            clock: self.clock.clone(),
+            dispatch: self.dispatch,
+            overlap: self.overlap,
+            callbacks: self.callbacks.count(),
+            suspend_policy: self.suspend_policy,
+            calibration: self.calibration,
+        }
+    }
+}
+
+#[test]
+fn timer_introspect() {
+    use std::sync::{Arc, Condvar};
+    let mut t = Timer::new(Duration::from_millis(20), Duration::from_secs(0), Arc::new(Condvar::new()));
+    t.set_max_expiries(1);
+    t.on_expiry(|| {});
+    let snapshot = t.introspect();
+    assert_eq!(snapshot.state, TimerState::Stopped);
+    assert_eq!(snapshot.source, DeadlineSource::Step);
+    assert_eq!(snapshot.deadline, None);
+    assert_eq!(snapshot.callbacks, 1);
+    t.start();
+    std::thread::sleep(Duration::from_millis(5));
+    let snapshot = t.introspect();
+    assert_eq!(snapshot.state, TimerState::Running);
+    assert!(snapshot.deadline.is_some());
+    assert!(format!("{:#?}", snapshot).contains("deadline: Some("));
+    std::thread::sleep(Duration::from_millis(40));
+    let snapshot = t.introspect();
+    assert_eq!(snapshot.state, TimerState::Finished);
+    assert_eq!(snapshot.expiries, 1);
+    assert_eq!(snapshot.deadline, None);
+    t.stop();
+    assert_eq!(t.introspect().state, TimerState::Stopped);
+}
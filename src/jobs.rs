@@ -0,0 +1,343 @@
+use schedule::{Schedule, TickContext};
+use state::{JobState, StateStore};
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+use thread::ThreadConfig;
+use Timer;
+
+/// What a job has done so far, and what it'll do next.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JobStatus {
+    /// True unless the job has been disabled.
+    pub enabled: bool,
+    /// Number of times the job has run, including triggered runs.
+    pub runs: usize,
+    /// When the job last started running, if it has.
+    pub last_run: Option<SystemTime>,
+    /// When the job is next scheduled to run, if it's enabled and its
+    /// schedule hasn't run out.
+    pub next_run: Option<SystemTime>,
+    /// True if the state store showed the job falling due while the
+    /// process was down.
+    pub missed: bool,
+}
+
+impl JobStatus {
+    fn state(&self) -> JobState {
+        JobState { next_run: self.next_run, runs: self.runs }
+    }
+}
+
+/// A state store shared between every job.
+type Store = Arc<Mutex<Box<dyn StateStore>>>;
+
+/// Where, if anywhere, a job's state is saved.
+///
+#[derive(Clone)]
+struct Persist {
+    name: String,
+    store: Option<Store>,
+}
+
+impl Persist {
+    fn save(&self, status: &JobStatus) {
+        if let Some(ref store) = self.store {
+            if let Err(e) = store.lock().unwrap().save(&self.name, &status.state()) {
+                println!("Error: {}", e);
+            }
+        }
+    }
+}
+
+/// A job's body, shared between its timer and `trigger`.
+type Body = Arc<dyn Fn() + Send + Sync>;
+
+/// Wraps a job's schedule to record when it's due next.
+///
+struct Tracked<S> {
+    schedule: S,
+    status: Arc<Mutex<JobStatus>>,
+    persist: Persist,
+}
+
+impl<S: Schedule> Schedule for Tracked<S> {
+    fn next_interval(&mut self, ctx: &TickContext) -> Option<Duration> {
+        let interval = self.schedule.next_interval(ctx);
+        let mut status = self.status.lock().unwrap();
+        status.next_run = interval.and_then(|d| ctx.now.checked_add(d));
+        self.persist.save(&status);
+        interval
+    }
+    fn restart(&mut self) {
+        self.schedule.restart();
+    }
+}
+
+/// A registered job.
+///
+struct Job {
+    timer: Timer,
+    body: Body,
+    status: Arc<Mutex<JobStatus>>,
+    persist: Persist,
+}
+
+/// Run `body` as a job, keeping its status up to date.
+///
+fn run(body: &Body, status: &Mutex<JobStatus>, persist: &Persist) {
+    {
+        let mut status = status.lock().unwrap();
+        status.runs += 1;
+        status.last_run = Some(SystemTime::now());
+        persist.save(&status);
+    }
+    body();
+}
+
+/// Runs named jobs on their own schedules.
+///
+/// Each job gets a timer of its own and runs on that timer's thread, so a
+/// slow job only ever delays itself. Jobs can be disabled, enabled again,
+/// and triggered by hand at any time.
+///
+/// With a state store, each job's next run time and run count survive
+/// restarts: a re-added job picks up its count and resumes counting down
+/// to its saved next run.
+///
+#[derive(Default)]
+pub struct JobScheduler {
+    jobs: HashMap<String, Job>,
+    // Where job state is saved, if anywhere.
+    store: Option<Store>,
+    // True if jobs that fell due while the process was down run as soon as
+    // they're added.
+    run_missed: bool,
+    // How to spawn each job's timer thread.
+    threads: ThreadConfig,
+}
+
+impl JobScheduler {
+    /// Create a new scheduler with no jobs.
+    ///
+    pub fn new() -> JobScheduler {
+        JobScheduler::default()
+    }
+    /// Create a new scheduler with no jobs that saves their state to
+    /// `store`.
+    ///
+    pub fn with_store<T: StateStore + 'static>(store: T) -> JobScheduler {
+        JobScheduler {
+            jobs: HashMap::new(),
+            store: Some(Arc::new(Mutex::new(Box::new(store)))),
+            run_missed: false,
+            threads: ThreadConfig::default(),
+        }
+    }
+    /// Choose whether jobs that fell due while the process was down run as
+    /// soon as they're added, rather than waiting for their next run.
+    ///
+    /// Either way, they're reported as `missed` in their status.
+    ///
+    pub fn set_run_missed(&mut self, run_missed: bool) {
+        self.run_missed = run_missed;
+    }
+    /// Choose how the threads of jobs added from now on are spawned.
+    ///
+    pub fn set_thread_config(&mut self, threads: ThreadConfig) {
+        self.threads = threads;
+    }
+    /// Register a job and start running it on `schedule`.
+    ///
+    /// Replaces any job already registered under `name`. If a job of the
+    /// same name was saved to the state store, its run count is restored
+    /// and it runs next at its saved next run time.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name to refer to the job by.
+    /// * `schedule` - When to run the job.
+    /// * `f` - The job itself.
+    ///
+    pub fn add<S, F>(&mut self, name: &str, schedule: S, f: F)
+        where S: Schedule + Send + 'static,
+              F: Fn() + Send + Sync + 'static
+    {
+        self.remove(name);
+        let persist = Persist { name: name.to_string(), store: self.store.clone() };
+        let saved = match self.store {
+            Some(ref store) => store.lock().unwrap().load(name).unwrap_or_else(|e| {
+                println!("Error: {}", e);
+                None
+            }),
+            None => None,
+        };
+        let saved = saved.unwrap_or_default();
+        let now = SystemTime::now();
+        let missed = saved.next_run.is_some_and(|at| at <= now);
+        let status = Arc::new(Mutex::new(JobStatus {
+            enabled: true,
+            runs: saved.runs,
+            last_run: None,
+            next_run: None,
+            missed,
+        }));
+        let body: Body = Arc::new(f);
+        let mut timer = Timer::new(Duration::from_secs(0), Duration::from_secs(0), Arc::new(Condvar::new()));
+        timer.set_thread_config(self.threads.clone());
+        timer.set_schedule(Tracked { schedule, status: status.clone(), persist: persist.clone() });
+        match saved.next_run {
+            Some(at) if !missed || self.run_missed => {
+                let at = if missed { now } else { at };
+                status.lock().unwrap().next_run = Some(at);
+                timer.fire_at(at);
+            },
+            _ => {},
+        }
+        let (b, s, p) = (body.clone(), status.clone(), persist.clone());
+        timer.on_expiry(move || run(&b, &s, &p));
+        timer.start();
+        self.jobs.insert(name.to_string(), Job { timer, body, status, persist });
+    }
+    /// Stop and forget a job, returning false if there's no such job.
+    ///
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.jobs.remove(name) {
+            Some(mut job) => {
+                if job.timer.handle.is_some() {
+                    job.timer.halt();
+                }
+                true
+            },
+            None => false,
+        }
+    }
+    /// Resume running a disabled job on its schedule, returning false if
+    /// there's no such job.
+    ///
+    pub fn enable(&mut self, name: &str) -> bool {
+        let job = match self.jobs.get_mut(name) {
+            Some(job) => job,
+            None => return false,
+        };
+        let mut status = job.status.lock().unwrap();
+        if !status.enabled {
+            status.enabled = true;
+            drop(status);
+            job.timer.start();
+        }
+        true
+    }
+    /// Stop running a job on its schedule, returning false if there's no
+    /// such job.
+    ///
+    /// A disabled job can still be triggered by hand.
+    ///
+    pub fn disable(&mut self, name: &str) -> bool {
+        let job = match self.jobs.get_mut(name) {
+            Some(job) => job,
+            None => return false,
+        };
+        let mut status = job.status.lock().unwrap();
+        if status.enabled {
+            status.enabled = false;
+            status.next_run = None;
+            job.persist.save(&status);
+            drop(status);
+            job.timer.halt();
+        }
+        true
+    }
+    /// Run a job right away on the calling thread, outside of its schedule,
+    /// returning false if there's no such job.
+    ///
+    pub fn trigger(&self, name: &str) -> bool {
+        match self.jobs.get(name) {
+            Some(job) => {
+                run(&job.body, &job.status, &job.persist);
+                true
+            },
+            None => false,
+        }
+    }
+    /// The status of a job, or `None` if there's no such job.
+    ///
+    pub fn status(&self, name: &str) -> Option<JobStatus> {
+        self.jobs.get(name).map(|job| *job.status.lock().unwrap())
+    }
+    /// Names of every registered job, in no particular order.
+    ///
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.jobs.keys().map(|name| name.as_str())
+    }
+}
+
+impl Drop for JobScheduler {
+    fn drop(&mut self) {
+        for job in self.jobs.values_mut() {
+            if job.timer.handle.is_some() {
+                job.timer.halt();
+            }
+        }
+    }
+}
+
+#[test]
+fn job_scheduler_runs_jobs() {
+    use schedule::FixedStep;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let ms = Duration::from_millis;
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let mut jobs = JobScheduler::new();
+    jobs.add("tick", FixedStep::new(ms(10)), move || { c.fetch_add(1, Ordering::SeqCst); });
+    std::thread::sleep(ms(55));
+    let status = jobs.status("tick").unwrap();
+    assert!(status.runs >= 3);
+    assert!(status.last_run.is_some());
+    assert!(status.next_run.is_some());
+    assert!(jobs.disable("tick"));
+    let runs = count.load(Ordering::SeqCst);
+    std::thread::sleep(ms(30));
+    assert_eq!(count.load(Ordering::SeqCst), runs);
+    assert_eq!(jobs.status("tick").unwrap().next_run, None);
+    assert!(jobs.trigger("tick"));
+    assert_eq!(jobs.status("tick").unwrap().runs, runs + 1);
+    assert!(jobs.enable("tick"));
+    std::thread::sleep(ms(30));
+    assert!(count.load(Ordering::SeqCst) > runs + 1);
+    assert!(!jobs.trigger("missing"));
+    assert!(jobs.remove("tick"));
+    assert_eq!(jobs.names().count(), 0);
+}
+
+#[test]
+fn job_scheduler_restores_state() {
+    use schedule::FixedStep;
+    use state::FileStateStore;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let ms = Duration::from_millis;
+    let path = std::env::temp_dir().join(format!("timer-job-scheduler-{}.json", std::process::id()));
+    let count = Arc::new(AtomicUsize::new(0));
+    let mut jobs = JobScheduler::with_store(FileStateStore::new(&path));
+    let c = count.clone();
+    jobs.add("backup", FixedStep::new(ms(20)), move || { c.fetch_add(1, Ordering::SeqCst); });
+    std::thread::sleep(ms(50));
+    drop(jobs);
+    let runs = count.load(Ordering::SeqCst);
+    assert!(runs >= 2);
+    // Down for longer than the step, so the saved next run is missed...
+    std::thread::sleep(ms(30));
+    let mut jobs = JobScheduler::with_store(FileStateStore::new(&path));
+    jobs.set_run_missed(true);
+    let c = count.clone();
+    jobs.add("backup", FixedStep::new(ms(1000)), move || { c.fetch_add(1, Ordering::SeqCst); });
+    std::thread::sleep(ms(20));
+    let status = jobs.status("backup").unwrap();
+    assert!(status.missed);
+    assert_eq!(status.runs, runs + 1);
+    assert_eq!(count.load(Ordering::SeqCst), runs + 1);
+    drop(jobs);
+    std::fs::remove_file(&path).unwrap();
+}
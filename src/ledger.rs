@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Identifies a timer, unique within the process.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimerId(u64);
+
+impl TimerId {
+    /// Allocate the next unused id.
+    ///
+    pub fn next() -> TimerId {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        TimerId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for TimerId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "timer-{}", self.0)
+    }
+}
+
+/// Records which of many timers sharing one condition variable expired.
+///
+/// Timers given a ledger with `Timer::set_ledger` count each expiry in it
+/// before signalling their condition, so a waiter woken by the shared
+/// condition can find out which timers fired, and how often. Wait with
+/// `wait` or `wait_timeout` rather than on the condition directly, so that
+/// no expiry slips in between checking the ledger and waiting. Clones share
+/// the same ledger.
+///
+#[derive(Clone, Default)]
+pub struct ExpiryLedger {
+    pending: Arc<Mutex<HashMap<TimerId, usize>>>,
+}
+
+impl ExpiryLedger {
+    /// Create a new, empty ledger.
+    ///
+    pub fn new() -> ExpiryLedger {
+        ExpiryLedger::default()
+    }
+    /// Count an expiry of timer `id` and wake every waiter on `timed_out`.
+    ///
+    pub fn record(&self, id: TimerId, timed_out: &Condvar) {
+        *self.pending.lock().unwrap().entry(id).or_insert(0) += 1;
+        timed_out.notify_all();
+    }
+    /// Take every timer's pending expiries, leaving the ledger empty.
+    ///
+    pub fn drain(&self) -> Vec<(TimerId, usize)> {
+        self.pending.lock().unwrap().drain().collect()
+    }
+    /// Take the pending expiries of timer `id`.
+    ///
+    pub fn take(&self, id: TimerId) -> usize {
+        self.pending.lock().unwrap().remove(&id).unwrap_or(0)
+    }
+    /// Wait on `timed_out` until some timer has a pending expiry, then
+    /// drain them all.
+    ///
+    pub fn wait(&self, timed_out: &Condvar) -> Vec<(TimerId, usize)> {
+        let mut pending = self.pending.lock().unwrap();
+        while pending.is_empty() {
+            pending = timed_out.wait(pending).unwrap();
+        }
+        pending.drain().collect()
+    }
+    /// Like `wait`, but gives up after `timeout`, returning whatever is
+    /// pending by then.
+    ///
+    pub fn wait_timeout(&self, timed_out: &Condvar, timeout: Duration) -> Vec<(TimerId, usize)> {
+        let deadline = Instant::now() + timeout;
+        let mut pending = self.pending.lock().unwrap();
+        while pending.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            pending = timed_out.wait_timeout(pending, deadline - now).unwrap().0;
+        }
+        pending.drain().collect()
+    }
+}
+
+#[test]
+fn ledger_tags_shared_wakeups() {
+    use Timer;
+    let ms = Duration::from_millis;
+    let cv = Arc::new(Condvar::new());
+    let ledger = ExpiryLedger::new();
+    let mut fast = Timer::new(ms(10), ms(0), cv.clone());
+    let mut slow = Timer::new(ms(10_000), ms(0), cv.clone());
+    assert_ne!(fast.id(), slow.id());
+    fast.set_ledger(ledger.clone());
+    slow.set_ledger(ledger.clone());
+    fast.set_max_expiries(2);
+    fast.start();
+    slow.start();
+    let mut fired = ledger.wait(&cv);
+    std::thread::sleep(ms(30));
+    fired.extend(ledger.drain());
+    let total: usize = fired.iter().map(|&(id, n)| { assert_eq!(id, fast.id()); n }).sum();
+    assert_eq!(total, 2);
+    assert!(ledger.wait_timeout(&cv, ms(20)).is_empty());
+    assert_eq!(ledger.take(slow.id()), 0);
+    fast.stop();
+    slow.halt();
+}
@@ -1,9 +1,229 @@
+#[cfg(feature = "rand")]
 extern crate rand;
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
+#[cfg(all(unix, any(feature = "eventfd", feature = "posix", feature = "realtime", feature = "suspend")))]
+extern crate libc;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "time")]
+extern crate time;
+#[cfg(all(feature = "calloop", target_os = "linux"))]
+extern crate calloop;
+#[cfg(feature = "glib")]
+extern crate glib;
+#[cfg(all(feature = "mio", target_os = "linux"))]
+extern crate mio;
+#[cfg(feature = "prometheus")]
+extern crate prometheus;
+#[cfg(feature = "humantime")]
+extern crate humantime;
+#[cfg(feature = "watch")]
+extern crate notify;
+#[cfg(feature = "watch")]
+extern crate serde_json;
+#[cfg(feature = "watch")]
+extern crate toml;
+#[cfg(feature = "async-io")]
+extern crate async_io;
+#[cfg(feature = "futures-timer")]
+extern crate futures_timer;
 
+mod accounting;
+mod align;
+#[cfg(feature = "async-io")]
+mod async_io_compat;
+#[cfg(test)]
+mod alloc_count;
+mod backoff;
+mod barrier;
+mod breaker;
+mod budget;
+mod callback;
+mod channel;
+#[cfg(all(feature = "calloop", target_os = "linux"))]
+mod calloop_compat;
+mod cancel;
+mod checkpoint;
+#[cfg(feature = "chrono")]
+mod chrono_compat;
+mod clock;
+mod coalesce;
+mod completion;
+mod config;
+mod cron;
+mod deadline;
+mod delivery;
+mod drift;
+mod event;
+#[cfg(all(feature = "eventfd", target_os = "linux"))]
+mod eventfd;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod flight;
+#[cfg(feature = "async")]
+pub mod future;
+#[cfg(feature = "futures-timer")]
+mod futures_timer_compat;
+#[cfg(feature = "glib")]
+mod glib_compat;
+mod global;
+mod health;
+#[cfg(feature = "humantime")]
+mod human;
+mod idle;
+mod interval;
+mod introspect;
+mod jobs;
+mod ledger;
+mod lifecycle;
+mod local;
+mod metrics;
+#[cfg(all(feature = "mio", target_os = "linux"))]
+mod mio_compat;
+mod pool;
+#[cfg(all(feature = "posix", target_os = "linux"))]
+mod posix;
+mod precision;
+#[cfg(all(feature = "jitter", not(feature = "rand")))]
+mod prng;
+#[cfg(feature = "prometheus")]
+mod prometheus_compat;
+mod race;
+#[cfg(all(feature = "realtime", target_os = "linux"))]
+mod realtime;
+mod recurrence;
+mod retry;
+#[cfg(feature = "rrule")]
+mod rrule;
+mod schedule;
+mod scoped;
+mod session;
+mod state;
+mod stats;
+#[cfg(feature = "statsd")]
+mod statsd;
+mod suspend;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod thread;
+mod tick;
+mod trace;
+#[cfg(feature = "time")]
+mod time_compat;
+mod timer_pool;
+mod ttl;
+mod waiters;
+#[cfg(feature = "async")]
+mod waker;
+#[cfg(feature = "watch")]
+mod watch;
+mod wheel;
+
+pub use align::Alignment;
+pub use backoff::{Backoff, ConstantBackoff, ExponentialBackoff, FibonacciBackoff, LinearBackoff};
+pub use barrier::TimerBarrier;
+pub use breaker::{BreakerError, CircuitBreaker, CircuitState};
+pub use budget::{BudgetMetrics, RetryBudget};
+pub use callback::{Dispatch, OverlapPolicy, TickOutcome};
+pub use channel::{BoundedReceiver, OnFull};
+#[cfg(all(feature = "calloop", target_os = "linux"))]
+pub use calloop_compat::TimerSource;
+pub use cancel::{sleep, sleep_until, CancelRegistration, CancelToken, CancellationToken, Cancelled};
+pub use checkpoint::{Checkpoint, FileCheckpoint};
+pub use clock::{Clock, ClockJump, ClockSource, MockClock, Timestamp};
+pub use config::{ConfigError, JitterPolicy, TimerConfig, MAX_DURATION};
+pub use cron::{Cron, CronError};
+pub use deadline::{Deadline, WithDeadline};
+pub use delivery::DeliveryMode;
+pub use drift::{DriftBucket, DriftHistogram};
+pub use event::{Event, ExpiryEvent, Reconfiguration};
+#[cfg(all(feature = "eventfd", target_os = "linux"))]
+pub use eventfd::TimerFd;
+pub use flight::{FlightEntry, FlightEvent, WakeReason, FLIGHT_RECORDER_CAPACITY};
+#[cfg(feature = "async")]
+pub use future::{timeout, Elapsed, Timeout};
+#[cfg(feature = "async")]
+pub use waker::TimerTick;
+pub use global::{after, every, global};
+#[cfg(feature = "humantime")]
+pub use human::RemainingDisplay;
+pub use idle::IdleTimer;
+pub use interval::{Interval, MissedTickBehavior};
+pub use introspect::{DeadlineSource, TimerIntrospection, TimerState};
+pub use jobs::{JobScheduler, JobStatus};
+pub use ledger::{ExpiryLedger, TimerId};
+pub use lifecycle::{Phase, StartError, TransitionError};
+pub use local::{LocalTimerId, LocalTimerSet};
+pub use metrics::MetricsSink;
+pub use pool::QUEUED_PER_WORKER;
+#[cfg(all(feature = "posix", target_os = "linux"))]
+pub use posix::{Delivery, PosixTimer};
+pub use precision::Precision;
+#[cfg(all(feature = "jitter", not(feature = "rand")))]
+pub use prng::seed_jitter;
+#[cfg(all(feature = "realtime", target_os = "linux"))]
+pub use realtime::SchedPolicy;
+pub use recurrence::{Frequency, Recurrence, Weekday};
+#[cfg(feature = "async")]
+pub use retry::RetryFuture;
+pub use retry::{Retry, RetryError, RetryPolicy};
+#[cfg(feature = "rrule")]
+pub use rrule::RruleError;
+pub use schedule::{EarliestOf, FixedStep, Intervals, Ramp, Schedule, TickContext};
+pub use session::SessionTimeouts;
+pub use state::{FileStateStore, JobState, StateStore};
+pub use stats::TimerStats;
+#[cfg(feature = "statsd")]
+pub use statsd::StatsdSink;
+pub use suspend::SuspendPolicy;
+pub use thread::ThreadConfig;
+pub use timer_pool::{PooledTimer, TimerPool};
+pub use trace::{Recorder, Trace, TraceEntry, TraceError, TraceEvent};
+pub use ttl::{TimerKey, TtlScheduler};
+pub use waiters::{TickWaiter, WakePolicy};
+#[cfg(feature = "watch")]
+pub use watch::{ConfigWatcher, WatchError};
+pub use wheel::WheelConfig;
+
+use callback::Callbacks;
+use clock::JumpDetector;
+use drift::{Correction, Drift, DriftAlert};
+use completion::Completion;
+#[cfg(any(test, feature = "testing"))]
+use health::Idle;
+use health::Heartbeat;
+use lifecycle::Lifecycle;
+use waiters::Waiters;
+use accounting::Countdown;
+use coalesce::Coalescer;
+use delivery::Pending;
+use event::Subscribers;
+use tick::Ticker;
+#[cfg(feature = "async")]
+use waker::Wakers;
+use metrics::Sinks;
+use pool::ThreadPool;
+use std::any::Any;
+use std::cell::Cell;
+use std::io;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Mutex, Condvar};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A schedule of wall clock times, returning the next time to expire at
+/// after the given time.
+type WallSchedule = Box<dyn FnMut(SystemTime) -> Option<SystemTime> + Send>;
+
+/// How often a wall clock timer checks for jumps of the wall clock.
+const JUMP_POLL: Duration = Duration::from_secs(1);
+
+/// Longest single wait, so that long count downs are made of waits short
+/// enough for every platform to honour.
+const MAX_WAIT: Duration = Duration::from_secs(3600);
 
 /// A countdown timer.
 ///
@@ -23,20 +243,208 @@ pub struct Timer {
     // Internal thread handle to join on shutdown.
     handle: Option<std::thread::JoinHandle<()>>,
     // Condition variable signalled if/when timer expires.
-    pub timed_out: Arc<Condvar>,
-    // The amount of time to count down from.
-    pub step: Duration,
-    // The amount of time, if any, to randomize the count down from.
-    pub jitter: Duration,
-    // True if the timer is counting down.
-    pub alive: Arc<AtomicBool>,
-    /// Number of times this timer has expired.
-    pub expiries: Arc<AtomicUsize>,
+    timed_out: Arc<Condvar>,
+    // The amount of time to count down from, and to randomize it by.
+    pacing: Arc<Mutex<FixedStep>>,
+    // Whether the timer is counting down, starting or stopping.
+    lifecycle: Arc<Lifecycle>,
+    // Number of times this timer has expired.
+    expiries: Arc<AtomicUsize>,
+    // Callbacks to run each time the timer expires.
+    callbacks: Arc<Callbacks>,
+    // Where to run the callbacks.
+    dispatch: Dispatch,
+    // Number of callbacks dropped because the pool's queue was full.
+    dropped_callbacks: Arc<AtomicUsize>,
+    // What to do when a callback is still running at the next expiry.
+    overlap: OverlapPolicy,
+    // True if `start` should measure the platform's wait overshoot.
+    calibrate: bool,
+    // Measured wait overshoot subtracted from each count down.
+    calibration: Option<Duration>,
+    // Number of recent expiries to average overshoot over, if correcting.
+    correction: Option<usize>,
+    // Final stretch of each count down to busy-wait through, if any.
+    spin: Duration,
+    // Clock deadlines and event timestamps are computed against.
+    clock: ClockSource,
+    // Channels to deliver events to.
+    subscribers: Arc<Subscribers>,
+    // Number of times the timer has been reset.
+    resets: Arc<AtomicUsize>,
+    // Number of times the timer has been paused or resumed.
+    pauses: Arc<AtomicUsize>,
+    // Bumped whenever the time to stop at, the pacing or a schedule
+    // changes, so the timer thread only reads them again then.
+    control: Arc<AtomicUsize>,
+    // How to treat time spent with the machine suspended.
+    suspend_policy: SuspendPolicy,
+    // Smallest step of the wall clock that counts as a jump.
+    jump_threshold: Duration,
+    // Wall clock time to expire at next, instead of counting down `step`.
+    fire_at: Arc<Mutex<Option<SystemTime>>>,
+    // Wall clock schedule to follow instead of counting down `step`.
+    schedule: Arc<Mutex<Option<WallSchedule>>>,
+    // Sequence of count downs to follow instead of `step` and `jitter`.
+    intervals: Arc<Mutex<Option<Box<dyn Schedule + Send>>>>,
+    // Longest count down to follow, whatever the schedule says.
+    max_interval: Option<Duration>,
+    // Token that stops the timer when cancelled.
+    cancel: Option<CancellationToken>,
+    // The hook stopping the timer's current run when the token is
+    // cancelled, replaced each start.
+    cancel_hook: Option<CancelRegistration>,
+    // When the timer stops itself, if ever.
+    stop_at: Arc<Mutex<Option<Instant>>>,
+    // Number of expiries after which the timer stops, if any.
+    max_expiries: Option<usize>,
+    // Signalled once the timer stops after `max_expiries`.
+    completion: Arc<Completion>,
+    // Durable storage for the next deadline, if any.
+    checkpoint: Arc<Mutex<Option<Box<dyn Checkpoint>>>>,
+    // Realtime scheduling class and priority for the timer thread, if any.
+    #[cfg(all(feature = "realtime", target_os = "linux"))]
+    realtime: Option<(SchedPolicy, i32)>,
+    // Descriptors signalled on every expiry: the timer's own, if enabled,
+    // then any taken over from another timer by `race`.
+    #[cfg(all(feature = "eventfd", target_os = "linux"))]
+    eventfd: Arc<Mutex<Vec<Arc<std::os::unix::io::OwnedFd>>>>,
+    // Main context to run callbacks on, if any.
+    #[cfg(feature = "glib")]
+    main_context: Option<glib::MainContext>,
+    // Sinks to report metrics to.
+    metrics: Arc<Sinks>,
+    // The current count down, and how long it has spent paused.
+    countdown: Arc<Mutex<Countdown>>,
+    // Lateness of every expiry so far.
+    drift: Arc<Drift>,
+    // Calls back on expiries later than a threshold, if set.
+    drift_alert: Option<DriftAlert>,
+    // Lateness past which expiries are suppressed, if any.
+    stale_after: Option<Duration>,
+    // Number of expiries suppressed for being too late.
+    suppressed: Arc<AtomicUsize>,
+    // When the timer thread promised to check in again.
+    heartbeat: Arc<Heartbeat>,
+    // Identifies the timer in an expiry ledger.
+    id: TimerId,
+    // Ledger to record expiries in, if any.
+    ledger: Option<ExpiryLedger>,
+    // Who each expiry wakes.
+    wake: WakePolicy,
+    // Workers taking turns to claim expiries under `WakePolicy::One`.
+    waiters: Arc<Waiters>,
+    // Calls back periodically while counting down, if set.
+    ticker: Option<Ticker>,
+    // When the timer was first started, if it has been.
+    first_started: Option<Instant>,
+    // How expiries reach waiters: fire-and-forget notify or the pending
+    // counter.
+    delivery: DeliveryMode,
+    // Expiries not yet consumed, counted in `DeliveryMode::AtLeastOnce`.
+    pending: Arc<Pending>,
+    // Window to coalesce expiries within, if any.
+    coalesce: Option<Duration>,
+    // Wakers to wake on the next expiry.
+    #[cfg(feature = "async")]
+    wakers: Arc<Wakers>,
+    // Expiries seen by `poll_expired`.
+    #[cfg(feature = "async")]
+    polled: AtomicUsize,
+    // Where the timer's thread last went idle, for `testing::Harness`.
+    #[cfg(any(test, feature = "testing"))]
+    idle: Arc<Idle>,
+    // How to spawn the timer's thread and callback pool.
+    threads: ThreadConfig,
+}
+
+/// Internal state moved onto the timer thread.
+///
+/// The settings a timer's thread reads again only when `control` is
+/// bumped, rather than locking them on every tick.
+///
+#[derive(Clone, Copy)]
+struct Controlled {
+    // Value of `control` they were read at.
+    seen: usize,
+    stop_at: Option<Instant>,
+    pacing: FixedStep,
+    // True if a deadline, wall clock schedule or schedule of count downs
+    // may be set.
+    scheduled: bool,
+}
+
+/// Mirrors the fields of `Timer` that the timer loop needs.
+///
+struct Worker<'a> {
+    lifecycle: Arc<Lifecycle>,
+    cv: Arc<Condvar>,
+    m: Arc<Mutex<bool>>,
+    timed_out: Arc<Condvar>,
+    expiries: Arc<AtomicUsize>,
+    callbacks: Arc<Callbacks>,
+    // Pool to run callbacks on, or `None` to run them inline.
+    pool: Option<ThreadPool>,
+    dropped_callbacks: Arc<AtomicUsize>,
+    overlap: OverlapPolicy,
+    // Measured wait overshoot to compensate for.
+    bias: Duration,
+    // Recent overshoots to update `bias` from, if correcting.
+    correction: Option<Correction>,
+    // Final stretch of each count down to busy-wait through, if any.
+    spin: Duration,
+    // How late the last wait before busy-waiting woke up, if it did.
+    overslept: Cell<Option<Duration>>,
+    clock: ClockSource,
+    subscribers: Arc<Subscribers>,
+    resets: Arc<AtomicUsize>,
+    pauses: Arc<AtomicUsize>,
+    control: Arc<AtomicUsize>,
+    // What the timer thread last read under `control`, if anything.
+    controlled: Cell<Option<Controlled>>,
+    suspend_policy: SuspendPolicy,
+    jump_threshold: Duration,
+    fire_at: Arc<Mutex<Option<SystemTime>>>,
+    schedule: Arc<Mutex<Option<WallSchedule>>>,
+    pacing: Arc<Mutex<FixedStep>>,
+    intervals: Arc<Mutex<Option<Box<dyn Schedule + Send>>>>,
+    max_interval: Option<Duration>,
+    stop_at: Arc<Mutex<Option<Instant>>>,
+    max_expiries: Option<usize>,
+    completion: Arc<Completion>,
+    checkpoint: Arc<Mutex<Option<Box<dyn Checkpoint>>>>,
+    #[cfg(all(feature = "eventfd", target_os = "linux"))]
+    eventfd: Arc<Mutex<Vec<Arc<std::os::unix::io::OwnedFd>>>>,
+    // Main context to run callbacks on instead, if any.
+    #[cfg(feature = "glib")]
+    main_context: Option<glib::MainContext>,
+    metrics: Arc<Sinks>,
+    countdown: Arc<Mutex<Countdown>>,
+    drift: Arc<Drift>,
+    drift_alert: Option<DriftAlert>,
+    stale_after: Option<Duration>,
+    suppressed: Arc<AtomicUsize>,
+    heartbeat: Arc<Heartbeat>,
+    id: TimerId,
+    ledger: Option<ExpiryLedger>,
+    wake: WakePolicy,
+    waiters: Arc<Waiters>,
+    ticker: Option<Ticker>,
+    pending: Option<Arc<Pending>>,
+    coalesce: Option<Mutex<Coalescer>>,
+    #[cfg(feature = "async")]
+    wakers: Arc<Wakers>,
+    // Callback borrowing from a thread scope, if started in one.
+    borrowed: Option<Mutex<Box<dyn FnMut() + Send + 'a>>>,
+    #[cfg(any(test, feature = "testing"))]
+    idle: Arc<Idle>,
 }
 
 impl Timer {
     /// Create a new timer.
     ///
+    /// Doesn't check its arguments; prefer `from_config`.
+    ///
     /// # Arguments
     ///
     /// * `step` - The duration of time to wait for each count down.
@@ -44,88 +452,1139 @@ impl Timer {
     /// * `timed_out` - Condition to signal if the timer expires.
     ///
     pub fn new(step: Duration, jitter: Duration, timed_out: Arc<Condvar>) -> Timer {
+        Timer::with_clock(step, jitter, timed_out, ClockSource::Monotonic)
+    }
+    /// Create a new timer that counts down against the given clock.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The duration of time to wait for each count down.
+    /// * `jitter` - The duration of time to randomize each count down.
+    /// * `timed_out` - Condition to signal if the timer expires.
+    /// * `clock` - Clock to compute deadlines and event timestamps against.
+    ///
+    pub fn with_clock(step: Duration,
+                      jitter: Duration,
+                      timed_out: Arc<Condvar>,
+                      clock: ClockSource) -> Timer {
         Timer {
             handle: None,
-            alive: Arc::new(AtomicBool::new(false)),
+            lifecycle: Arc::new(Lifecycle::default()),
             cv: Arc::new(Condvar::new()),
             m: Arc::new(Mutex::new(false)),
-            timed_out: timed_out,
-            step: step,
-            jitter: jitter,
+            timed_out,
+            pacing: Arc::new(Mutex::new(FixedStep::new(step).with_jitter(jitter, JitterPolicy::default()))),
             expiries: Arc::new(AtomicUsize::new(0)),
+            callbacks: Arc::new(Callbacks::default()),
+            dispatch: Dispatch::Inline,
+            dropped_callbacks: Arc::new(AtomicUsize::new(0)),
+            overlap: OverlapPolicy::default(),
+            calibrate: false,
+            calibration: None,
+            correction: None,
+            spin: Duration::from_secs(0),
+            clock,
+            subscribers: Arc::new(Subscribers::default()),
+            resets: Arc::new(AtomicUsize::new(0)),
+            pauses: Arc::new(AtomicUsize::new(0)),
+            control: Arc::new(AtomicUsize::new(0)),
+            suspend_policy: SuspendPolicy::default(),
+            jump_threshold: Duration::from_secs(1),
+            fire_at: Arc::new(Mutex::new(None)),
+            schedule: Arc::new(Mutex::new(None)),
+            intervals: Arc::new(Mutex::new(None)),
+            max_interval: None,
+            cancel: None,
+            cancel_hook: None,
+            stop_at: Arc::new(Mutex::new(None)),
+            max_expiries: None,
+            completion: Arc::new(Completion::default()),
+            checkpoint: Arc::new(Mutex::new(None)),
+            #[cfg(all(feature = "realtime", target_os = "linux"))]
+            realtime: None,
+            #[cfg(all(feature = "eventfd", target_os = "linux"))]
+            eventfd: Arc::new(Mutex::new(Vec::new())),
+            #[cfg(feature = "glib")]
+            main_context: None,
+            metrics: Arc::new(Sinks::default()),
+            countdown: Arc::new(Mutex::new(Countdown::default())),
+            drift: Arc::new(Drift::default()),
+            drift_alert: None,
+            stale_after: None,
+            suppressed: Arc::new(AtomicUsize::new(0)),
+            heartbeat: Arc::new(Heartbeat::default()),
+            id: TimerId::next(),
+            ledger: None,
+            wake: WakePolicy::All,
+            waiters: Arc::new(Waiters::default()),
+            ticker: None,
+            first_started: None,
+            delivery: DeliveryMode::default(),
+            pending: Arc::new(Pending::default()),
+            coalesce: None,
+            #[cfg(feature = "async")]
+            wakers: Arc::new(Wakers::default()),
+            #[cfg(feature = "async")]
+            polled: AtomicUsize::new(0),
+            #[cfg(any(test, feature = "testing"))]
+            idle: Arc::new(Idle::default()),
+            threads: ThreadConfig::default(),
         }
     }
+    /// Create a new timer from a validated config.
+    ///
+    /// This is the preferred way to build a timer, since a bad config is
+    /// reported here rather than misbehaving once the timer is running.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The timer's configuration.
+    /// * `timed_out` - Condition to signal if the timer expires.
+    ///
+    pub fn from_config(config: TimerConfig, timed_out: Arc<Condvar>) -> Result<Timer, ConfigError> {
+        config.validate()?;
+        let timer = Timer::with_clock(config.step, config.jitter, timed_out, config.clock);
+        *timer.pacing.lock().unwrap() = FixedStep::new(config.step)
+            .with_jitter(config.jitter, config.jitter_policy);
+        Ok(timer)
+    }
     /// Convert a duration to milliseconds.
     ///
     /// Annoying, right? See https://github.com/rust-lang/rfcs/issues/1545.
     ///
-    fn duration_to_millis(d: Duration) -> u64 {
-        1000 * d.as_secs() + (d.subsec_nanos() as u64 / 1000000)
+    fn truncate_to_millis(d: Duration) -> Duration {
+        d - Duration::from_nanos((d.subsec_nanos() % 1_000_000) as u64)
     }
     /// Calculate a wait time.
     ///
-    fn calculate_wait_duration(step: Duration, jitter: Duration) -> Duration {
+    #[cfg(feature = "jitter")]
+    fn calculate_wait_duration(step: Duration, jitter: Duration, policy: JitterPolicy) -> Duration {
+        #[cfg(feature = "rand")]
         let random = rand::random::<u64>();
-        let step_ms = Timer::duration_to_millis(step);
-        let jitter_ms = Timer::duration_to_millis(jitter);
+        #[cfg(not(feature = "rand"))]
+        let random = prng::random();
+        let step = Timer::truncate_to_millis(step);
+        let jitter_ms = jitter.as_millis();
         if jitter_ms > 0 {
-            return Duration::from_millis(step_ms - (random % jitter_ms))
+            // Less than `random`, so always fits back in a u64.
+            let offset = Duration::from_millis((random as u128 % jitter_ms) as u64);
+            match policy {
+                JitterPolicy::Subtractive => step.saturating_sub(offset),
+                JitterPolicy::Additive => step.saturating_add(offset),
+            }
         } else {
-            return Duration::from_millis(step_ms)
+            step
         }
     }
-    /// Internal timer loop.
+    /// Calculate a wait time, ignoring jitter, since it's compiled out.
     ///
-    fn spin(alive: Arc<AtomicBool>,
-            cv: Arc<Condvar>,
-            m: Arc<Mutex<bool>>,
-            timed_out: Arc<Condvar>,
-            expiries: Arc<AtomicUsize>,
-            step: Duration,
-            jitter: Duration) {
-        alive.store(true, Ordering::SeqCst);
-        while alive.load(Ordering::SeqCst) {
-            let wait_duration = Timer::calculate_wait_duration(step, jitter);
-            match cv.wait_timeout(m.lock().unwrap(), wait_duration) {
-                Ok((_, result)) => {
-                    if result.timed_out() {
-                        expiries.fetch_add(1, Ordering::SeqCst);
-                        timed_out.notify_all();
-                    }
-                },
-                Err(e) => {
-                    println!("Error: {}", e);
-                }
+    #[cfg(not(feature = "jitter"))]
+    fn calculate_wait_duration(step: Duration, _jitter: Duration, _policy: JitterPolicy) -> Duration {
+        Timer::truncate_to_millis(step)
+    }
+    /// Measure how much a timed wait overshoots its timeout.
+    ///
+    /// Takes a handful of short samples and returns their average overshoot,
+    /// e.g., close to 15ms on platforms with a coarse scheduler quantum.
+    ///
+    fn measure_overshoot() -> Duration {
+        const SAMPLES: u32 = 5;
+        let requested = Duration::from_millis(1);
+        let cv = Condvar::new();
+        let m = Mutex::new(false);
+        let mut total = Duration::from_millis(0);
+        for _ in 0..SAMPLES {
+            let started = Instant::now();
+            let _ = cv.wait_timeout(m.lock().unwrap(), requested);
+            let elapsed = started.elapsed();
+            if elapsed > requested {
+                total += elapsed - requested;
             }
         }
+        total / SAMPLES
+    }
+    /// The condition signalled each time the timer expires.
+    ///
+    pub fn timed_out(&self) -> &Arc<Condvar> {
+        &self.timed_out
+    }
+    /// The duration of time counted down from.
+    ///
+    pub fn step(&self) -> Duration {
+        self.pacing.lock().unwrap().step()
+    }
+    /// The duration of time each count down is randomized by.
+    ///
+    pub fn jitter(&self) -> Duration {
+        self.pacing.lock().unwrap().jitter()
+    }
+    /// How `jitter` is applied to `step`.
+    ///
+    pub fn jitter_policy(&self) -> JitterPolicy {
+        self.pacing.lock().unwrap().jitter_policy()
+    }
+    /// True if the timer is counting down.
+    ///
+    pub fn is_running(&self) -> bool {
+        self.lifecycle.is_running()
+    }
+    /// Number of times the timer has expired.
+    ///
+    pub fn expiries(&self) -> usize {
+        self.expiries.load(Ordering::SeqCst)
+    }
+    /// Time left in the current count down, or zero while the timer isn't
+    /// counting down.
+    ///
+    pub fn remaining(&self) -> Duration {
+        self.countdown.lock().unwrap().remaining(self.clock.reading())
+    }
+    /// Time spent on the current count down, excluding pauses, or zero
+    /// while the timer isn't counting down.
+    ///
+    pub fn elapsed(&self) -> Duration {
+        self.countdown.lock().unwrap().elapsed(self.clock.reading())
+    }
+    /// How far through the current count down the timer is, from 0.0 as it
+    /// starts to 1.0 as it expires.
+    ///
+    /// For progress bars. Zero while the timer isn't counting down.
+    ///
+    pub fn fraction_elapsed(&self) -> f64 {
+        self.countdown.lock().unwrap().fraction_elapsed(self.clock.reading())
+    }
+    /// Validate and apply a new step, jitter and jitter policy, restarting
+    /// the current count down so they take effect right away.
+    ///
+    fn set_pacing(&mut self, step: Duration, jitter: Duration, jitter_policy: JitterPolicy)
+                  -> Result<(), ConfigError> {
+        let mut config = TimerConfig::new(step);
+        config.jitter = jitter;
+        config.jitter_policy = jitter_policy;
+        config.validate()?;
+        let _guard = self.m.lock().unwrap();
+        *self.pacing.lock().unwrap() = FixedStep::new(step).with_jitter(jitter, jitter_policy);
+        self.control.fetch_add(1, Ordering::SeqCst);
+        self.resets.fetch_add(1, Ordering::SeqCst);
+        self.cv.notify_all();
+        Ok(())
+    }
+    /// Count down from `step` instead, starting with the current count
+    /// down.
+    ///
+    /// Returns an error, changing nothing, if `step` doesn't suit the
+    /// current jitter.
+    ///
+    pub fn set_step(&mut self, step: Duration) -> Result<(), ConfigError> {
+        let (jitter, jitter_policy) = (self.jitter(), self.jitter_policy());
+        self.set_pacing(step, jitter, jitter_policy)
+    }
+    /// Randomize each count down by `jitter` instead, starting with the
+    /// current count down.
+    ///
+    /// Returns an error, changing nothing, if `jitter` doesn't suit the
+    /// current step.
+    ///
+    #[cfg(feature = "jitter")]
+    pub fn set_jitter(&mut self, jitter: Duration) -> Result<(), ConfigError> {
+        let (step, jitter_policy) = (self.step(), self.jitter_policy());
+        self.set_pacing(step, jitter, jitter_policy)
+    }
+    /// Apply jitter per `jitter_policy` instead, starting with the current
+    /// count down.
+    ///
+    /// Returns an error, changing nothing, if the current jitter is too
+    /// large for a subtractive policy.
+    ///
+    #[cfg(feature = "jitter")]
+    pub fn set_jitter_policy(&mut self, jitter_policy: JitterPolicy) -> Result<(), ConfigError> {
+        let (step, jitter) = (self.step(), self.jitter());
+        self.set_pacing(step, jitter, jitter_policy)
     }
     /// Start the timer.
     ///
+    /// Nothing is spawned until a timer is first started. Starting a running
+    /// timer does nothing, while starting a stopped or finished one spawns a
+    /// fresh thread that carries on where the last left off: expiries keep
+    /// counting, and callbacks, subscribers and schedules carry over.
+    ///
+    /// A timer whose thread can't be spawned is left stopped. Use
+    /// `try_start` to find out.
+    ///
     pub fn start(&mut self) {
-        let alive = self.alive.clone();
-        let expiries = self.expiries.clone();
-        let cv = self.cv.clone();
+        if let Err(StartError::Spawn(e)) = self.try_start() {
+            println!("Error: {}", e);
+        }
+    }
+    /// Spawn the timer's thread, once `try_start` has moved to starting.
+    ///
+    fn spawn(&mut self) -> io::Result<()> {
+        let handle = self.prepare(None).and_then(|worker| {
+            self.threads.spawn(&self.id.to_string(), move || worker.spin())
+        });
+        let handle = match handle {
+            Ok(handle) => handle,
+            Err(e) => {
+                self.abandon_start();
+                return Err(e);
+            },
+        };
+        #[cfg(all(feature = "realtime", target_os = "linux"))]
+        if let Some((policy, priority)) = self.realtime {
+            if let Err(e) = realtime::apply(&handle, policy, priority) {
+                println!("Error: {}", e);
+            }
+        }
+        self.handle = Some(handle);
+        self.watch_cancel();
+        Ok(())
+    }
+    /// Go back to stopped after failing to spawn the timer's thread, from
+    /// wherever the failed start got to.
+    ///
+    fn abandon_start(&self) {
+        for &from in &[Phase::Starting, Phase::Running, Phase::Stopping] {
+            if self.lifecycle.transition(from, Phase::Stopped).is_ok() {
+                return;
+            }
+        }
+    }
+    /// Build the state for the timer's thread, moving from starting to
+    /// running, or fail if its callback pool can't be spawned.
+    ///
+    fn prepare<'a>(&mut self, borrowed: Option<Box<dyn FnMut() + Send + 'a>>) -> io::Result<Worker<'a>> {
+        self.first_started.get_or_insert_with(Instant::now);
+        self.completion.clear();
+        {
+            // A time to stop at that passed while stopped is stale.
+            let mut stop_at = self.stop_at.lock().unwrap();
+            if stop_at.is_some_and(|at| at <= Instant::now()) {
+                *stop_at = None;
+            }
+        }
+        if self.calibrate {
+            self.calibration = Some(Timer::measure_overshoot());
+        }
+        if let Some(ref mut checkpoint) = *self.checkpoint.lock().unwrap() {
+            match checkpoint.load() {
+                Ok(Some(due)) => {
+                    self.fire_at.lock().unwrap().get_or_insert(due);
+                },
+                Ok(None) => {},
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+        let worker = Worker {
+            lifecycle: self.lifecycle.clone(),
+            cv: self.cv.clone(),
+            m: self.m.clone(),
+            timed_out: self.timed_out.clone(),
+            expiries: self.expiries.clone(),
+            callbacks: self.callbacks.clone(),
+            pool: match self.dispatch {
+                Dispatch::Inline => None,
+                Dispatch::Pool(size) => {
+                    Some(ThreadPool::new(size, &self.threads, &format!("{}-callback", self.id))?)
+                },
+            },
+            dropped_callbacks: self.dropped_callbacks.clone(),
+            overlap: self.overlap,
+            bias: self.calibration.unwrap_or_default(),
+            correction: self.correction.map(Correction::new),
+            spin: match self.clock {
+                ClockSource::Custom(_) => Duration::from_secs(0),
+                _ => self.spin,
+            },
+            overslept: Cell::new(None),
+            clock: self.clock.clone(),
+            subscribers: self.subscribers.clone(),
+            resets: self.resets.clone(),
+            pauses: self.pauses.clone(),
+            control: self.control.clone(),
+            controlled: Cell::new(None),
+            suspend_policy: self.suspend_policy,
+            jump_threshold: self.jump_threshold,
+            fire_at: self.fire_at.clone(),
+            schedule: self.schedule.clone(),
+            pacing: self.pacing.clone(),
+            intervals: self.intervals.clone(),
+            max_interval: self.max_interval,
+            stop_at: self.stop_at.clone(),
+            max_expiries: self.max_expiries,
+            completion: self.completion.clone(),
+            checkpoint: self.checkpoint.clone(),
+            #[cfg(all(feature = "eventfd", target_os = "linux"))]
+            eventfd: self.eventfd.clone(),
+            #[cfg(feature = "glib")]
+            main_context: self.main_context.clone(),
+            metrics: self.metrics.clone(),
+            countdown: self.countdown.clone(),
+            drift: self.drift.clone(),
+            drift_alert: self.drift_alert.clone(),
+            stale_after: self.stale_after,
+            suppressed: self.suppressed.clone(),
+            heartbeat: self.heartbeat.clone(),
+            id: self.id,
+            ledger: self.ledger.clone(),
+            wake: self.wake,
+            waiters: self.waiters.clone(),
+            ticker: self.ticker.clone(),
+            pending: match self.delivery {
+                DeliveryMode::Notify => None,
+                DeliveryMode::AtLeastOnce => Some(self.pending.clone()),
+            },
+            coalesce: self.coalesce.map(|window| Mutex::new(Coalescer::new(window))),
+            #[cfg(feature = "async")]
+            wakers: self.wakers.clone(),
+            borrowed: borrowed.map(Mutex::new),
+            #[cfg(any(test, feature = "testing"))]
+            idle: self.idle.clone(),
+        };
+        #[cfg(any(test, feature = "testing"))]
+        self.idle.clear();
+        self.callbacks.set_break_hook(self.stopper());
+        self.subscribers.interrupt(false);
+        self.heartbeat.beat(Duration::from_secs(0));
+        self.lifecycle.transition(Phase::Starting, Phase::Running).expect("Only start leaves Starting!");
+        self.subscribers.note(TraceEvent::Started);
+        Ok(worker)
+    }
+    /// Stop the timer when its cancellation token, if any, is cancelled.
+    ///
+    fn watch_cancel(&mut self) {
+        self.cancel_hook = self.cancel.as_ref().map(|token| token.on_cancel(self.stopper()));
+    }
+    /// A function that asks the timer to stop like `request_stop`, from any
+    /// thread, even once the timer itself is out of reach.
+    ///
+    fn stopper(&self) -> impl Fn() + Send + Sync + 'static {
+        let lifecycle = self.lifecycle.clone();
         let m = self.m.clone();
-        let timed_out = self.timed_out.clone();
-        let step = self.step;
-        let jitter = self.jitter;
-        self.handle = Some(std::thread::spawn(move || {
-            Timer::spin(alive, cv, m, timed_out, expiries, step, jitter);
-        }));
+        let cv = self.cv.clone();
+        let subscribers = self.subscribers.clone();
+        move || {
+            subscribers.interrupt(true);
+            let _guard = m.lock().unwrap();
+            if lifecycle.transition(Phase::Running, Phase::Stopping).is_ok() {
+                subscribers.note(TraceEvent::StopRequested);
+            }
+            cv.notify_all();
+        }
     }
     /// Stop the timer.
     ///
+    /// Waits for the current count down and any callbacks to finish, then
+    /// joins the timer's thread. Stopping a timer that isn't running does
+    /// nothing.
+    ///
     pub fn stop(&mut self) {
-        self.alive.store(false, Ordering::SeqCst);
-        self.handle
-            .take().expect("Couldn't stop non-running thread!")
-            .join().expect("Couldn't join spawned thread!");
+        let _ = self.try_stop();
+    }
+    /// Stop the timer without waiting out the current count down.
+    ///
+    fn halt(&mut self) {
+        self.request_stop();
+        self.stop();
+    }
+    /// Pause the count down, keeping the time left until `resume`.
+    ///
+    /// Time spent paused doesn't count towards the count down, `elapsed` or
+    /// `fraction_elapsed`, and ticks stop until the timer resumes. A reset
+    /// while paused restarts the count down paused, stopping a paused timer
+    /// returns right away, and a stopped timer paused before it starts
+    /// starts paused. Returns false if already paused.
+    ///
+    pub fn pause(&mut self) -> bool {
+        let _guard = self.m.lock().unwrap();
+        self.pauses.fetch_add(1, Ordering::SeqCst);
+        self.countdown.lock().unwrap().pause(self.clock.reading())
+    }
+    /// Resume a paused count down, returning false if it wasn't paused.
+    ///
+    pub fn resume(&mut self) -> bool {
+        let _guard = self.m.lock().unwrap();
+        self.pauses.fetch_add(1, Ordering::SeqCst);
+        let resumed = self.countdown.lock().unwrap().resume(self.clock.reading());
+        self.cv.notify_all();
+        resumed
+    }
+    /// True if the count down is paused.
+    ///
+    pub fn is_paused(&self) -> bool {
+        self.countdown.lock().unwrap().is_paused()
     }
     /// Reset the timer.
     ///
     pub fn reset(&mut self) {
+        let _guard = self.m.lock().unwrap();
+        self.resets.fetch_add(1, Ordering::SeqCst);
+        self.cv.notify_all();
+        self.metrics.reset();
+    }
+    /// Choose how time spent with the machine suspended is treated.
+    ///
+    /// Takes effect the next time the timer is started.
+    ///
+    pub fn set_suspend_policy(&mut self, policy: SuspendPolicy) {
+        self.suspend_policy = policy;
+    }
+    /// Expire at the given wall clock time, then go back to counting down.
+    ///
+    /// Replaces the pending count down if the timer is running. Accepts
+    /// anything convertible to a `SystemTime`, such as a `chrono::DateTime`.
+    ///
+    pub fn fire_at<T: Into<SystemTime>>(&mut self, at: T) {
+        let _guard = self.m.lock().unwrap();
+        *self.fire_at.lock().unwrap() = Some(at.into());
+        self.control.fetch_add(1, Ordering::SeqCst);
+        self.resets.fetch_add(1, Ordering::SeqCst);
+        self.cv.notify_all();
+    }
+    /// Follow a schedule of count downs instead of `step` and `jitter`.
+    ///
+    /// The timer stops once the schedule returns `None`. Deadlines set with
+    /// `fire_at` or `set_wall_schedule` take precedence.
+    ///
+    pub fn set_schedule<S: Schedule + Send + 'static>(&mut self, schedule: S) {
+        let _guard = self.m.lock().unwrap();
+        *self.intervals.lock().unwrap() = Some(Box::new(schedule));
+        self.control.fetch_add(1, Ordering::SeqCst);
+        self.resets.fetch_add(1, Ordering::SeqCst);
         self.cv.notify_all();
     }
+    /// Never count down from longer than `max`, whatever the step or
+    /// schedule says.
+    ///
+    /// A hard cap for backoff driven timers. Takes effect the next time the
+    /// timer is started.
+    ///
+    pub fn set_max_interval(&mut self, max: Duration) {
+        self.max_interval = Some(max);
+    }
+    /// Report that whatever the timer's schedule paces has succeeded.
+    ///
+    /// Restarts the schedule from its first interval, e.g., the base delay
+    /// of a backoff, and the current count down with it, so a reconnect
+    /// loop recovers quickly rather than staying at its longest backoff.
+    ///
+    pub fn notify_success(&mut self) {
+        if let Some(ref mut intervals) = *self.intervals.lock().unwrap() {
+            intervals.restart();
+        }
+        self.reset();
+    }
+    /// Follow a schedule of wall clock times instead of counting down `step`.
+    ///
+    /// Before each count down the schedule is called with the current time
+    /// and returns the time to expire at next. The timer stops once the
+    /// schedule returns `None`.
+    ///
+    pub fn set_wall_schedule<F>(&mut self, schedule: F)
+        where F: FnMut(SystemTime) -> Option<SystemTime> + Send + 'static
+    {
+        let _guard = self.m.lock().unwrap();
+        *self.schedule.lock().unwrap() = Some(Box::new(schedule));
+        self.control.fetch_add(1, Ordering::SeqCst);
+        self.resets.fetch_add(1, Ordering::SeqCst);
+        self.cv.notify_all();
+    }
+    /// Set the smallest step of the wall clock that counts as a jump.
+    ///
+    /// Only used by timers counting down against `ClockSource::Wall`, or to
+    /// a wall clock time set by `fire_at` or a wall clock schedule. On a
+    /// jump the pending count down is re-armed against the new time and an
+    /// `Event::ClockJumped` is emitted. Custom clocks are only watched if
+    /// they tell the wall clock time themselves. Defaults to one second.
+    ///
+    pub fn set_jump_threshold(&mut self, threshold: Duration) {
+        self.jump_threshold = threshold;
+    }
+    /// Tell the timer the machine just resumed from a suspend.
+    ///
+    /// Suspends are detected on their own within a second, but applications
+    /// that get resume notifications from the platform can call this to have
+    /// an overdue timer handled right away.
+    ///
+    pub fn notify_resumed(&self) {
+        let _guard = self.m.lock().unwrap();
+        self.cv.notify_all();
+    }
+    /// Subscribe to events from this timer.
+    ///
+    /// Every subscriber receives every event until its receiver is dropped.
+    ///
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        self.subscribers.subscribe()
+    }
+    /// Subscribe to events from this timer, holding at most `capacity`
+    /// undelivered events.
+    ///
+    /// Unlike `subscribe`, a slow consumer can't make events pile up
+    /// without bound. Once full, new events are handled per `on_full`, and
+    /// the receiver counts any that had to be dropped. Room for every event
+    /// is set aside up front, so delivering them never allocates.
+    ///
+    pub fn subscribe_bounded(&mut self, capacity: usize, on_full: OnFull) -> BoundedReceiver {
+        self.subscribers.subscribe_bounded(capacity, on_full)
+    }
+    /// Stop the timer when `token` is cancelled.
+    ///
+    /// The timer's thread exits as soon as the token is cancelled; `stop`
+    /// still needs to be called to join it.
+    ///
+    pub fn with_cancel(mut self, token: CancellationToken) -> Timer {
+        self.cancel = Some(token);
+        self
+    }
+    /// Register a callback to run each time the timer expires.
+    ///
+    /// Callbacks run on the timer thread unless a pool is configured with
+    /// `set_dispatch`. A callback may return `ControlFlow::Break(())` to
+    /// stop the timer like `request_stop` does, though the rest of the
+    /// callbacks for that expiry still run, or `ControlFlow::Continue(())`
+    /// or nothing to keep it going. Reap the stopped timer's thread with
+    /// `join` or `stop`.
+    ///
+    pub fn on_expiry<F, R>(&mut self, f: F)
+        where F: Fn() -> R + Send + Sync + 'static,
+              R: TickOutcome
+    {
+        self.callbacks.push(f);
+    }
+    /// Register a callback to run every `period` while counting down, with
+    /// the time left, replacing any registered before.
+    ///
+    /// For countdown displays. Ticks fall on whole multiples of `period`
+    /// before each deadline and are given the time left rounded up to a
+    /// whole period, so a display counts down 3s, 2s, 1s and only goes back
+    /// up when a new count down begins, such as after a `reset`. Ticks run on
+    /// the timer thread, and any missed while it was busy are skipped rather
+    /// than run late. A zero `period` disables ticks. Takes effect the next
+    /// time the timer is started.
+    ///
+    pub fn on_tick_every<F>(&mut self, period: Duration, f: F)
+        where F: Fn(Duration) + Send + Sync + 'static
+    {
+        self.ticker = if period > Duration::from_secs(0) {
+            Some(Ticker::new(period, f))
+        } else {
+            None
+        };
+    }
+    /// Register a hook to run when an expiry callback panics.
+    ///
+    /// Panicking callbacks are always caught so that the timer keeps
+    /// running. The hook receives the panic payload.
+    ///
+    pub fn on_callback_panic<F>(&mut self, f: F)
+        where F: Fn(Box<dyn Any + Send>) + Send + Sync + 'static
+    {
+        self.callbacks.set_panic_hook(f);
+    }
+    /// Choose where expiry callbacks are run.
+    ///
+    /// A pool queues at most `QUEUED_PER_WORKER` callbacks per thread.
+    /// Expiries that find the queue full skip their callbacks, counting
+    /// them in `dropped_callbacks`. Takes effect the next time the timer is
+    /// started.
+    ///
+    pub fn set_dispatch(&mut self, dispatch: Dispatch) {
+        self.dispatch = dispatch;
+    }
+    /// Number of callbacks skipped because the callback pool's queue was
+    /// full.
+    ///
+    pub fn dropped_callbacks(&self) -> usize {
+        self.dropped_callbacks.load(Ordering::SeqCst)
+    }
+    /// Choose how the timer's thread, and its callback pool if any, are
+    /// spawned.
+    ///
+    /// Takes effect the next time the timer is started. Threads are named
+    /// after the timer's `id`, e.g., `"timer-3"` and `"timer-3-callback-0"`,
+    /// following the config's prefix.
+    ///
+    pub fn set_thread_config(&mut self, threads: ThreadConfig) {
+        self.threads = threads;
+    }
+    /// Choose what happens when the timer expires while a callback from an
+    /// earlier expiry is still running on the pool.
+    ///
+    /// Takes effect the next time the timer is started.
+    ///
+    pub fn set_overlap_policy(&mut self, overlap: OverlapPolicy) {
+        self.overlap = overlap;
+    }
+    /// Measure the platform's wait overshoot each time the timer starts.
+    ///
+    /// When enabled, `start` takes a few short timed waits to measure how
+    /// late they wake up, and subtracts that bias from every count down.
+    ///
+    pub fn set_calibrate(&mut self, calibrate: bool) {
+        self.calibrate = calibrate;
+    }
+    /// Continuously correct for the platform's wait overshoot.
+    ///
+    /// Rather than measuring the overshoot once like `set_calibrate`, the
+    /// timer averages how late each of its last `window` expiries woke up,
+    /// and subtracts that from subsequent count downs. The correction never
+    /// exceeds half of `step`, so a single stall can't make the timer fire
+    /// early by much. Takes effect the next time the timer is started.
+    ///
+    pub fn set_drift_correction(&mut self, window: usize) {
+        self.correction = Some(window);
+    }
+    /// This timer's id, unique within the process.
+    ///
+    pub fn id(&self) -> TimerId {
+        self.id
+    }
+    /// Record every expiry in `ledger` before signalling `timed_out`.
+    ///
+    /// Lets a waiter on a condition shared by many timers tell which of
+    /// them fired. Takes effect the next time the timer is started.
+    ///
+    pub fn set_ledger(&mut self, ledger: ExpiryLedger) {
+        self.ledger = Some(ledger);
+    }
+    /// Stop the timer on its own after it expires `max` times.
+    ///
+    /// A maximum of one makes a one-shot timer. Takes effect the next time
+    /// the timer is started.
+    ///
+    pub fn set_max_expiries(&mut self, max: usize) {
+        self.max_expiries = Some(max);
+    }
+    /// Save every deadline to `checkpoint`, and resume from the saved one
+    /// when the timer starts.
+    ///
+    /// Meant for count downs of days or weeks that must survive restarts.
+    /// While checkpointing, the timer also expires once the wall clock
+    /// passes the saved deadline, even if its own clock hasn't caught up,
+    /// such as after the machine was suspended.
+    ///
+    pub fn set_checkpoint<C>(&mut self, checkpoint: C)
+        where C: Checkpoint + 'static
+    {
+        *self.checkpoint.lock().unwrap() = Some(Box::new(checkpoint));
+    }
+    /// Report this timer's expiries, overruns and resets to `sink`, under
+    /// `name`.
+    ///
+    /// An overrun is an expiry that fired a whole step or more after its
+    /// deadline. Sinks can be added while the timer is running.
+    ///
+    pub fn add_metrics_sink<S>(&mut self, name: &str, sink: S)
+        where S: MetricsSink + 'static
+    {
+        self.metrics.push(name, Box::new(sink));
+    }
+    /// The wait overshoot measured by the last calibrated `start`, if any.
+    ///
+    pub fn calibration(&self) -> Option<Duration> {
+        self.calibration
+    }
+    /// Detach the timer, leaving it running for the rest of the process.
+    ///
+    /// Consumes the timer without stopping it. Use this for timers that are
+    /// meant to live as long as the process does, such as a global metrics
+    /// flusher, rather than relying on never calling `stop`.
+    ///
+    pub fn detach(mut self) {
+        self.handle.take();
+    }
+}
+
+impl<'a> Worker<'a> {
+    /// Internal timer loop.
+    ///
+    fn spin(mut self) {
+        let mut completed = false;
+        while self.lifecycle.is_running() {
+            let started = self.clock.reading();
+            let (deadline, target) = match self.next_deadline() {
+                Some(next) => next,
+                None => break,
+            };
+            self.countdown.lock().unwrap().begin(started, deadline);
+            let due = self.save_checkpoint(deadline);
+            let expired = self.wait_until(due, target);
+            self.countdown.lock().unwrap().finish(self.clock.reading());
+            if let Some((fired, deadline)) = expired {
+                let count = self.expiries.fetch_add(1, Ordering::SeqCst) + 1;
+                self.lifecycle.flight().record(FlightEvent::Expired(count));
+                let expiry = ExpiryEvent {
+                    count,
+                    deadline: self.clock.stamp(deadline),
+                    fired: self.clock.stamp(fired),
+                    coalesced: 1,
+                };
+                let latency = fired.saturating_sub(deadline);
+                let expiry = if self.stale_after.is_some_and(|bound| latency > bound) {
+                    self.suppressed.fetch_add(1, Ordering::SeqCst);
+                    None
+                } else {
+                    match self.coalesce {
+                        Some(ref coalesce) => coalesce.lock().unwrap().hold(fired, expiry),
+                        None => Some(expiry),
+                    }
+                };
+                let step = self.controls().pacing.step();
+                self.metrics.expired(latency, latency >= step);
+                self.drift.record(latency);
+                if let Some(ref alert) = self.drift_alert {
+                    alert.check(latency);
+                }
+                if let Some(ref mut correction) = self.correction {
+                    // How late the wait woke up relative to when it asked to.
+                    let overshoot = if self.spin > Duration::from_secs(0) {
+                        self.overslept.take()
+                    } else {
+                        Some(fired.saturating_add(self.bias).saturating_sub(deadline))
+                    };
+                    if let Some(overshoot) = overshoot {
+                        self.bias = std::cmp::min(correction.observe(overshoot), step / 2);
+                    }
+                }
+                if let Some(expiry) = expiry {
+                    self.deliver(expiry);
+                }
+                if self.max_expiries.is_some_and(|max| count >= max) {
+                    completed = true;
+                    break;
+                }
+            }
+        }
+        if let Some(held) = self.coalesce.as_ref().and_then(|coalesce| coalesce.lock().unwrap().flush()) {
+            self.deliver(held);
+        }
+        self.countdown.lock().unwrap().end(self.clock.reading());
+        let _ = self.lifecycle.transition(Phase::Running, Phase::Stopping);
+        if completed {
+            self.subscribers.emit(Event::Completed);
+            self.completion.complete();
+        }
+        let _ = self.lifecycle.transition(Phase::Stopping, Phase::Stopped);
+    }
+    /// Compute the clock reading to expire at next, and the wall clock
+    /// time it was computed from, if any.
+    ///
+    /// Returns `None` if a wall clock schedule has run out of times, or a
+    /// schedule has run out of intervals.
+    ///
+    fn next_deadline(&self) -> Option<(Duration, Option<SystemTime>)> {
+        let mut controls = self.controls();
+        let ctx = TickContext {
+            count: self.expiries.load(Ordering::SeqCst),
+            now: self.clock.wall(),
+        };
+        if controls.scheduled {
+            let at = self.fire_at.lock().unwrap().take();
+            let at = match (at, self.schedule.lock().unwrap().as_mut()) {
+                (Some(at), _) => Some(at),
+                (None, Some(schedule)) => Some(schedule(self.clock.wall())?),
+                (None, None) => None,
+            };
+            if let Some(at) = at {
+                return Some((self.clock.reading_at(at), Some(at)));
+            }
+            match *self.intervals.lock().unwrap() {
+                Some(ref mut intervals) => {
+                    let wait_duration = intervals.next_interval(&ctx)?;
+                    return Some((self.capped(wait_duration), None));
+                },
+                None => {
+                    // Only a deadline was set, and it's been used.
+                    controls.scheduled = false;
+                    self.controlled.set(Some(controls));
+                },
+            }
+        }
+        Some((self.capped(controls.pacing.next_interval(&ctx)?), None))
+    }
+    /// The clock reading `wait_duration` from now, waiting no longer than
+    /// `max_interval`.
+    ///
+    fn capped(&self, wait_duration: Duration) -> Duration {
+        let wait_duration = self.max_interval.map_or(wait_duration, |max| wait_duration.min(max));
+        self.clock.reading().saturating_add(wait_duration)
+    }
+    /// The settings read under `control`, read again if it's been bumped
+    /// since.
+    ///
+    fn controls(&self) -> Controlled {
+        let seen = self.control.load(Ordering::SeqCst);
+        if let Some(controlled) = self.controlled.get().filter(|controlled| controlled.seen == seen) {
+            return controlled;
+        }
+        let controlled = Controlled {
+            seen,
+            stop_at: *self.stop_at.lock().unwrap(),
+            pacing: *self.pacing.lock().unwrap(),
+            scheduled: self.fire_at.lock().unwrap().is_some()
+                || self.schedule.lock().unwrap().is_some()
+                || self.intervals.lock().unwrap().is_some(),
+        };
+        self.controlled.set(Some(controlled));
+        controlled
+    }
+    /// Tell everyone waiting on the timer that it expired.
+    ///
+    fn deliver(&self, expiry: ExpiryEvent) {
+        if let Some(ref pending) = self.pending {
+            pending.add(expiry);
+        }
+        match self.ledger {
+            Some(ref ledger) => {
+                for _ in 0..expiry.coalesced {
+                    ledger.record(self.id, &self.timed_out);
+                }
+            },
+            None if self.wake == WakePolicy::One => {
+                for _ in 0..expiry.coalesced {
+                    if !self.waiters.hand_one() {
+                        self.timed_out.notify_one();
+                    }
+                }
+            },
+            None => self.timed_out.notify_all(),
+        }
+        // Signalled here rather than as a callback, so it's never dropped
+        // or held back by how callbacks are dispatched.
+        #[cfg(all(feature = "eventfd", target_os = "linux"))]
+        for fd in self.eventfd.lock().unwrap().iter() {
+            eventfd::signal(fd, expiry.coalesced as u64);
+        }
+        self.subscribers.emit(Event::Expired(expiry));
+        #[cfg(feature = "async")]
+        self.wakers.expired(expiry);
+        self.run_callbacks();
+    }
+    /// Run the callbacks wherever they're dispatched to.
+    ///
+    fn run_callbacks(&self) {
+        if let Some(ref borrowed) = self.borrowed {
+            self.callbacks.run_borrowed(&mut **borrowed.lock().unwrap());
+        }
+        #[cfg(feature = "glib")]
+        if let Some(ref context) = self.main_context {
+            let (callbacks, overlap) = (self.callbacks.clone(), self.overlap);
+            context.invoke(move || {
+                callbacks.run(None, overlap);
+            });
+            return;
+        }
+        let dropped = self.callbacks.run(self.pool.as_ref(), self.overlap);
+        self.dropped_callbacks.fetch_add(dropped, Ordering::SeqCst);
+    }
+    /// Save `deadline` to the checkpoint, if any, returning the wall clock
+    /// time it was saved as.
+    ///
+    fn save_checkpoint(&self, deadline: Duration) -> Option<SystemTime> {
+        let mut checkpoint = self.checkpoint.lock().unwrap();
+        let checkpoint = checkpoint.as_mut()?;
+        let due = self.clock.wall_time_at(deadline)?;
+        if let Err(e) = checkpoint.save(due) {
+            println!("Error: {}", e);
+        }
+        Some(due)
+    }
+    /// The wall clock time as a duration since the UNIX epoch.
+    ///
+    fn wall_reading(&self) -> Duration {
+        self.clock.wall().duration_since(UNIX_EPOCH).unwrap_or_default()
+    }
+    /// Wait until the current count down is due, or the wall clock reaches
+    /// `due`, either pushed back by any pauses.
+    ///
+    /// If the count down was computed from the wall clock time `target`,
+    /// it's re-armed against the new time whenever the wall clock jumps.
+    ///
+    /// Returns the clock readings at expiry and that the count down was due
+    /// at, or `None` if the wait was cut short by a reset or stop, or
+    /// skipped because of a suspend.
+    ///
+    fn wait_until(&self, due: Option<SystemTime>, target: Option<SystemTime>) -> Option<(Duration, Duration)> {
+        let resets = self.resets.load(Ordering::SeqCst);
+        let suspended = self.clock.suspended();
+        let mut jumps = match self.clock {
+            ClockSource::Wall => Some(JumpDetector::new(self.clock.reading(),
+                                                        ClockSource::Monotonic.reading(),
+                                                        self.jump_threshold)),
+            _ if target.is_some() && self.clock.has_wall() => Some(JumpDetector::new(self.wall_reading(),
+                                                                                     self.clock.reading(),
+                                                                                     self.jump_threshold)),
+            _ => None,
+        };
+        let mut tick = self.ticker.as_ref()
+            .map(|ticker| ticker.first(self.countdown.lock().unwrap().remaining(self.clock.reading())));
+        // Only pausing and resuming move the deadline, so it's read once and
+        // again after each, not on every wake up.
+        let mut pauses = None;
+        let mut pushed_back = None;
+        // Clock reading the last wait asked to wake up at, if busy-waiting.
+        let mut asked = None;
+        let mut guard = self.m.lock().unwrap();
+        loop {
+            self.heartbeat.beat(Duration::from_secs(0));
+            let stop_in = match self.controls().stop_at {
+                Some(at) => at.checked_duration_since(Instant::now()),
+                None => Some(MAX_WAIT),
+            };
+            let stop_in = match stop_in {
+                Some(stop_in) if stop_in > Duration::from_secs(0) => stop_in,
+                _ => {
+                    // Fired, so it mustn't stop the next start straight away.
+                    self.stop_at.lock().unwrap().take();
+                    self.control.fetch_add(1, Ordering::SeqCst);
+                    if self.lifecycle.transition(Phase::Running, Phase::Stopping).is_ok() {
+                        self.subscribers.emit(Event::Stopped);
+                    }
+                    return self.woke(WakeReason::Stop, None);
+                },
+            };
+            let now = self.clock.reading();
+            if let Some(ref mut jumps) = jumps {
+                let jump = match self.clock {
+                    ClockSource::Wall => jumps.observe(now, ClockSource::Monotonic.reading()),
+                    _ => jumps.observe(self.wall_reading(), now),
+                };
+                if let Some(jump) = jump {
+                    // The wall clock deadline is now a different distance
+                    // away on this clock.
+                    if let (Some(target), false) = (target, matches!(self.clock, ClockSource::Wall)) {
+                        self.countdown.lock().unwrap().rearm(self.clock.reading_at(target));
+                        pauses = None;
+                    }
+                    self.subscribers.emit(Event::ClockJumped(jump));
+                }
+            }
+            let seen = self.pauses.load(Ordering::SeqCst);
+            if pauses != Some(seen) {
+                let countdown = self.countdown.lock().unwrap();
+                pushed_back = countdown.due().map(|deadline| {
+                    (deadline, due.map(|due| due + countdown.delay()))
+                });
+                pauses = Some(seen);
+            }
+            let (deadline, due) = match pushed_back {
+                Some(pushed_back) => pushed_back,
+                None => {
+                    // Paused, so sleep until resumed, reset or stopped.
+                    if let Some(reason) = self.interrupted(resets) {
+                        return self.woke(reason, None);
+                    }
+                    let wait = std::cmp::min(stop_in, MAX_WAIT);
+                    #[cfg(any(test, feature = "testing"))]
+                    self.idle.record(now, resets, seen);
+                    self.lifecycle.flight().record(FlightEvent::Waiting { wait, paused: true });
+                    self.heartbeat.beat(wait);
+                    guard = self.cv.wait_timeout(guard, wait).unwrap().0;
+                    continue;
+                },
+            };
+            // Busy-waiting makes up for the overshoot instead of firing early.
+            let lead = if self.spin > Duration::from_secs(0) { Duration::from_secs(0) } else { self.bias };
+            if now.saturating_add(lead) >= deadline || due.is_some_and(|due| self.clock.wall() >= due) {
+                return self.woke(WakeReason::Due, Some((now, deadline)));
+            }
+            if let Some(reason) = self.interrupted(resets) {
+                return self.woke(reason, None);
+            }
+            if self.spin > Duration::from_secs(0) && deadline - now <= self.spin.saturating_add(self.bias) {
+                if let Some(asked) = asked.take() {
+                    self.overslept.set(Some(now.saturating_sub(asked)));
+                }
+                drop(guard);
+                std::hint::spin_loop();
+                guard = self.m.lock().unwrap();
+                continue;
+            }
+            let mut wait = deadline - now - self.spin - self.bias;
+            if let (Some(ticker), Some(next)) = (self.ticker.as_ref(), tick.as_mut()) {
+                match ticker.until(*next, deadline - now) {
+                    Some(until) if until == Duration::from_secs(0) => {
+                        // Tick without the lock, so the callback can reset
+                        // the timer.
+                        drop(guard);
+                        ticker.tick(next, deadline - now);
+                        guard = self.m.lock().unwrap();
+                        continue;
+                    },
+                    Some(until) => wait = std::cmp::min(wait, until),
+                    None => {},
+                }
+            }
+            if let Some(ref coalesce) = self.coalesce {
+                let mut coalesce = coalesce.lock().unwrap();
+                match coalesce.due() {
+                    Some(closes) if now >= closes => {
+                        let held = coalesce.flush();
+                        drop(coalesce);
+                        drop(guard);
+                        if let Some(held) = held {
+                            self.deliver(held);
+                        }
+                        guard = self.m.lock().unwrap();
+                        continue;
+                    },
+                    Some(closes) => wait = std::cmp::min(wait, closes - now),
+                    None => {},
+                }
+            }
+            if self.suspend_policy != SuspendPolicy::Exclude {
+                let asleep = self.clock.suspended().checked_sub(suspended).unwrap_or_default();
+                if now.saturating_add(asleep) >= deadline {
+                    return match self.suspend_policy {
+                        SuspendPolicy::Skip => self.woke(WakeReason::Skipped, None),
+                        _ => self.woke(WakeReason::Due, Some((now, deadline))),
+                    };
+                }
+                wait = std::cmp::min(wait.checked_sub(asleep).unwrap_or_default(),
+                                     suspend::SUSPEND_POLL);
+            }
+            if jumps.is_some() {
+                // Waits are measured on the monotonic clock, so keep them
+                // short enough to notice the wall clock jumping under them.
+                wait = std::cmp::min(wait, JUMP_POLL);
+            }
+            wait = std::cmp::min(wait, MAX_WAIT);
+            // Waits are on the monotonic clock, like the time to stop at.
+            wait = std::cmp::min(self.clock.real_wait(wait), stop_in);
+            if self.spin > Duration::from_secs(0) {
+                asked = Some(now + wait);
+            }
+            #[cfg(any(test, feature = "testing"))]
+            self.idle.record(now, resets, seen);
+            self.lifecycle.flight().record(FlightEvent::Waiting { wait, paused: false });
+            self.heartbeat.beat(wait);
+            guard = match self.cv.wait_timeout(guard, wait) {
+                Ok((guard, _)) => guard,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return self.woke(WakeReason::Error, None);
+                }
+            };
+        }
+    }
+    /// Why the count down begun after `resets` resets has been cut short, if
+    /// it has.
+    ///
+    fn interrupted(&self, resets: usize) -> Option<WakeReason> {
+        if !self.lifecycle.is_running() {
+            Some(WakeReason::Stop)
+        } else if self.resets.load(Ordering::SeqCst) != resets {
+            Some(WakeReason::Reset)
+        } else {
+            None
+        }
+    }
+    /// Record waking up for `reason`, passing `expired` through.
+    ///
+    fn woke(&self, reason: WakeReason, expired: Option<(Duration, Duration)>) -> Option<(Duration, Duration)> {
+        self.lifecycle.flight().record(FlightEvent::Woke(reason));
+        expired
+    }
 }
 
 #[test]
@@ -134,7 +1593,7 @@ fn it_works() {
     let d = Duration::from_secs(5);
     let j = Duration::from_secs(0);
     let t = Timer::new(d, j, cv);
-    assert!(t.alive.load(Ordering::SeqCst) == false);
+    assert!(!t.lifecycle.is_running());
 }
 
 #[test]
@@ -152,6 +1611,60 @@ fn timer_start() {
     assert!(t.expiries.load(Ordering::SeqCst) < 5);
 }
 
+#[test]
+fn timer_set_step() {
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(Duration::from_secs(3600), ms(0), Arc::new(Condvar::new()));
+    t.start();
+    // Takes effect on the count down already under way...
+    t.set_step(ms(10)).unwrap();
+    std::thread::sleep(ms(55));
+    assert!(t.expiries() >= 3);
+    assert!(t.is_running());
+    #[cfg(feature = "jitter")]
+    {
+        assert_eq!(t.set_jitter(ms(20)), Err(ConfigError::JitterExceedsStep { step: ms(10), jitter: ms(20) }));
+        assert_eq!(t.jitter(), ms(0));
+        t.set_jitter_policy(JitterPolicy::Additive).unwrap();
+        t.set_jitter(ms(20)).unwrap();
+    }
+    assert_eq!(t.step(), ms(10));
+    t.stop();
+    assert!(!t.is_running());
+}
+
+#[test]
+fn timer_lifecycle() {
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(20), ms(0), Arc::new(Condvar::new()));
+    assert!(t.handle.is_none());
+    t.stop();
+    t.start();
+    // Starting again while running mustn't spawn a second thread...
+    t.start();
+    std::thread::sleep(ms(110));
+    t.stop();
+    let expiries = t.expiries.load(Ordering::SeqCst);
+    assert!((4..=6).contains(&expiries));
+    t.stop();
+    assert!(!t.lifecycle.is_running());
+    t.start();
+    std::thread::sleep(ms(50));
+    assert!(t.lifecycle.is_running());
+    t.stop();
+    assert!(t.expiries.load(Ordering::SeqCst) > expiries);
+    // A timer that finished on its own starts again too.
+    t.set_max_expiries(t.expiries.load(Ordering::SeqCst) + 1);
+    t.start();
+    std::thread::sleep(ms(50));
+    assert!(!t.lifecycle.is_running());
+    t.set_max_expiries(usize::MAX);
+    t.start();
+    std::thread::sleep(ms(30));
+    assert!(t.lifecycle.is_running());
+    t.stop();
+}
+
 #[test]
 fn timer_reset() {
     let cv = Arc::new(Condvar::new());
@@ -169,3 +1682,497 @@ fn timer_reset() {
     assert!(t.expiries.load(Ordering::SeqCst) >= 4);
     assert!(t.expiries.load(Ordering::SeqCst) < 6);
 }
+
+#[test]
+fn timer_detach() {
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::new(Duration::from_millis(20),
+                           Duration::from_millis(0),
+                           cv);
+    t.start();
+    let expiries = t.expiries.clone();
+    let lifecycle = t.lifecycle.clone();
+    t.detach();
+    // The detached thread keeps counting down...
+    std::thread::sleep(Duration::from_millis(70));
+    assert!(lifecycle.is_running());
+    assert!(expiries.load(Ordering::SeqCst) >= 2);
+}
+
+#[test]
+fn timer_callbacks_inline() {
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::new(Duration::from_millis(20),
+                           Duration::from_millis(0),
+                           cv);
+    let calls = Arc::new(AtomicUsize::new(0));
+    let c = calls.clone();
+    t.on_expiry(move || { c.fetch_add(1, Ordering::SeqCst); });
+    t.start();
+    std::thread::sleep(Duration::from_millis(70));
+    t.stop();
+    assert_eq!(calls.load(Ordering::SeqCst), t.expiries.load(Ordering::SeqCst));
+}
+
+#[test]
+fn timer_callbacks_on_pool() {
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::new(Duration::from_millis(20),
+                           Duration::from_millis(0),
+                           cv);
+    let calls = Arc::new(AtomicUsize::new(0));
+    let c = calls.clone();
+    // A slow callback shouldn't hold up the ticks behind it...
+    t.on_expiry(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+    t.set_dispatch(Dispatch::Pool(4));
+    t.start();
+    std::thread::sleep(Duration::from_millis(110));
+    t.stop();
+    assert!(t.expiries.load(Ordering::SeqCst) >= 4);
+    assert_eq!(calls.load(Ordering::SeqCst), t.expiries.load(Ordering::SeqCst));
+}
+
+#[test]
+fn timer_drops_callbacks_when_pool_is_full() {
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(1), ms(0), Arc::new(Condvar::new()));
+    let release = Arc::new((Mutex::new(false), Condvar::new()));
+    let r = release.clone();
+    // A callback that never finishes while the timer runs backs up the
+    // queue...
+    t.on_expiry(move || {
+        let mut released = r.0.lock().unwrap();
+        while !*released {
+            released = r.1.wait(released).unwrap();
+        }
+    });
+    t.set_dispatch(Dispatch::Pool(1));
+    t.start();
+    let started = std::time::Instant::now();
+    while t.dropped_callbacks() == 0 && started.elapsed() < Duration::from_secs(2) {
+        std::thread::sleep(ms(5));
+    }
+    // ...so later expiries skip their callbacks instead of queueing them.
+    assert!(t.dropped_callbacks() > 0);
+    assert!(t.expiries.load(Ordering::SeqCst) > QUEUED_PER_WORKER);
+    *release.0.lock().unwrap() = true;
+    release.1.notify_all();
+    t.stop();
+}
+
+#[test]
+fn timer_overlap_skip() {
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::new(Duration::from_millis(20),
+                           Duration::from_millis(0),
+                           cv);
+    let calls = Arc::new(AtomicUsize::new(0));
+    let c = calls.clone();
+    t.on_expiry(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+    t.set_dispatch(Dispatch::Pool(4));
+    t.set_overlap_policy(OverlapPolicy::Skip);
+    t.start();
+    std::thread::sleep(Duration::from_millis(110));
+    t.stop();
+    assert!(t.expiries.load(Ordering::SeqCst) >= 4);
+    assert!(calls.load(Ordering::SeqCst) <= 2);
+}
+
+#[test]
+fn timer_callback_break() {
+    use std::ops::ControlFlow;
+    let ms = Duration::from_millis;
+    for &dispatch in &[Dispatch::Inline, Dispatch::Pool(2)] {
+        let mut t = Timer::new(ms(5), ms(0), Arc::new(Condvar::new()));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c = calls.clone();
+        t.on_expiry(move || {
+            match c.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => ControlFlow::Continue(()),
+                _ => ControlFlow::Break(()),
+            }
+        });
+        let c = calls.clone();
+        t.on_expiry(move || assert!(c.load(Ordering::SeqCst) <= 3));
+        t.set_dispatch(dispatch);
+        t.start();
+        assert!(t.join(Duration::from_secs(1)), "{:?}", dispatch);
+        assert_eq!(t.phase(), Phase::Stopped);
+        assert!(calls.load(Ordering::SeqCst) >= 3);
+        if dispatch == Dispatch::Inline {
+            assert_eq!(t.expiries(), 3);
+        }
+        // Breaking stops this run only.
+        t.start();
+        assert!(t.is_running());
+        t.halt();
+    }
+}
+
+#[test]
+fn timer_survives_callback_panic() {
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::new(Duration::from_millis(20),
+                           Duration::from_millis(0),
+                           cv);
+    let panics = Arc::new(AtomicUsize::new(0));
+    let p = panics.clone();
+    t.on_expiry::<_, ()>(|| panic!("bad tick"));
+    t.on_callback_panic(move |_| { p.fetch_add(1, Ordering::SeqCst); });
+    t.start();
+    std::thread::sleep(Duration::from_millis(70));
+    assert!(t.lifecycle.is_running());
+    t.stop();
+    assert!(panics.load(Ordering::SeqCst) >= 2);
+}
+
+#[test]
+fn timer_calibration() {
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::new(Duration::from_millis(20),
+                           Duration::from_millis(0),
+                           cv);
+    assert_eq!(t.calibration(), None);
+    t.start();
+    std::thread::sleep(Duration::from_millis(10));
+    t.stop();
+    assert_eq!(t.calibration(), None);
+    t.set_calibrate(true);
+    t.start();
+    std::thread::sleep(Duration::from_millis(10));
+    t.stop();
+    assert!(t.calibration().unwrap() < Duration::from_millis(20));
+}
+
+#[test]
+fn timer_mock_clock() {
+    let cv = Arc::new(Condvar::new());
+    let mock = Arc::new(MockClock::new());
+    let mut t = Timer::with_clock(Duration::from_secs(60),
+                                  Duration::from_secs(0),
+                                  cv,
+                                  ClockSource::Custom(mock.clone()));
+    let events = t.subscribe();
+    t.start();
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 0);
+    mock.advance(Duration::from_secs(60));
+    match events.recv_timeout(Duration::from_secs(1)).unwrap() {
+        Event::Expired(e) => {
+            assert_eq!(e.count, 1);
+            assert_eq!(e.deadline, Timestamp::Custom(Duration::from_secs(60)));
+            assert_eq!(e.fired, Timestamp::Custom(Duration::from_secs(60)));
+        },
+        other => panic!("unexpected {:?}", other),
+    }
+    t.stop();
+}
+
+#[test]
+fn timer_wall_clock_events() {
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::with_clock(Duration::from_millis(20),
+                                  Duration::from_millis(0),
+                                  cv,
+                                  ClockSource::Wall);
+    let events = t.subscribe();
+    t.start();
+    match events.recv_timeout(Duration::from_secs(1)).unwrap() {
+        Event::Expired(e) => match (e.deadline, e.fired) {
+            (Timestamp::Wall(deadline), Timestamp::Wall(fired)) => assert!(fired >= deadline),
+            other => panic!("unexpected {:?}", other),
+        },
+        other => panic!("unexpected {:?}", other),
+    }
+    t.stop();
+}
+
+#[test]
+fn timer_suspend_policy() {
+    let cv = Arc::new(Condvar::new());
+    let mock = Arc::new(MockClock::new());
+    let mut t = Timer::with_clock(Duration::from_secs(60),
+                                  Duration::from_secs(0),
+                                  cv.clone(),
+                                  ClockSource::Custom(mock.clone()));
+    // By default time spent asleep doesn't count...
+    t.start();
+    std::thread::sleep(Duration::from_millis(10));
+    mock.suspend(Duration::from_secs(3600));
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 0);
+    t.stop();
+    // ...unless the policy says so.
+    let mut t = Timer::with_clock(Duration::from_secs(60),
+                                  Duration::from_secs(0),
+                                  cv.clone(),
+                                  ClockSource::Custom(mock.clone()));
+    t.set_suspend_policy(SuspendPolicy::FireOnWake);
+    t.start();
+    std::thread::sleep(Duration::from_millis(10));
+    mock.suspend(Duration::from_secs(3600));
+    t.notify_resumed();
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 1);
+    t.stop();
+    let mut t = Timer::with_clock(Duration::from_secs(60),
+                                  Duration::from_secs(0),
+                                  cv,
+                                  ClockSource::Custom(mock.clone()));
+    t.set_suspend_policy(SuspendPolicy::Skip);
+    t.start();
+    std::thread::sleep(Duration::from_millis(10));
+    mock.suspend(Duration::from_secs(3600));
+    std::thread::sleep(Duration::from_millis(20));
+    // The overdue count down was dropped and a fresh one started on wake...
+    mock.advance(Duration::from_secs(30));
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 0);
+    mock.advance(Duration::from_secs(30));
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 1);
+    t.stop();
+}
+
+#[test]
+fn timer_fire_at() {
+    let cv = Arc::new(Condvar::new());
+    let mock = Arc::new(MockClock::new());
+    let mut t = Timer::with_clock(Duration::from_secs(60),
+                                  Duration::from_secs(0),
+                                  cv,
+                                  ClockSource::Custom(mock.clone()));
+    t.start();
+    std::thread::sleep(Duration::from_millis(10));
+    t.fire_at(SystemTime::now() + Duration::from_secs(10));
+    std::thread::sleep(Duration::from_millis(10));
+    mock.advance(Duration::from_secs(9));
+    std::thread::sleep(Duration::from_millis(10));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 0);
+    mock.advance(Duration::from_secs(1));
+    std::thread::sleep(Duration::from_millis(10));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 1);
+    t.stop();
+}
+
+#[test]
+fn timer_fire_at_follows_wall_jumps() {
+    let cv = Arc::new(Condvar::new());
+    let mock = Arc::new(MockClock::new());
+    let wall = SystemTime::now();
+    mock.set_wall(wall);
+    let mut t = Timer::with_clock(Duration::from_secs(60),
+                                  Duration::from_secs(0),
+                                  cv,
+                                  ClockSource::Custom(mock.clone()));
+    let events = t.subscribe();
+    t.fire_at(wall + Duration::from_secs(3600));
+    t.start();
+    std::thread::sleep(Duration::from_millis(10));
+    // NTP steps the wall clock most of the way to the deadline...
+    mock.set_wall(wall + Duration::from_secs(3540));
+    match events.recv_timeout(Duration::from_secs(1)).unwrap() {
+        Event::ClockJumped(jump) => assert_eq!(jump, ClockJump { forward: true, by: Duration::from_secs(3540) }),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 0);
+    // ...so it's due a minute later on the timer's clock, not an hour.
+    mock.advance(Duration::from_secs(60));
+    match events.recv_timeout(Duration::from_secs(1)).unwrap() {
+        Event::Expired(e) => assert_eq!(e.count, 1),
+        other => panic!("unexpected {:?}", other),
+    }
+    t.stop();
+}
+
+#[test]
+fn timer_wall_schedule() {
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::new(Duration::from_secs(60),
+                           Duration::from_secs(0),
+                           cv);
+    let mut remaining = 3;
+    t.set_wall_schedule(move |now| {
+        remaining -= 1;
+        if remaining > 0 {
+            Some(now + Duration::from_millis(10))
+        } else {
+            None
+        }
+    });
+    t.start();
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 2);
+    assert!(!t.lifecycle.is_running());
+    t.stop();
+}
+
+#[test]
+#[cfg(feature = "jitter")]
+fn timer_from_config() {
+    let cv = Arc::new(Condvar::new());
+    let mut config = TimerConfig::new(Duration::from_millis(20));
+    config.jitter = Duration::from_millis(40);
+    assert!(Timer::from_config(config.clone(), cv.clone()).is_err());
+    config.jitter_policy = JitterPolicy::Additive;
+    let t = Timer::from_config(config, cv).unwrap();
+    // Additive jitter never counts down from less than the step...
+    for _ in 0..100 {
+        let wait = Timer::calculate_wait_duration(t.step(), t.jitter(), t.jitter_policy());
+        assert!(wait >= Duration::from_millis(20));
+        assert!(wait < Duration::from_millis(60));
+    }
+}
+
+#[test]
+fn timer_with_cancel() {
+    let cv = Arc::new(Condvar::new());
+    let token = CancellationToken::new();
+    let mut t = Timer::new(Duration::from_secs(60),
+                           Duration::from_secs(0),
+                           cv).with_cancel(token.child_token());
+    t.start();
+    token.cancel();
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(!t.lifecycle.is_running());
+    t.stop();
+}
+
+#[test]
+fn timer_max_expiries() {
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::new(Duration::from_millis(10),
+                           Duration::from_secs(0),
+                           cv);
+    t.set_max_expiries(1);
+    t.start();
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(!t.lifecycle.is_running());
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 1);
+    t.stop();
+}
+
+#[test]
+fn timer_checkpoint() {
+    let path = std::env::temp_dir().join(format!("timer-resume-{}", std::process::id()));
+    let week = Duration::from_secs(7 * 24 * 3600);
+    let token = CancellationToken::new();
+    let mut t = Timer::new(week, Duration::from_secs(0), Arc::new(Condvar::new()))
+        .with_cancel(token.clone());
+    t.set_checkpoint(FileCheckpoint::new(&path));
+    t.start();
+    std::thread::sleep(Duration::from_millis(20));
+    let due = FileCheckpoint::new(&path).load().unwrap().unwrap();
+    assert!(due > SystemTime::now() + week - Duration::from_secs(60));
+    token.cancel();
+    t.stop();
+    // A deadline that passed while the process was down fires on start...
+    FileCheckpoint::new(&path).save(SystemTime::now() - Duration::from_secs(3600)).unwrap();
+    let token = CancellationToken::new();
+    let mut t = Timer::new(week, Duration::from_secs(0), Arc::new(Condvar::new()))
+        .with_cancel(token.clone());
+    t.set_checkpoint(FileCheckpoint::new(&path));
+    t.start();
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 1);
+    token.cancel();
+    t.stop();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(test)]
+fn any_duration() -> impl proptest::strategy::Strategy<Value = Duration> {
+    use proptest::prelude::*;
+    // The whole `Duration` range, biased towards the extremes where
+    // overflow lives.
+    prop_oneof![
+        Just(Duration::from_secs(0)),
+        Just(Duration::MAX),
+        (0u64..2_000_000).prop_map(Duration::from_nanos),
+        (any::<u64>(), 0u32..1_000_000_000).prop_map(|(secs, nanos)| Duration::new(secs, nanos)),
+    ]
+}
+
+#[cfg(test)]
+proptest! {
+    #![proptest_config(proptest::test_runner::Config::with_cases(10_000))]
+    #[test]
+    fn timer_wait_duration_never_overflows(step in any_duration(), jitter in any_duration()) {
+        let ms = Duration::from_millis(1);
+        let wait = Timer::calculate_wait_duration(step, jitter, JitterPolicy::Subtractive);
+        prop_assert!(wait <= step);
+        prop_assert!(wait.saturating_add(jitter).saturating_add(ms) >= step);
+        let wait = Timer::calculate_wait_duration(step, jitter, JitterPolicy::Additive);
+        prop_assert!(wait.saturating_add(ms) >= step);
+        prop_assert!(wait <= step.saturating_add(jitter));
+    }
+
+    #[test]
+    fn clock_conversions_never_overflow(reading in any_duration()) {
+        for clock in &[ClockSource::Monotonic, ClockSource::Wall] {
+            let _ = clock.wall_time_at(reading);
+            let at = SystemTime::UNIX_EPOCH.checked_add(reading).unwrap_or(SystemTime::now());
+            let _ = clock.reading_at(at);
+        }
+    }
+}
+
+#[test]
+fn timer_tick_every() {
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(100), ms(0), Arc::new(Condvar::new()));
+    let ticks = Arc::new(Mutex::new(Vec::new()));
+    {
+        let ticks = ticks.clone();
+        t.on_tick_every(ms(20), move |left| ticks.lock().unwrap().push(left));
+    }
+    t.set_max_expiries(1);
+    t.start();
+    std::thread::sleep(ms(150));
+    t.stop();
+    let ticks = ticks.lock().unwrap();
+    assert!(!ticks.is_empty());
+    assert!(ticks.windows(2).all(|w| w[0] > w[1]));
+    assert!(ticks.iter().all(|&left| left < ms(100) && left.as_millis() % 20 == 0));
+}
+
+#[test]
+fn timer_fraction_elapsed() {
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(200), ms(0), Arc::new(Condvar::new()));
+    assert_eq!(t.fraction_elapsed(), 0.0);
+    t.start();
+    std::thread::sleep(ms(100));
+    let fraction = t.fraction_elapsed();
+    assert!(fraction > 0.3 && fraction < 0.8, "{}", fraction);
+    t.halt();
+    assert_eq!(t.fraction_elapsed(), 0.0);
+}
+
+#[test]
+fn timer_pause_resume() {
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(150), ms(0), Arc::new(Condvar::new()));
+    t.start();
+    std::thread::sleep(ms(50));
+    assert!(t.pause());
+    assert!(!t.pause());
+    std::thread::sleep(ms(150));
+    assert_eq!(t.expiries(), 0);
+    let elapsed = t.elapsed();
+    assert!(elapsed >= ms(40) && elapsed < ms(100), "{:?}", elapsed);
+    assert!(t.resume());
+    std::thread::sleep(ms(50));
+    assert_eq!(t.expiries(), 0);
+    std::thread::sleep(ms(100));
+    assert_eq!(t.expiries(), 1);
+    t.pause();
+    t.stop();
+    assert!(!t.is_paused());
+}
@@ -0,0 +1,389 @@
+use flight::{FlightEvent, FlightRecorder};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+use trace::TraceEvent;
+use Timer;
+
+/// How often `Timer::join` checks whether the timer's thread has exited.
+///
+const JOIN_POLL: Duration = Duration::from_millis(1);
+
+/// Where a timer is in its life.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Not running, its thread, if any, exited.
+    Stopped,
+    /// Being started, its thread not yet spawned.
+    Starting,
+    /// Counting down on its thread.
+    Running,
+    /// Asked to stop, or finished on its own, its thread not yet exited.
+    Stopping,
+}
+
+impl Phase {
+    fn from_u8(phase: u8) -> Phase {
+        match phase {
+            0 => Phase::Stopped,
+            1 => Phase::Starting,
+            2 => Phase::Running,
+            _ => Phase::Stopping,
+        }
+    }
+}
+
+/// A lifecycle transition that was refused because the timer wasn't in the
+/// phase it starts from.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransitionError {
+    /// The phase the timer was actually in.
+    pub from: Phase,
+    /// The phase the transition was to.
+    pub to: Phase,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "timer can't go from {:?} to {:?}", self.from, self.to)
+    }
+}
+
+impl Error for TransitionError {}
+
+/// Why a timer couldn't be started.
+///
+#[derive(Debug)]
+pub enum StartError {
+    /// The timer wasn't stopped.
+    Transition(TransitionError),
+    /// The timer's thread, or its callback pool, couldn't be spawned. The
+    /// timer is left stopped.
+    Spawn(io::Error),
+}
+
+impl PartialEq for StartError {
+    fn eq(&self, other: &StartError) -> bool {
+        match (self, other) {
+            (StartError::Transition(a), StartError::Transition(b)) => a == b,
+            (StartError::Spawn(a), StartError::Spawn(b)) => a.kind() == b.kind(),
+            _ => false,
+        }
+    }
+}
+
+impl From<TransitionError> for StartError {
+    fn from(e: TransitionError) -> StartError {
+        StartError::Transition(e)
+    }
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StartError::Transition(ref e) => e.fmt(f),
+            StartError::Spawn(ref e) => write!(f, "couldn't spawn timer thread: {}", e),
+        }
+    }
+}
+
+impl Error for StartError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            StartError::Transition(ref e) => Some(e),
+            StartError::Spawn(ref e) => Some(e),
+        }
+    }
+}
+
+/// A timer's phase, only ever changed by compare and swap, so that of
+/// several threads racing to start or stop it exactly one wins.
+///
+#[derive(Debug, Default)]
+pub struct Lifecycle {
+    phase: AtomicU8,
+    // Records every transition, for `Timer::dump_flight_recorder`.
+    flight: FlightRecorder,
+}
+
+impl Lifecycle {
+    /// The current phase.
+    ///
+    pub fn phase(&self) -> Phase {
+        Phase::from_u8(self.phase.load(Ordering::SeqCst))
+    }
+    /// True if the timer is counting down.
+    ///
+    pub fn is_running(&self) -> bool {
+        self.phase() == Phase::Running
+    }
+    /// Move from `from` to `to`, failing with the actual phase if it isn't
+    /// `from`.
+    ///
+    pub fn transition(&self, from: Phase, to: Phase) -> Result<(), TransitionError> {
+        self.phase.compare_exchange(from as u8, to as u8, Ordering::SeqCst, Ordering::SeqCst)
+            .map(|_| self.flight.record(FlightEvent::Transition { from, to }))
+            .map_err(|actual| TransitionError { from: Phase::from_u8(actual), to })
+    }
+    /// The timer's flight recorder.
+    ///
+    pub fn flight(&self) -> &FlightRecorder {
+        &self.flight
+    }
+}
+
+impl Timer {
+    /// Where the timer is in its life.
+    ///
+    pub fn phase(&self) -> Phase {
+        self.lifecycle.phase()
+    }
+    /// Start the timer, failing if it's already running, or its thread
+    /// can't be spawned.
+    ///
+    /// Like `start`, but reports a start that does nothing.
+    ///
+    pub fn try_start(&mut self) -> Result<(), StartError> {
+        if let Some(handle) = self.handle.take() {
+            if self.lifecycle.is_running() {
+                self.handle = Some(handle);
+                return Err(StartError::Transition(TransitionError { from: Phase::Running, to: Phase::Starting }));
+            }
+            // Finished on its own, so reap its thread before spawning another.
+            handle.join().expect("Couldn't join spawned thread!");
+        }
+        self.lifecycle.transition(Phase::Stopped, Phase::Starting)?;
+        self.spawn().map_err(StartError::Spawn)
+    }
+    /// Stop the timer, failing if it isn't running.
+    ///
+    /// Like `stop`, but reports a stop that does nothing. A timer that
+    /// finished on its own isn't running, but its thread is still reaped.
+    ///
+    pub fn try_stop(&mut self) -> Result<(), TransitionError> {
+        self.subscribers.interrupt(true);
+        let stopped = {
+            let _guard = self.m.lock().unwrap();
+            let stopped = self.lifecycle.transition(Phase::Running, Phase::Stopping);
+            if stopped.is_ok() {
+                self.subscribers.note(TraceEvent::StopRequested);
+            }
+            // A paused count down would never finish, so don't wait for it.
+            if self.is_paused() {
+                self.cv.notify_all();
+            }
+            stopped
+        };
+        if let Some(handle) = self.handle.take() {
+            handle.join().expect("Couldn't join spawned thread!");
+        }
+        stopped
+    }
+    /// Ask the timer to stop, without waiting for it to.
+    ///
+    /// Wakes the timer's thread straight away, cutting the current count
+    /// down short, though a callback already running is let finish. Reap
+    /// the thread with `join`, so that many timers can be asked to stop
+    /// first and then joined, rather than stopped one after another.
+    ///
+    pub fn request_stop(&self) {
+        self.subscribers.interrupt(true);
+        let _guard = self.m.lock().unwrap();
+        if self.lifecycle.transition(Phase::Running, Phase::Stopping).is_ok() {
+            self.subscribers.note(TraceEvent::StopRequested);
+        }
+        self.cv.notify_all();
+    }
+    /// Stop the timer on its own at `at`, emitting `Event::Stopped`.
+    ///
+    /// Arms a running timer straight away, and otherwise takes effect the
+    /// next time it's started, unless `at` has passed by then. An expiry due
+    /// at the same time as the stop may or may not fire. Fires once, so
+    /// the timer can be started again afterwards. Reap the stopped timer's
+    /// thread with `join` or `stop`.
+    ///
+    pub fn stop_at(&self, at: Instant) {
+        *self.stop_at.lock().unwrap() = Some(at);
+        self.control.fetch_add(1, Ordering::SeqCst);
+        let _guard = self.m.lock().unwrap();
+        self.cv.notify_all();
+    }
+    /// Stop the timer on its own `after` from now, emitting
+    /// `Event::Stopped`.
+    ///
+    pub fn stop_after(&self, after: Duration) {
+        self.stop_at(Instant::now() + after);
+    }
+    /// Forget the time set by `stop_at`, `stop_after` or `run_for`, if it
+    /// hasn't been reached yet, leaving the timer running.
+    ///
+    pub fn cancel_stop_at(&self) {
+        *self.stop_at.lock().unwrap() = None;
+        self.control.fetch_add(1, Ordering::SeqCst);
+        let _guard = self.m.lock().unwrap();
+        self.cv.notify_all();
+    }
+    /// Start the timer for `total` only, after which it stops on its own
+    /// however many times it expired.
+    ///
+    /// For load tests and sampling windows. Like `stop_after` then `start`,
+    /// so the timer emits `Event::Stopped` when it stops. Reap its thread
+    /// with `join` or `stop`.
+    ///
+    pub fn run_for(&mut self, total: Duration) {
+        self.stop_after(total);
+        self.start();
+    }
+    /// Wait up to `timeout` for the timer's thread to exit, then reap it.
+    ///
+    /// Returns false if the thread is still running after `timeout`, e.g.,
+    /// because nothing asked it to stop, or a callback is still running.
+    /// Returns true straight away if there's no thread to reap.
+    ///
+    pub fn join(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        if let Some(handle) = self.handle.take() {
+            while !handle.is_finished() {
+                let now = Instant::now();
+                if now >= deadline {
+                    self.handle = Some(handle);
+                    return false;
+                }
+                std::thread::sleep(std::cmp::min(JOIN_POLL, deadline - now));
+            }
+            handle.join().expect("Couldn't join spawned thread!");
+        }
+        true
+    }
+}
+
+#[test]
+fn lifecycle_races_have_one_winner() {
+    use std::sync::{Arc, Barrier};
+    for _ in 0..100 {
+        let lifecycle = Arc::new(Lifecycle::default());
+        let barrier = Arc::new(Barrier::new(8));
+        let racers: Vec<_> = (0..8).map(|i| {
+            let (lifecycle, barrier) = (lifecycle.clone(), barrier.clone());
+            std::thread::spawn(move || {
+                barrier.wait();
+                // Half race to start, half to stop once started.
+                if i % 2 == 0 {
+                    lifecycle.transition(Phase::Stopped, Phase::Running).is_ok()
+                } else {
+                    while lifecycle.phase() == Phase::Stopped {
+                        std::thread::yield_now();
+                    }
+                    lifecycle.transition(Phase::Running, Phase::Stopping).is_ok()
+                }
+            })
+        }).collect();
+        let won: Vec<bool> = racers.into_iter().map(|racer| racer.join().unwrap()).collect();
+        assert_eq!(won.iter().step_by(2).filter(|&&won| won).count(), 1);
+        assert_eq!(won.iter().skip(1).step_by(2).filter(|&&won| won).count(), 1);
+        assert_eq!(lifecycle.phase(), Phase::Stopping);
+    }
+}
+
+#[test]
+fn timer_transitions() {
+    use std::sync::{Arc, Condvar};
+    use std::time::Duration;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(10), ms(0), Arc::new(Condvar::new()));
+    assert_eq!(t.try_stop(), Err(TransitionError { from: Phase::Stopped, to: Phase::Stopping }));
+    assert_eq!(t.try_start(), Ok(()));
+    assert_eq!(t.phase(), Phase::Running);
+    assert_eq!(t.try_start(), Err(StartError::Transition(TransitionError { from: Phase::Running, to: Phase::Starting })));
+    assert_eq!(t.try_stop(), Ok(()));
+    assert_eq!(t.phase(), Phase::Stopped);
+    // Finished on its own, it can't be stopped, but can be started again.
+    t.set_max_expiries(1);
+    t.start();
+    std::thread::sleep(ms(50));
+    assert_eq!(t.phase(), Phase::Stopped);
+    assert_eq!(t.try_start(), Ok(()));
+    std::thread::sleep(ms(50));
+    assert!(t.try_stop().is_err());
+    assert_eq!(t.phase(), Phase::Stopped);
+}
+
+#[test]
+fn timer_request_stop_then_join() {
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut timers: Vec<Timer> = (0..5)
+        .map(|_| Timer::new(Duration::from_secs(10), ms(0), Arc::new(Condvar::new())))
+        .collect();
+    for t in timers.iter_mut() {
+        t.start();
+    }
+    assert!(!timers[0].join(ms(10)));
+    let started = Instant::now();
+    for t in &timers {
+        t.request_stop();
+        assert!(!t.is_running());
+    }
+    for t in timers.iter_mut() {
+        assert!(t.join(Duration::from_secs(1)));
+        assert_eq!(t.phase(), Phase::Stopped);
+    }
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert!(timers[0].join(ms(0)));
+}
+
+#[test]
+fn timer_stop_after() {
+    use event::Event;
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(20), ms(0), Arc::new(Condvar::new()));
+    let events = t.subscribe();
+    t.start();
+    t.stop_after(ms(90));
+    assert!(t.join(Duration::from_secs(1)));
+    let events: Vec<Event> = events.try_iter().collect();
+    let expired = events.iter().filter(|event| matches!(event, Event::Expired(_))).count();
+    assert!(expired >= 2, "{:?}", events);
+    assert_eq!(events.last(), Some(&Event::Stopped));
+    assert_eq!(t.phase(), Phase::Stopped);
+}
+
+#[test]
+fn timer_run_for() {
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(20), ms(0), Arc::new(Condvar::new()));
+    let started = Instant::now();
+    t.run_for(ms(90));
+    assert!(t.is_running());
+    assert!(t.join(Duration::from_secs(1)));
+    assert!(started.elapsed() >= ms(90));
+    assert!((2..=4).contains(&t.expiries()), "{}", t.expiries());
+}
+
+#[test]
+fn timer_restarts_after_run_for() {
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(10), ms(0), Arc::new(Condvar::new()));
+    t.run_for(ms(50));
+    assert!(t.join(Duration::from_secs(1)));
+    let expiries = t.expiries();
+    t.start();
+    std::thread::sleep(ms(50));
+    assert!(t.is_running());
+    assert!(t.expiries() > expiries);
+    t.halt();
+    // A stop cancelled before it's due never happens.
+    t.stop_after(ms(20));
+    t.start();
+    t.cancel_stop_at();
+    std::thread::sleep(ms(50));
+    assert!(t.is_running());
+    t.halt();
+}
@@ -0,0 +1,155 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time::{Duration, Instant};
+
+/// What to run when a local timer falls due.
+enum LocalJob {
+    Once(Box<dyn FnOnce()>),
+    // Run every period until cancelled.
+    Every(Duration, Box<dyn FnMut()>),
+}
+
+/// Identifies a timer in a `LocalTimerSet`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LocalTimerId(u64);
+
+/// Timers that run entirely on the thread that polls them.
+///
+/// Nothing is spawned and nothing is locked: the set only checks its
+/// deadlines when `poll`ed, running whichever have fallen due right there.
+/// Meant for single threaded event loops, which sleep until
+/// `next_deadline` or their next event, whichever comes first, then poll.
+/// Callbacks needn't be `Send`, so they can capture `Rc`s and the like.
+///
+#[derive(Default)]
+pub struct LocalTimerSet {
+    // Deadline and id of every pending timer, earliest first.
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+    // What to run for each pending timer.
+    jobs: HashMap<u64, LocalJob>,
+    // Id to hand out next.
+    next_id: u64,
+}
+
+impl LocalTimerSet {
+    /// Create a new, empty set.
+    ///
+    pub fn new() -> LocalTimerSet {
+        LocalTimerSet::default()
+    }
+    /// Run `f` once, at the first poll at least `after` from now.
+    ///
+    pub fn after<F>(&mut self, after: Duration, f: F) -> LocalTimerId
+        where F: FnOnce() + 'static
+    {
+        self.push(Instant::now() + after, LocalJob::Once(Box::new(f)))
+    }
+    /// Run `f` every `period` from now, at the first poll each falls due,
+    /// until cancelled.
+    ///
+    /// Runs missed between polls are skipped rather than run late.
+    ///
+    /// # Panics
+    ///
+    /// If `period` is zero.
+    ///
+    pub fn every<F>(&mut self, period: Duration, f: F) -> LocalTimerId
+        where F: FnMut() + 'static
+    {
+        assert!(period > Duration::from_secs(0), "Local timer period must be non-zero!");
+        self.push(Instant::now() + period, LocalJob::Every(period, Box::new(f)))
+    }
+    /// Schedule `job` at `at`.
+    ///
+    fn push(&mut self, at: Instant, job: LocalJob) -> LocalTimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.deadlines.push(Reverse((at, id)));
+        self.jobs.insert(id, job);
+        LocalTimerId(id)
+    }
+    /// Cancel a timer, returning false if it had already fired or been
+    /// cancelled.
+    ///
+    pub fn cancel(&mut self, id: LocalTimerId) -> bool {
+        if self.jobs.remove(&id.0).is_none() {
+            return false;
+        }
+        self.deadlines.retain(|&Reverse((_, pending))| pending != id.0);
+        true
+    }
+    /// When the next timer falls due, if any are pending.
+    ///
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.peek().map(|&Reverse((at, _))| at)
+    }
+    /// Run every timer that has fallen due, returning how many ran.
+    ///
+    /// Each timer runs at most once per poll, in order of deadline.
+    ///
+    pub fn poll(&mut self) -> usize {
+        let now = Instant::now();
+        let mut ran = 0;
+        let mut rearm = Vec::new();
+        while let Some(&Reverse((at, id))) = self.deadlines.peek() {
+            if at > now {
+                break;
+            }
+            self.deadlines.pop();
+            match self.jobs.remove(&id) {
+                Some(LocalJob::Once(f)) => f(),
+                Some(LocalJob::Every(period, mut f)) => {
+                    f();
+                    let next = if at + period > now { at + period } else { now + period };
+                    rearm.push((next, id, LocalJob::Every(period, f)));
+                },
+                None => continue,
+            }
+            ran += 1;
+        }
+        for (next, id, job) in rearm {
+            self.deadlines.push(Reverse((next, id)));
+            self.jobs.insert(id, job);
+        }
+        ran
+    }
+    /// Number of pending timers.
+    ///
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+    /// True if no timers are pending.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+}
+
+#[test]
+fn local_timer_set() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    let ms = Duration::from_millis;
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut timers = LocalTimerSet::new();
+    assert_eq!(timers.next_deadline(), None);
+    let (a, b, c) = (log.clone(), log.clone(), log.clone());
+    let started = Instant::now();
+    let every = timers.every(ms(10), move || a.borrow_mut().push("every"));
+    timers.after(ms(5), move || b.borrow_mut().push("after"));
+    let cancelled = timers.after(ms(1), move || c.borrow_mut().push("cancelled"));
+    assert!(timers.cancel(cancelled));
+    assert!(!timers.cancel(cancelled));
+    assert_eq!(timers.poll(), 0);
+    while let Some(at) = timers.next_deadline() {
+        std::thread::sleep(at.saturating_duration_since(Instant::now()));
+        timers.poll();
+        if log.borrow().len() == 3 {
+            timers.cancel(every);
+        }
+    }
+    assert!(started.elapsed() >= ms(20));
+    assert_eq!(*log.borrow(), vec!["after", "every", "every"]);
+    assert!(timers.is_empty());
+}
@@ -0,0 +1,74 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Somewhere to report a timer's health to.
+///
+/// Every method has an empty default, so a sink only implements what it
+/// reports.
+///
+pub trait MetricsSink: Send + Sync {
+    /// The timer called `timer` expired `latency` after its deadline.
+    ///
+    fn expired(&self, _timer: &str, _latency: Duration) {}
+    /// The timer called `timer` expired a whole step or more after its
+    /// deadline, effectively missing a tick. Reported along with `expired`.
+    ///
+    fn overrun(&self, _timer: &str, _latency: Duration) {}
+    /// The timer called `timer` was reset.
+    ///
+    fn reset(&self, _timer: &str) {}
+}
+
+/// The set of sinks a timer reports to, each under a name.
+///
+#[derive(Default)]
+pub struct Sinks {
+    sinks: Mutex<Vec<(String, Box<dyn MetricsSink>)>>,
+}
+
+impl Sinks {
+    /// Add a sink, reporting the timer as `name`.
+    ///
+    pub fn push(&self, name: &str, sink: Box<dyn MetricsSink>) {
+        self.sinks.lock().unwrap().push((name.to_owned(), sink));
+    }
+    /// Report an expiry to every sink.
+    ///
+    pub fn expired(&self, latency: Duration, overrun: bool) {
+        for (name, sink) in self.sinks.lock().unwrap().iter() {
+            sink.expired(name, latency);
+            if overrun {
+                sink.overrun(name, latency);
+            }
+        }
+    }
+    /// Report a reset to every sink.
+    ///
+    pub fn reset(&self) {
+        for (name, sink) in self.sinks.lock().unwrap().iter() {
+            sink.reset(name);
+        }
+    }
+}
+
+#[test]
+fn sinks_report_overruns() {
+    use std::sync::Arc;
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+    impl MetricsSink for Arc<Recorder> {
+        fn expired(&self, timer: &str, _: Duration) {
+            self.0.lock().unwrap().push(format!("{} expired", timer));
+        }
+        fn overrun(&self, timer: &str, _: Duration) {
+            self.0.lock().unwrap().push(format!("{} overrun", timer));
+        }
+    }
+    let recorder = Arc::new(Recorder::default());
+    let sinks = Sinks::default();
+    sinks.push("tick", Box::new(recorder.clone()));
+    sinks.expired(Duration::from_millis(1), false);
+    sinks.expired(Duration::from_secs(2), true);
+    sinks.reset();
+    assert_eq!(*recorder.0.lock().unwrap(), vec!["tick expired", "tick expired", "tick overrun"]);
+}
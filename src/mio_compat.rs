@@ -0,0 +1,43 @@
+use mio::event::Source;
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use TimerFd;
+
+/// Registers the timer's eventfd, which becomes readable on expiry.
+///
+/// Drain it on each readable event, since mio readiness is edge triggered.
+///
+impl Source for TimerFd {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
+#[test]
+fn timer_mio_source() {
+    use mio::{Events, Poll};
+    use std::sync::{Arc, Condvar};
+    use std::time::Duration;
+    use Timer;
+    let mut poll = Poll::new().unwrap();
+    let mut t = Timer::new(Duration::from_millis(10), Duration::from_secs(0), Arc::new(Condvar::new()));
+    let mut fd = t.enable_eventfd().unwrap();
+    poll.registry().register(&mut fd, Token(7), Interest::READABLE).unwrap();
+    t.start();
+    let mut events = Events::with_capacity(4);
+    poll.poll(&mut events, Some(Duration::from_secs(1))).unwrap();
+    let event = events.iter().next().unwrap();
+    assert_eq!(event.token(), Token(7));
+    assert!(event.is_readable());
+    assert!(fd.drain().unwrap() >= 1);
+    poll.registry().deregister(&mut fd).unwrap();
+    t.stop();
+}
@@ -0,0 +1,128 @@
+use std::io;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use thread::ThreadConfig;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// How many jobs may wait for each worker before `execute` turns new ones
+/// away.
+///
+pub const QUEUED_PER_WORKER: usize = 16;
+
+/// A fixed size pool of worker threads.
+///
+/// Used to run expiry callbacks off of the timer thread so that a slow
+/// callback doesn't delay subsequent ticks. The queue is bounded, so
+/// callbacks that can't keep up are dropped rather than piling up without
+/// limit. Dropping the pool waits for queued jobs to finish and joins every
+/// worker.
+///
+pub struct ThreadPool {
+    // Sending half of the job queue, dropped on shutdown.
+    jobs: Option<SyncSender<Job>>,
+    // Worker thread handles to join on shutdown.
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    /// Create a new pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The number of worker threads, at least one.
+    /// * `threads` - How to spawn them.
+    /// * `what` - What the pool is for, to name its threads after.
+    ///
+    pub fn new(size: usize, threads: &ThreadConfig, what: &str) -> io::Result<ThreadPool> {
+        let (tx, rx) = sync_channel::<Job>(size.max(1) * QUEUED_PER_WORKER);
+        let rx = Arc::new(Mutex::new(rx));
+        let mut pool = ThreadPool {
+            jobs: Some(tx),
+            workers: Vec::with_capacity(size.max(1)),
+        };
+        for i in 0..size.max(1) {
+            let rx = rx.clone();
+            // On failure, dropping the pool joins the workers spawned so far.
+            let worker = threads.spawn(&format!("{}-{}", what, i), move || ThreadPool::work(rx))?;
+            pool.workers.push(worker);
+        }
+        Ok(pool)
+    }
+    /// Internal worker loop.
+    ///
+    fn work(rx: Arc<Mutex<Receiver<Job>>>) {
+        loop {
+            let job = match rx.lock().unwrap().recv() {
+                Ok(job) => job,
+                Err(_) => return,
+            };
+            job();
+        }
+    }
+    /// Queue a job to run on the next free worker.
+    ///
+    /// Returns false, dropping the job, if the queue is full.
+    ///
+    pub fn execute<F>(&self, f: F) -> bool
+        where F: FnOnce() + Send + 'static
+    {
+        let jobs = match self.jobs {
+            Some(ref jobs) => jobs,
+            None => return false,
+        };
+        match jobs.try_send(Box::new(f)) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => false,
+            Err(TrySendError::Disconnected(_)) => panic!("Couldn't queue job on pool!"),
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            worker.join().expect("Couldn't join pool thread!");
+        }
+    }
+}
+
+#[test]
+fn pool_runs_jobs_in_parallel() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+    let count = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+    {
+        let pool = ThreadPool::new(4, &ThreadConfig::default(), "pool").unwrap();
+        for _ in 0..4 {
+            let count = count.clone();
+            pool.execute(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                count.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+    }
+    assert_eq!(count.load(Ordering::SeqCst), 4);
+    assert!(started.elapsed() < Duration::from_millis(150));
+}
+
+#[test]
+fn pool_drops_jobs_when_full() {
+    use std::sync::mpsc::channel;
+    let pool = ThreadPool::new(1, &ThreadConfig::default(), "pool").unwrap();
+    let (release, held) = channel::<()>();
+    let (started, running) = channel();
+    assert!(pool.execute(move || {
+        started.send(()).unwrap();
+        let _ = held.recv();
+    }));
+    running.recv().unwrap();
+    for _ in 0..QUEUED_PER_WORKER {
+        assert!(pool.execute(|| {}));
+    }
+    assert!(!pool.execute(|| {}));
+    release.send(()).unwrap();
+}
@@ -0,0 +1,256 @@
+use callback::{Callbacks, OverlapPolicy};
+use clock::Timestamp;
+use event::{Event, ExpiryEvent, Subscribers};
+use std::convert::TryFrom;
+use std::io;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// How a `PosixTimer` is notified of each expiry.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// `SIGEV_THREAD`: the C library runs each expiry on a thread of its
+    /// own, where the timer records it and runs its callbacks.
+    Thread,
+    /// `SIGEV_SIGNAL`: the kernel raises the given signal. The application
+    /// handles it however its signal driven design already does, then calls
+    /// `PosixTimer::record_signal` from outside the signal handler.
+    Signal(::libc::c_int),
+}
+
+/// `struct sigevent` laid out for `SIGEV_THREAD`, whose members the libc
+/// crate hides inside an opaque union.
+#[repr(C)]
+struct ThreadSigEvent {
+    sigev_value: ::libc::sigval,
+    sigev_signo: ::libc::c_int,
+    sigev_notify: ::libc::c_int,
+    sigev_notify_function: extern "C" fn(::libc::sigval),
+    sigev_notify_attributes: *mut ::libc::pthread_attr_t,
+    #[cfg(target_pointer_width = "64")]
+    _pad: [::libc::c_int; 8],
+    #[cfg(target_pointer_width = "32")]
+    _pad: [::libc::c_int; 11],
+}
+
+const _: () = assert!(std::mem::size_of::<ThreadSigEvent>() == std::mem::size_of::<::libc::sigevent>());
+
+/// State shared with expiry notifications.
+struct Shared {
+    // The kernel's id for the timer.
+    id: AtomicPtr<::libc::c_void>,
+    expiries: AtomicUsize,
+    timed_out: Arc<Condvar>,
+    callbacks: Callbacks,
+    subscribers: Subscribers,
+    step: Duration,
+    // When the timer was last armed.
+    armed: Mutex<Instant>,
+}
+
+impl Shared {
+    /// Record a delivered expiry, along with any the kernel coalesced into
+    /// it.
+    ///
+    fn expired(&self) {
+        let overrun = unsafe { ::libc::timer_getoverrun(self.id.load(Ordering::SeqCst)) };
+        let missed = usize::try_from(overrun).unwrap_or(0);
+        let count = self.expiries.fetch_add(missed + 1, Ordering::SeqCst) + missed + 1;
+        let fired = Instant::now();
+        let armed = *self.armed.lock().unwrap();
+        // The kernel re-arms the timer by itself, so the deadline is the
+        // last whole step since it was armed.
+        let steps = (fired - armed).as_nanos() / self.step.as_nanos().max(1);
+        let deadline = u32::try_from(steps).ok()
+            .and_then(|n| self.step.checked_mul(n))
+            .and_then(|ahead| armed.checked_add(ahead))
+            .unwrap_or(fired);
+        self.timed_out.notify_all();
+        self.subscribers.emit(Event::Expired(ExpiryEvent {
+            count,
+            deadline: Timestamp::Monotonic(deadline),
+            fired: Timestamp::Monotonic(fired),
+            coalesced: missed + 1,
+        }));
+        self.callbacks.run(None, OverlapPolicy::default());
+    }
+}
+
+/// Entry point for `SIGEV_THREAD` notifications.
+///
+extern "C" fn notify(value: ::libc::sigval) {
+    let weak = unsafe { &*(value.sival_ptr as *const Weak<Shared>) };
+    if let Some(shared) = weak.upgrade() {
+        shared.expired();
+    }
+}
+
+/// A timer backed by the kernel's POSIX per-process timers.
+///
+/// Rather than a thread of its own waiting on a condition variable, the
+/// kernel tracks the count down and notifies the process of each expiry,
+/// either on a short lived thread or with a signal. Expiries are mapped
+/// back onto the same `timed_out` condition, events and callbacks a
+/// `Timer` uses.
+///
+pub struct PosixTimer {
+    shared: Arc<Shared>,
+    // Condition variable signalled if/when the timer expires.
+    timed_out: Arc<Condvar>,
+    // The amount of time to count down from.
+    step: Duration,
+}
+
+impl PosixTimer {
+    /// Create a new, disarmed timer.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The duration of time to wait for each count down.
+    /// * `timed_out` - Condition to signal if the timer expires.
+    /// * `delivery` - How the kernel notifies the process of an expiry.
+    ///
+    pub fn new(step: Duration, timed_out: Arc<Condvar>, delivery: Delivery) -> io::Result<PosixTimer> {
+        let shared = Arc::new(Shared {
+            id: AtomicPtr::new(std::ptr::null_mut()),
+            expiries: AtomicUsize::new(0),
+            timed_out: timed_out.clone(),
+            callbacks: Callbacks::default(),
+            subscribers: Subscribers::default(),
+            step,
+            armed: Mutex::new(Instant::now()),
+        });
+        let mut id: ::libc::timer_t = std::ptr::null_mut();
+        let rc = match delivery {
+            Delivery::Thread => {
+                // Leaked, so that a notification racing with drop still
+                // finds a valid, if dead, reference.
+                let weak = Box::into_raw(Box::new(Arc::downgrade(&shared)));
+                let mut event = ThreadSigEvent {
+                    sigev_value: ::libc::sigval { sival_ptr: weak as *mut ::libc::c_void },
+                    sigev_signo: 0,
+                    sigev_notify: ::libc::SIGEV_THREAD,
+                    sigev_notify_function: notify,
+                    sigev_notify_attributes: std::ptr::null_mut(),
+                    _pad: Default::default(),
+                };
+                let event = &mut event as *mut ThreadSigEvent as *mut ::libc::sigevent;
+                unsafe { ::libc::timer_create(::libc::CLOCK_MONOTONIC, event, &mut id) }
+            },
+            Delivery::Signal(signo) => {
+                let mut event: ::libc::sigevent = unsafe { std::mem::zeroed() };
+                event.sigev_notify = ::libc::SIGEV_SIGNAL;
+                event.sigev_signo = signo;
+                unsafe { ::libc::timer_create(::libc::CLOCK_MONOTONIC, &mut event, &mut id) }
+            },
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        shared.id.store(id, Ordering::SeqCst);
+        Ok(PosixTimer { shared, timed_out, step })
+    }
+    /// Arm the kernel timer to expire every `step` from now.
+    ///
+    fn arm(&self, step: Duration) -> io::Result<()> {
+        let spec = ::libc::timespec {
+            tv_sec: step.as_secs() as ::libc::time_t,
+            tv_nsec: step.subsec_nanos() as ::libc::c_long,
+        };
+        let value = ::libc::itimerspec { it_interval: spec, it_value: spec };
+        *self.shared.armed.lock().unwrap() = Instant::now();
+        let id = self.shared.id.load(Ordering::SeqCst);
+        match unsafe { ::libc::timer_settime(id, 0, &value, std::ptr::null_mut()) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+    /// Start counting down.
+    ///
+    pub fn start(&mut self) -> io::Result<()> {
+        self.arm(self.step)
+    }
+    /// Stop counting down.
+    ///
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.arm(Duration::from_secs(0))
+    }
+    /// Start the count down over from a full `step`.
+    ///
+    pub fn reset(&mut self) -> io::Result<()> {
+        self.arm(self.step)
+    }
+    /// The condition signalled each time the timer expires.
+    ///
+    pub fn timed_out(&self) -> &Arc<Condvar> {
+        &self.timed_out
+    }
+    /// The duration of time counted down from.
+    ///
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+    /// Number of times this timer has expired.
+    ///
+    pub fn expiries(&self) -> usize {
+        self.shared.expiries.load(Ordering::SeqCst)
+    }
+    /// Record an expiry delivered as a signal, running the timer's
+    /// callbacks and delivering its events.
+    ///
+    /// Only needed with `Delivery::Signal`. Call it once per signal
+    /// received, from ordinary code rather than the signal handler itself,
+    /// such as after `sigwait` or reading a signalfd.
+    ///
+    pub fn record_signal(&self) {
+        self.shared.expired();
+    }
+    /// Subscribe to events from this timer.
+    ///
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.shared.subscribers.subscribe()
+    }
+    /// Register a callback to run each time the timer expires.
+    ///
+    pub fn on_expiry<F>(&mut self, f: F)
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.shared.callbacks.push(f);
+    }
+}
+
+impl Drop for PosixTimer {
+    fn drop(&mut self) {
+        unsafe {
+            ::libc::timer_delete(self.shared.id.load(Ordering::SeqCst));
+        }
+    }
+}
+
+#[test]
+fn posix_timer_thread_delivery() {
+    let counted = Arc::new(AtomicUsize::new(0));
+    let c = counted.clone();
+    let mut t = PosixTimer::new(Duration::from_millis(10), Arc::new(Condvar::new()), Delivery::Thread)
+        .unwrap();
+    assert_eq!(t.step(), Duration::from_millis(10));
+    t.on_expiry(move || {
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+    let events = t.subscribe();
+    t.start().unwrap();
+    match events.recv_timeout(Duration::from_secs(1)).unwrap() {
+        Event::Expired(e) => assert!(e.count >= 1),
+        other => panic!("unexpected event {:?}", other),
+    }
+    std::thread::sleep(Duration::from_millis(45));
+    t.stop().unwrap();
+    let expiries = t.expiries();
+    assert!((3..10).contains(&expiries));
+    std::thread::sleep(Duration::from_millis(30));
+    assert_eq!(t.expiries(), expiries);
+    assert!(counted.load(Ordering::SeqCst) >= 3);
+}
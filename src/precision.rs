@@ -0,0 +1,76 @@
+use std::time::Duration;
+use Timer;
+
+/// Final stretch of each count down a high precision timer busy-waits
+/// through, rather than trusting a timed wait to wake up on time.
+///
+pub const SPIN_WINDOW: Duration = Duration::from_millis(1);
+
+/// Number of recent expiries a high precision timer averages its wait
+/// overshoot over.
+///
+const CORRECTION_WINDOW: usize = 16;
+
+/// How hard a timer works to fire exactly on its deadlines.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precision {
+    /// Sleep until each deadline, firing as late as the platform's timed
+    /// waits wake up, typically tens of microseconds to a few milliseconds.
+    #[default]
+    Standard,
+    /// Aim to fire within 100µs of each deadline.
+    ///
+    /// Calibrates and continuously corrects for the platform's wait
+    /// overshoot, so the timer wakes up just before the last millisecond of
+    /// each count down, then busy-waits through that millisecond. The cost
+    /// is a core kept busy for up to a millisecond plus the overshoot per
+    /// expiry, e.g., some 10% of a core for a 10ms step.
+    High,
+}
+
+impl Timer {
+    /// Choose how hard the timer works to fire exactly on its deadlines.
+    ///
+    /// `Precision::High` turns on `set_calibrate` and
+    /// `set_drift_correction` too, while `Precision::Standard` turns all of
+    /// them off. Check the precision achieved with `stats`. Busy-waiting
+    /// only happens on the monotonic and wall clocks. Takes effect the next
+    /// time the timer is started.
+    ///
+    pub fn set_precision(&mut self, precision: Precision) {
+        let high = precision == Precision::High;
+        self.calibrate = high;
+        self.correction = if high { Some(CORRECTION_WINDOW) } else { None };
+        self.spin = if high { SPIN_WINDOW } else { Duration::from_secs(0) };
+    }
+}
+
+#[test]
+fn timer_high_precision() {
+    use clock::Timestamp;
+    use event::{Event, ExpiryEvent};
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(5), ms(0), Arc::new(Condvar::new()));
+    t.set_precision(Precision::High);
+    t.set_max_expiries(20);
+    let events = t.subscribe();
+    t.start();
+    std::thread::sleep(ms(200));
+    t.stop();
+    assert_eq!(t.stats().expiries, 20);
+    // The median, as the odd expiry may be preempted by other tests.
+    let mut errors: Vec<Duration> = events.try_iter()
+        .filter_map(|event| match event {
+            Event::Expired(ExpiryEvent {
+                fired: Timestamp::Monotonic(fired),
+                deadline: Timestamp::Monotonic(deadline),
+                ..
+            }) => Some(fired.saturating_duration_since(deadline)),
+            _ => None,
+        })
+        .collect();
+    errors.sort();
+    assert!(errors[errors.len() / 2] < Duration::from_micros(100), "{:?}", errors);
+}
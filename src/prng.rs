@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Multiplier scrambling each xorshift64* output.
+///
+const MULTIPLIER: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// Stands in for a zero seed, which would stick xorshift at zero forever.
+///
+const FALLBACK_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// State shared by every thread drawing jitter.
+///
+static STATE: AtomicU64 = AtomicU64::new(FALLBACK_SEED);
+
+/// Seeds `STATE` from the clock on first use.
+///
+static SEEDED: Once = Once::new();
+
+/// Advance xorshift64* state `x` by one step.
+///
+fn step(mut x: u64) -> u64 {
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    x
+}
+
+/// Mix `seed` so that nearby seeds, such as successive clock readings,
+/// start far apart, and never zero.
+///
+fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(FALLBACK_SEED);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    match z ^ (z >> 31) {
+        0 => FALLBACK_SEED,
+        z => z,
+    }
+}
+
+/// Seed from the clock, unless already seeded.
+///
+fn seed_from_clock() {
+    SEEDED.call_once(|| {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let local = 0u8;
+        // The stack address adds a little per process entropy.
+        let seed = now.as_nanos() as u64 ^ (&local as *const u8 as u64).rotate_left(32);
+        STATE.store(mix(seed), Ordering::Relaxed);
+    });
+}
+
+/// Seed the generator that jitter is drawn from, making it repeatable.
+///
+/// Only available without the `rand` feature, when jitter comes from a
+/// tiny built in xorshift64* generator rather than the `rand` crate. It's
+/// otherwise seeded from the clock on first use. Not fit for anything that
+/// needs unpredictable numbers.
+///
+pub fn seed_jitter(seed: u64) {
+    seed_from_clock();
+    STATE.store(mix(seed), Ordering::Relaxed);
+}
+
+/// Draw the next number from the generator with state `state`.
+///
+fn next(state: &AtomicU64) -> u64 {
+    let previous = state.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| Some(step(x)))
+        .unwrap_or_else(|x| x);
+    step(previous).wrapping_mul(MULTIPLIER)
+}
+
+/// Draw the next number from the shared generator.
+///
+pub fn random() -> u64 {
+    seed_from_clock();
+    next(&STATE)
+}
+
+#[test]
+fn prng_is_uniform() {
+    const SAMPLES: usize = 100_000;
+    const BUCKETS: usize = 16;
+    // A fixed seed, so that the statistical bounds pass or fail for good.
+    let state = AtomicU64::new(mix(42));
+    let mut counts = [0usize; BUCKETS];
+    let mut ones = 0u64;
+    for _ in 0..SAMPLES {
+        let x = next(&state);
+        counts[(x % BUCKETS as u64) as usize] += 1;
+        ones += x.count_ones() as u64;
+    }
+    // Chi-squared with 15 degrees of freedom is under 37.7 with
+    // probability 0.999.
+    let expected = (SAMPLES / BUCKETS) as f64;
+    let chi_squared: f64 = counts.iter()
+        .map(|&count| (count as f64 - expected).powi(2) / expected)
+        .sum();
+    assert!(chi_squared < 37.7, "{} {:?}", chi_squared, counts);
+    // About half of all bits are set.
+    let mean_ones = ones as f64 / SAMPLES as f64;
+    assert!((mean_ones - 32.0).abs() < 0.1, "{}", mean_ones);
+}
+
+#[test]
+fn prng_seeds_repeatably() {
+    let draw = |seed| {
+        let state = AtomicU64::new(mix(seed));
+        (0..5).map(|_| next(&state)).collect::<Vec<u64>>()
+    };
+    assert_eq!(draw(42), draw(42));
+    assert_ne!(draw(42), draw(43));
+    assert!(draw(0).iter().all(|&x| x != 0));
+}
+
+#[test]
+fn jitter_without_rand() {
+    use config::JitterPolicy;
+    use std::time::Duration;
+    use Timer;
+    let ms = Duration::from_millis;
+    let mut seen = [false; 10];
+    for _ in 0..1000 {
+        let wait = Timer::calculate_wait_duration(ms(100), ms(10), JitterPolicy::Additive);
+        assert!(wait >= ms(100) && wait < ms(110), "{:?}", wait);
+        seen[(wait - ms(100)).as_millis() as usize] = true;
+    }
+    assert!(seen.iter().all(|&seen| seen));
+}
@@ -0,0 +1,99 @@
+use metrics::MetricsSink;
+use prometheus::{Histogram, HistogramOpts, IntCounter, Opts, Registry};
+use std::time::Duration;
+use Timer;
+
+/// Per-timer Prometheus collectors, labelled with the timer's name.
+///
+struct TimerMetrics {
+    expiries: IntCounter,
+    resets: IntCounter,
+    overruns: IntCounter,
+    // Time from each deadline to the expiry actually firing.
+    latency: Histogram,
+}
+
+impl TimerMetrics {
+    /// Create the collectors for the timer called `name`, and register them
+    /// with `registry`.
+    ///
+    fn register(name: &str, registry: &Registry) -> prometheus::Result<TimerMetrics> {
+        let counter = |metric: &str, help: &str| {
+            IntCounter::with_opts(Opts::new(metric, help).const_label("timer", name))
+        };
+        let metrics = TimerMetrics {
+            expiries: counter("timer_expiries_total", "Number of times the timer expired.")?,
+            resets: counter("timer_resets_total", "Number of times the timer was reset.")?,
+            overruns: counter("timer_overruns_total",
+                              "Number of expiries that fired a whole step or more late.")?,
+            latency: Histogram::with_opts(
+                HistogramOpts::new("timer_expiry_latency_seconds",
+                                   "Time from each deadline to the timer expiring.")
+                    .const_label("timer", name)
+                    .buckets(vec![0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]))?,
+        };
+        registry.register(Box::new(metrics.expiries.clone()))?;
+        registry.register(Box::new(metrics.resets.clone()))?;
+        registry.register(Box::new(metrics.overruns.clone()))?;
+        registry.register(Box::new(metrics.latency.clone()))?;
+        Ok(metrics)
+    }
+}
+
+impl MetricsSink for TimerMetrics {
+    fn expired(&self, _: &str, latency: Duration) {
+        self.expiries.inc();
+        self.latency.observe(latency.as_secs_f64());
+    }
+    fn overrun(&self, _: &str, _: Duration) {
+        self.overruns.inc();
+    }
+    fn reset(&self, _: &str) {
+        self.resets.inc();
+    }
+}
+
+impl Timer {
+    /// Export this timer's expiries, resets, overruns and expiry latency to
+    /// `registry`, labelled `timer="<name>"`.
+    ///
+    /// An overrun is an expiry that fired a whole step or more after its
+    /// deadline, meaning a tick was effectively missed. Fails if another
+    /// timer with the same name is already registered.
+    ///
+    pub fn register_metrics(&mut self, name: &str, registry: &Registry) -> prometheus::Result<()> {
+        let metrics = TimerMetrics::register(name, registry)?;
+        self.add_metrics_sink(name, metrics);
+        Ok(())
+    }
+}
+
+#[test]
+fn timer_prometheus_metrics() {
+    use std::sync::{Arc, Condvar};
+    let registry = Registry::new();
+    let mut t = Timer::new(Duration::from_millis(10), Duration::from_secs(0), Arc::new(Condvar::new()));
+    t.register_metrics("heartbeat", &registry).unwrap();
+    let mut other = Timer::new(Duration::from_millis(10), Duration::from_secs(0), Arc::new(Condvar::new()));
+    assert!(other.register_metrics("heartbeat", &registry).is_err());
+    other.register_metrics("election", &registry).unwrap();
+    t.start();
+    std::thread::sleep(Duration::from_millis(35));
+    t.reset();
+    t.stop();
+    let families = registry.gather();
+    let value = |name: &str| {
+        let family = families.iter().find(|f| f.name() == name).unwrap();
+        let metric = family.get_metric().iter()
+            .find(|m| m.get_label().iter().any(|l| l.value() == "heartbeat"))
+            .unwrap();
+        match family.get_field_type() {
+            prometheus::proto::MetricType::HISTOGRAM => metric.get_histogram().get_sample_count(),
+            _ => metric.get_counter().get_value() as u64,
+        }
+    };
+    let expiries = t.expiries.load(std::sync::atomic::Ordering::SeqCst) as u64;
+    assert_eq!(value("timer_expiries_total"), expiries);
+    assert_eq!(value("timer_expiry_latency_seconds"), expiries);
+    assert_eq!(value("timer_resets_total"), 1);
+}
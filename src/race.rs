@@ -0,0 +1,186 @@
+use std::time::{Duration, SystemTime};
+use schedule::{FixedStep, Schedule, TickContext};
+use {Timer, WallSchedule};
+
+/// The deadlines of one timer entered into a race.
+///
+struct Entrant {
+    // Wall clock time to expire at first, if set.
+    fire_at: Option<SystemTime>,
+    // Wall clock schedule to follow, if set.
+    schedule: Option<WallSchedule>,
+    // Sequence of count downs to follow, if set.
+    intervals: Option<Box<dyn Schedule + Send>>,
+    // The timer's own step and jitter, followed otherwise.
+    pacing: FixedStep,
+}
+
+impl Entrant {
+    /// Take the deadlines out of `timer`, stopping it if running.
+    ///
+    fn new(timer: &mut Timer) -> Entrant {
+        if timer.handle.is_some() {
+            timer.stop();
+        }
+        Entrant {
+            fire_at: timer.fire_at.lock().unwrap().take(),
+            schedule: timer.schedule.lock().unwrap().take(),
+            intervals: timer.intervals.lock().unwrap().take(),
+            pacing: *timer.pacing.lock().unwrap(),
+        }
+    }
+}
+
+impl Schedule for Entrant {
+    fn next_interval(&mut self, ctx: &TickContext) -> Option<Duration> {
+        let at = match (self.fire_at.take(), self.schedule.as_mut()) {
+            (Some(at), _) => at,
+            (None, Some(schedule)) => schedule(ctx.now)?,
+            (None, None) => {
+                return match self.intervals {
+                    Some(ref mut intervals) => intervals.next_interval(ctx),
+                    None => self.pacing.next_interval(ctx),
+                };
+            },
+        };
+        Some(at.duration_since(ctx.now).unwrap_or_default())
+    }
+    fn restart(&mut self) {
+        if let Some(ref mut intervals) = self.intervals {
+            intervals.restart();
+        }
+    }
+}
+
+impl Timer {
+    /// Create a new timer that expires at whichever of two timers' deadlines
+    /// comes first, e.g., 30s idle or 5min absolute max.
+    ///
+    /// Each timer keeps its deadline until it's the one that expires, as
+    /// with `Schedule::earliest_of`, so the 5min max fires even while the
+    /// 30s idle keeps winning. A timer whose schedule runs out drops out of
+    /// the race, and the returned timer stops once both have.
+    ///
+    /// Both timers are stopped if running. The returned timer is `a`, with
+    /// the earlier step of the two and everything else it was configured
+    /// with, e.g., its condition, clock, ledger, expiry limit and cancel
+    /// token. `b`'s callbacks, subscribers and eventfd move over to it,
+    /// with its callbacks running after `a`'s under `a`'s dispatch and
+    /// hooks. The rest of `b`'s configuration is dropped along with it.
+    ///
+    pub fn race(mut a: Timer, mut b: Timer) -> Timer {
+        let step = std::cmp::min(a.step(), b.step());
+        let (first, second) = (Entrant::new(&mut a), Entrant::new(&mut b));
+        a.callbacks.absorb(&b.callbacks);
+        a.subscribers.absorb(&b.subscribers);
+        #[cfg(all(feature = "eventfd", target_os = "linux"))]
+        {
+            let moved = std::mem::take(&mut *b.eventfd.lock().unwrap());
+            a.eventfd.lock().unwrap().extend(moved);
+        }
+        *a.pacing.lock().unwrap() = FixedStep::new(step);
+        a.set_schedule(first.earliest_of(second));
+        a
+    }
+}
+
+#[test]
+fn timer_race_earliest_wins() {
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Condvar};
+    let cv = Arc::new(Condvar::new());
+    let slow = Timer::new(Duration::from_secs(60), Duration::from_secs(0), cv.clone());
+    let fast = Timer::new(Duration::from_millis(20), Duration::from_secs(0), cv);
+    let mut t = Timer::race(slow, fast);
+    assert_eq!(t.step(), Duration::from_millis(20));
+    t.start();
+    std::thread::sleep(Duration::from_millis(70));
+    t.stop();
+    assert!(t.expiries.load(Ordering::SeqCst) >= 2);
+}
+
+#[test]
+fn timer_race_stops_when_both_run_out() {
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Condvar, Mutex};
+    let cv = Arc::new(Condvar::new());
+    let deadlines = Arc::new(Mutex::new(Vec::new()));
+    let mut once = Timer::new(Duration::from_secs(60), Duration::from_secs(0), cv.clone());
+    let mut twice = Timer::new(Duration::from_secs(60), Duration::from_secs(0), cv);
+    let (mut left_once, mut left_twice) = (1, 2);
+    let seen = deadlines.clone();
+    once.set_wall_schedule(move |now| {
+        seen.lock().unwrap().push("once");
+        left_once -= 1;
+        if left_once >= 0 { Some(now + Duration::from_millis(10)) } else { None }
+    });
+    twice.set_wall_schedule(move |now| {
+        left_twice -= 1;
+        if left_twice >= 0 { Some(now + Duration::from_millis(15)) } else { None }
+    });
+    let mut t = Timer::race(once, twice);
+    t.start();
+    std::thread::sleep(Duration::from_millis(80));
+    // `once` wins at 10ms and drops out, then `twice` expires at 15ms and
+    // 30ms before it runs out too.
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 3);
+    assert_eq!(deadlines.lock().unwrap().len(), 2);
+    assert!(!t.lifecycle.is_running());
+    t.stop();
+}
+
+#[test]
+fn timer_race_keeps_the_losers_deadline() {
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let cv = Arc::new(Condvar::new());
+    let mut max = Timer::new(ms(60), ms(0), cv.clone());
+    max.set_schedule(::Intervals::new(vec![ms(70)]));
+    let idle = Timer::new(ms(20), ms(0), cv.clone());
+    let mut t = Timer::race(max, idle);
+    t.set_max_expiries(4);
+    let got = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let (g, started) = (got.clone(), std::time::Instant::now());
+    t.on_expiry(move || g.lock().unwrap().push(started.elapsed()));
+    t.start();
+    assert!(t.wait_for_completion(Duration::from_secs(1)));
+    t.stop();
+    // The idle step wins three times, then the max fires 10ms later.
+    let got = got.lock().unwrap();
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 4);
+    assert!(got[3] - got[2] < ms(15), "{:?}", got);
+}
+
+#[test]
+fn timer_race_keeps_callbacks_and_subscribers() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Condvar};
+    use Event;
+    let ms = Duration::from_millis;
+    let cv = Arc::new(Condvar::new());
+    let (a_calls, b_calls) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let mut a = Timer::new(ms(10), ms(0), cv.clone());
+    let c = a_calls.clone();
+    a.on_expiry(move || {
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+    a.set_max_expiries(2);
+    let mut b = Timer::new(ms(60), ms(0), cv);
+    let c = b_calls.clone();
+    b.on_expiry(move || {
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+    let events = b.subscribe();
+    b.start();
+    let mut t = Timer::race(a, b);
+    t.start();
+    assert!(t.wait_for_completion(Duration::from_secs(1)));
+    t.stop();
+    // `a`'s expiry limit still applies, and both sets of callbacks ran.
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 2);
+    assert_eq!(a_calls.load(Ordering::SeqCst), 2);
+    assert_eq!(b_calls.load(Ordering::SeqCst), 2);
+    let expired = events.try_iter().filter(|event| matches!(event, Event::Expired(_))).count();
+    assert_eq!(expired, 2);
+}
@@ -0,0 +1,88 @@
+use std::io;
+use std::os::unix::thread::JoinHandleExt;
+use std::thread::JoinHandle;
+use Timer;
+
+/// A Linux realtime scheduling class.
+///
+/// Threads in either class preempt every normally scheduled thread, so a
+/// timer thread running in one isn't delayed by a busy machine.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedPolicy {
+    /// `SCHED_FIFO`: run until blocking or preempted by a higher priority.
+    Fifo,
+    /// `SCHED_RR`: like `Fifo`, but time sliced among equal priorities.
+    RoundRobin,
+}
+
+impl SchedPolicy {
+    fn raw(self) -> ::libc::c_int {
+        match self {
+            SchedPolicy::Fifo => ::libc::SCHED_FIFO,
+            SchedPolicy::RoundRobin => ::libc::SCHED_RR,
+        }
+    }
+}
+
+/// Check that `priority` is valid for `policy`, and that this process is
+/// allowed to use it.
+///
+fn check(policy: SchedPolicy, priority: i32) -> io::Result<()> {
+    let (min, max) = unsafe {
+        (::libc::sched_get_priority_min(policy.raw()), ::libc::sched_get_priority_max(policy.raw()))
+    };
+    if priority < min || priority > max {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  format!("priority {} outside of {}..={}", priority, min, max)));
+    }
+    if unsafe { ::libc::geteuid() } != 0 {
+        let mut limit = ::libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        if unsafe { ::libc::getrlimit(::libc::RLIMIT_RTPRIO, &mut limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if (priority as ::libc::rlim_t) > limit.rlim_cur {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                      format!("RLIMIT_RTPRIO only allows priority {}", limit.rlim_cur)));
+        }
+    }
+    Ok(())
+}
+
+/// Move an already spawned thread into a realtime scheduling class.
+///
+pub fn apply<T>(handle: &JoinHandle<T>, policy: SchedPolicy, priority: i32) -> io::Result<()> {
+    let mut param: ::libc::sched_param = unsafe { std::mem::zeroed() };
+    param.sched_priority = priority;
+    match unsafe { ::libc::pthread_setschedparam(handle.as_pthread_t(), policy.raw(), &param) } {
+        0 => Ok(()),
+        rc => Err(io::Error::from_raw_os_error(rc)),
+    }
+}
+
+impl Timer {
+    /// Run the timer thread in a realtime scheduling class, so its ticks
+    /// aren't delayed by normally scheduled threads.
+    ///
+    /// Fails up front if `priority` is out of range for `policy`, or if the
+    /// process lacks the privilege to use it. Takes effect the next time
+    /// the timer is started.
+    ///
+    pub fn set_realtime(&mut self, policy: SchedPolicy, priority: i32) -> io::Result<()> {
+        check(policy, priority)?;
+        self.realtime = Some((policy, priority));
+        Ok(())
+    }
+}
+
+#[test]
+fn realtime_checks_priority() {
+    use std::sync::{Arc, Condvar};
+    use std::time::Duration;
+    let mut t = Timer::new(Duration::from_secs(1), Duration::from_secs(0), Arc::new(Condvar::new()));
+    let err = t.set_realtime(SchedPolicy::Fifo, 0).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let err = t.set_realtime(SchedPolicy::RoundRobin, 1000).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(t.realtime.is_none());
+}
@@ -0,0 +1,233 @@
+use cron::civil_from_days;
+use schedule::{Schedule, TickContext};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Most hours to step through looking for an occurrence before giving up,
+/// over fifty years' worth.
+///
+const SEARCH_LIMIT: usize = 500_000;
+
+/// How often a recurrence repeats, before `interval` and the `by_` rules.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frequency {
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// A day of the week.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// A schedule given by a calendar recurrence rule, evaluated in UTC.
+///
+/// A practical subset of iCalendar's RRULE: `FREQ` of hourly, daily,
+/// weekly or monthly, with `INTERVAL`, `BYDAY`, `BYHOUR`, `COUNT` and
+/// `UNTIL`. Occurrences fall on the minute and second of the first one,
+/// `start`, and are counted from it. As in RRULE, weeks start on Monday,
+/// and unrestricted fields follow `start`: a weekly rule repeats on its
+/// weekday, a daily one at its hour, and a monthly one on its day of the
+/// month.
+///
+/// E.g., every second Tuesday at 09:00 is a weekly rule with an interval
+/// of two, by day Tuesday and by hour 9, starting on a Tuesday at 09:00.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recurrence {
+    frequency: Frequency,
+    // Seconds since the Unix epoch of the first occurrence.
+    start: i64,
+    interval: u32,
+    // One bit per allowed weekday, Monday first, or zero to follow `start`.
+    days: u8,
+    // One bit per allowed hour, or zero to follow `start`.
+    hours: u32,
+    count: Option<usize>,
+    // Seconds since the Unix epoch of the last allowed occurrence.
+    until: Option<i64>,
+}
+
+/// Seconds since the Unix epoch of `t`, or zero if before it.
+///
+fn epoch_secs(t: SystemTime) -> i64 {
+    t.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() as i64)
+}
+
+/// Weeks since the Unix epoch of `day`, with weeks starting on Monday.
+///
+fn week(day: i64) -> i64 {
+    (day + 3).div_euclid(7)
+}
+
+/// Months since the year zero of `day`.
+///
+fn month(day: i64) -> i64 {
+    let (year, month, _) = civil_from_days(day);
+    year * 12 + month as i64 - 1
+}
+
+impl Recurrence {
+    /// Create a new rule repeating every `frequency` from `start`, the
+    /// first occurrence. Times before the Unix epoch count as the epoch.
+    ///
+    pub fn new(frequency: Frequency, start: SystemTime) -> Recurrence {
+        Recurrence {
+            frequency,
+            start: epoch_secs(start),
+            interval: 1,
+            days: 0,
+            hours: 0,
+            count: None,
+            until: None,
+        }
+    }
+    /// Repeat every `interval` hours, days, weeks or months, per the
+    /// frequency, instead of every one.
+    ///
+    pub fn interval(mut self, interval: u32) -> Recurrence {
+        self.interval = interval.max(1);
+        self
+    }
+    /// Only occur on the given days of the week.
+    ///
+    pub fn by_day(mut self, days: &[Weekday]) -> Recurrence {
+        self.days = days.iter().fold(0, |bits, &day| bits | 1 << day as u8);
+        self
+    }
+    /// Only occur in the given hours of the day.
+    ///
+    /// # Panics
+    ///
+    /// If an hour is over 23.
+    ///
+    pub fn by_hour(mut self, hours: &[u32]) -> Recurrence {
+        assert!(hours.iter().all(|&hour| hour < 24), "Hours must be 0 through 23!");
+        self.hours = hours.iter().fold(0, |bits, &hour| bits | 1 << hour);
+        self
+    }
+    /// Stop after `count` occurrences in all, counting from the first.
+    ///
+    pub fn count(mut self, count: usize) -> Recurrence {
+        self.count = Some(count);
+        self
+    }
+    /// Stop after the last occurrence at or before `until`.
+    ///
+    pub fn until(mut self, until: SystemTime) -> Recurrence {
+        self.until = Some(epoch_secs(until));
+        self
+    }
+    /// True if the rule occurs in `hour`, counted in hours since the Unix
+    /// epoch.
+    ///
+    fn occurs(&self, hour: i64) -> bool {
+        let start_hour = self.start.div_euclid(3600);
+        let (day, start_day) = (hour.div_euclid(24), start_hour.div_euclid(24));
+        let hour_matches = match self.hours {
+            0 => self.frequency == Frequency::Hourly || hour.rem_euclid(24) == start_hour.rem_euclid(24),
+            hours => hours & 1 << hour.rem_euclid(24) != 0,
+        };
+        let day_matches = match (self.days, self.frequency) {
+            (0, Frequency::Weekly) => (day - start_day).rem_euclid(7) == 0,
+            (0, Frequency::Monthly) => civil_from_days(day).2 == civil_from_days(start_day).2,
+            (0, _) => true,
+            (days, _) => days & 1 << (day + 3).rem_euclid(7) != 0,
+        };
+        let periods = match self.frequency {
+            Frequency::Hourly => hour - start_hour,
+            Frequency::Daily => day - start_day,
+            Frequency::Weekly => week(day) - week(start_day),
+            Frequency::Monthly => month(day) - month(start_day),
+        };
+        hour >= start_hour && hour_matches && day_matches && periods % self.interval as i64 == 0
+    }
+    /// The first occurrence strictly after `t`.
+    ///
+    /// Returns `None` once the rule has run out of occurrences, or if none
+    /// could be found, e.g., for the 31st of every other February.
+    ///
+    pub fn next_after(&self, t: SystemTime) -> Option<SystemTime> {
+        let after = epoch_secs(t);
+        let offset = self.start.rem_euclid(3600);
+        // Occurrences are only counted from the start when there's a count.
+        let mut hour = self.start.div_euclid(3600);
+        if self.count.is_none() {
+            hour = hour.max((after - offset).div_euclid(3600) + 1);
+        }
+        let mut seen = 0;
+        for _ in 0..SEARCH_LIMIT {
+            if self.occurs(hour) {
+                let at = hour * 3600 + offset;
+                seen += 1;
+                if self.until.is_some_and(|until| at > until) || self.count.is_some_and(|count| seen > count) {
+                    return None;
+                }
+                if at > after {
+                    return UNIX_EPOCH.checked_add(Duration::from_secs(at as u64));
+                }
+            }
+            hour += 1;
+        }
+        None
+    }
+}
+
+impl Schedule for Recurrence {
+    fn next_interval(&mut self, ctx: &TickContext) -> Option<Duration> {
+        let at = self.next_after(ctx.now)?;
+        Some(at.duration_since(ctx.now).unwrap_or_default())
+    }
+}
+
+#[test]
+fn recurrence_every_other_tuesday() {
+    use cron::days_from_civil;
+    let at = |year, month, day, hour: u64| {
+        UNIX_EPOCH + Duration::from_secs(days_from_civil(year, month, day) as u64 * 86_400 + hour * 3600)
+    };
+    // 2024-01-02 was a Tuesday.
+    let rule = Recurrence::new(Frequency::Weekly, at(2024, 1, 2, 9))
+        .interval(2)
+        .by_day(&[Weekday::Tuesday])
+        .by_hour(&[9]);
+    assert_eq!(rule.next_after(at(2024, 1, 1, 0)), Some(at(2024, 1, 2, 9)));
+    assert_eq!(rule.next_after(at(2024, 1, 2, 9)), Some(at(2024, 1, 16, 9)));
+    assert_eq!(rule.next_after(at(2024, 1, 20, 0)), Some(at(2024, 1, 30, 9)));
+    let rule = rule.count(3);
+    assert_eq!(rule.next_after(at(2024, 1, 20, 0)), Some(at(2024, 1, 30, 9)));
+    assert_eq!(rule.next_after(at(2024, 1, 30, 9)), None);
+}
+
+#[test]
+fn recurrence_rules() {
+    use cron::days_from_civil;
+    let at = |year, month, day, hour: u64| {
+        UNIX_EPOCH + Duration::from_secs(days_from_civil(year, month, day) as u64 * 86_400 + hour * 3600)
+    };
+    let minutes = Duration::from_secs(30 * 60);
+    // Twice a day on weekdays, on the start's half hour.
+    let rule = Recurrence::new(Frequency::Daily, at(2024, 3, 1, 0) + minutes)
+        .by_day(&[Weekday::Monday, Weekday::Tuesday, Weekday::Wednesday, Weekday::Thursday, Weekday::Friday])
+        .by_hour(&[9, 17]);
+    assert_eq!(rule.next_after(at(2024, 3, 1, 10)), Some(at(2024, 3, 1, 17) + minutes));
+    assert_eq!(rule.next_after(at(2024, 3, 1, 18)), Some(at(2024, 3, 4, 9) + minutes));
+    // Monthly on the start's day, skipping months without one.
+    let rule = Recurrence::new(Frequency::Monthly, at(2024, 1, 31, 8));
+    assert_eq!(rule.next_after(at(2024, 1, 31, 8)), Some(at(2024, 3, 31, 8)));
+    let rule = rule.until(at(2024, 5, 1, 0));
+    assert_eq!(rule.next_after(at(2024, 3, 31, 8)), None);
+    // Every six hours.
+    let rule = Recurrence::new(Frequency::Hourly, at(2024, 1, 1, 0)).interval(6);
+    assert_eq!(rule.next_after(at(2024, 1, 1, 7)), Some(at(2024, 1, 1, 12)));
+}
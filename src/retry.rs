@@ -0,0 +1,338 @@
+use backoff::Backoff;
+use budget::RetryBudget;
+use cancel::{self, CancellationToken};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// When and how often to retry a failing operation.
+///
+pub struct RetryPolicy {
+    // Delays between attempts.
+    backoff: Box<dyn Backoff + Send>,
+    // Most attempts to make, including the first.
+    max_attempts: Option<usize>,
+    // Longest time to keep retrying for, measured from the first attempt.
+    deadline: Option<Duration>,
+    // Token that abandons the retries when cancelled.
+    cancel: Option<CancellationToken>,
+    // Budget every retry is withdrawn from, if any.
+    budget: Option<RetryBudget>,
+}
+
+impl RetryPolicy {
+    /// Create a new policy that retries forever with the given backoff.
+    ///
+    pub fn new<B>(backoff: B) -> RetryPolicy
+        where B: Backoff + Send + 'static
+    {
+        RetryPolicy {
+            backoff: Box::new(backoff),
+            max_attempts: None,
+            deadline: None,
+            cancel: None,
+            budget: None,
+        }
+    }
+    /// Give up after `n` attempts, including the first.
+    ///
+    pub fn max_attempts(mut self, n: usize) -> RetryPolicy {
+        self.max_attempts = Some(n);
+        self
+    }
+    /// Give up rather than wait past `d` after the first attempt.
+    ///
+    pub fn deadline(mut self, d: Duration) -> RetryPolicy {
+        self.deadline = Some(d);
+        self
+    }
+    /// Give up as soon as `token` is cancelled.
+    ///
+    pub fn cancel_token(mut self, token: CancellationToken) -> RetryPolicy {
+        self.cancel = Some(token);
+        self
+    }
+    /// Give up when `budget` refuses a retry.
+    ///
+    /// Share one budget between every policy retrying against the same
+    /// service to bound the total retry load on it.
+    ///
+    pub fn budget(mut self, budget: RetryBudget) -> RetryPolicy {
+        self.budget = Some(budget);
+        self
+    }
+}
+
+/// Why a retried operation never succeeded.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RetryError<E> {
+    /// Every allowed attempt failed, or the backoff gave up. Holds the last
+    /// error.
+    Exhausted(E),
+    /// Waiting for the next attempt would pass the deadline. Holds the last
+    /// error.
+    DeadlineExceeded(E),
+    /// The retry budget refused another attempt. Holds the last error.
+    BudgetExhausted(E),
+    /// The cancellation token fired. Holds the last error, if any attempt
+    /// was made.
+    Cancelled(Option<E>),
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RetryError::Exhausted(ref e) => write!(f, "retries exhausted: {}", e),
+            RetryError::DeadlineExceeded(ref e) => write!(f, "retry deadline exceeded: {}", e),
+            RetryError::BudgetExhausted(ref e) => write!(f, "retry budget exhausted: {}", e),
+            RetryError::Cancelled(Some(ref e)) => write!(f, "retries cancelled: {}", e),
+            RetryError::Cancelled(None) => f.write_str("retries cancelled"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> Error for RetryError<E> {}
+
+/// Runs an operation until it succeeds or its policy gives up.
+///
+pub struct Retry {
+    policy: RetryPolicy,
+}
+
+impl Retry {
+    /// Create a new retry executor.
+    ///
+    pub fn new(policy: RetryPolicy) -> Retry {
+        Retry { policy }
+    }
+    /// Decide what to do after a failed attempt.
+    ///
+    /// Returns how long to wait before the next attempt, or the error to
+    /// give up with.
+    ///
+    fn after_failure<E>(&mut self, attempts: usize, started: Instant, e: E)
+                        -> Result<(Duration, E), RetryError<E>> {
+        if self.policy.max_attempts.is_some_and(|max| attempts >= max) {
+            return Err(RetryError::Exhausted(e));
+        }
+        let delay = match self.policy.backoff.next_backoff() {
+            Some(delay) => delay,
+            None => return Err(RetryError::Exhausted(e)),
+        };
+        if self.policy.deadline.is_some_and(|deadline| started.elapsed().saturating_add(delay) > deadline) {
+            return Err(RetryError::DeadlineExceeded(e));
+        }
+        if self.policy.budget.as_ref().is_some_and(|budget| !budget.try_withdraw()) {
+            return Err(RetryError::BudgetExhausted(e));
+        }
+        Ok((delay, e))
+    }
+    /// Call `op` until it succeeds or the policy gives up, blocking between
+    /// attempts.
+    ///
+    pub fn run<T, E, F>(&mut self, mut op: F) -> Result<T, RetryError<E>>
+        where F: FnMut() -> Result<T, E>
+    {
+        let token = self.policy.cancel.clone().unwrap_or_default();
+        self.policy.backoff.reset();
+        let started = Instant::now();
+        let mut attempts = 0;
+        if token.is_cancelled() {
+            return Err(RetryError::Cancelled(None));
+        }
+        loop {
+            attempts += 1;
+            let e = match op() {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            let (delay, e) = self.after_failure(attempts, started, e)?;
+            if cancel::sleep(delay, &token).is_err() {
+                return Err(RetryError::Cancelled(Some(e)));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+mod nonblocking {
+    use super::{Retry, RetryError};
+    use cancel::CancelRegistration;
+    use future::Sleep;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Instant;
+
+    /// What a `RetryFuture` is doing.
+    enum State<Fut, E> {
+        Attempting(Pin<Box<Fut>>),
+        Waiting(Sleep, Option<E>),
+    }
+
+    /// A future that drives an async operation until it succeeds or its
+    /// policy gives up.
+    ///
+    pub struct RetryFuture<'a, F, Fut, E> {
+        retry: &'a mut Retry,
+        op: F,
+        state: Option<State<Fut, E>>,
+        attempts: usize,
+        started: Instant,
+        // The waker registered with the cancellation token, if any.
+        watching: Option<CancelRegistration>,
+    }
+
+    impl Retry {
+        /// Call `op` until the future it returns succeeds or the policy
+        /// gives up, sleeping asynchronously between attempts.
+        ///
+        pub fn run_async<T, E, F, Fut>(&mut self, op: F) -> RetryFuture<'_, F, Fut, E>
+            where F: FnMut() -> Fut,
+                  Fut: Future<Output = Result<T, E>>
+        {
+            self.policy.backoff.reset();
+            RetryFuture {
+                retry: self,
+                op,
+                state: None,
+                attempts: 0,
+                started: Instant::now(),
+                watching: None,
+            }
+        }
+    }
+
+    // Nothing is pinned in place: attempts are boxed, and the operation and
+    // last error are only ever touched through `&mut`.
+    impl<'a, F, Fut, E> Unpin for RetryFuture<'a, F, Fut, E> {}
+
+    impl<'a, T, E, F, Fut> Future for RetryFuture<'a, F, Fut, E>
+        where F: FnMut() -> Fut,
+              Fut: Future<Output = Result<T, E>>
+    {
+        type Output = Result<T, RetryError<E>>;
+        fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+            let this = self.get_mut();
+            if let Some(ref token) = this.retry.policy.cancel {
+                if token.is_cancelled() {
+                    let last = match this.state.take() {
+                        Some(State::Waiting(_, e)) => e,
+                        _ => None,
+                    };
+                    return Poll::Ready(Err(RetryError::Cancelled(last)));
+                }
+                if this.watching.is_none() {
+                    let waker = cx.waker().clone();
+                    this.watching = Some(token.on_cancel(move || waker.wake()));
+                }
+            }
+            loop {
+                match this.state.take() {
+                    None | Some(State::Waiting(_, None)) => {
+                        this.attempts += 1;
+                        this.state = Some(State::Attempting(Box::pin((this.op)())));
+                    },
+                    Some(State::Attempting(mut fut)) => {
+                        match fut.as_mut().poll(cx) {
+                            Poll::Pending => {
+                                this.state = Some(State::Attempting(fut));
+                                return Poll::Pending;
+                            },
+                            Poll::Ready(Ok(value)) => return Poll::Ready(Ok(value)),
+                            Poll::Ready(Err(e)) => {
+                                match this.retry.after_failure(this.attempts, this.started, e) {
+                                    Ok((delay, e)) => {
+                                        this.state = Some(State::Waiting(Sleep::new(delay), Some(e)));
+                                    },
+                                    Err(e) => return Poll::Ready(Err(e)),
+                                }
+                            },
+                        }
+                    },
+                    Some(State::Waiting(mut sleep, e)) => {
+                        match Pin::new(&mut sleep).poll(cx) {
+                            Poll::Pending => {
+                                this.state = Some(State::Waiting(sleep, e));
+                                return Poll::Pending;
+                            },
+                            Poll::Ready(()) => this.state = Some(State::Waiting(sleep, None)),
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn retry_run_async() {
+        use backoff::ConstantBackoff;
+        use future::block_on;
+        use retry::RetryPolicy;
+        use std::future::ready;
+        use std::time::Duration;
+        let mut retry = Retry::new(RetryPolicy::new(ConstantBackoff::new(Duration::from_millis(5)))
+                                   .max_attempts(5));
+        let mut calls = 0;
+        let result: Result<usize, RetryError<&str>> = block_on(retry.run_async(|| {
+            calls += 1;
+            ready(if calls < 3 { Err("flaky") } else { Ok(calls) })
+        }));
+        assert_eq!(result, Ok(3));
+        let result: Result<(), RetryError<&str>> = block_on(retry.run_async(|| ready(Err("down"))));
+        assert_eq!(result, Err(RetryError::Exhausted("down")));
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::nonblocking::RetryFuture;
+
+#[test]
+fn retry_until_success() {
+    use backoff::ExponentialBackoff;
+    let policy = RetryPolicy::new(ExponentialBackoff::new(Duration::from_millis(1),
+                                                          Duration::from_millis(10)))
+        .max_attempts(5);
+    let mut calls = 0;
+    let result: Result<usize, RetryError<&str>> = Retry::new(policy).run(|| {
+        calls += 1;
+        if calls < 3 { Err("flaky") } else { Ok(calls) }
+    });
+    assert_eq!(result, Ok(3));
+}
+
+#[test]
+fn retry_gives_up() {
+    use backoff::ConstantBackoff;
+    let ms = Duration::from_millis;
+    let mut retry = Retry::new(RetryPolicy::new(ConstantBackoff::new(ms(5))).max_attempts(3));
+    let mut calls = 0;
+    let result: Result<(), _> = retry.run(|| { calls += 1; Err(calls) });
+    assert_eq!(result, Err(RetryError::Exhausted(3)));
+    let mut retry = Retry::new(RetryPolicy::new(ConstantBackoff::new(ms(20))).deadline(ms(50)));
+    let result: Result<(), _> = retry.run(|| Err("down"));
+    assert_eq!(result, Err(RetryError::DeadlineExceeded("down")));
+    let token = CancellationToken::new();
+    token.cancel_after(ms(20));
+    let mut retry = Retry::new(RetryPolicy::new(ConstantBackoff::new(ms(5))).cancel_token(token));
+    let result: Result<(), _> = retry.run(|| Err("down"));
+    assert_eq!(result, Err(RetryError::Cancelled(Some("down"))));
+}
+
+#[test]
+fn retry_budget_shared() {
+    use backoff::ConstantBackoff;
+    use budget::BudgetMetrics;
+    let budget = RetryBudget::new(3, Duration::from_secs(60));
+    let policy = || RetryPolicy::new(ConstantBackoff::new(Duration::from_millis(1)))
+        .max_attempts(10)
+        .budget(budget.clone());
+    let mut calls = 0;
+    let result: Result<(), _> = Retry::new(policy()).run(|| { calls += 1; Err(calls) });
+    assert_eq!(result, Err(RetryError::BudgetExhausted(4)));
+    // The budget is spent, so the next operation gets no retries at all.
+    let result: Result<(), _> = Retry::new(policy()).run(|| Err("down"));
+    assert_eq!(result, Err(RetryError::BudgetExhausted("down")));
+    assert_eq!(budget.metrics(), BudgetMetrics { available: 0, withdrawn: 3, rejected: 2 });
+}
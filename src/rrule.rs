@@ -0,0 +1,164 @@
+use cron::days_from_civil;
+use recurrence::{Frequency, Recurrence, Weekday};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Why an RRULE string couldn't be parsed.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RruleError {
+    /// The rule had no `FREQ` part.
+    MissingFrequency,
+    /// A part had a malformed or out of range value.
+    Invalid {
+        part: String,
+        value: String,
+    },
+    /// A part, or a value of it, is valid RRULE but not supported.
+    Unsupported(String),
+}
+
+impl fmt::Display for RruleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RruleError::MissingFrequency => write!(f, "RRULE has no FREQ"),
+            RruleError::Invalid { ref part, ref value } => write!(f, "invalid RRULE {} {:?}", part, value),
+            RruleError::Unsupported(ref part) => write!(f, "unsupported RRULE {}", part),
+        }
+    }
+}
+
+impl Error for RruleError {}
+
+/// Parse a weekday as RRULE writes it, e.g., `MO`.
+///
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    Some(match value {
+        "MO" => Weekday::Monday,
+        "TU" => Weekday::Tuesday,
+        "WE" => Weekday::Wednesday,
+        "TH" => Weekday::Thursday,
+        "FR" => Weekday::Friday,
+        "SA" => Weekday::Saturday,
+        "SU" => Weekday::Sunday,
+        _ => return None,
+    })
+}
+
+/// Parse an `UNTIL` date, `YYYYMMDD`, or UTC date and time,
+/// `YYYYMMDDTHHMMSSZ`.
+///
+fn parse_until(value: &str) -> Option<SystemTime> {
+    let digits = |range: std::ops::Range<usize>| value.get(range)?.parse::<u32>().ok();
+    let (year, month, day) = (digits(0..4)?, digits(4..6)?, digits(6..8)?);
+    let (hour, minute, second) = match value.len() {
+        8 => (0, 0, 0),
+        16 if &value[8..9] == "T" && value.ends_with('Z') => (digits(9..11)?, digits(11..13)?, digits(13..15)?),
+        _ => return None,
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let days = days_from_civil(year as i64, month, day);
+    let secs = days * 86_400 + (hour * 3600 + minute * 60 + second) as i64;
+    if secs < 0 {
+        return None;
+    }
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+}
+
+impl Recurrence {
+    /// Parse an iCalendar RRULE, e.g., `FREQ=WEEKLY;BYDAY=MO,WE;BYHOUR=9`,
+    /// starting from `start`.
+    ///
+    /// Supports the parts `Recurrence` does, with an optional `RRULE:`
+    /// prefix. `UNTIL` is read as UTC. Anything else, including `WKST`
+    /// other than Monday and `BYDAY` with ordinals, is `Unsupported`.
+    ///
+    pub fn parse(rule: &str, start: SystemTime) -> Result<Recurrence, RruleError> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+        let parts: Vec<(&str, &str)> = rule.split(';')
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let mut pair = part.splitn(2, '=');
+                (pair.next().unwrap_or(""), pair.next().unwrap_or(""))
+            })
+            .collect();
+        let invalid = |part: &str, value: &str| RruleError::Invalid { part: part.to_string(), value: value.to_string() };
+        let frequency = match parts.iter().find(|&&(part, _)| part == "FREQ") {
+            Some(&(_, "HOURLY")) => Frequency::Hourly,
+            Some(&(_, "DAILY")) => Frequency::Daily,
+            Some(&(_, "WEEKLY")) => Frequency::Weekly,
+            Some(&(_, "MONTHLY")) => Frequency::Monthly,
+            Some(&(_, value @ ("SECONDLY" | "MINUTELY" | "YEARLY"))) => {
+                return Err(RruleError::Unsupported(format!("FREQ={}", value)))
+            },
+            Some(&(part, value)) => return Err(invalid(part, value)),
+            None => return Err(RruleError::MissingFrequency),
+        };
+        let mut recurrence = Recurrence::new(frequency, start);
+        for &(part, value) in &parts {
+            recurrence = match part {
+                "FREQ" => recurrence,
+                "INTERVAL" => match value.parse() {
+                    Ok(interval) if interval > 0 => recurrence.interval(interval),
+                    _ => return Err(invalid(part, value)),
+                },
+                "COUNT" => recurrence.count(value.parse().map_err(|_| invalid(part, value))?),
+                "UNTIL" => recurrence.until(parse_until(value).ok_or_else(|| invalid(part, value))?),
+                "BYHOUR" => {
+                    let hours = value.split(',')
+                        .map(|hour| hour.parse().ok().filter(|&hour| hour < 24))
+                        .collect::<Option<Vec<u32>>>()
+                        .ok_or_else(|| invalid(part, value))?;
+                    recurrence.by_hour(&hours)
+                },
+                "BYDAY" => {
+                    let mut days = Vec::new();
+                    for day in value.split(',') {
+                        match parse_weekday(day) {
+                            Some(day) => days.push(day),
+                            None if day.len() > 2 && parse_weekday(&day[day.len() - 2..]).is_some() => {
+                                return Err(RruleError::Unsupported(format!("BYDAY={}", day)))
+                            },
+                            None => return Err(invalid(part, value)),
+                        }
+                    }
+                    recurrence.by_day(&days)
+                },
+                "WKST" if value == "MO" => recurrence,
+                _ => return Err(RruleError::Unsupported(format!("{}={}", part, value))),
+            };
+        }
+        Ok(recurrence)
+    }
+}
+
+#[test]
+fn rrule_parse() {
+    let start = UNIX_EPOCH + Duration::from_secs(days_from_civil(2024, 1, 2) as u64 * 86_400 + 9 * 3600);
+    let rule = Recurrence::parse("RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=TU;BYHOUR=9;COUNT=3", start).unwrap();
+    assert_eq!(rule, Recurrence::new(Frequency::Weekly, start)
+                         .interval(2)
+                         .by_day(&[Weekday::Tuesday])
+                         .by_hour(&[9])
+                         .count(3));
+    let rule = Recurrence::parse("FREQ=DAILY;UNTIL=20240105T090000Z", start).unwrap();
+    assert_eq!(rule, Recurrence::new(Frequency::Daily, start).until(start + Duration::from_secs(3 * 86_400)));
+    assert_eq!(Recurrence::parse("FREQ=DAILY;UNTIL=20240105", start).unwrap(),
+               Recurrence::new(Frequency::Daily, start).until(start + Duration::from_secs(3 * 86_400 - 9 * 3600)));
+}
+
+#[test]
+fn rrule_parse_errors() {
+    let parse = |rule| Recurrence::parse(rule, UNIX_EPOCH);
+    assert_eq!(parse("BYHOUR=9"), Err(RruleError::MissingFrequency));
+    assert_eq!(parse("FREQ=FORTNIGHTLY"), Err(RruleError::Invalid { part: "FREQ".into(), value: "FORTNIGHTLY".into() }));
+    assert_eq!(parse("FREQ=DAILY;BYHOUR=24"), Err(RruleError::Invalid { part: "BYHOUR".into(), value: "24".into() }));
+    assert_eq!(parse("FREQ=DAILY;INTERVAL=0").unwrap_err().to_string(), "invalid RRULE INTERVAL \"0\"");
+    assert_eq!(parse("FREQ=YEARLY"), Err(RruleError::Unsupported("FREQ=YEARLY".into())));
+    assert_eq!(parse("FREQ=MONTHLY;BYDAY=2TU"), Err(RruleError::Unsupported("BYDAY=2TU".into())));
+    assert_eq!(parse("FREQ=WEEKLY;BYMONTHDAY=1"), Err(RruleError::Unsupported("BYMONTHDAY=1".into())));
+}
@@ -0,0 +1,422 @@
+use backoff::{Backoff, ConstantBackoff, ExponentialBackoff, FibonacciBackoff, LinearBackoff};
+use config::JitterPolicy;
+use std::iter::FromIterator;
+use std::time::{Duration, SystemTime};
+use std::vec;
+use Timer;
+
+/// What a schedule knows about the timer when picking the next count down.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TickContext {
+    /// Number of times the timer has expired so far.
+    pub count: usize,
+    /// Wall clock time the next count down starts at.
+    pub now: SystemTime,
+}
+
+/// A sequence of count downs for a timer to follow.
+///
+/// Generalizes a timer's step and jitter: the timer asks for the next
+/// interval before every count down, and stops once there isn't one.
+///
+/// Ramps are built with `Ramp::linear` or `Ramp::exponential` rather than
+/// a `Schedule::ramp`, and sequences of intervals with `Intervals::new` or
+/// `Intervals::from_iter` rather than a `Schedule::from_iter`, since a
+/// constructor on the trait itself would have no type to build.
+///
+pub trait Schedule {
+    /// How long to count down from next, or `None` to stop the timer.
+    ///
+    fn next_interval(&mut self, ctx: &TickContext) -> Option<Duration>;
+    /// Start over from the first interval, e.g., after the operation a
+    /// backoff paces succeeds.
+    ///
+    /// Does nothing by default.
+    ///
+    fn restart(&mut self) {}
+    /// Follow whichever of this schedule and `other` comes first, e.g., 30s
+    /// idle or 5min absolute max.
+    ///
+    /// Each schedule keeps its own deadline across count downs, and only
+    /// the one that expired moves on to its next interval, so the 5min max
+    /// still fires while the 30s idle keeps winning. A reset part way
+    /// through a count down keeps both deadlines, less the time already
+    /// waited, and `restart` starts both schedules over. A schedule that
+    /// runs out drops out of the race, and the combined schedule runs out
+    /// once both have.
+    ///
+    fn earliest_of<S: Schedule>(self, other: S) -> EarliestOf<Self, S>
+        where Self: Sized
+    {
+        EarliestOf { a: Some(self), b: Some(other), due: (None, None), elapsed: Duration::from_secs(0), leg: None }
+    }
+}
+
+/// Count down from the same step every time, randomized by a jitter.
+///
+/// This is what a timer follows when no other schedule is set.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedStep {
+    step: Duration,
+    jitter: Duration,
+    jitter_policy: JitterPolicy,
+}
+
+impl FixedStep {
+    /// Create a new schedule that counts down from `step` every time.
+    ///
+    pub fn new(step: Duration) -> FixedStep {
+        FixedStep {
+            step,
+            jitter: Duration::from_secs(0),
+            jitter_policy: JitterPolicy::default(),
+        }
+    }
+    /// Randomize each count down by up to `jitter`, applied per `policy`.
+    ///
+    /// Ignored without the `jitter` feature.
+    ///
+    pub fn with_jitter(mut self, jitter: Duration, policy: JitterPolicy) -> FixedStep {
+        self.jitter = jitter;
+        self.jitter_policy = policy;
+        self
+    }
+    /// The duration of time counted down from.
+    ///
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+    /// The duration of time each count down is randomized by.
+    ///
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+    /// How `jitter` is applied to `step`.
+    ///
+    pub fn jitter_policy(&self) -> JitterPolicy {
+        self.jitter_policy
+    }
+}
+
+impl Schedule for FixedStep {
+    fn next_interval(&mut self, _ctx: &TickContext) -> Option<Duration> {
+        Some(Timer::calculate_wait_duration(self.step, self.jitter, self.jitter_policy))
+    }
+}
+
+impl Schedule for ConstantBackoff {
+    fn next_interval(&mut self, _ctx: &TickContext) -> Option<Duration> {
+        self.next_backoff()
+    }
+    fn restart(&mut self) {
+        self.reset();
+    }
+}
+
+impl Schedule for ExponentialBackoff {
+    fn next_interval(&mut self, _ctx: &TickContext) -> Option<Duration> {
+        self.next_backoff()
+    }
+    fn restart(&mut self) {
+        self.reset();
+    }
+}
+
+impl Schedule for FibonacciBackoff {
+    fn next_interval(&mut self, _ctx: &TickContext) -> Option<Duration> {
+        self.next_backoff()
+    }
+    fn restart(&mut self) {
+        self.reset();
+    }
+}
+
+impl Schedule for LinearBackoff {
+    fn next_interval(&mut self, _ctx: &TickContext) -> Option<Duration> {
+        self.next_backoff()
+    }
+    fn restart(&mut self) {
+        self.reset();
+    }
+}
+
+/// Count down from each interval an iterator yields, stopping once it's
+/// exhausted.
+///
+/// Handy for test scripts and for replaying recorded timing traces. Lazy
+/// iterators are wrapped with `Intervals::new`, and precomputed sequences
+/// can also be collected into `Intervals`, e.g., with `Intervals::from_iter`.
+///
+#[derive(Clone, Debug)]
+pub struct Intervals<I> {
+    iter: I,
+}
+
+impl<I> Intervals<I>
+    where I: Iterator<Item = Duration>
+{
+    /// Create a new schedule from an iterator of intervals.
+    ///
+    pub fn new<T>(intervals: T) -> Intervals<I>
+        where T: IntoIterator<Item = Duration, IntoIter = I>
+    {
+        Intervals { iter: intervals.into_iter() }
+    }
+}
+
+impl FromIterator<Duration> for Intervals<vec::IntoIter<Duration>> {
+    fn from_iter<T: IntoIterator<Item = Duration>>(intervals: T) -> Self {
+        Intervals::new(intervals.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<I> Schedule for Intervals<I>
+    where I: Iterator<Item = Duration>
+{
+    fn next_interval(&mut self, _ctx: &TickContext) -> Option<Duration> {
+        self.iter.next()
+    }
+}
+
+/// How a `Ramp` moves between its first and last interval.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Curve {
+    Linear,
+    Exponential,
+}
+
+/// Move the interval from one step to another across a span of time, then
+/// hold it there.
+///
+/// E.g., poll every second at first, backing off to every 30 seconds over
+/// the first minute. The span starts at the first count down.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ramp {
+    from: Duration,
+    to: Duration,
+    over: Duration,
+    curve: Curve,
+    // When the first count down started.
+    started: Option<SystemTime>,
+}
+
+impl Ramp {
+    /// Create a new ramp that changes the interval by the same amount per
+    /// second throughout.
+    ///
+    pub fn linear(from: Duration, to: Duration, over: Duration) -> Ramp {
+        Ramp { from, to, over, curve: Curve::Linear, started: None }
+    }
+    /// Create a new ramp that changes the interval by the same factor per
+    /// second throughout, so it moves slowly at the short end and quickly
+    /// at the long end.
+    ///
+    pub fn exponential(from: Duration, to: Duration, over: Duration) -> Ramp {
+        Ramp { from, to, over, curve: Curve::Exponential, started: None }
+    }
+}
+
+impl Schedule for Ramp {
+    fn next_interval(&mut self, ctx: &TickContext) -> Option<Duration> {
+        let started = *self.started.get_or_insert(ctx.now);
+        let elapsed = ctx.now.duration_since(started).unwrap_or_default();
+        if elapsed >= self.over {
+            return Some(self.to);
+        }
+        let progress = elapsed.as_secs_f64() / self.over.as_secs_f64();
+        let (from, to) = (self.from.as_secs_f64(), self.to.as_secs_f64());
+        let interval = match self.curve {
+            Curve::Linear => from + (to - from) * progress,
+            // A zero end can't be scaled from, so fall back to linear.
+            Curve::Exponential if from > 0.0 && to > 0.0 => from * (to / from).powf(progress),
+            Curve::Exponential => from + (to - from) * progress,
+        };
+        // Rounding can land just outside the ends, past `Duration::MAX`
+        // even, so keep within them.
+        let (short, long) = (self.from.min(self.to), self.from.max(self.to));
+        Some(Duration::try_from_secs_f64(interval).map_or(long, |interval| interval.clamp(short, long)))
+    }
+    fn restart(&mut self) {
+        self.started = None;
+    }
+}
+
+/// Follows whichever of two schedules comes first.
+///
+/// See `Schedule::earliest_of`.
+///
+#[derive(Clone, Debug)]
+pub struct EarliestOf<A, B> {
+    // Either schedule is dropped once it runs out.
+    a: Option<A>,
+    b: Option<B>,
+    // Each schedule's next deadline, as time into the race, drawn once its
+    // last one has passed.
+    due: (Option<Duration>, Option<Duration>),
+    // Time into the race that the count down in progress started at.
+    elapsed: Duration,
+    // The count down in progress: expiries before it, when it started, and
+    // how long it was.
+    leg: Option<(usize, SystemTime, Duration)>,
+}
+
+/// Draw `schedule`'s next deadline if it has none still to come, dropping
+/// the schedule once it runs out.
+///
+fn redraw<S: Schedule>(schedule: &mut Option<S>, due: &mut Option<Duration>, elapsed: Duration, ctx: &TickContext) {
+    if due.is_some_and(|due| due > elapsed) {
+        return;
+    }
+    *due = schedule.as_mut().and_then(|s| s.next_interval(ctx)).map(|interval| elapsed + interval);
+    if due.is_none() {
+        *schedule = None;
+    }
+}
+
+impl<A: Schedule, B: Schedule> Schedule for EarliestOf<A, B> {
+    fn next_interval(&mut self, ctx: &TickContext) -> Option<Duration> {
+        if let Some((count, started, interval)) = self.leg.take() {
+            // The last count down either ran out, or was cut short by a
+            // reset after however long has passed since it started.
+            self.elapsed += if ctx.count > count {
+                interval
+            } else {
+                ctx.now.duration_since(started).unwrap_or_default().min(interval)
+            };
+        }
+        redraw(&mut self.a, &mut self.due.0, self.elapsed, ctx);
+        redraw(&mut self.b, &mut self.due.1, self.elapsed, ctx);
+        let due = match self.due {
+            (Some(a), Some(b)) => a.min(b),
+            (a, b) => a.or(b)?,
+        };
+        let interval = due - self.elapsed;
+        self.leg = Some((ctx.count, ctx.now, interval));
+        Some(interval)
+    }
+    fn restart(&mut self) {
+        if let Some(ref mut a) = self.a {
+            a.restart();
+        }
+        if let Some(ref mut b) = self.b {
+            b.restart();
+        }
+        self.due = (None, None);
+        self.elapsed = Duration::from_secs(0);
+        self.leg = None;
+    }
+}
+
+#[test]
+fn schedule_earliest_of() {
+    let ms = Duration::from_millis;
+    let start = SystemTime::now();
+    let mut schedule = Intervals::new(vec![ms(30), ms(5)])
+        .earliest_of(FixedStep::new(ms(10)));
+    // Expire after each count down: the step wins at 10 and 20, both are
+    // due at 30, then the intervals' 5 at 35, and the step alone after.
+    let (mut count, mut at) = (0, start);
+    let mut expired = Vec::new();
+    while expired.len() < 6 {
+        let interval = schedule.next_interval(&TickContext { count, now: at }).unwrap();
+        expired.push(interval);
+        count += 1;
+        at += interval;
+    }
+    assert_eq!(expired, vec![ms(10), ms(10), ms(10), ms(5), ms(5), ms(10)]);
+    // A reset 4ms into a count down keeps both deadlines.
+    assert_eq!(schedule.next_interval(&TickContext { count, now: at }), Some(ms(10)));
+    assert_eq!(schedule.next_interval(&TickContext { count, now: at + ms(4) }), Some(ms(6)));
+    schedule.restart();
+    assert_eq!(schedule.next_interval(&TickContext { count, now: at }), Some(ms(10)));
+    let ctx = TickContext { count: 0, now: start };
+    let mut schedule = Schedule::earliest_of(Intervals::new(vec![ms(1)]), Intervals::new(vec![]));
+    assert_eq!(schedule.next_interval(&ctx), Some(ms(1)));
+    assert_eq!(schedule.next_interval(&TickContext { count: 1, now: start + ms(1) }), None);
+}
+
+#[test]
+fn schedule_from_iter() {
+    let ctx = TickContext { count: 0, now: SystemTime::now() };
+    let trace = "12 7 30";
+    let mut schedule: Intervals<_> = trace.split(' ')
+        .map(|ms| Duration::from_millis(ms.parse().unwrap()))
+        .collect();
+    assert_eq!(schedule.next_interval(&ctx), Some(Duration::from_millis(12)));
+    assert_eq!(schedule.next_interval(&ctx), Some(Duration::from_millis(7)));
+    assert_eq!(schedule.next_interval(&ctx), Some(Duration::from_millis(30)));
+    assert_eq!(schedule.next_interval(&ctx), None);
+    let mut schedule = Intervals::from_iter(vec![Duration::from_secs(1)]);
+    assert_eq!(schedule.next_interval(&ctx), Some(Duration::from_secs(1)));
+    assert_eq!(schedule.next_interval(&ctx), None);
+}
+
+#[test]
+fn schedule_ramp() {
+    let start = SystemTime::now();
+    let at = |secs| TickContext { count: 0, now: start + Duration::from_secs(secs) };
+    let s = Duration::from_secs;
+    let mut linear = Ramp::linear(s(1), s(31), s(60));
+    assert_eq!(linear.next_interval(&at(0)), Some(s(1)));
+    assert_eq!(linear.next_interval(&at(30)), Some(s(16)));
+    assert_eq!(linear.next_interval(&at(90)), Some(s(31)));
+    let mut exponential = Ramp::exponential(s(1), s(100), s(60));
+    assert_eq!(exponential.next_interval(&at(0)), Some(s(1)));
+    let halfway = exponential.next_interval(&at(30)).unwrap();
+    assert!((halfway.as_secs_f64() - 10.0).abs() < 1e-6);
+    assert_eq!(exponential.next_interval(&at(60)), Some(s(100)));
+    // Near the largest duration, rounding mustn't overshoot it.
+    let mut huge = Ramp::linear(Duration::MAX - s(1), Duration::MAX, s(60));
+    assert!(huge.next_interval(&at(0)).unwrap() >= Duration::MAX - s(1));
+    assert!(huge.next_interval(&at(59)).is_some());
+}
+
+#[test]
+fn timer_follows_schedule() {
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Condvar};
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::new(Duration::from_secs(60), Duration::from_secs(0), cv);
+    t.set_schedule(Intervals::new(vec![Duration::from_millis(10); 3]));
+    t.start();
+    std::thread::sleep(Duration::from_millis(80));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 3);
+    assert!(!t.lifecycle.is_running());
+    t.stop();
+}
+
+#[test]
+fn timer_max_interval() {
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(Duration::from_secs(60), Duration::from_secs(0), Arc::new(Condvar::new()));
+    t.set_schedule(Intervals::new(vec![ms(10), ms(500), ms(500)]));
+    t.set_max_interval(ms(20));
+    t.start();
+    std::thread::sleep(ms(100));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 3);
+    t.stop();
+}
+
+#[test]
+fn timer_notify_success() {
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(Duration::from_secs(60), Duration::from_secs(0), Arc::new(Condvar::new()));
+    t.set_schedule(ExponentialBackoff::new(ms(30), ms(10_000)));
+    t.start();
+    // Expires at 30ms and 90ms, then backs off to 210ms...
+    std::thread::sleep(ms(100));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 2);
+    // ...until a success starts over from 30ms.
+    t.notify_success();
+    std::thread::sleep(ms(60));
+    assert_eq!(t.expiries.load(Ordering::SeqCst), 3);
+    t.stop();
+}
@@ -0,0 +1,74 @@
+use lifecycle::{Phase, StartError, TransitionError};
+use std::thread::{Scope, ScopedJoinHandle};
+use Timer;
+
+impl Timer {
+    /// Start the timer on a thread of `scope`, running `f` on each expiry.
+    ///
+    /// Unlike callbacks registered with `on_expiry`, `f` may borrow from the
+    /// stack of the enclosing `std::thread::scope` block rather than being
+    /// `'static`. It runs on the timer's thread ahead of any other
+    /// callbacks, whatever the dispatch, and a panic in it is caught and
+    /// handed to the panic hook like theirs. Fails like `try_start` if the
+    /// timer is already running, its last thread hasn't exited yet, or its
+    /// thread can't be spawned.
+    ///
+    /// The scope doesn't end until the timer's thread does, so stop the
+    /// timer within the scope, with `request_stop` or `stop`, or bound it
+    /// with `set_max_expiries` or `stop_after`. The real-time policy isn't
+    /// applied to scoped threads.
+    ///
+    pub fn start_in_scope<'scope, 'env, F>(&mut self, scope: &'scope Scope<'scope, 'env>, f: F)
+                                           -> Result<ScopedJoinHandle<'scope, ()>, StartError>
+        where F: FnMut() + Send + 'scope
+    {
+        if let Some(handle) = self.handle.take() {
+            if self.lifecycle.is_running() {
+                self.handle = Some(handle);
+                return Err(StartError::Transition(TransitionError { from: Phase::Running, to: Phase::Starting }));
+            }
+            handle.join().expect("Couldn't join spawned thread!");
+        }
+        self.lifecycle.transition(Phase::Stopped, Phase::Starting)?;
+        let handle = self.prepare(Some(Box::new(f))).and_then(|worker| {
+            self.threads.spawn_scoped(scope, &self.id.to_string(), move || worker.spin())
+        });
+        match handle {
+            Ok(handle) => {
+                self.watch_cancel();
+                Ok(handle)
+            },
+            Err(e) => {
+                self.abandon_start();
+                Err(StartError::Spawn(e))
+            },
+        }
+    }
+}
+
+#[test]
+fn timer_start_in_scope() {
+    use std::sync::{Arc, Condvar};
+    use std::time::Duration;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(5), ms(0), Arc::new(Condvar::new()));
+    t.set_max_expiries(4);
+    let mut ticks = Vec::new();
+    std::thread::scope(|scope| {
+        let handle = t.start_in_scope(scope, || ticks.push(ticks.len())).unwrap();
+        handle.join().unwrap();
+    });
+    assert_eq!(ticks, vec![0, 1, 2, 3]);
+    assert_eq!(t.phase(), Phase::Stopped);
+    // Borrowed again, and stopped from within the scope.
+    let mut ticks = 0;
+    std::thread::scope(|scope| {
+        t.set_max_expiries(100);
+        t.start_in_scope(scope, || ticks += 1).unwrap();
+        assert!(t.start_in_scope(scope, || {}).is_err());
+        std::thread::sleep(ms(30));
+        t.halt();
+    });
+    assert!(ticks >= 2, "{}", ticks);
+    assert_eq!(t.phase(), Phase::Stopped);
+}
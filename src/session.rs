@@ -0,0 +1,79 @@
+use std::hash::Hash;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+use ttl::TtlScheduler;
+
+/// Evicts sessions that go quiet for too long, all on one thread.
+///
+/// Each `touch` pushes a session's eviction back to a full inactivity
+/// window from now. Sessions whose window runs out are sent down the
+/// eviction channel returned by `new`. Thousands of sessions share the one
+/// timing wheel thread rather than needing a `Timer` each.
+///
+pub struct SessionTimeouts<K> {
+    sessions: TtlScheduler<K>,
+    // Inactivity window after which a session is evicted.
+    idle_for: Duration,
+}
+
+impl<K> SessionTimeouts<K>
+    where K: Clone + Eq + Hash + Send + 'static
+{
+    /// Create a new session tracker, returning it along with the channel
+    /// evicted sessions are sent down.
+    ///
+    /// Evictions are accurate to a sixty fourth of `idle_for`, or a
+    /// millisecond, whichever is longer.
+    ///
+    pub fn new(idle_for: Duration) -> (SessionTimeouts<K>, Receiver<K>) {
+        let resolution = (idle_for / 64).max(Duration::from_millis(1));
+        let (sessions, evicted) = TtlScheduler::channel(resolution);
+        (SessionTimeouts { sessions, idle_for }, evicted)
+    }
+    /// Record activity on `key`, starting its inactivity window over. A
+    /// key not already tracked starts being tracked.
+    ///
+    pub fn touch(&self, key: K) {
+        self.sessions.insert(key, self.idle_for);
+    }
+    /// Stop tracking `key` without evicting it. Returns false if it wasn't
+    /// tracked.
+    ///
+    pub fn remove(&self, key: &K) -> bool {
+        self.sessions.remove(key)
+    }
+    /// True if `key` is tracked and hasn't been evicted.
+    ///
+    pub fn contains(&self, key: &K) -> bool {
+        self.sessions.contains(key)
+    }
+    /// Number of tracked sessions.
+    ///
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+    /// True if no sessions are tracked.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+#[test]
+fn session_timeouts_evict_quiet_sessions() {
+    let ms = Duration::from_millis;
+    let (sessions, evicted) = SessionTimeouts::new(ms(50));
+    sessions.touch(1);
+    sessions.touch(2);
+    sessions.touch(3);
+    assert!(sessions.remove(&3));
+    for _ in 0..4 {
+        std::thread::sleep(ms(20));
+        sessions.touch(1);
+    }
+    // Session 2 went quiet while 1 was kept alive...
+    assert_eq!(evicted.try_recv(), Ok(2));
+    assert!(sessions.contains(&1));
+    assert_eq!(evicted.recv_timeout(ms(200)), Ok(1));
+    assert!(sessions.is_empty());
+}
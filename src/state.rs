@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::Chars;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What's remembered about a job across restarts.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct JobState {
+    /// When the job was next due to run, if it was scheduled.
+    pub next_run: Option<SystemTime>,
+    /// Number of times the job had run.
+    pub runs: usize,
+}
+
+/// Durable storage for the state of a `JobScheduler`'s jobs.
+///
+pub trait StateStore: Send {
+    /// Record the current state of the job called `name`.
+    ///
+    fn save(&mut self, name: &str, state: &JobState) -> io::Result<()>;
+    /// The state last recorded for the job called `name`, if any.
+    ///
+    fn load(&mut self, name: &str) -> io::Result<Option<JobState>>;
+}
+
+/// A state store kept in a JSON file, mapping each job's name to its state.
+///
+/// Like `FileCheckpoint`, each save writes a sibling temporary file and
+/// renames it over the original.
+///
+#[derive(Clone, Debug)]
+pub struct FileStateStore {
+    path: PathBuf,
+    // Every job's state, loaded on first use.
+    jobs: Option<BTreeMap<String, JobState>>,
+}
+
+impl FileStateStore {
+    /// Create a new state store kept at `path`.
+    ///
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileStateStore {
+        FileStateStore { path: path.into(), jobs: None }
+    }
+    fn jobs(&mut self) -> io::Result<&mut BTreeMap<String, JobState>> {
+        if self.jobs.is_none() {
+            let jobs = match fs::read_to_string(&self.path) {
+                Ok(contents) => parse(&contents)?,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+                Err(e) => return Err(e),
+            };
+            self.jobs = Some(jobs);
+        }
+        Ok(self.jobs.as_mut().unwrap())
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn save(&mut self, name: &str, state: &JobState) -> io::Result<()> {
+        self.jobs()?.insert(name.to_string(), *state);
+        let contents = format(self.jobs.as_ref().unwrap());
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &self.path)
+    }
+    fn load(&mut self, name: &str) -> io::Result<Option<JobState>> {
+        Ok(self.jobs()?.get(name).cloned())
+    }
+}
+
+/// Write a JSON string literal.
+///
+fn quote(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Format every job's state as a JSON object.
+///
+/// Times are written as `"secs.nanos"` since the Unix epoch, like a
+/// `FileCheckpoint`, so they round trip exactly.
+///
+fn format(jobs: &BTreeMap<String, JobState>) -> String {
+    let mut out = String::from("{\n");
+    for (i, (name, state)) in jobs.iter().enumerate() {
+        out.push_str("  ");
+        quote(name, &mut out);
+        out.push_str(": {\"next_run\": ");
+        match state.next_run {
+            Some(at) => {
+                let since = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+                out.push_str(&format!("\"{}.{:09}\"", since.as_secs(), since.subsec_nanos()));
+            },
+            None => out.push_str("null"),
+        }
+        out.push_str(&format!(", \"runs\": {}}}", state.runs));
+        out.push_str(if i + 1 < jobs.len() { ",\n" } else { "\n" });
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// A value in the subset of JSON that `format` writes.
+///
+enum Value {
+    Null,
+    Number(u64),
+    String(String),
+    Object(Vec<(String, Value)>),
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed job state")
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> io::Result<()> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> io::Result<String> {
+    expect(chars, '"')?;
+    let mut s = String::new();
+    loop {
+        match chars.next().ok_or_else(invalid)? {
+            '"' => return Ok(s),
+            '\\' => match chars.next().ok_or_else(invalid)? {
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32);
+                    s.push(c.ok_or_else(invalid)?);
+                },
+                'n' => s.push('\n'),
+                't' => s.push('\t'),
+                c => s.push(c),
+            },
+            c => s.push(c),
+        }
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> io::Result<Value> {
+    skip_whitespace(chars);
+    match *chars.peek().ok_or_else(invalid)? {
+        '"' => Ok(Value::String(parse_string(chars)?)),
+        '{' => {
+            chars.next();
+            let mut fields = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Value::Object(fields));
+            }
+            loop {
+                let key = parse_string(chars)?;
+                expect(chars, ':')?;
+                fields.push((key, parse_value(chars)?));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {},
+                    Some('}') => return Ok(Value::Object(fields)),
+                    _ => return Err(invalid()),
+                }
+            }
+        },
+        'n' => {
+            let word: String = chars.by_ref().take(4).collect();
+            if word == "null" { Ok(Value::Null) } else { Err(invalid()) }
+        },
+        _ => {
+            let mut digits = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(c);
+                chars.next();
+            }
+            digits.parse().map(Value::Number).map_err(|_| invalid())
+        },
+    }
+}
+
+/// Parse a time written by `format`.
+///
+fn parse_time(s: &str) -> io::Result<SystemTime> {
+    let mut parts = s.splitn(2, '.');
+    let secs = parts.next().and_then(|s| s.parse().ok()).ok_or_else(invalid)?;
+    let nanos = parts.next().and_then(|s| s.parse().ok()).ok_or_else(invalid)?;
+    UNIX_EPOCH.checked_add(Duration::new(secs, nanos)).ok_or_else(invalid)
+}
+
+/// Parse every job's state from the JSON written by `format`.
+///
+fn parse(contents: &str) -> io::Result<BTreeMap<String, JobState>> {
+    let mut chars = contents.chars().peekable();
+    let jobs = match parse_value(&mut chars)? {
+        Value::Object(jobs) => jobs,
+        _ => return Err(invalid()),
+    };
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() {
+        return Err(invalid());
+    }
+    let mut states = BTreeMap::new();
+    for (name, fields) in jobs {
+        let mut state = JobState::default();
+        let fields = match fields {
+            Value::Object(fields) => fields,
+            _ => return Err(invalid()),
+        };
+        for (key, value) in fields {
+            match (key.as_str(), value) {
+                ("next_run", Value::String(at)) => state.next_run = Some(parse_time(&at)?),
+                ("next_run", Value::Null) => state.next_run = None,
+                ("runs", Value::Number(runs)) => state.runs = runs as usize,
+                _ => return Err(invalid()),
+            }
+        }
+        states.insert(name, state);
+    }
+    Ok(states)
+}
+
+#[test]
+fn file_state_store_round_trips() {
+    let path = std::env::temp_dir().join(format!("timer-jobs-{}.json", std::process::id()));
+    let mut store = FileStateStore::new(&path);
+    assert_eq!(store.load("backup").unwrap(), None);
+    let backup = JobState {
+        next_run: Some(UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789)),
+        runs: 42,
+    };
+    let odd = JobState { next_run: None, runs: 0 };
+    store.save("backup", &backup).unwrap();
+    store.save("say \"hi\"\n", &odd).unwrap();
+    let mut store = FileStateStore::new(&path);
+    assert_eq!(store.load("backup").unwrap(), Some(backup));
+    assert_eq!(store.load("say \"hi\"\n").unwrap(), Some(odd));
+    fs::write(&path, "{\"backup\": {\"runs\": -1}}").unwrap();
+    assert!(FileStateStore::new(&path).load("backup").is_err());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,83 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use Timer;
+
+/// Where a timer's time has gone, for capacity planning.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimerStats {
+    /// Wall clock time since the timer was first started, including time
+    /// spent paused or stopped.
+    pub uptime: Duration,
+    /// Time spent counting down on the timer's clock, excluding time spent
+    /// paused or stopped.
+    pub active: Duration,
+    /// Total time spent running expiry callbacks.
+    pub callbacks: Duration,
+    /// Number of times the timer has expired.
+    pub expiries: usize,
+    /// Mean lateness of an expiry relative to its deadline.
+    pub mean_error: Duration,
+    /// Greatest lateness of any expiry relative to its deadline.
+    pub max_error: Duration,
+}
+
+impl TimerStats {
+    /// Share of each interval spent running callbacks rather than counting
+    /// down, from 0.0 to 1.0.
+    ///
+    /// Close to 1.0 means the periodic work barely fits its interval.
+    ///
+    pub fn callback_load(&self) -> f64 {
+        let total = self.active.saturating_add(self.callbacks);
+        if total == Duration::from_secs(0) {
+            return 0.0;
+        }
+        self.callbacks.as_secs_f64() / total.as_secs_f64()
+    }
+    /// Mean time spent running callbacks per expiry.
+    ///
+    pub fn mean_callback_time(&self) -> Duration {
+        match self.expiries {
+            0 => Duration::from_secs(0),
+            n => self.callbacks / n as u32,
+        }
+    }
+}
+
+impl Timer {
+    /// Take a snapshot of where the timer's time has gone.
+    ///
+    pub fn stats(&self) -> TimerStats {
+        TimerStats {
+            uptime: self.first_started.map_or(Duration::from_secs(0), |started| started.elapsed()),
+            active: self.countdown.lock().unwrap().active(self.clock.reading()),
+            callbacks: self.callbacks.busy(),
+            expiries: self.expiries.load(Ordering::SeqCst),
+            mean_error: self.drift.mean(),
+            max_error: self.drift.worst(),
+        }
+    }
+}
+
+#[test]
+fn timer_stats() {
+    use std::sync::{Arc, Condvar};
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(30), ms(0), Arc::new(Condvar::new()));
+    t.on_expiry(move || std::thread::sleep(ms(10)));
+    assert_eq!(t.stats().uptime, ms(0));
+    t.start();
+    std::thread::sleep(ms(100));
+    t.pause();
+    std::thread::sleep(ms(50));
+    t.stop();
+    std::thread::sleep(ms(50));
+    let stats = t.stats();
+    assert!(stats.uptime >= ms(200));
+    assert!(stats.active < ms(100), "{:?}", stats);
+    assert!(stats.expiries >= 2);
+    assert!(stats.max_error >= stats.mean_error);
+    assert!(stats.mean_callback_time() >= ms(10));
+    assert!(stats.callback_load() > 0.1 && stats.callback_load() < 0.5, "{:?}", stats);
+}
@@ -0,0 +1,82 @@
+use metrics::MetricsSink;
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// Reports timer metrics to a StatsD or Datadog agent over UDP.
+///
+/// Plain StatsD has no tags, so the timer's name goes in the metric name,
+/// as in `<prefix>.<timer>.expiries`. The Datadog flavour uses a single
+/// `<prefix>.expiries` metric tagged `timer:<timer>` instead.
+///
+/// Sends are fire and forget, so an unreachable agent never slows the
+/// timer down.
+///
+#[derive(Debug)]
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    // True to tag metrics Datadog style rather than name them per timer.
+    tagged: bool,
+}
+
+impl StatsdSink {
+    /// Create a new sink reporting to the StatsD agent at `addr`.
+    ///
+    pub fn new<A: ToSocketAddrs>(addr: A, prefix: &str) -> io::Result<StatsdSink> {
+        StatsdSink::connect(addr, prefix, false)
+    }
+    /// Create a new sink reporting to the Datadog agent at `addr`.
+    ///
+    pub fn datadog<A: ToSocketAddrs>(addr: A, prefix: &str) -> io::Result<StatsdSink> {
+        StatsdSink::connect(addr, prefix, true)
+    }
+    fn connect<A: ToSocketAddrs>(addr: A, prefix: &str, tagged: bool) -> io::Result<StatsdSink> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(StatsdSink { socket, prefix: prefix.to_owned(), tagged })
+    }
+    /// Send one metric, ignoring failures.
+    ///
+    fn send(&self, timer: &str, metric: &str, value: &str) {
+        let line = if self.tagged {
+            format!("{}.{}:{}|#timer:{}", self.prefix, metric, value, timer)
+        } else {
+            format!("{}.{}.{}:{}", self.prefix, timer, metric, value)
+        };
+        let _ = self.socket.send(line.as_bytes());
+    }
+}
+
+impl MetricsSink for StatsdSink {
+    fn expired(&self, timer: &str, latency: Duration) {
+        self.send(timer, "expiries", "1|c");
+        self.send(timer, "latency", &format!("{}|ms", latency.as_secs_f64() * 1000.0));
+    }
+    fn overrun(&self, timer: &str, _: Duration) {
+        self.send(timer, "overruns", "1|c");
+    }
+    fn reset(&self, timer: &str) {
+        self.send(timer, "resets", "1|c");
+    }
+}
+
+#[test]
+fn statsd_sink_formats() {
+    let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    agent.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    let addr = agent.local_addr().unwrap();
+    let mut buf = [0; 256];
+    let mut recv = || {
+        let n = agent.recv(&mut buf).unwrap();
+        String::from_utf8(buf[..n].to_vec()).unwrap()
+    };
+    let sink = StatsdSink::new(addr, "app").unwrap();
+    sink.expired("heartbeat", Duration::from_micros(1500));
+    assert_eq!(recv(), "app.heartbeat.expiries:1|c");
+    assert_eq!(recv(), "app.heartbeat.latency:1.5|ms");
+    let sink = StatsdSink::datadog(addr, "app").unwrap();
+    sink.reset("heartbeat");
+    assert_eq!(recv(), "app.resets:1|c|#timer:heartbeat");
+}
@@ -0,0 +1,275 @@
+//! Drive a timer deterministically, for testing code that depends on it.
+//!
+//! A `Harness` runs a timer against a `MockClock` and applies a script of
+//! `Action`s one at a time. After each, it waits for the timer's thread to
+//! settle, i.e., to finish reacting and go back to waiting on the clock,
+//! before applying the next. The same script therefore always yields the
+//! same events, however the threads happen to be scheduled, which makes
+//! it suitable for checking interleavings of resets, stops and expiries
+//! with property tests.
+//!
+use clock::{ClockSource, MockClock};
+use event::Event;
+use lifecycle::Phase;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Condvar};
+use std::time::{Duration, Instant};
+use trace::{Trace, TraceEntry, TraceEvent};
+use Timer;
+
+/// How long `Harness` waits for the timer to settle before giving up.
+///
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One step of a script driving a `Harness`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Move the mock clock forward.
+    Advance(Duration),
+    /// Call `Timer::reset`.
+    Reset,
+    /// Call `Timer::pause`.
+    Pause,
+    /// Call `Timer::resume`.
+    Resume,
+    /// Call `Timer::start`.
+    Start,
+    /// Call `Timer::request_stop`, then reap the timer's thread.
+    Stop,
+}
+
+/// A timer on a mock clock, driven one `Action` at a time.
+///
+pub struct Harness {
+    timer: Timer,
+    clock: Arc<MockClock>,
+    events: Receiver<Event>,
+}
+
+impl Harness {
+    /// Create a new harness around a stopped timer counting down `step`
+    /// on a mock clock reading zero.
+    ///
+    pub fn new(step: Duration) -> Harness {
+        let clock = Arc::new(MockClock::new());
+        let mut timer = Timer::with_clock(step,
+                                      Duration::from_secs(0),
+                                      Arc::new(Condvar::new()),
+                                      ClockSource::Custom(clock.clone()));
+        let events = timer.subscribe();
+        Harness { timer, clock, events }
+    }
+    /// The timer, e.g., to register callbacks or change settings before
+    /// running a script.
+    ///
+    pub fn timer(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+    /// The mock clock the timer counts down against.
+    ///
+    pub fn clock(&self) -> &MockClock {
+        &self.clock
+    }
+    /// Apply `action`, wait for the timer to settle, and return the events
+    /// it emitted meanwhile.
+    ///
+    /// # Panics
+    ///
+    /// If the timer doesn't settle within a few seconds, e.g., because a
+    /// callback never returns.
+    ///
+    pub fn apply(&mut self, action: Action) -> Vec<Event> {
+        match action {
+            Action::Advance(d) => self.clock.advance(d),
+            Action::Reset => self.timer.reset(),
+            Action::Pause => {
+                self.timer.pause();
+            },
+            Action::Resume => {
+                self.timer.resume();
+            },
+            Action::Start => self.timer.start(),
+            Action::Stop => {
+                self.timer.request_stop();
+                assert!(self.timer.join(SETTLE_TIMEOUT), "Timer never stopped!");
+            },
+        }
+        self.settle();
+        self.events.try_iter().collect()
+    }
+    /// Apply every action in `script` in turn, returning all the events
+    /// the timer emitted.
+    ///
+    pub fn run(&mut self, script: &[Action]) -> Vec<Event> {
+        script.iter().flat_map(|&action| self.apply(action)).collect()
+    }
+    /// Wait for the timer's thread to go idle at the current clock reading,
+    /// having seen every reset and pause, or to exit.
+    ///
+    fn settle(&self) {
+        let deadline = Instant::now() + SETTLE_TIMEOUT;
+        loop {
+            {
+                let _guard = self.timer.m.lock().unwrap();
+                let now = self.timer.clock.reading();
+                let resets = self.timer.resets.load(Ordering::SeqCst);
+                let pauses = self.timer.pauses.load(Ordering::SeqCst);
+                if self.timer.phase() == Phase::Stopped || self.timer.idle.is(now, resets, pauses) {
+                    return;
+                }
+                // Pausing and advancing the clock don't wake the thread, so
+                // make sure it takes another look.
+                self.timer.cv.notify_all();
+            }
+            assert!(Instant::now() < deadline, "Timer never settled!");
+            std::thread::yield_now();
+        }
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.timer.request_stop();
+        self.timer.join(SETTLE_TIMEOUT);
+    }
+}
+
+/// Plays back a recorded `Trace` on a mock clock, to reproduce timing
+/// seen elsewhere, e.g., in production.
+///
+pub struct Replayer {
+    trace: Trace,
+}
+
+impl Replayer {
+    /// Create a new replayer for `trace`.
+    ///
+    pub fn new(trace: Trace) -> Replayer {
+        Replayer { trace }
+    }
+    /// Step `clock` through the trace, setting it to when each entry was
+    /// recorded, then handing the entry to `f`.
+    ///
+    /// The clock reads zero where the trace began.
+    ///
+    pub fn replay<F: FnMut(&TraceEntry)>(&self, clock: &MockClock, mut f: F) {
+        for entry in &self.trace.entries {
+            clock.set(entry.at);
+            f(entry);
+        }
+    }
+    /// The trace as a script for a `Harness`, starting and stopping its
+    /// timer where the recorded one was, and advancing the clock to each
+    /// recorded event in between.
+    ///
+    /// A harness timer with the recorded timer's settings then expires
+    /// where the recorded one did.
+    ///
+    pub fn script(&self) -> Vec<Action> {
+        let mut now = Duration::from_secs(0);
+        let mut script = Vec::new();
+        for entry in &self.trace.entries {
+            if entry.at > now {
+                script.push(Action::Advance(entry.at - now));
+                now = entry.at;
+            }
+            match entry.event {
+                TraceEvent::Started => script.push(Action::Start),
+                TraceEvent::StopRequested => script.push(Action::Stop),
+                TraceEvent::Emitted(_) => {},
+            }
+        }
+        script
+    }
+}
+
+#[test]
+fn harness_runs_scripts() {
+    use clock::Timestamp;
+    let ms = Duration::from_millis;
+    let mut harness = Harness::new(ms(10));
+    let expired = |events: Vec<Event>| -> Vec<(usize, Timestamp)> {
+        events.into_iter()
+            .filter_map(|event| match event {
+                Event::Expired(expiry) => Some((expiry.count, expiry.deadline)),
+                _ => None,
+            })
+            .collect()
+    };
+    let stamp = |t| Timestamp::Custom(ms(t));
+    let events = harness.run(&[Action::Start,
+                               Action::Advance(ms(10)),
+                               Action::Advance(ms(5)),
+                               Action::Reset,
+                               Action::Advance(ms(9)),
+                               Action::Advance(ms(1))]);
+    assert_eq!(expired(events), vec![(1, stamp(10)), (2, stamp(25))]);
+    assert!(harness.apply(Action::Pause).is_empty());
+    assert!(harness.apply(Action::Advance(ms(100))).is_empty());
+    let events = harness.run(&[Action::Resume, Action::Advance(ms(10)), Action::Stop]);
+    assert_eq!(expired(events), vec![(3, stamp(135))]);
+    assert!(harness.apply(Action::Advance(ms(100))).is_empty());
+}
+
+#[test]
+fn replayer_reproduces_recorded_expiries() {
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(10), ms(0), Arc::new(Condvar::new()));
+    let recorder = t.record();
+    t.start();
+    std::thread::sleep(ms(45));
+    t.request_stop();
+    assert!(t.join(SETTLE_TIMEOUT));
+    let trace: Trace = recorder.trace().to_string().parse().unwrap();
+    let recorded = trace.entries.iter()
+        .filter(|entry| matches!(entry.event, TraceEvent::Emitted(Event::Expired(_))))
+        .count();
+    assert!(recorded >= 3, "{}", trace);
+    let replayer = Replayer::new(trace);
+    let mut harness = Harness::new(ms(10));
+    let events = harness.run(&replayer.script());
+    assert_eq!(events.len(), recorded, "{:?}", events);
+    let mut seen = Vec::new();
+    replayer.replay(harness.clock(), |entry| seen.push(entry.at));
+    assert_eq!(harness.timer().clock.reading(), *seen.last().unwrap());
+}
+
+#[cfg(test)]
+fn any_action() -> impl proptest::strategy::Strategy<Value = Action> {
+    use proptest::prelude::*;
+    prop_oneof![
+        (1..30u64).prop_map(|t| Action::Advance(Duration::from_millis(t))),
+        Just(Action::Reset),
+        Just(Action::Pause),
+        Just(Action::Resume),
+        Just(Action::Start),
+        Just(Action::Stop),
+    ]
+}
+
+#[cfg(test)]
+proptest! {
+    #![proptest_config(proptest::test_runner::Config::with_cases(32))]
+    #[test]
+    fn harness_interleavings_are_deterministic(script in proptest::collection::vec(any_action(), 1..20)) {
+        let run = |script: &[Action]| {
+            let mut harness = Harness::new(Duration::from_millis(10));
+            let events = harness.run(script);
+            (events, harness.timer().expiries())
+        };
+        let (events, expiries) = run(&script);
+        prop_assert_eq!(run(&script), (events.clone(), expiries));
+        // Every expiry is reported exactly once, in order, never early.
+        let mut count = 0;
+        for event in &events {
+            if let Event::Expired(ref expiry) = *event {
+                count += 1;
+                prop_assert_eq!(expiry.count, count);
+                prop_assert!(expiry.fired >= expiry.deadline);
+            }
+        }
+        prop_assert_eq!(count, expiries);
+    }
+}
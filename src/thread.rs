@@ -0,0 +1,144 @@
+use std::io;
+use std::sync::Arc;
+use std::thread::{Builder, JoinHandle, Scope, ScopedJoinHandle};
+
+/// Called on each new thread before it does anything else.
+type SpawnHook = Arc<dyn Fn() + Send + Sync>;
+
+/// How the crate's own threads are spawned: timer threads, their callback
+/// pools, and the threads of a `TimerPool` or `TtlScheduler`.
+///
+/// By default threads are spawned like `std::thread::spawn` spawns them,
+/// unnamed, with the platform's default stack size. Constrained containers
+/// may need smaller stacks, and named threads are easier to pick out in a
+/// debugger or `top -H`.
+///
+#[derive(Clone, Default)]
+pub struct ThreadConfig {
+    // Prepended to each thread's name, if threads are named at all.
+    name_prefix: Option<String>,
+    // Stack size in bytes, or the platform default.
+    stack_size: Option<usize>,
+    hook: Option<SpawnHook>,
+}
+
+impl ThreadConfig {
+    /// Create a new config spawning threads like `std::thread::spawn`.
+    ///
+    pub fn new() -> ThreadConfig {
+        ThreadConfig::default()
+    }
+    /// Name each thread `prefix` followed by what it's for, e.g.,
+    /// `"app-timer-3"` for the thread of timer 3 given the prefix `"app-"`.
+    ///
+    pub fn with_name_prefix(mut self, prefix: &str) -> ThreadConfig {
+        self.name_prefix = Some(prefix.to_string());
+        self
+    }
+    /// Give each thread a stack of `bytes`, rounded up as the platform
+    /// requires.
+    ///
+    pub fn with_stack_size(mut self, bytes: usize) -> ThreadConfig {
+        self.stack_size = Some(bytes);
+        self
+    }
+    /// Run `hook` on each thread as it starts, before anything else runs
+    /// on it, e.g., to pin it to a core or register it with a profiler.
+    ///
+    pub fn with_spawn_hook<F>(mut self, hook: F) -> ThreadConfig
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+    /// A builder for a thread doing `what`, e.g., `"timer-3"`.
+    ///
+    fn builder(&self, what: &str) -> Builder {
+        let mut builder = Builder::new();
+        if let Some(ref prefix) = self.name_prefix {
+            builder = builder.name(format!("{}{}", prefix, what));
+        }
+        if let Some(bytes) = self.stack_size {
+            builder = builder.stack_size(bytes);
+        }
+        builder
+    }
+    /// Spawn a thread doing `what` to run `f`, failing if the platform
+    /// refuses, e.g., for lack of memory or of threads left to spawn.
+    ///
+    pub fn spawn<F, T>(&self, what: &str, f: F) -> io::Result<JoinHandle<T>>
+        where F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static
+    {
+        let hook = self.hook.clone();
+        self.builder(what).spawn(move || {
+            if let Some(hook) = hook {
+                hook();
+            }
+            f()
+        })
+    }
+    /// Like `spawn`, but on a thread of `scope`.
+    ///
+    pub fn spawn_scoped<'scope, 'env, F, T>(&self, scope: &'scope Scope<'scope, 'env>, what: &str, f: F)
+                                            -> io::Result<ScopedJoinHandle<'scope, T>>
+        where F: FnOnce() -> T + Send + 'scope,
+              T: Send + 'scope
+    {
+        let hook = self.hook.clone();
+        self.builder(what).spawn_scoped(scope, move || {
+            if let Some(hook) = hook {
+                hook();
+            }
+            f()
+        })
+    }
+}
+
+#[test]
+fn thread_config_spawn() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let hooked = Arc::new(AtomicUsize::new(0));
+    let h = hooked.clone();
+    let config = ThreadConfig::new()
+        .with_name_prefix("app-")
+        .with_stack_size(256 * 1024)
+        .with_spawn_hook(move || {
+            h.fetch_add(1, Ordering::SeqCst);
+        });
+    let name = config.spawn("worker-1", || std::thread::current().name().map(str::to_string)).unwrap();
+    assert_eq!(name.join().unwrap(), Some("app-worker-1".to_string()));
+    assert_eq!(hooked.load(Ordering::SeqCst), 1);
+    let unnamed = ThreadConfig::new().spawn("worker-1", || std::thread::current().name().is_none());
+    assert!(unnamed.unwrap().join().unwrap());
+    // Far more stack than there's address space for.
+    assert!(ThreadConfig::new().with_stack_size(usize::MAX / 2).spawn("huge", || {}).is_err());
+    assert_eq!(hooked.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn timer_thread_config() {
+    use lifecycle::{Phase, StartError};
+    use std::sync::mpsc::channel;
+    use std::sync::Condvar;
+    use std::time::Duration;
+    use Timer;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(5), ms(0), Arc::new(Condvar::new()));
+    t.set_thread_config(ThreadConfig::new().with_stack_size(usize::MAX / 2));
+    match t.try_start() {
+        Err(StartError::Spawn(_)) => {},
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(t.phase(), Phase::Stopped);
+    let (tx, rx) = channel();
+    let tx = std::sync::Mutex::new(tx);
+    t.on_expiry(move || {
+        let _ = tx.lock().unwrap().send(std::thread::current().name().map(str::to_string));
+    });
+    t.set_thread_config(ThreadConfig::new().with_name_prefix("app-"));
+    assert_eq!(t.try_start(), Ok(()));
+    let name = rx.recv_timeout(ms(500)).unwrap();
+    t.stop();
+    assert_eq!(name, Some(format!("app-{}", t.id())));
+}
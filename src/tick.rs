@@ -0,0 +1,87 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+fn from_nanos(nanos: u128) -> Duration {
+    Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+}
+
+/// Calls back at whole multiples of a period before each deadline, with
+/// the time left.
+///
+#[derive(Clone)]
+pub struct Ticker {
+    period: Duration,
+    f: Arc<dyn Fn(Duration) + Send + Sync>,
+}
+
+impl Ticker {
+    /// Create a new ticker calling `f` every `period`, which must not be
+    /// zero.
+    ///
+    pub fn new<F>(period: Duration, f: F) -> Ticker
+        where F: Fn(Duration) + Send + Sync + 'static
+    {
+        Ticker { period, f: Arc::new(f) }
+    }
+    /// Number of whole periods in `remaining`, rounded up.
+    ///
+    fn periods(&self, remaining: Duration) -> u64 {
+        remaining.as_nanos().div_ceil(self.period.as_nanos()) as u64
+    }
+    /// The first tick of a count down with `remaining` left, counted in
+    /// periods before the deadline.
+    ///
+    /// The deadline itself is never a tick, so the first is a whole period
+    /// in unless the count down is an uneven number of periods.
+    ///
+    pub fn first(&self, remaining: Duration) -> u64 {
+        self.periods(remaining).saturating_sub(1)
+    }
+    /// How long until tick `next` is due with `remaining` left, or `None`
+    /// if there are no more ticks this count down.
+    ///
+    pub fn until(&self, next: u64, remaining: Duration) -> Option<Duration> {
+        if next == 0 {
+            return None;
+        }
+        let at = self.period.as_nanos().saturating_mul(next as u128);
+        Some(from_nanos(remaining.as_nanos().saturating_sub(at)))
+    }
+    /// Run the callback if tick `next` is due with `remaining` left,
+    /// advancing `next` past it.
+    ///
+    /// The callback is given the time left rounded up to a whole period, so
+    /// successive ticks count down evenly. Ticks missed entirely are
+    /// skipped rather than run late.
+    ///
+    pub fn tick(&self, next: &mut u64, remaining: Duration) -> bool {
+        if self.until(*next, remaining) != Some(Duration::from_secs(0)) {
+            return false;
+        }
+        let periods = self.periods(remaining);
+        (self.f)(from_nanos(self.period.as_nanos().saturating_mul(periods as u128)));
+        *next = periods.saturating_sub(1);
+        true
+    }
+}
+
+#[test]
+fn ticker_counts_down_evenly() {
+    use std::sync::Mutex;
+    let ms = Duration::from_millis;
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let ticker = {
+        let seen = seen.clone();
+        Ticker::new(ms(10), move |left| seen.lock().unwrap().push(left))
+    };
+    let mut next = ticker.first(ms(35));
+    assert_eq!(next, 3);
+    assert_eq!(ticker.until(next, ms(35)), Some(ms(5)));
+    assert!(!ticker.tick(&mut next, ms(31)));
+    assert!(ticker.tick(&mut next, ms(29)));
+    // Running late skips the tick at 20ms.
+    assert!(ticker.tick(&mut next, ms(9)));
+    assert_eq!(next, 0);
+    assert_eq!(ticker.until(next, ms(5)), None);
+    assert_eq!(*seen.lock().unwrap(), vec![ms(30), ms(10)]);
+}
@@ -0,0 +1,67 @@
+use std::convert::TryFrom;
+use std::sync::{Arc, Condvar};
+use time::OffsetDateTime;
+use time::error::ConversionRange;
+use {Timer, Timestamp};
+
+impl Timer {
+    /// Create a new timer from `time` durations.
+    ///
+    /// Fails if either duration is negative.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The duration of time to wait for each count down.
+    /// * `jitter` - The duration of time to randomize each count down.
+    /// * `timed_out` - Condition to signal if the timer expires.
+    ///
+    pub fn from_time(step: time::Duration,
+                     jitter: time::Duration,
+                     timed_out: Arc<Condvar>) -> Result<Timer, ConversionRange> {
+        Ok(Timer::new(std::time::Duration::try_from(step)?,
+                      std::time::Duration::try_from(jitter)?,
+                      timed_out))
+    }
+}
+
+impl Timestamp {
+    /// The wall clock time this timestamp corresponds to, in UTC.
+    ///
+    /// See `to_system_time` for which timestamps can be mapped.
+    ///
+    pub fn to_offset_date_time(&self) -> Option<OffsetDateTime> {
+        self.to_system_time().map(OffsetDateTime::from)
+    }
+}
+
+#[test]
+fn time_constructor() {
+    let cv = Arc::new(Condvar::new());
+    let t = Timer::from_time(time::Duration::seconds(5),
+                             time::Duration::milliseconds(10),
+                             cv.clone()).unwrap();
+    assert_eq!(t.step(), std::time::Duration::from_secs(5));
+    assert!(Timer::from_time(time::Duration::seconds(-5),
+                             time::Duration::ZERO,
+                             cv).is_err());
+}
+
+#[test]
+fn time_fire_at_and_timestamps() {
+    use Event;
+    let cv = Arc::new(Condvar::new());
+    let mut t = Timer::with_clock(std::time::Duration::from_secs(60),
+                                  std::time::Duration::from_secs(0),
+                                  cv,
+                                  ::ClockSource::Wall);
+    let events = t.subscribe();
+    let at = OffsetDateTime::now_utc() + time::Duration::milliseconds(20);
+    t.fire_at(at);
+    t.start();
+    match events.recv_timeout(std::time::Duration::from_secs(1)) {
+        Ok(Event::Expired(e)) => assert!(e.fired.to_offset_date_time().unwrap() >= at),
+        Ok(other) => panic!("unexpected {:?}", other),
+        Err(e) => panic!("no expiry: {:?}", e),
+    }
+    t.stop();
+}
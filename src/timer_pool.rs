@@ -0,0 +1,293 @@
+use deadline::Deadline;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use thread::ThreadConfig;
+
+/// What to run when a timer falls due.
+enum Job {
+    Once(Box<dyn FnOnce() + Send + 'static>),
+    // Run every period until cancelled.
+    Every(Duration, Arc<dyn Fn() + Send + Sync + 'static>),
+}
+
+/// State shared with the worker threads.
+struct Inner {
+    // Deadline and id of every timer, earliest first. Cancelled timers are
+    // left in place and skipped once due, until they make up over half the
+    // heap.
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+    // What to run for each pending timer.
+    jobs: HashMap<u64, Job>,
+    // Id to hand out next.
+    next_id: u64,
+    // True until the pool is dropped.
+    alive: bool,
+}
+
+/// Runs many short-lived timers on a few shared threads.
+///
+/// Each `Timer` spawns and joins a thread of its own, which adds up for
+/// applications that arm and disarm timers constantly, like per-request
+/// timeouts. Timers scheduled on a pool are just a heap entry instead, and
+/// the pool's workers run them as they fall due. A panicking timer is
+/// caught, so it can't take a worker down with it. Dropping the pool joins
+/// its workers, discarding timers that haven't fired.
+///
+pub struct TimerPool {
+    shared: Arc<(Mutex<Inner>, Condvar)>,
+    // Worker thread handles to join on drop.
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// A timer scheduled on a `TimerPool`.
+///
+/// Dropping the handle leaves the timer scheduled.
+///
+#[derive(Clone)]
+pub struct PooledTimer {
+    id: u64,
+    shared: Arc<(Mutex<Inner>, Condvar)>,
+}
+
+impl TimerPool {
+    /// Create a new pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The number of worker threads, at least one. Timers due at
+    ///   the same time run concurrently on up to this many.
+    ///
+    pub fn new(size: usize) -> TimerPool {
+        TimerPool::with_threads(size, &ThreadConfig::default()).expect("Couldn't spawn pool thread!")
+    }
+    /// Create a new pool of `size` worker threads spawned per `threads`,
+    /// named `"timer-pool-0"` and so on after its prefix.
+    ///
+    /// Fails, joining any workers already spawned, if a worker can't be
+    /// spawned.
+    ///
+    pub fn with_threads(size: usize, threads: &ThreadConfig) -> io::Result<TimerPool> {
+        let shared = Arc::new((Mutex::new(Inner {
+            deadlines: BinaryHeap::new(),
+            jobs: HashMap::new(),
+            next_id: 0,
+            alive: true,
+        }), Condvar::new()));
+        let mut pool = TimerPool { shared, workers: Vec::with_capacity(size.max(1)) };
+        for i in 0..size.max(1) {
+            let shared = pool.shared.clone();
+            let worker = threads.spawn(&format!("timer-pool-{}", i), move || TimerPool::work(shared))?;
+            pool.workers.push(worker);
+        }
+        Ok(pool)
+    }
+    /// Internal worker loop.
+    ///
+    fn work(shared: Arc<(Mutex<Inner>, Condvar)>) {
+        let (ref m, ref cv) = *shared;
+        let mut inner = m.lock().unwrap();
+        while inner.alive {
+            let now = Instant::now();
+            let at = match inner.deadlines.peek() {
+                Some(&Reverse((at, _))) => at,
+                None => {
+                    inner = cv.wait(inner).unwrap();
+                    continue;
+                }
+            };
+            if at > now {
+                inner = cv.wait_timeout(inner, at - now).unwrap().0;
+                continue;
+            }
+            let Reverse((_, id)) = inner.deadlines.pop().unwrap();
+            let job: Box<dyn FnOnce() + Send> = match inner.jobs.remove(&id) {
+                Some(Job::Once(job)) => job,
+                Some(Job::Every(period, f)) => {
+                    // Left pending but off the heap while it runs, so it
+                    // can be cancelled, even by `f`, but can't run twice at
+                    // once.
+                    inner.jobs.insert(id, Job::Every(period, f.clone()));
+                    drop(inner);
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| f()));
+                    inner = m.lock().unwrap();
+                    if inner.jobs.contains_key(&id) {
+                        // Runs missed while this one ran, or while the pool
+                        // was busy, are skipped.
+                        let now = Instant::now();
+                        let next = if at + period > now { at + period } else { now + period };
+                        inner.deadlines.push(Reverse((next, id)));
+                        cv.notify_one();
+                    }
+                    continue;
+                },
+                None => continue,
+            };
+            drop(inner);
+            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            inner = m.lock().unwrap();
+        }
+    }
+    /// Run `f` once, `after` from now, on one of the pool's workers.
+    ///
+    pub fn schedule<F>(&self, after: Duration, f: F) -> PooledTimer
+        where F: FnOnce() + Send + 'static
+    {
+        self.push(after, Job::Once(Box::new(f)))
+    }
+    /// Run `f` every `period` from now, on the pool's workers, until
+    /// cancelled.
+    ///
+    /// Runs are due at whole periods from now, and any missed while every
+    /// worker was busy are skipped rather than run late. Runs never
+    /// overlap: the next is only scheduled once `f` returns, so one that
+    /// takes longer than `period` skips the runs it overlapped.
+    ///
+    /// # Panics
+    ///
+    /// If `period` is zero.
+    ///
+    pub fn schedule_every<F>(&self, period: Duration, f: F) -> PooledTimer
+        where F: Fn() + Send + Sync + 'static
+    {
+        assert!(period > Duration::from_secs(0), "Pool timer period must be non-zero!");
+        self.push(period, Job::Every(period, Arc::new(f)))
+    }
+    /// Schedule `job`, `after` from now.
+    ///
+    fn push(&self, after: Duration, job: Job) -> PooledTimer {
+        let at = Deadline::after(after).instant();
+        let (ref m, ref cv) = *self.shared;
+        let mut inner = m.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.deadlines.push(Reverse((at, id)));
+        inner.jobs.insert(id, job);
+        cv.notify_one();
+        PooledTimer { id, shared: self.shared.clone() }
+    }
+    /// Number of timers that haven't fired or been cancelled.
+    ///
+    pub fn pending(&self) -> usize {
+        self.shared.0.lock().unwrap().jobs.len()
+    }
+}
+
+impl PooledTimer {
+    /// Cancel the timer, returning false if it had already fired or been
+    /// cancelled.
+    ///
+    pub fn cancel(&self) -> bool {
+        let mut inner = self.shared.0.lock().unwrap();
+        if inner.jobs.remove(&self.id).is_none() {
+            return false;
+        }
+        // Drop the cancelled entries once they outnumber the live ones, so
+        // timers armed and cancelled constantly don't grow the heap.
+        if inner.deadlines.len() > 2 * inner.jobs.len() {
+            let Inner { ref mut deadlines, ref jobs, .. } = *inner;
+            deadlines.retain(|&Reverse((_, id))| jobs.contains_key(&id));
+        }
+        true
+    }
+    /// True if the timer hasn't fired or been cancelled.
+    ///
+    pub fn is_pending(&self) -> bool {
+        self.shared.0.lock().unwrap().jobs.contains_key(&self.id)
+    }
+}
+
+impl Drop for TimerPool {
+    fn drop(&mut self) {
+        {
+            let (ref m, ref cv) = *self.shared;
+            m.lock().unwrap().alive = false;
+            cv.notify_all();
+        }
+        for worker in self.workers.drain(..) {
+            worker.join().expect("Couldn't join pool thread!");
+        }
+    }
+}
+
+#[test]
+fn timer_pool_runs_and_cancels() {
+    use std::sync::mpsc::channel;
+    let ms = Duration::from_millis;
+    let pool = TimerPool::new(2);
+    let (tx, rx) = channel();
+    let mut timers = Vec::new();
+    for i in (0..10).rev() {
+        let tx = tx.clone();
+        timers.push(pool.schedule(ms(5 * i), move || tx.send(i).unwrap()));
+    }
+    let panicky = pool.schedule(ms(1), || panic!("caught by the pool"));
+    assert!(timers[0].cancel());
+    assert!(!timers[0].cancel());
+    let fired: Vec<u64> = rx.iter().take(9).collect();
+    assert_eq!(fired, (0..9).collect::<Vec<_>>());
+    assert!(!panicky.is_pending());
+    assert!(!timers[1].is_pending());
+    assert_eq!(pool.pending(), 0);
+}
+
+#[test]
+fn timer_pool_schedule_every() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let ms = Duration::from_millis;
+    let pool = TimerPool::new(1);
+    let runs = Arc::new(AtomicUsize::new(0));
+    let r = runs.clone();
+    let every = pool.schedule_every(ms(10), move || {
+        if r.fetch_add(1, Ordering::SeqCst) == 0 {
+            panic!("caught by the pool");
+        }
+    });
+    std::thread::sleep(ms(55));
+    assert!(every.is_pending());
+    assert!(every.cancel());
+    let seen = runs.load(Ordering::SeqCst);
+    assert!((4..=6).contains(&seen), "{}", seen);
+    std::thread::sleep(ms(30));
+    assert_eq!(runs.load(Ordering::SeqCst), seen);
+    assert_eq!(pool.pending(), 0);
+}
+
+#[test]
+fn timer_pool_purges_cancelled() {
+    let pool = TimerPool::new(1);
+    let timers: Vec<PooledTimer> = (0..100).map(|_| pool.schedule(Duration::from_secs(60), || {})).collect();
+    for timer in &timers[..60] {
+        assert!(timer.cancel());
+    }
+    let heap = pool.shared.0.lock().unwrap().deadlines.len();
+    assert!(heap <= 2 * pool.pending(), "{}", heap);
+    assert_eq!(pool.pending(), 40);
+}
+
+#[test]
+fn timer_pool_every_never_overlaps() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let ms = Duration::from_millis;
+    let pool = TimerPool::new(4);
+    let (running, runs) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let (r, n) = (running.clone(), runs.clone());
+    // Each run takes three periods...
+    let every = pool.schedule_every(ms(5), move || {
+        assert_eq!(r.fetch_add(1, Ordering::SeqCst), 0);
+        std::thread::sleep(ms(15));
+        r.fetch_sub(1, Ordering::SeqCst);
+        n.fetch_add(1, Ordering::SeqCst);
+    });
+    std::thread::sleep(ms(100));
+    every.cancel();
+    std::thread::sleep(ms(30));
+    // ...so the ones it overlapped are skipped, not run alongside it.
+    let seen = runs.load(Ordering::SeqCst);
+    assert!((2..=7).contains(&seen), "{}", seen);
+    assert_eq!(running.load(Ordering::SeqCst), 0);
+}
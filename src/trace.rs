@@ -0,0 +1,338 @@
+use clock::{ClockJump, ClockSource, Timestamp};
+use config::JitterPolicy;
+use event::{Event, ExpiryEvent, Reconfiguration};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use Timer;
+
+/// Something recorded in a trace.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceEvent {
+    /// The timer was started.
+    Started,
+    /// The timer was asked to stop.
+    StopRequested,
+    /// The timer emitted an event. Its timestamps are offsets from the
+    /// start of the trace, as `Timestamp::Custom`.
+    Emitted(Event),
+}
+
+/// A recorded event and when it happened.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    /// Time since the trace began, on the recorded timer's clock.
+    pub at: Duration,
+    /// What happened.
+    pub event: TraceEvent,
+}
+
+/// Why a trace couldn't be parsed.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceError {
+    /// The line that couldn't be parsed, counting from one.
+    pub line: usize,
+    /// The offending line.
+    pub text: String,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid trace entry on line {}: {:?}", self.line, self.text)
+    }
+}
+
+impl Error for TraceError {}
+
+/// A recording of a timer's life, from `Recorder::trace`.
+///
+/// Converts to and from a plain text form, one entry per line, so that a
+/// trace captured in production can be saved and replayed against a mock
+/// clock with `testing::Replayer`. Times are written in nanoseconds, and
+/// read back as offsets from the start of the trace.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    /// Everything recorded, in the order it happened.
+    pub entries: Vec<TraceEntry>,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            write!(f, "{} ", entry.at.as_nanos())?;
+            match entry.event {
+                TraceEvent::Started => writeln!(f, "started")?,
+                TraceEvent::StopRequested => writeln!(f, "stop")?,
+                TraceEvent::Emitted(Event::Expired(ref e)) => {
+                    writeln!(f, "expired {} {} {} {}", e.count, nanos(e.deadline), nanos(e.fired), e.coalesced)?
+                },
+                TraceEvent::Emitted(Event::ClockJumped(ref jump)) => {
+                    let direction = if jump.forward { "forward" } else { "back" };
+                    writeln!(f, "jumped {} {}", direction, jump.by.as_nanos())?
+                },
+                TraceEvent::Emitted(Event::Reconfigured(ref r)) => {
+                    let policy = match r.jitter_policy {
+                        JitterPolicy::Subtractive => "subtractive",
+                        JitterPolicy::Additive => "additive",
+                    };
+                    write!(f, "reconfigured {} {} {}", r.step.as_nanos(), r.jitter.as_nanos(), policy)?;
+                    match r.schedule {
+                        Some(ref schedule) => writeln!(f, " {}", schedule)?,
+                        None => writeln!(f)?,
+                    }
+                },
+                TraceEvent::Emitted(Event::Stopped) => writeln!(f, "stopped")?,
+                TraceEvent::Emitted(Event::Completed) => writeln!(f, "completed")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = TraceError;
+    fn from_str(s: &str) -> Result<Trace, TraceError> {
+        let mut entries = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = parse_entry(line).ok_or_else(|| TraceError { line: i + 1, text: line.to_string() })?;
+            entries.push(entry);
+        }
+        Ok(Trace { entries })
+    }
+}
+
+/// The nanoseconds in a trace timestamp.
+///
+/// Recorded traces only hold offsets. Monotonic and wall clock stamps, as
+/// in a trace built by hand, are written as their wall clock time since
+/// the UNIX epoch.
+///
+fn nanos(stamp: Timestamp) -> u128 {
+    match stamp {
+        Timestamp::Custom(d) => d.as_nanos(),
+        _ => stamp.to_system_time()
+            .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos()),
+    }
+}
+
+/// Parse a count of nanoseconds.
+///
+fn duration(field: Option<&str>) -> Option<Duration> {
+    let nanos: u128 = field?.parse().ok()?;
+    let secs = nanos / 1_000_000_000;
+    if secs > u64::MAX as u128 {
+        return None;
+    }
+    Some(Duration::new(secs as u64, (nanos % 1_000_000_000) as u32))
+}
+
+/// Parse one line of a trace.
+///
+fn parse_entry(line: &str) -> Option<TraceEntry> {
+    let mut fields = line.splitn(3, ' ');
+    let at = duration(fields.next())?;
+    let kind = fields.next()?;
+    let rest = fields.next().unwrap_or("");
+    let mut args = rest.split(' ');
+    let event = match kind {
+        "started" => TraceEvent::Started,
+        "stop" => TraceEvent::StopRequested,
+        "expired" => TraceEvent::Emitted(Event::Expired(ExpiryEvent {
+            count: args.next()?.parse().ok()?,
+            deadline: Timestamp::Custom(duration(args.next())?),
+            fired: Timestamp::Custom(duration(args.next())?),
+            coalesced: args.next()?.parse().ok()?,
+        })),
+        "jumped" => TraceEvent::Emitted(Event::ClockJumped(ClockJump {
+            forward: match args.next()? {
+                "forward" => true,
+                "back" => false,
+                _ => return None,
+            },
+            by: duration(args.next())?,
+        })),
+        "reconfigured" => {
+            let mut args = rest.splitn(4, ' ');
+            TraceEvent::Emitted(Event::Reconfigured(Reconfiguration {
+                step: duration(args.next())?,
+                jitter: duration(args.next())?,
+                jitter_policy: args.next()?.parse().ok()?,
+                schedule: args.next().map(str::to_string),
+            }))
+        },
+        "stopped" => TraceEvent::Emitted(Event::Stopped),
+        "completed" => TraceEvent::Emitted(Event::Completed),
+        _ => return None,
+    };
+    Some(TraceEntry { at, event })
+}
+
+/// State shared by a `Recorder` and the timer it records.
+///
+pub struct Recording {
+    clock: ClockSource,
+    // Clock reading the trace began at.
+    origin: Duration,
+    entries: Mutex<Vec<TraceEntry>>,
+}
+
+impl Recording {
+    /// Offset of a reading of the timer's clock from the start of the
+    /// trace.
+    ///
+    fn offset(&self, reading: Duration) -> Duration {
+        reading.saturating_sub(self.origin)
+    }
+    /// Offset of `stamp` from the start of the trace.
+    ///
+    fn rebase(&self, stamp: Timestamp) -> Timestamp {
+        let now = self.clock.reading();
+        let reading = match (stamp, self.clock.stamp(now)) {
+            (Timestamp::Monotonic(at), Timestamp::Monotonic(then)) if at <= then => {
+                now.saturating_sub(then - at)
+            },
+            (Timestamp::Monotonic(at), Timestamp::Monotonic(then)) => now.saturating_add(at - then),
+            (Timestamp::Wall(at), _) => at.duration_since(UNIX_EPOCH).unwrap_or_default(),
+            (Timestamp::Custom(at), _) => at,
+            _ => now,
+        };
+        Timestamp::Custom(self.offset(reading))
+    }
+    /// Record `event` as happening now.
+    ///
+    pub fn record(&self, event: TraceEvent) {
+        let event = match event {
+            TraceEvent::Emitted(Event::Expired(e)) => TraceEvent::Emitted(Event::Expired(ExpiryEvent {
+                deadline: self.rebase(e.deadline),
+                fired: self.rebase(e.fired),
+                ..e
+            })),
+            event => event,
+        };
+        let at = self.offset(self.clock.reading());
+        self.entries.lock().unwrap().push(TraceEntry { at, event });
+    }
+}
+
+/// Records everything that happens to a timer, from `Timer::record`.
+///
+/// Every start, stop request and event is captured with the time it
+/// happened, until the recorder is dropped.
+///
+pub struct Recorder {
+    recording: Arc<Recording>,
+}
+
+impl Recorder {
+    /// A copy of everything recorded so far.
+    ///
+    pub fn trace(&self) -> Trace {
+        Trace { entries: self.recording.entries.lock().unwrap().clone() }
+    }
+}
+
+impl Timer {
+    /// Start recording the timer's life into a trace, from now until the
+    /// returned recorder is dropped.
+    ///
+    pub fn record(&self) -> Recorder {
+        let recording = Arc::new(Recording {
+            clock: self.clock.clone(),
+            origin: self.clock.reading(),
+            entries: Mutex::new(Vec::new()),
+        });
+        self.subscribers.record(&recording);
+        Recorder { recording }
+    }
+}
+
+#[test]
+fn trace_round_trips() {
+    let ms = Duration::from_millis;
+    let stamp = |t| Timestamp::Custom(ms(t));
+    let trace = Trace {
+        entries: vec![
+            TraceEntry { at: ms(0), event: TraceEvent::Started },
+            TraceEntry {
+                at: ms(11),
+                event: TraceEvent::Emitted(Event::Expired(ExpiryEvent {
+                    count: 1, deadline: stamp(10), fired: stamp(11), coalesced: 1,
+                })),
+            },
+            TraceEntry {
+                at: ms(12),
+                event: TraceEvent::Emitted(Event::ClockJumped(ClockJump { forward: false, by: ms(5) })),
+            },
+            TraceEntry {
+                at: ms(13),
+                event: TraceEvent::Emitted(Event::Reconfigured(Reconfiguration {
+                    step: ms(20),
+                    jitter: ms(1),
+                    jitter_policy: JitterPolicy::Additive,
+                    schedule: Some("0 3 * * *".to_string()),
+                })),
+            },
+            TraceEntry { at: ms(14), event: TraceEvent::StopRequested },
+            TraceEntry { at: Duration::new(5, 1), event: TraceEvent::Emitted(Event::Completed) },
+        ],
+    };
+    let text = trace.to_string();
+    assert!(text.starts_with("0 started\n11000000 expired 1 10000000 11000000 1\n"), "{}", text);
+    assert_eq!(text.parse::<Trace>(), Ok(trace));
+    assert_eq!("0 started\n5 expired 1\n".parse::<Trace>(),
+               Err(TraceError { line: 2, text: "5 expired 1".to_string() }));
+}
+
+#[test]
+fn timer_record() {
+    use std::sync::Condvar;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(10), ms(0), Arc::new(Condvar::new()));
+    let recorder = t.record();
+    t.set_max_expiries(3);
+    t.start();
+    assert!(t.wait_for_completion(Duration::from_secs(1)));
+    t.stop();
+    let trace = recorder.trace();
+    let text = trace.to_string();
+    let kinds: Vec<&str> = text.lines().map(|line| line.split(' ').nth(1).unwrap()).collect();
+    assert_eq!(kinds, vec!["started", "expired", "expired", "expired", "completed"]);
+    let mut last = Duration::from_secs(0);
+    for entry in &trace.entries {
+        assert!(entry.at >= last);
+        last = entry.at;
+        if let TraceEvent::Emitted(Event::Expired(ref e)) = entry.event {
+            assert!(e.fired >= e.deadline && e.deadline >= Timestamp::Custom(ms(10)));
+            assert!(Timestamp::Custom(entry.at) >= e.fired);
+        }
+    }
+}
+
+#[test]
+fn trace_writes_clock_stamps() {
+    let at = UNIX_EPOCH + Duration::from_secs(5);
+    let trace = Trace {
+        entries: vec![TraceEntry {
+            at: Duration::from_secs(1),
+            event: TraceEvent::Emitted(Event::Expired(ExpiryEvent {
+                count: 1,
+                deadline: Timestamp::Wall(at),
+                fired: Timestamp::Monotonic(std::time::Instant::now()),
+                coalesced: 1,
+            })),
+        }],
+    };
+    let text = trace.to_string();
+    assert!(text.starts_with("1000000000 expired 1 5000000000 "), "{}", text);
+    assert_eq!(text.parse::<Trace>().unwrap().entries.len(), 1);
+}
@@ -0,0 +1,626 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::io;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use thread::ThreadConfig;
+use wheel::{EntryKey, Wheel, WheelConfig};
+
+/// A key inserted, but not yet put on the wheel.
+struct Registration<K> {
+    key: K,
+    // Tells this insertion of the key apart from any other.
+    id: u64,
+    // When the key is due, as time since the wheel was at tick zero.
+    due: Duration,
+}
+
+/// State guarded by a shard's lock.
+struct Inner<K> {
+    wheel: Wheel<K>,
+    // The wheel entry for each pending key, and the insertion it's from.
+    keys: HashMap<K, (EntryKey, u64)>,
+    // Keys inserted since the wheel was last brought up to date.
+    registrations: Receiver<Registration<K>>,
+    // How late keys may expire to share a wake up with later ones.
+    slack: Duration,
+    // True until the scheduler is dropped.
+    alive: bool,
+}
+
+impl<K> Inner<K>
+    where K: Clone + Eq + Hash
+{
+    /// Put every key inserted since the last call on the wheel, returning
+    /// true if there were any.
+    ///
+    fn register(&mut self) -> bool {
+        let mut any = false;
+        while let Ok(registration) = self.registrations.try_recv() {
+            if let Some((old, _)) = self.keys.remove(&registration.key) {
+                self.wheel.remove(old);
+            }
+            // Due is fixed at insertion, not now, so a key is never expired
+            // late for having waited in the queue.
+            let delay = registration.due.saturating_sub(self.wheel.elapsed_at(self.wheel.tick()));
+            let entry = self.wheel.insert(delay, registration.key.clone());
+            self.keys.insert(registration.key, (entry, registration.id));
+            any = true;
+        }
+        any
+    }
+    /// Forget `key` without expiring it, if it's pending, and from the
+    /// insertion `id` when given. Returns false if it wasn't.
+    ///
+    fn remove(&mut self, key: &K, id: Option<u64>) -> bool {
+        match self.keys.get(key) {
+            Some(&(entry, inserted)) if id.is_none_or(|id| id == inserted) => {
+                self.keys.remove(key);
+                self.wheel.remove(entry).is_some()
+            },
+            _ => false,
+        }
+    }
+}
+
+/// State shared with a shard's expiry thread.
+struct Shared<K> {
+    inner: Mutex<Inner<K>>,
+    cv: Condvar,
+    // When the wheel was at tick zero.
+    start: Instant,
+    // Length of one tick.
+    resolution: Duration,
+    // The tick the expiry thread sleeps until, `u64::MAX` if until woken,
+    // or zero if awake. Keys due sooner must wake it.
+    sleeps_until: AtomicU64,
+    // Nanoseconds to round deadlines up to a multiple of, or zero if not in
+    // low power mode.
+    boundary: AtomicU64,
+    // Source of insertion ids.
+    insertions: AtomicU64,
+}
+
+impl<K> Shared<K> {
+    /// When a key inserted now with `ttl` is due, as time since tick zero,
+    /// rounded up to the low power boundary unless `precise`.
+    ///
+    fn due(&self, ttl: Duration, precise: bool) -> Duration {
+        let due = self.start.elapsed().saturating_add(ttl);
+        let boundary = self.boundary.load(Ordering::Relaxed) as u128;
+        if precise || boundary == 0 {
+            return due;
+        }
+        let due = due.as_nanos().div_ceil(boundary) * boundary;
+        Duration::from_nanos(due.min(u64::MAX as u128) as u64)
+    }
+}
+
+/// Cancels one insertion of a key into a `TtlScheduler`.
+///
+/// Returned by `insert`. Unlike `remove`, which forgets whatever time to
+/// live the key has, cancelling leaves the key alone if it's been inserted
+/// again since.
+///
+pub struct TimerKey<K> {
+    shared: Weak<Shared<K>>,
+    key: K,
+    id: u64,
+}
+
+impl<K> TimerKey<K>
+    where K: Clone + Eq + Hash
+{
+    /// The key inserted.
+    ///
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+    /// Forget the key without expiring it. Returns false if it had already
+    /// expired, been removed or been inserted again, or the scheduler has
+    /// been dropped.
+    ///
+    /// Takes constant time. Like `remove`, a key is either cancelled or
+    /// expired, never both.
+    ///
+    pub fn cancel(&self) -> bool {
+        let shared = match self.shared.upgrade() {
+            Some(shared) => shared,
+            None => return false,
+        };
+        let mut inner = shared.inner.lock().unwrap();
+        inner.register();
+        inner.remove(&self.key, Some(self.id))
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for TimerKey<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TimerKey").field("key", &self.key).field("id", &self.id).finish()
+    }
+}
+
+/// One wheel and the thread that expires it.
+struct Shard<K> {
+    shared: Arc<Shared<K>>,
+    // Where inserted keys queue for the expiry thread.
+    registrations: Sender<Registration<K>>,
+    // Expiry thread handle to join on drop.
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Expires keys after a time to live, all on one thread.
+///
+/// Keys are kept on a timing wheel rather than given a timer each, so
+/// millions of cache entries cost one thread and constant time per insert
+/// and removal. Expiry is accurate to the wheel's resolution, and keys are
+/// only ever expired late, never early.
+///
+/// Inserting doesn't take the wheel's lock. Inserted keys queue up, and are
+/// put on the wheel by the expiry thread between ticks, or by the next call
+/// that looks at the wheel, so they're always visible to the caller.
+///
+/// Where many threads insert and remove keys at once, `sharded` splits keys
+/// over several wheels so they rarely contend on the same one.
+///
+pub struct TtlScheduler<K> {
+    shards: Vec<Shard<K>>,
+    // Picks each key's shard.
+    hasher: RandomState,
+}
+
+impl<K> TtlScheduler<K>
+    where K: Clone + Eq + Hash + Send + 'static
+{
+    /// Create a new scheduler that calls `f` with each key that expires.
+    ///
+    /// `f` runs on the scheduler's thread, so it should hand off rather
+    /// than block.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - Granularity time to live is rounded up to.
+    /// * `f` - Called with each expired key.
+    ///
+    pub fn new<F>(resolution: Duration, f: F) -> TtlScheduler<K>
+        where F: FnMut(K) + Send + 'static
+    {
+        TtlScheduler::with_config(WheelConfig::new(resolution), f)
+    }
+    /// Create a new scheduler on a wheel of the given shape that calls `f`
+    /// with each key that expires.
+    ///
+    pub fn with_config<F>(config: WheelConfig, f: F) -> TtlScheduler<K>
+        where F: FnMut(K) + Send + 'static
+    {
+        let shard = TtlScheduler::shard(config, &ThreadConfig::default(), 0, f)
+            .expect("Couldn't spawn scheduler thread!");
+        TtlScheduler {
+            shards: vec![shard],
+            hasher: RandomState::new(),
+        }
+    }
+    /// Create a new scheduler that splits keys over `shards` wheels, each
+    /// expired by a thread of its own, and calls `f` with each key that
+    /// expires.
+    ///
+    /// Keys are assigned a shard by hash, so inserts and removals only
+    /// contend when they land on the same shard. One shard per core, from
+    /// `std::thread::available_parallelism`, suits most servers. `f` may
+    /// be called from every shard's thread at once.
+    ///
+    pub fn sharded<F>(config: WheelConfig, shards: usize, f: F) -> TtlScheduler<K>
+        where F: Fn(K) + Send + Sync + 'static
+    {
+        TtlScheduler::with_threads(config, shards, &ThreadConfig::default(), f)
+            .expect("Couldn't spawn scheduler thread!")
+    }
+    /// Like `sharded`, but spawning each shard's thread per `threads`,
+    /// named `"ttl-0"` and so on after its prefix.
+    ///
+    /// Fails, stopping any shards already started, if a shard's thread
+    /// can't be spawned.
+    ///
+    pub fn with_threads<F>(config: WheelConfig, shards: usize, threads: &ThreadConfig, f: F)
+                           -> io::Result<TtlScheduler<K>>
+        where F: Fn(K) + Send + Sync + 'static
+    {
+        let f = Arc::new(f);
+        Ok(TtlScheduler {
+            shards: (0..shards.max(1))
+                .map(|i| {
+                    let f = f.clone();
+                    TtlScheduler::shard(config, threads, i, move |key| f(key))
+                })
+                .collect::<io::Result<_>>()?,
+            hasher: RandomState::new(),
+        })
+    }
+    /// Create the `i`th shard, and start its expiry thread.
+    ///
+    fn shard<F>(config: WheelConfig, threads: &ThreadConfig, i: usize, f: F) -> io::Result<Shard<K>>
+        where F: FnMut(K) + Send + 'static
+    {
+        let (tx, rx) = channel();
+        let start = Instant::now();
+        let shared = Arc::new(Shared {
+            inner: Mutex::new(Inner {
+                wheel: Wheel::new(config),
+                keys: HashMap::new(),
+                registrations: rx,
+                slack: Duration::from_secs(0),
+                alive: true,
+            }),
+            cv: Condvar::new(),
+            start,
+            resolution: config.tick,
+            sleeps_until: AtomicU64::new(0),
+            boundary: AtomicU64::new(0),
+            insertions: AtomicU64::new(0),
+        });
+        let s = shared.clone();
+        let handle = threads.spawn(&format!("ttl-{}", i), move || TtlScheduler::run(s, f))?;
+        Ok(Shard {
+            shared,
+            registrations: tx,
+            handle: Some(handle),
+        })
+    }
+    /// The shard `key` belongs to.
+    ///
+    fn shard_of(&self, key: &K) -> &Shard<K> {
+        let shard = match self.shards.len() {
+            1 => 0,
+            n => (self.hasher.hash_one(key) % n as u64) as usize,
+        };
+        &self.shards[shard]
+    }
+    /// Lock the wheel of the shard `key` belongs to, bringing it up to date.
+    ///
+    fn lock(&self, key: &K) -> MutexGuard<'_, Inner<K>> {
+        let shared = &*self.shard_of(key).shared;
+        let mut inner = shared.inner.lock().unwrap();
+        inner.register();
+        inner
+    }
+    /// Create a new scheduler that sends each key that expires down a
+    /// channel.
+    ///
+    pub fn channel(resolution: Duration) -> (TtlScheduler<K>, Receiver<K>) {
+        let (tx, rx) = channel();
+        let scheduler = TtlScheduler::new(resolution, move |key| {
+            let _ = tx.send(key);
+        });
+        (scheduler, rx)
+    }
+    /// Internal expiry loop.
+    ///
+    fn run<F>(shared: Arc<Shared<K>>, mut f: F)
+        where F: FnMut(K)
+    {
+        let shared = &*shared;
+        let mut inner = shared.inner.lock().unwrap();
+        while inner.alive {
+            inner.register();
+            let tick = inner.wheel.tick_at(shared.start.elapsed());
+            let expired = inner.wheel.advance(tick);
+            for key in &expired {
+                inner.keys.remove(key);
+            }
+            if !expired.is_empty() {
+                drop(inner);
+                for key in expired {
+                    f(key);
+                }
+                inner = shared.inner.lock().unwrap();
+                continue;
+            }
+            // Sleep right through to the next tick with anything to do,
+            // however far off. Pairs with the fence in insert, so either a
+            // new key is seen here, or the inserter sees it must wake us.
+            let next = inner.wheel.next_tick();
+            shared.sleeps_until.store(next.unwrap_or(u64::MAX), Ordering::SeqCst);
+            fence(Ordering::SeqCst);
+            if inner.register() {
+                shared.sleeps_until.store(0, Ordering::SeqCst);
+                continue;
+            }
+            inner = match next {
+                Some(next) => {
+                    let wake = inner.wheel.elapsed_at(next).saturating_add(inner.slack);
+                    let timeout = wake.saturating_sub(shared.start.elapsed());
+                    shared.cv.wait_timeout(inner, timeout).unwrap().0
+                },
+                None => shared.cv.wait(inner).unwrap(),
+            };
+            shared.sleeps_until.store(0, Ordering::SeqCst);
+        }
+    }
+    /// Expire `key` after `ttl`, replacing any time to live it already had.
+    ///
+    /// Returns a key to cancel this insertion with.
+    ///
+    pub fn insert(&self, key: K, ttl: Duration) -> TimerKey<K> {
+        self.register(key, ttl, false)
+    }
+    /// Expire `key` after `ttl`, like `insert`, but exempt from low power
+    /// mode's rounding.
+    ///
+    pub fn insert_precise(&self, key: K, ttl: Duration) -> TimerKey<K> {
+        self.register(key, ttl, true)
+    }
+    /// Queue `key` for its shard's expiry thread, waking the thread if the
+    /// key is due before it would otherwise wake up.
+    ///
+    fn register(&self, key: K, ttl: Duration, precise: bool) -> TimerKey<K> {
+        let shard = self.shard_of(&key);
+        let shared = &*shard.shared;
+        let due = shared.due(ttl, precise);
+        let id = shared.insertions.fetch_add(1, Ordering::Relaxed);
+        let timer_key = TimerKey {
+            shared: Arc::downgrade(&shard.shared),
+            key: key.clone(),
+            id,
+        };
+        let _ = shard.registrations.send(Registration { key, id, due });
+        fence(Ordering::SeqCst);
+        let tick = due.as_nanos().div_ceil(shared.resolution.as_nanos()).min(u64::MAX as u128) as u64;
+        if tick < shared.sleeps_until.load(Ordering::SeqCst) {
+            // Locking makes sure the thread is waiting before it's woken.
+            let _inner = shared.inner.lock().unwrap();
+            shared.cv.notify_all();
+        }
+        timer_key
+    }
+    /// Forget `key` without expiring it. Returns false if it wasn't pending.
+    ///
+    /// Takes constant time. A key is either removed or expired, never both:
+    /// if this returns true, `f` is never called with the key, and if it
+    /// returns false, `f` has been or is about to be.
+    ///
+    pub fn remove(&self, key: &K) -> bool {
+        self.lock(key).remove(key, None)
+    }
+    /// True if `key` is waiting to expire.
+    ///
+    pub fn contains(&self, key: &K) -> bool {
+        self.lock(key).keys.contains_key(key)
+    }
+    /// Number of keys waiting to expire.
+    ///
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| {
+                let mut inner = shard.shared.inner.lock().unwrap();
+                inner.register();
+                inner.keys.len()
+            })
+            .sum()
+    }
+    /// True if no keys are waiting to expire.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Let keys expire up to `slack` late, so that keys due close together
+    /// expire on one wake up rather than one each.
+    ///
+    /// Like Linux timer slack, this trades accuracy for fewer wake ups,
+    /// which saves power on battery powered devices. Keys still never
+    /// expire early. Zero, the default, wakes for every tick with a key due.
+    ///
+    pub fn set_slack(&self, slack: Duration) {
+        for shard in &self.shards {
+            shard.shared.inner.lock().unwrap().slack = slack;
+            shard.shared.cv.notify_all();
+        }
+    }
+    /// Round the deadline of every key inserted from now on up to a
+    /// multiple of `boundary`, or stop rounding with `None`.
+    ///
+    /// Low power mode: keys due within the same boundary all expire on one
+    /// wake up, at the cost of expiring up to a whole boundary late, such
+    /// as 250ms. Keys inserted with `insert_precise` are exempt. Keys
+    /// already waiting keep their deadlines.
+    ///
+    pub fn set_low_power(&self, boundary: Option<Duration>) {
+        let boundary = boundary.map_or(0, |boundary| boundary.as_nanos().min(u64::MAX as u128) as u64);
+        for shard in &self.shards {
+            shard.shared.boundary.store(boundary, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<K> Drop for Shard<K> {
+    fn drop(&mut self) {
+        self.shared.inner.lock().unwrap().alive = false;
+        self.shared.cv.notify_all();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[test]
+fn ttl_scheduler_expires_keys() {
+    let ms = Duration::from_millis;
+    let (ttl, expired) = TtlScheduler::channel(ms(5));
+    let started = Instant::now();
+    ttl.insert("a", ms(20));
+    ttl.insert("b", ms(40));
+    ttl.insert("c", ms(30));
+    assert!(ttl.remove(&"c"));
+    assert_eq!(ttl.len(), 2);
+    assert_eq!(expired.recv().unwrap(), "a");
+    assert!(started.elapsed() >= ms(20));
+    assert_eq!(expired.recv().unwrap(), "b");
+    assert!(ttl.is_empty());
+    assert!(expired.recv_timeout(ms(30)).is_err());
+}
+
+#[test]
+fn ttl_scheduler_many_keys() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let ttl = TtlScheduler::new(Duration::from_millis(1), move |_| {
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+    for i in 0..100_000 {
+        ttl.insert(i, Duration::from_millis(i as u64 % 50));
+    }
+    // Re-inserting a key replaces its time to live...
+    ttl.insert(0, Duration::from_secs(60));
+    while count.load(Ordering::SeqCst) < 99_999 {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(ttl.contains(&0));
+}
+
+#[test]
+fn ttl_scheduler_sharded() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let config = WheelConfig::new(Duration::from_millis(1));
+    let ttl = Arc::new(TtlScheduler::sharded(config, 4, move |_| {
+        c.fetch_add(1, Ordering::SeqCst);
+    }));
+    let inserters: Vec<_> = (0..4)
+        .map(|t| {
+            let ttl = ttl.clone();
+            std::thread::spawn(move || {
+                for i in 0..10_000 {
+                    ttl.insert((t, i), Duration::from_millis(i as u64 % 20));
+                }
+                // Removing a key keeps it from ever expiring.
+                ttl.insert((t, 10_000), Duration::from_secs(1));
+                assert!(ttl.remove(&(t, 10_000)));
+            })
+        })
+        .collect();
+    for inserter in inserters {
+        inserter.join().unwrap();
+    }
+    while count.load(Ordering::SeqCst) < 40_000 {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    std::thread::sleep(Duration::from_millis(30));
+    assert_eq!(count.load(Ordering::SeqCst), 40_000);
+    assert!(ttl.is_empty());
+}
+
+#[test]
+fn ttl_scheduler_wakes_for_queued_keys() {
+    let ms = Duration::from_millis;
+    let (ttl, expired) = TtlScheduler::channel(ms(1));
+    // Let the expiry thread go idle on an empty wheel first.
+    std::thread::sleep(ms(10));
+    let started = Instant::now();
+    ttl.insert(1, ms(10));
+    assert_eq!(expired.recv_timeout(ms(1000)), Ok(1));
+    assert!(started.elapsed() >= ms(10));
+    ttl.insert(2, ms(10));
+    ttl.insert(2, ms(20));
+    assert_eq!(ttl.len(), 1);
+    assert_eq!(expired.recv_timeout(ms(1000)), Ok(2));
+    assert!(started.elapsed() >= ms(30));
+}
+
+#[test]
+fn ttl_scheduler_remove_races_expiry() {
+    let ms = Duration::from_millis;
+    let (ttl, expired) = TtlScheduler::channel(ms(1));
+    let mut removed = Vec::new();
+    for i in 0..2_000 {
+        ttl.insert(i, ms(1));
+        if i % 2 == 0 && ttl.remove(&i) {
+            removed.push(i);
+        }
+    }
+    let mut fired: Vec<usize> = (removed.len()..2_000).map(|_| expired.recv().unwrap()).collect();
+    assert!(expired.recv_timeout(ms(20)).is_err());
+    fired.extend(removed);
+    fired.sort();
+    assert_eq!(fired, (0..2_000).collect::<Vec<_>>());
+}
+
+#[test]
+fn ttl_scheduler_wakes_for_sooner_keys() {
+    let ms = Duration::from_millis;
+    let (ttl, expired) = TtlScheduler::channel(ms(1));
+    ttl.insert("later", Duration::from_secs(3600));
+    // Let the expiry thread go to sleep until the far off key is due.
+    std::thread::sleep(ms(10));
+    let started = Instant::now();
+    ttl.insert("sooner", ms(10));
+    assert_eq!(expired.recv_timeout(ms(1000)), Ok("sooner"));
+    assert!(started.elapsed() >= ms(10));
+    assert!(ttl.contains(&"later"));
+}
+
+#[test]
+fn ttl_scheduler_slack_batches_expiries() {
+    let ms = Duration::from_millis;
+    let (ttl, expired) = TtlScheduler::channel(ms(1));
+    ttl.set_slack(ms(50));
+    let started = Instant::now();
+    ttl.insert(1, ms(10));
+    ttl.insert(2, ms(40));
+    assert_eq!(expired.recv().unwrap(), 1);
+    let first = started.elapsed();
+    assert_eq!(expired.recv().unwrap(), 2);
+    // The first key waited to expire along with the second...
+    assert!(first >= ms(40), "{:?}", first);
+    assert!(started.elapsed() - first < ms(5));
+    // ...but no longer than its slack.
+    assert!(first < ms(100), "{:?}", first);
+}
+
+#[test]
+fn ttl_scheduler_low_power_mode() {
+    let ms = Duration::from_millis;
+    let (ttl, expired) = TtlScheduler::channel(ms(1));
+    ttl.set_low_power(Some(ms(100)));
+    let started = Instant::now();
+    ttl.insert("a", ms(10));
+    ttl.insert("b", ms(60));
+    ttl.insert_precise("precise", ms(30));
+    assert_eq!(expired.recv().unwrap(), "precise");
+    assert!(started.elapsed() < ms(90), "{:?}", started.elapsed());
+    // The others expire together on the boundary.
+    let mut batch = vec![expired.recv().unwrap()];
+    let at = started.elapsed();
+    batch.push(expired.recv().unwrap());
+    batch.sort();
+    assert_eq!(batch, vec!["a", "b"]);
+    assert!(at >= ms(60), "{:?}", at);
+    assert!(started.elapsed() - at < ms(5));
+}
+
+#[test]
+fn ttl_scheduler_cancel_by_key() {
+    let ms = Duration::from_millis;
+    let (ttl, expired) = TtlScheduler::channel(ms(1));
+    let a = ttl.insert("a", ms(10));
+    let b = ttl.insert("b", ms(10));
+    assert_eq!(a.key(), &"a");
+    assert!(a.cancel());
+    assert!(!a.cancel());
+    // A key inserted again isn't cancelled by its old insertion's key.
+    ttl.insert("b", ms(20));
+    assert!(!b.cancel());
+    assert_eq!(expired.recv_timeout(ms(1000)), Ok("b"));
+    assert!(expired.recv_timeout(ms(30)).is_err());
+    let c = ttl.insert("c", ms(1));
+    assert_eq!(expired.recv_timeout(ms(1000)), Ok("c"));
+    assert!(!c.cancel());
+    drop(ttl);
+    assert!(!c.cancel());
+}
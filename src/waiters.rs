@@ -0,0 +1,155 @@
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+use Timer;
+
+/// Who an expiry wakes.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WakePolicy {
+    /// Wake every waiter on `timed_out`.
+    #[default]
+    All,
+    /// Hand each expiry to exactly one waiter, so that workers sharing a
+    /// timer each claim their own ticks rather than all waking for every
+    /// one.
+    ///
+    /// Expiries go round-robin to the `TickWaiter`s from `Timer::waiter`,
+    /// or, if there are none, wake one waiter on `timed_out`.
+    One,
+}
+
+/// Ticks handed to one waiter, not yet claimed.
+///
+#[derive(Default)]
+struct Slot {
+    ticks: Mutex<usize>,
+    cv: Condvar,
+}
+
+/// Waiters taking turns to be handed expiries.
+///
+#[derive(Default)]
+pub struct Waiters {
+    // Every registered waiter, and which is handed the next expiry.
+    slots: Mutex<(Vec<Weak<Slot>>, usize)>,
+}
+
+impl Waiters {
+    /// Register a new waiter, last in turn.
+    ///
+    fn register(&self) -> Arc<Slot> {
+        let slot = Arc::new(Slot::default());
+        self.slots.lock().unwrap().0.push(Arc::downgrade(&slot));
+        slot
+    }
+    /// Hand an expiry to the next waiter in turn, returning false if no
+    /// waiter is left to take it.
+    ///
+    pub fn hand_one(&self) -> bool {
+        let mut slots = self.slots.lock().unwrap();
+        let (ref mut slots, ref mut next) = *slots;
+        while !slots.is_empty() {
+            *next %= slots.len();
+            match slots[*next].upgrade() {
+                Some(slot) => {
+                    *next += 1;
+                    *slot.ticks.lock().unwrap() += 1;
+                    slot.cv.notify_one();
+                    return true;
+                },
+                None => {
+                    slots.remove(*next);
+                },
+            }
+        }
+        false
+    }
+}
+
+/// One of several workers taking turns to claim a timer's expiries, from
+/// `Timer::waiter`.
+///
+/// Only handed expiries under `WakePolicy::One`. Expiries handed to a
+/// waiter that's dropped before claiming them are lost.
+///
+pub struct TickWaiter {
+    slot: Arc<Slot>,
+}
+
+impl TickWaiter {
+    /// Block until handed at least one expiry, then claim every expiry
+    /// handed so far, returning how many.
+    ///
+    pub fn wait(&self) -> usize {
+        let mut ticks = self.slot.ticks.lock().unwrap();
+        while *ticks == 0 {
+            ticks = self.slot.cv.wait(ticks).unwrap();
+        }
+        std::mem::replace(&mut *ticks, 0)
+    }
+    /// Like `wait`, but gives up after `timeout`, returning `None` if not
+    /// handed any expiry by then.
+    ///
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<usize> {
+        let deadline = Instant::now() + timeout;
+        let mut ticks = self.slot.ticks.lock().unwrap();
+        while *ticks == 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            ticks = self.slot.cv.wait_timeout(ticks, deadline - now).unwrap().0;
+        }
+        Some(std::mem::replace(&mut *ticks, 0))
+    }
+}
+
+impl Timer {
+    /// Choose who each expiry wakes. Takes effect the next time the timer
+    /// is started.
+    ///
+    /// Has no effect on timers with an `ExpiryLedger`, which always wake
+    /// every waiter.
+    ///
+    pub fn set_wake_policy(&mut self, policy: WakePolicy) {
+        self.wake = policy;
+    }
+    /// Register a worker to take its turn claiming expiries under
+    /// `WakePolicy::One`.
+    ///
+    pub fn waiter(&self) -> TickWaiter {
+        TickWaiter { slot: self.waiters.register() }
+    }
+}
+
+#[test]
+fn waiters_take_turns() {
+    let waiters = Waiters::default();
+    assert!(!waiters.hand_one());
+    let (a, b, c) = (waiters.register(), waiters.register(), waiters.register());
+    for _ in 0..4 {
+        assert!(waiters.hand_one());
+    }
+    assert_eq!(*a.ticks.lock().unwrap(), 2);
+    assert_eq!(*b.ticks.lock().unwrap(), 1);
+    drop(c);
+    assert!(waiters.hand_one());
+    assert!(waiters.hand_one());
+    assert_eq!(*a.ticks.lock().unwrap(), 3);
+    assert_eq!(*b.ticks.lock().unwrap(), 2);
+}
+
+#[test]
+fn timer_wake_one() {
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(5), ms(0), Arc::new(Condvar::new()));
+    t.set_wake_policy(WakePolicy::One);
+    t.set_max_expiries(9);
+    let workers: Vec<TickWaiter> = (0..3).map(|_| t.waiter()).collect();
+    t.start();
+    std::thread::sleep(ms(100));
+    t.stop();
+    for worker in &workers {
+        assert_eq!(worker.wait_timeout(ms(0)), Some(3));
+    }
+}
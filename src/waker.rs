@@ -0,0 +1,176 @@
+use event::ExpiryEvent;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+use Timer;
+
+/// Wakers to wake on the next expiry.
+///
+#[derive(Default)]
+pub struct Wakers {
+    wakers: Mutex<Vec<Waker>>,
+    // The last expiry delivered, if any.
+    last: Mutex<Option<ExpiryEvent>>,
+}
+
+impl Wakers {
+    /// Wake `waker` on the next expiry, unless it's already due to be.
+    ///
+    pub fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+    /// Record `expiry` as the last delivered, then wake, and forget, every
+    /// registered waker.
+    ///
+    pub fn expired(&self, expiry: ExpiryEvent) {
+        *self.last.lock().unwrap() = Some(expiry);
+        for waker in self.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+    /// The last expiry delivered, if any.
+    ///
+    pub fn last(&self) -> Option<ExpiryEvent> {
+        *self.last.lock().unwrap()
+    }
+}
+
+/// A future that completes with the timer's next expiry, from
+/// `Timer::tick`.
+///
+/// An expiry is only consumed when the future completes, so one dropped
+/// unfinished, e.g., by losing a `select!` race, loses nothing.
+///
+pub struct TimerTick<'a> {
+    timer: &'a Timer,
+}
+
+impl<'a> Future for TimerTick<'a> {
+    type Output = ExpiryEvent;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<ExpiryEvent> {
+        if let Some(expiry) = self.timer.take_delivered() {
+            return Poll::Ready(expiry);
+        }
+        self.timer.register_waker(cx.waker());
+        // It may have expired before the waker was registered.
+        match self.timer.take_delivered() {
+            Some(expiry) => Poll::Ready(expiry),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Timer {
+    /// Wake `waker` the next time the timer expires.
+    ///
+    /// Each registration wakes once, like any future's. Works with any
+    /// executor, or none at all.
+    ///
+    pub fn register_waker(&self, waker: &Waker) {
+        self.wakers.register(waker);
+    }
+    /// Check whether the timer has expired since it was last polled,
+    /// arranging for the task to be woken when it next does if not.
+    ///
+    /// For hand written futures and custom runtimes. Returns how many times
+    /// the timer expired since the last poll that returned `Ready`.
+    ///
+    pub fn poll_expired(&self, cx: &mut Context) -> Poll<usize> {
+        if let Some(n) = self.take_unpolled() {
+            return Poll::Ready(n);
+        }
+        self.register_waker(cx.waker());
+        // It may have expired before the waker was registered.
+        match self.take_unpolled() {
+            Some(n) => Poll::Ready(n),
+            None => Poll::Pending,
+        }
+    }
+    /// Wait for the timer's next expiry.
+    ///
+    /// Usable directly as a branch of `select!` alongside I/O, with no task
+    /// to bridge the timer's thread. Resolves with the last expiry since the
+    /// last poll that returned `Ready`, carrying how many there were in
+    /// `coalesced`, so a tick that arrives while another branch runs is
+    /// picked up by the next call. Shares that bookkeeping with
+    /// `poll_expired`.
+    ///
+    pub fn tick<'a>(&'a self) -> TimerTick<'a> {
+        TimerTick { timer: self }
+    }
+    /// Take the last expiry delivered since the last poll, if any, with the
+    /// number delivered in `coalesced`.
+    ///
+    fn take_delivered(&self) -> Option<ExpiryEvent> {
+        let last = self.wakers.last()?;
+        let polled = self.polled.fetch_max(last.count, Ordering::SeqCst);
+        if last.count > polled {
+            Some(ExpiryEvent { coalesced: last.count - polled, ..last })
+        } else {
+            None
+        }
+    }
+    /// Take the expiries since the last poll, if any.
+    ///
+    fn take_unpolled(&self) -> Option<usize> {
+        let expiries = self.expiries.load(Ordering::SeqCst);
+        let polled = self.polled.fetch_max(expiries, Ordering::SeqCst);
+        if expiries > polled {
+            Some(expiries - polled)
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn timer_poll_expired() {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{Arc, Condvar};
+    use std::task::Wake;
+    use std::time::Duration;
+    struct Counter(AtomicUsize);
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+    let ms = Duration::from_millis;
+    let wakes = Arc::new(Counter(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut t = Timer::new(ms(20), ms(0), Arc::new(Condvar::new()));
+    assert_eq!(t.poll_expired(&mut cx), Poll::Pending);
+    t.set_max_expiries(2);
+    t.start();
+    std::thread::sleep(ms(60));
+    assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+    assert_eq!(t.poll_expired(&mut cx), Poll::Ready(2));
+    assert_eq!(t.poll_expired(&mut cx), Poll::Pending);
+    t.stop();
+}
+
+#[test]
+fn timer_tick() {
+    use future::{block_on, timeout};
+    use std::sync::{Arc, Condvar};
+    use std::time::Duration;
+    let ms = Duration::from_millis;
+    let mut t = Timer::new(ms(20), ms(0), Arc::new(Condvar::new()));
+    t.start();
+    let first = block_on(t.tick());
+    assert_eq!((first.count, first.coalesced), (1, 1));
+    // Dropped by a quicker branch, then picked up by the next tick.
+    assert!(block_on(timeout(ms(5), t.tick())).is_err());
+    std::thread::sleep(ms(50));
+    let late = block_on(t.tick());
+    assert!(late.count >= 3, "{:?}", late);
+    assert_eq!(late.coalesced, late.count - 1);
+    assert_eq!(block_on(t.tick()).count, late.count + 1);
+    t.halt();
+}
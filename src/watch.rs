@@ -0,0 +1,338 @@
+use config::{ConfigError, JitterPolicy, TimerConfig};
+use cron::{Cron, CronError};
+use event::{Event, Reconfiguration};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use Timer;
+
+/// Why a configuration file couldn't be watched or applied.
+///
+#[derive(Debug)]
+pub enum WatchError {
+    /// The file couldn't be read.
+    Io(io::Error),
+    /// The file couldn't be watched.
+    Watch(notify::Error),
+    /// The file isn't valid TOML or JSON, or holds a key or value that
+    /// isn't understood.
+    Parse(String),
+    /// The step and jitter given for `timer` don't suit each other.
+    Config {
+        timer: String,
+        error: ConfigError,
+    },
+    /// The cron expression given for `timer` is invalid.
+    Schedule {
+        timer: String,
+        error: CronError,
+    },
+}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WatchError::Io(ref e) => write!(f, "{}", e),
+            WatchError::Watch(ref e) => write!(f, "{}", e),
+            WatchError::Parse(ref reason) => write!(f, "{}", reason),
+            WatchError::Config { ref timer, ref error } => write!(f, "{}: {}", timer, error),
+            WatchError::Schedule { ref timer, ref error } => write!(f, "{}: {}", timer, error),
+        }
+    }
+}
+
+impl Error for WatchError {}
+
+/// A single value from the file, whichever format it's in.
+enum Field {
+    Str(String),
+    Int(u64),
+    Other,
+}
+
+/// The settings given for one timer. Anything left out stays as it is.
+#[derive(Debug, Default)]
+struct Settings {
+    step: Option<Duration>,
+    jitter: Option<Duration>,
+    jitter_policy: Option<JitterPolicy>,
+    cron: Option<String>,
+}
+
+impl Settings {
+    fn set(&mut self, timer: &str, key: &str, field: Field) -> Result<(), WatchError> {
+        let invalid = |expected: &str| {
+            WatchError::Parse(format!("{}.{}: expected {}", timer, key, expected))
+        };
+        let duration = |field: Field| match field {
+            Field::Str(s) => humantime::parse_duration(&s).map_err(|_| invalid("a duration")),
+            Field::Int(ms) => Ok(Duration::from_millis(ms)),
+            Field::Other => Err(invalid("a duration")),
+        };
+        match key {
+            "step" => self.step = Some(duration(field)?),
+            "jitter" => self.jitter = Some(duration(field)?),
+            "jitter_policy" => {
+                self.jitter_policy = Some(match field {
+                    Field::Str(ref s) => s.parse().map_err(|_| invalid("\"additive\" or \"subtractive\""))?,
+                    _ => return Err(invalid("\"additive\" or \"subtractive\"")),
+                })
+            },
+            "cron" => {
+                self.cron = Some(match field {
+                    Field::Str(s) => s,
+                    _ => return Err(invalid("a cron expression")),
+                })
+            },
+            _ => return Err(WatchError::Parse(format!("{}.{}: unknown setting", timer, key))),
+        }
+        Ok(())
+    }
+}
+
+/// Parse a configuration file into the settings for each timer it names,
+/// as JSON if it has a `.json` extension and as TOML otherwise.
+fn parse(path: &Path, text: &str) -> Result<HashMap<String, Settings>, WatchError> {
+    let mut sections = HashMap::new();
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let root: serde_json::Value = serde_json::from_str(text)
+            .map_err(|e| WatchError::Parse(e.to_string()))?;
+        let timers = root.as_object()
+            .ok_or_else(|| WatchError::Parse("expected an object of timers".to_string()))?;
+        for (name, section) in timers {
+            let section = section.as_object()
+                .ok_or_else(|| WatchError::Parse(format!("{}: expected an object", name)))?;
+            let mut settings = Settings::default();
+            for (key, value) in section {
+                let field = match *value {
+                    serde_json::Value::String(ref s) => Field::Str(s.clone()),
+                    serde_json::Value::Number(ref n) => n.as_u64().map_or(Field::Other, Field::Int),
+                    _ => Field::Other,
+                };
+                settings.set(name, key, field)?;
+            }
+            sections.insert(name.clone(), settings);
+        }
+    } else {
+        let root: toml::Table = text.parse().map_err(|e: toml::de::Error| WatchError::Parse(e.to_string()))?;
+        for (name, section) in root {
+            let section = section.as_table()
+                .ok_or_else(|| WatchError::Parse(format!("{}: expected a table", name)))?;
+            let mut settings = Settings::default();
+            for (key, value) in section {
+                let field = match *value {
+                    toml::Value::String(ref s) => Field::Str(s.clone()),
+                    toml::Value::Integer(n) if n >= 0 => Field::Int(n as u64),
+                    _ => Field::Other,
+                };
+                settings.set(&name, key, field)?;
+            }
+            sections.insert(name, settings);
+        }
+    }
+    Ok(sections)
+}
+
+/// A timer registered with a watcher.
+struct Registered {
+    timer: Arc<Mutex<Timer>>,
+    // The cron expression last applied, so it isn't re-applied, restarting
+    // the timer's schedule, every time the file is touched.
+    cron: Option<String>,
+}
+
+/// State shared with the file watching thread.
+struct Shared {
+    path: PathBuf,
+    timers: Mutex<HashMap<String, Registered>>,
+}
+
+impl Shared {
+    fn reload(&self) -> Result<(), WatchError> {
+        let text = fs::read_to_string(&self.path).map_err(WatchError::Io)?;
+        let mut sections = parse(&self.path, &text)?;
+        let mut timers = self.timers.lock().unwrap();
+        // Check every change before making any, so a bad file is rejected
+        // whole rather than half applied.
+        let mut changes = Vec::new();
+        for (name, registered) in timers.iter() {
+            let settings = match sections.remove(name) {
+                Some(settings) => settings,
+                None => continue,
+            };
+            let timer = registered.timer.lock().unwrap();
+            let mut config = TimerConfig::new(settings.step.unwrap_or(timer.step()));
+            config.jitter = settings.jitter.unwrap_or(timer.jitter());
+            config.jitter_policy = settings.jitter_policy.unwrap_or(timer.jitter_policy());
+            config.validate()
+                .map_err(|error| WatchError::Config { timer: name.clone(), error })?;
+            let cron = match settings.cron {
+                Some(ref expr) if registered.cron.as_ref() != Some(expr) => {
+                    let cron = Cron::parse(expr)
+                        .map_err(|error| WatchError::Schedule { timer: name.clone(), error })?;
+                    Some((cron, expr.clone()))
+                },
+                _ => None,
+            };
+            changes.push((name.clone(), config, cron));
+        }
+        for (name, config, cron) in changes {
+            let registered = timers.get_mut(&name).unwrap();
+            let mut timer = registered.timer.lock().unwrap();
+            let pacing = (timer.step(), timer.jitter(), timer.jitter_policy());
+            let paced = pacing != (config.step, config.jitter, config.jitter_policy);
+            if !paced && cron.is_none() {
+                continue;
+            }
+            if paced {
+                timer.set_pacing(config.step, config.jitter, config.jitter_policy)
+                    .expect("validated above");
+            }
+            let schedule = match cron {
+                Some((cron, expr)) => {
+                    timer.set_schedule(cron);
+                    registered.cron = Some(expr.clone());
+                    Some(expr)
+                },
+                None => None,
+            };
+            timer.subscribers.emit(Event::Reconfigured(Reconfiguration {
+                step: config.step,
+                jitter: config.jitter,
+                jitter_policy: config.jitter_policy,
+                schedule,
+            }));
+        }
+        Ok(())
+    }
+}
+
+/// Applies a configuration file to timers at runtime, whenever it changes.
+///
+/// The file maps the name each timer was registered under to its settings,
+/// in TOML, or in JSON if its name ends in `.json`:
+///
+/// ```toml
+/// [heartbeat]
+/// step = "30s"
+/// jitter = "5s"
+/// jitter_policy = "additive"
+/// cron = "*/5 * * * *"
+/// ```
+///
+/// Durations are written like `"1m 30s"`, or as a number of milliseconds.
+/// Settings left out keep their current value, and timers the file doesn't
+/// name are left alone. Each change takes effect immediately, the same as
+/// calling `set_step` and friends, and reconfigured timers emit an
+/// `Event::Reconfigured`. A file that fails to parse or validate is
+/// rejected whole, and errors hit while watching are printed.
+///
+pub struct ConfigWatcher {
+    shared: Arc<Shared>,
+    // Watching stops when this is dropped.
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Start watching the configuration file at `path`.
+    ///
+    /// Nothing is applied until timers are registered and the file next
+    /// changes, or `reload` is called.
+    ///
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<ConfigWatcher, WatchError> {
+        let path = path.as_ref().to_path_buf();
+        let shared = Arc::new(Shared { path: path.clone(), timers: Mutex::new(HashMap::new()) });
+        let file_name: Option<OsString> = path.file_name().map(|name| name.to_os_string());
+        let watched = shared.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            match res {
+                Ok(event) => {
+                    let ours = event.paths.iter().any(|p| p.file_name() == file_name.as_deref());
+                    if ours && (event.kind.is_create() || event.kind.is_modify()) {
+                        if let Err(e) = watched.reload() {
+                            println!("Error: {}", e);
+                        }
+                    }
+                },
+                Err(e) => println!("Error: {}", e),
+            }
+        }).map_err(WatchError::Watch)?;
+        // Watch the directory rather than the file, so that editors and
+        // deployment tools that replace the file wholesale are noticed.
+        let dir = match path.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        watcher.watch(dir, RecursiveMode::NonRecursive).map_err(WatchError::Watch)?;
+        Ok(ConfigWatcher { shared, _watcher: watcher })
+    }
+    /// Apply the settings the file gives under `name` to `timer` from now
+    /// on, replacing any timer already registered under that name.
+    ///
+    pub fn register(&self, name: &str, timer: Arc<Mutex<Timer>>) {
+        let registered = Registered { timer, cron: None };
+        self.shared.timers.lock().unwrap().insert(name.to_string(), registered);
+    }
+    /// Stop applying settings to the timer registered under `name`,
+    /// returning false if there was none.
+    ///
+    pub fn unregister(&self, name: &str) -> bool {
+        self.shared.timers.lock().unwrap().remove(name).is_some()
+    }
+    /// Apply the file as it stands now.
+    ///
+    pub fn reload(&self) -> Result<(), WatchError> {
+        self.shared.reload()
+    }
+}
+
+#[test]
+#[cfg(feature = "jitter")]
+fn config_watcher_reconfigures_timers() {
+    use std::sync::Condvar;
+    let ms = Duration::from_millis;
+    let dir = std::env::temp_dir().join(format!("timer-watch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("timers.toml");
+    fs::write(&path, "[heartbeat]\nstep = \"50ms\"\n").unwrap();
+    let timer = Arc::new(Mutex::new(Timer::new(ms(20), ms(0), Arc::new(Condvar::new()))));
+    let events = timer.lock().unwrap().subscribe();
+    let watcher = ConfigWatcher::new(&path).unwrap();
+    watcher.register("heartbeat", timer.clone());
+    watcher.reload().unwrap();
+    assert_eq!(timer.lock().unwrap().step(), ms(50));
+    match events.try_recv() {
+        Ok(Event::Reconfigured(r)) => assert_eq!(r.step, ms(50)),
+        other => panic!("unexpected {:?}", other),
+    }
+    fs::write(&path, "[heartbeat]\nstep = \"80ms\"\njitter = 10\njitter_policy = \"additive\"\n").unwrap();
+    match events.recv_timeout(Duration::from_secs(5)) {
+        Ok(Event::Reconfigured(r)) => {
+            assert_eq!((r.step, r.jitter, r.jitter_policy), (ms(80), ms(10), JitterPolicy::Additive));
+        },
+        other => panic!("unexpected {:?}", other),
+    }
+    fs::write(&path, "[heartbeat]\nstep = \"5ms\"\njitter_policy = \"subtractive\"\n").unwrap();
+    assert!(matches!(watcher.reload(), Err(WatchError::Config { .. })));
+    assert_eq!(timer.lock().unwrap().step(), ms(80));
+    drop(watcher);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn config_watcher_parses_json() {
+    let text = r#"{"nightly": {"step": "1m 30s", "cron": "0 3 * * *"}}"#;
+    let sections = parse(Path::new("timers.json"), text).unwrap();
+    let settings = &sections["nightly"];
+    assert_eq!(settings.step, Some(Duration::from_secs(90)));
+    assert_eq!(settings.cron.as_deref(), Some("0 3 * * *"));
+    let err = parse(Path::new("timers.json"), r#"{"nightly": {"stpe": "1s"}}"#).unwrap_err();
+    assert_eq!(err.to_string(), "nightly.stpe: unknown setting");
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Identifies an entry in a `Wheel`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EntryKey(u64);
+
+/// An entry waiting in one of the wheel's slots.
+struct Entry<T> {
+    key: EntryKey,
+    // The tick the entry expires on.
+    due: u64,
+    value: T,
+}
+
+/// The shape of a timing wheel, trading memory for precision and reach.
+///
+/// Entries expire on whole ticks, so no earlier than asked and at most one
+/// `tick` late, whatever the shape. Each level's slots span
+/// `slots_per_level` times as long as the level below's, and entries move
+/// down a level as their time nears. Entries due beyond the `horizon` wait
+/// on the top level, costing another move for every extra revolution.
+/// Memory is a slot per level and slot, plus the entries themselves.
+///
+/// A 1ms tick suits RPC timeouts, and a 1s tick session expiry.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WheelConfig {
+    /// Length of one tick, which delays are rounded up to. Must be
+    /// non-zero.
+    pub tick: Duration,
+    /// Number of slots on each level, at least one.
+    pub slots_per_level: usize,
+    /// Number of levels, at least one.
+    pub levels: usize,
+}
+
+impl WheelConfig {
+    /// Create a new config with the given tick, and room for over sixteen
+    /// million ticks before entries wait on the top level.
+    ///
+    pub fn new(tick: Duration) -> WheelConfig {
+        WheelConfig { tick, slots_per_level: 64, levels: 4 }
+    }
+    /// How far out entries can be placed without waiting on the top level.
+    ///
+    pub fn horizon(&self) -> Duration {
+        let ticks = (self.slots_per_level.max(1) as u128).saturating_pow(self.levels.max(1) as u32);
+        Duration::from_nanos(self.tick.as_nanos().saturating_mul(ticks).min(u64::MAX as u128) as u64)
+    }
+}
+
+/// A hierarchical hashed timing wheel.
+///
+/// Entries are bucketed by the tick they expire on, at the coarsest level
+/// whose slots still tell it apart from the current tick, so inserting,
+/// removing and expiring an entry are all constant time however many are
+/// pending. See `WheelConfig` for the wheel's shape.
+///
+pub struct Wheel<T> {
+    // Length of one tick.
+    resolution: Duration,
+    // Slots on each level.
+    slots: usize,
+    // Each level's slots, finest first.
+    levels: Vec<Vec<Vec<Entry<T>>>>,
+    // The last tick expired.
+    tick: u64,
+    // The level, slot and position in it of each pending entry.
+    index: HashMap<EntryKey, (usize, usize, usize)>,
+    // Source of unique entry keys.
+    next_key: u64,
+}
+
+impl<T> Wheel<T> {
+    /// Create a new, empty wheel at tick zero.
+    ///
+    pub fn new(config: WheelConfig) -> Wheel<T> {
+        assert!(config.tick > Duration::from_secs(0), "Wheel resolution must be non-zero!");
+        let slots = config.slots_per_level.max(1);
+        Wheel {
+            resolution: config.tick,
+            slots,
+            levels: (0..config.levels.max(1)).map(|_| (0..slots).map(|_| Vec::new()).collect()).collect(),
+            tick: 0,
+            index: HashMap::new(),
+            next_key: 0,
+        }
+    }
+    /// The tick `elapsed` falls in.
+    ///
+    pub fn tick_at(&self, elapsed: Duration) -> u64 {
+        (elapsed.as_nanos() / self.resolution.as_nanos()) as u64
+    }
+    /// The time since tick zero at which `tick` begins.
+    ///
+    pub fn elapsed_at(&self, tick: u64) -> Duration {
+        Duration::from_nanos((self.resolution.as_nanos() * tick as u128).min(u64::MAX as u128) as u64)
+    }
+    /// The last tick expired.
+    ///
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+    /// True if no entries are pending.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+    /// The next tick advancing to would expire or move an entry, if there
+    /// are any entries.
+    ///
+    /// Takes time in proportion to the number of slots.
+    ///
+    pub fn next_tick(&self) -> Option<u64> {
+        if self.is_empty() {
+            return None;
+        }
+        let slots = self.slots as u64;
+        let mut next: Option<u64> = None;
+        for (level, level_slots) in self.levels.iter().enumerate() {
+            let span = self.span(level);
+            // The first of the level's slot boundaries after the last tick.
+            let first = self.tick / span + 1;
+            for (slot, entries) in level_slots.iter().enumerate() {
+                if entries.is_empty() {
+                    continue;
+                }
+                let boundary = first + (slot as u64 + slots - first % slots) % slots;
+                let tick = boundary.saturating_mul(span);
+                next = Some(next.map_or(tick, |next| next.min(tick)));
+            }
+        }
+        next
+    }
+    /// Number of ticks each of `level`'s slots spans.
+    ///
+    fn span(&self, level: usize) -> u64 {
+        (self.slots as u64).saturating_pow(level as u32)
+    }
+    /// Put `entry`, due after tick `now`, in its slot.
+    ///
+    fn place(&mut self, now: u64, entry: Entry<T>) {
+        let top = self.levels.len() - 1;
+        let level = (0..top)
+            .find(|&l| entry.due / self.span(l) - now / self.span(l) < self.slots as u64)
+            .unwrap_or(top);
+        let slot = (entry.due / self.span(level) % self.slots as u64) as usize;
+        let entries = &mut self.levels[level][slot];
+        self.index.insert(entry.key, (level, slot, entries.len()));
+        entries.push(entry);
+    }
+    /// Add an entry expiring `delay` after the current tick, rounded up to
+    /// a whole tick.
+    ///
+    pub fn insert(&mut self, delay: Duration, value: T) -> EntryKey {
+        let ticks = delay.as_nanos().div_ceil(self.resolution.as_nanos()).max(1);
+        let due = self.tick.saturating_add(ticks.min(u64::MAX as u128) as u64);
+        let key = EntryKey(self.next_key);
+        self.next_key += 1;
+        let now = self.tick;
+        self.place(now, Entry { key, due, value });
+        key
+    }
+    /// Remove a pending entry in constant time, returning its value.
+    ///
+    pub fn remove(&mut self, key: EntryKey) -> Option<T> {
+        let (level, slot, i) = self.index.remove(&key)?;
+        let entries = &mut self.levels[level][slot];
+        let entry = entries.swap_remove(i);
+        // The last entry took the removed one's place.
+        if let Some(moved) = entries.get(i) {
+            self.index.insert(moved.key, (level, slot, i));
+        }
+        Some(entry.value)
+    }
+    /// Empty a slot at tick `now`, expiring its entries that are due and
+    /// placing the rest anew.
+    ///
+    fn sweep(&mut self, level: usize, slot: usize, now: u64, expired: &mut Vec<T>) {
+        for entry in std::mem::take(&mut self.levels[level][slot]) {
+            if entry.due <= now {
+                self.index.remove(&entry.key);
+                expired.push(entry.value);
+            } else {
+                self.place(now, entry);
+            }
+        }
+    }
+    /// Expire every entry due up to and including `tick`, returning their
+    /// values.
+    ///
+    pub fn advance(&mut self, tick: u64) -> Vec<T> {
+        let mut expired = Vec::new();
+        if tick <= self.tick {
+            return expired;
+        }
+        // Past a revolution of the finest level, sweeping every slot once
+        // beats stepping through each tick.
+        if tick - self.tick > self.slots as u64 {
+            for level in 0..self.levels.len() {
+                for slot in 0..self.slots {
+                    self.sweep(level, slot, tick, &mut expired);
+                }
+            }
+            self.tick = tick;
+            return expired;
+        }
+        for now in self.tick + 1..=tick {
+            // Coarser levels first, so entries moving down can expire now.
+            for level in (1..self.levels.len()).rev() {
+                let span = self.span(level);
+                if now % span == 0 {
+                    let slot = (now / span % self.slots as u64) as usize;
+                    self.sweep(level, slot, now, &mut expired);
+                }
+            }
+            let slot = (now % self.slots as u64) as usize;
+            self.sweep(0, slot, now, &mut expired);
+        }
+        self.tick = tick;
+        expired
+    }
+}
+
+#[test]
+fn wheel_expires_in_order_of_ticks() {
+    let ms = Duration::from_millis;
+    let mut wheel = Wheel::new(WheelConfig { tick: ms(10), slots_per_level: 4, levels: 1 });
+    wheel.insert(ms(5), "a");
+    let b = wheel.insert(ms(20), "b");
+    wheel.insert(ms(100), "c");
+    wheel.insert(ms(30), "d");
+    assert_eq!(wheel.advance(1), vec!["a"]);
+    assert_eq!(wheel.remove(b), Some("b"));
+    assert_eq!(wheel.remove(b), None);
+    assert_eq!(wheel.advance(3), vec!["d"]);
+    // "c" shares a slot with "d" but is two revolutions further out...
+    assert!(!wheel.is_empty());
+    assert_eq!(wheel.advance(1000), vec!["c"]);
+    assert!(wheel.is_empty());
+}
+
+#[test]
+fn wheel_config_extremes() {
+    let ms = Duration::from_millis;
+    // A single slot is a plain list, checked every tick.
+    let mut wheel = Wheel::new(WheelConfig { tick: ms(1), slots_per_level: 1, levels: 1 });
+    wheel.insert(ms(3), 3);
+    wheel.insert(ms(1), 1);
+    assert_eq!(wheel.advance(2), vec![1]);
+    assert_eq!(wheel.advance(3), vec![3]);
+    // Tiny levels move far out entries down level by level...
+    let config = WheelConfig { tick: ms(1), slots_per_level: 2, levels: 3 };
+    assert_eq!(config.horizon(), ms(8));
+    let mut wheel = Wheel::new(config);
+    for &due in &[1, 2, 5, 7, 8, 9, 30] {
+        wheel.insert(ms(due), due);
+    }
+    let mut expired = Vec::new();
+    for tick in 1..=30 {
+        for due in wheel.advance(tick) {
+            assert_eq!(due, tick);
+            expired.push(due);
+        }
+    }
+    assert_eq!(expired, vec![1, 2, 5, 7, 8, 9, 30]);
+    // ...and coarse ticks round up, never down.
+    let s = Duration::from_secs;
+    let mut wheel = Wheel::new(WheelConfig { tick: s(1), slots_per_level: 4096, levels: 1 });
+    wheel.insert(ms(1), "rounded up");
+    wheel.insert(s(3600), "an hour");
+    assert_eq!(wheel.advance(1), vec!["rounded up"]);
+    assert_eq!(wheel.advance(3599), Vec::<&str>::new());
+    assert_eq!(wheel.advance(3600), vec!["an hour"]);
+}
+
+#[test]
+fn wheel_remove_keeps_index() {
+    let ms = Duration::from_millis;
+    let mut wheel = Wheel::new(WheelConfig::new(ms(1)));
+    let keys: Vec<EntryKey> = (0..5).map(|i| wheel.insert(ms(10), i)).collect();
+    assert_eq!(wheel.remove(keys[1]), Some(1));
+    assert_eq!(wheel.remove(keys[4]), Some(4));
+    assert_eq!(wheel.remove(keys[1]), None);
+    assert_eq!(wheel.remove(keys[0]), Some(0));
+    let mut expired = wheel.advance(10);
+    expired.sort();
+    assert_eq!(expired, vec![2, 3]);
+}
+
+#[test]
+fn wheel_next_tick() {
+    let ms = Duration::from_millis;
+    let mut wheel = Wheel::new(WheelConfig { tick: ms(1), slots_per_level: 4, levels: 2 });
+    assert_eq!(wheel.next_tick(), None);
+    let far = wheel.insert(ms(13), "far");
+    // Waits on the second level, so moves down at the start of its slot...
+    assert_eq!(wheel.next_tick(), Some(12));
+    wheel.insert(ms(2), "near");
+    assert_eq!(wheel.next_tick(), Some(2));
+    assert_eq!(wheel.advance(2), vec!["near"]);
+    assert_eq!(wheel.next_tick(), Some(12));
+    assert!(wheel.advance(12).is_empty());
+    // ...then expires at its own.
+    assert_eq!(wheel.next_tick(), Some(13));
+    wheel.remove(far);
+    assert_eq!(wheel.next_tick(), None);
+}